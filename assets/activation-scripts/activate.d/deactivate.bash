@@ -0,0 +1,74 @@
+# Support for the `hook.on-deactivate` script of the environment.
+#
+# Activations of an environment with a hook-on-deactivate script attach to
+# the activation of the environment tracked by flox-activations,
+# and detach from it once their shell or command exits.
+# The activation that detaches last runs the script.
+# If the last activation is terminated before it can detach
+# (e.g. because its terminal is closed), the watchdog runs the script instead.
+# In-place activations are not tracked.
+
+# The ID of the activation this process is attached to, if any
+_flox_activation_id=""
+
+# Attach this process to the activation of the environment.
+flox_attach() {
+  if [ ! -e "$FLOX_ENV/activate.d/hook-on-deactivate" ]; then
+    return 0
+  fi
+  local _output _key _value _attaching=""
+  if ! _output="$("$_flox_activations" start-or-attach \
+    --pid "$$" --flox-env "$FLOX_ENV" \
+    --store-path "$("$_coreutils/bin/realpath" "$FLOX_ENV")")"; then
+    echo "WARN: failed to track activation of '$FLOX_ENV_DESCRIPTION', hook.on-deactivate will not run" >&2
+    return 0
+  fi
+  while IFS='=' read -r _key _value; do
+    case "$_key" in
+      _FLOX_ATTACH) _attaching="$_value" ;;
+      _FLOX_ACTIVATION_ID) _flox_activation_id="$_value" ;;
+    esac
+  done <<< "$_output"
+  # This process started the activation after running hook.on-activate,
+  # so other activations can attach to it right away.
+  if [ "$_attaching" != true ]; then
+    "$_flox_activations" set-ready --flox-env "$FLOX_ENV" --id "$_flox_activation_id"
+  fi
+}
+
+# Detach this process from the activation of the environment,
+# and run the hook-on-deactivate script if no other process is attached to it.
+flox_deactivate() {
+  if [ -z "$_flox_activation_id" ]; then
+    return 0
+  fi
+  local _output
+  if ! _output="$("$_flox_activations" detach \
+    --pid "$$" --flox-env "$FLOX_ENV" --id "$_flox_activation_id")"; then
+    echo "WARN: failed to detach from activation of '$FLOX_ENV_DESCRIPTION'" >&2
+    return 0
+  fi
+  _flox_activation_id=""
+  if [ "$_output" != "_FLOX_DEACTIVATE=true" ]; then
+    return 0
+  fi
+  # Like hook-on-activate, run in bash and redirect stdout to stderr,
+  # but in a subshell so that an `exit` in the script can't change our status.
+  # shellcheck disable=SC1091 # from rendered environment
+  (source "$FLOX_ENV/activate.d/hook-on-deactivate") 1>&2 \
+    || echo "WARN: hook.on-deactivate of '$FLOX_ENV_DESCRIPTION' failed" >&2
+}
+
+# Used in place of `exec` to invoke the shell or command of an activation.
+# Without a hook-on-deactivate script this is just `exec`, otherwise the
+# shell or command is run as a child so that the script can run after it exits.
+flox_exec() {
+  if [ ! -e "$FLOX_ENV/activate.d/hook-on-deactivate" ]; then
+    exec "$@"
+  fi
+  flox_attach
+  local status=0
+  "$@" || status=$?
+  flox_deactivate
+  exit "$status"
+}
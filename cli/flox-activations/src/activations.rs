@@ -11,8 +11,9 @@ use serde_json::json;
 use time::{Duration, OffsetDateTime};
 use tracing::debug;
 use uuid::Uuid;
+use xdg::BaseDirectories;
 
-type Error = anyhow::Error;
+use crate::Error;
 
 /// Deserialized contents of activations.json
 ///
@@ -26,8 +27,10 @@ type Error = anyhow::Error;
 /// Activations are identifiable by their [Activation::id], for simpler lookups
 /// and global uniqueness in case the that two environments have the same store path.
 ///
-/// Notably, the [Activations] does not feature methods to remove activations.
-/// Removing actiavtions falls onto the watchdog, which is responsible for cleaning up activations.
+/// Activations are removed once no PID is attached to them anymore,
+/// either by the last PID detaching with [Activations::detach],
+/// or by the watchdog with [Activations::remove_detached_activations],
+/// if attached PIDs terminated without detaching.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Activations {
     version: Version<1>,
@@ -94,6 +97,42 @@ impl Activations {
         self.activations.push(activation);
         Ok(self.activations.last_mut().unwrap())
     }
+
+    /// Detach a PID from the activation with the given ID.
+    ///
+    /// If no other PID remains attached, the activation is removed
+    /// and `true` is returned, i.e. the caller was the last process
+    /// attached to the activation and is responsible for deactivating it.
+    /// activations.json is locked while it is read and written,
+    /// so at most one process removes a given activation,
+    /// either through this method or [Self::remove_detached_activations].
+    pub fn detach(&mut self, activation_id: Uuid, pid: u32) -> bool {
+        let Some(activation) = self.activation_for_id_mut(activation_id) else {
+            return false;
+        };
+
+        activation.remove_pid(pid);
+        if activation.has_attached_pids() {
+            return false;
+        }
+
+        self.activations
+            .retain(|activation| activation.id != activation_id);
+        true
+    }
+
+    /// Remove all activations that no PID is attached to anymore.
+    ///
+    /// Used by the watchdog to clean up after processes that terminated
+    /// without detaching from their activation.
+    /// The caller is responsible for deactivating the removed activations.
+    pub fn remove_detached_activations(&mut self) -> Vec<Activation> {
+        let (detached, attached) = std::mem::take(&mut self.activations)
+            .into_iter()
+            .partition(|activation| !activation.has_attached_pids());
+        self.activations = attached;
+        detached
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -126,6 +165,11 @@ impl Activation {
         self.id
     }
 
+    /// The store path of the built environment
+    pub fn store_path(&self) -> &str {
+        &self.store_path
+    }
+
     /// Whether the activation is ready to be attached to.
     ///
     /// "Readyness" is a one way state change, set via [Self::set_ready].
@@ -173,6 +217,18 @@ impl Activation {
         self.attached_pids
             .retain(|attached_pid| attached_pid.pid != pid);
     }
+
+    /// Whether any PID is still attached to the activation,
+    /// i.e. is running or hasn't reached its expiration yet.
+    pub fn has_attached_pids(&self) -> bool {
+        let now = OffsetDateTime::now_utc();
+        self.attached_pids.iter().any(|attached_pid| {
+            attached_pid.is_running()
+                || attached_pid
+                    .expiration
+                    .is_some_and(|expiration| expiration > now)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +291,18 @@ fn activations_json_lock_path(activations_json_path: impl AsRef<Path>) -> PathBu
     activations_json_path.as_ref().with_extension("lock")
 }
 
+/// The runtime directory keeping activation data,
+/// if none is provided explicitly.
+///
+/// XDG_RUNTIME_DIR/flox or XDG_CACHE_HOME/flox if there is no runtime directory.
+pub fn default_runtime_dir() -> Result<PathBuf, Error> {
+    let dirs = BaseDirectories::with_prefix("flox")?;
+    match dirs.get_runtime_directory() {
+        Ok(runtime_dir) => Ok(runtime_dir.to_path_buf()),
+        Err(_) => Ok(dirs.get_cache_home()),
+    }
+}
+
 /// {flox_runtime_dir}/{path_hash(flox_env)}/activations.json
 pub fn activations_json_path(
     runtime_dir: impl AsRef<Path>,
@@ -357,6 +425,69 @@ mod test {
         assert_eq!(activation.attached_pids[0].pid, 123);
     }
 
+    /// PID of a process that has exited
+    fn terminated_pid() -> u32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        child.id()
+    }
+
+    #[test]
+    fn detach_removes_activation_after_last_pid() {
+        let mut activations = Activations::default();
+        let running_pid = std::process::id();
+        let activation = activations
+            .create_activation("/store/path", running_pid)
+            .unwrap();
+        activation.attach_pid(123, None);
+        let id = activation.id();
+
+        assert!(
+            !activations.detach(id, 123),
+            "running PID is still attached"
+        );
+        assert!(activations.activation_for_id_ref(id).is_some());
+
+        assert!(activations.detach(id, running_pid));
+        assert!(activations.activation_for_id_ref(id).is_none());
+
+        assert!(
+            !activations.detach(id, running_pid),
+            "removed activations are only detached once"
+        );
+    }
+
+    #[test]
+    fn detach_keeps_activation_with_unexpired_pid() {
+        let mut activations = Activations::default();
+        let activation = activations.create_activation("/store/path", 123).unwrap();
+        activation.attach_pid(terminated_pid(), Some(Duration::seconds(60)));
+        let id = activation.id();
+
+        assert!(!activations.detach(id, 123));
+        assert!(activations.activation_for_id_ref(id).is_some());
+    }
+
+    #[test]
+    fn remove_detached_activations() {
+        let mut activations = Activations::default();
+        let detached = activations
+            .create_activation("/store/detached", terminated_pid())
+            .unwrap()
+            .id();
+        let attached = activations
+            .create_activation("/store/attached", std::process::id())
+            .unwrap()
+            .id();
+
+        let removed = activations.remove_detached_activations();
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id(), detached);
+        assert!(activations.activation_for_id_ref(attached).is_some());
+        assert!(activations.remove_detached_activations().is_empty());
+    }
+
     #[test]
     fn activation_remove_pid() {
         let mut activation = Activation {
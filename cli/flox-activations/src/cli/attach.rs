@@ -34,7 +34,7 @@ pub struct AttachExclusiveArgs {
 }
 
 impl AttachArgs {
-    pub(crate) fn handle(self, runtime_dir: PathBuf) -> Result<(), Error> {
+    pub fn handle(self, runtime_dir: PathBuf) -> Result<(), Error> {
         let activations_json_path =
             activations::activations_json_path(&runtime_dir, &self.flox_env)?;
 
@@ -0,0 +1,104 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+use uuid::Uuid;
+
+use crate::{activations, Error};
+
+#[derive(Debug, Args)]
+pub struct DetachArgs {
+    #[arg(help = "The PID of the shell that is no longer interested in the activation.")]
+    #[arg(short, long, value_name = "PID")]
+    pub pid: u32,
+    #[arg(help = "The path to the .flox directory for the environment.")]
+    #[arg(short, long, value_name = "PATH")]
+    pub flox_env: PathBuf,
+    #[arg(help = "The UUID for this particular activation of this environment.")]
+    #[arg(short, long, value_name = "UUID")]
+    pub id: Uuid,
+}
+
+impl DetachArgs {
+    pub fn handle(self, runtime_dir: PathBuf) -> Result<(), Error> {
+        self.handle_inner(runtime_dir, std::io::stdout())
+    }
+
+    /// Detach the PID and print whether the caller has to deactivate the environment,
+    /// i.e. whether it was the last PID attached to the activation.
+    fn handle_inner(self, runtime_dir: PathBuf, mut output: impl Write) -> Result<(), Error> {
+        let activations_json_path =
+            activations::activations_json_path(&runtime_dir, &self.flox_env)?;
+
+        let (activations, lock) = activations::read_activations_json(&activations_json_path)?;
+        let Some(mut activations) = activations else {
+            anyhow::bail!("Expected an existing activations.json file");
+        };
+
+        let deactivate = activations.detach(self.id, self.pid);
+
+        activations::write_activations_json(&activations, &activations_json_path, lock)?;
+
+        writeln!(&mut output, "_FLOX_DEACTIVATE={deactivate}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::cli::test::{read_activations, write_activations};
+
+    fn detach(runtime_dir: &TempDir, flox_env: &Path, id: Uuid, pid: u32) -> String {
+        let args = DetachArgs {
+            pid,
+            flox_env: flox_env.to_path_buf(),
+            id,
+        };
+        let mut output = Vec::new();
+        args.handle_inner(runtime_dir.path().to_path_buf(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn last_pid_deactivates() {
+        let runtime_dir = TempDir::new().unwrap();
+        let flox_env = PathBuf::from("/path/to/floxenv");
+        // The PID of the current process, guaranteed to be running
+        let running_pid = std::process::id();
+        let pid = 5678;
+
+        let id = write_activations(&runtime_dir, &flox_env, |activations| {
+            let activation = activations
+                .create_activation("/store/path", running_pid)
+                .unwrap();
+            activation.attach_pid(pid, None);
+            activation.id()
+        });
+
+        assert_eq!(
+            detach(&runtime_dir, &flox_env, id, pid),
+            "_FLOX_DEACTIVATE=false\n"
+        );
+        assert_eq!(
+            detach(&runtime_dir, &flox_env, id, running_pid),
+            "_FLOX_DEACTIVATE=true\n"
+        );
+
+        let activation = read_activations(&runtime_dir, &flox_env, |activations| {
+            activations.activation_for_id_ref(id).cloned()
+        })
+        .unwrap();
+        assert!(activation.is_none());
+
+        assert_eq!(
+            detach(&runtime_dir, &flox_env, id, running_pid),
+            "_FLOX_DEACTIVATE=false\n"
+        );
+    }
+}
@@ -3,10 +3,12 @@ use std::path::PathBuf;
 use clap::{Parser, Subcommand};
 
 mod attach;
+mod detach;
 mod set_ready;
 mod start_or_attach;
 
 pub use attach::AttachArgs;
+pub use detach::DetachArgs;
 pub use set_ready::SetReadyArgs;
 pub use start_or_attach::StartOrAttachArgs;
 
@@ -38,6 +40,8 @@ pub enum Command {
     SetReady(SetReadyArgs),
     #[command(about = "Attach to an existing activation.")]
     Attach(AttachArgs),
+    #[command(about = "Detach from an activation, removing it if this was the last attached PID.")]
+    Detach(DetachArgs),
 }
 
 #[cfg(test)]
@@ -19,7 +19,7 @@ pub struct SetReadyArgs {
 }
 
 impl SetReadyArgs {
-    pub(crate) fn handle(self, runtime_dir: PathBuf) -> Result<(), Error> {
+    pub fn handle(self, runtime_dir: PathBuf) -> Result<(), Error> {
         let activations_json_path =
             activations::activations_json_path(&runtime_dir, &self.flox_env)?;
 
@@ -25,7 +25,7 @@ pub struct StartOrAttachArgs {
 }
 
 impl StartOrAttachArgs {
-    pub(crate) fn handle(self, runtime_dir: PathBuf) -> Result<(), anyhow::Error> {
+    pub fn handle(self, runtime_dir: PathBuf) -> Result<(), anyhow::Error> {
         let mut retries = 3;
 
         loop {
@@ -0,0 +1,4 @@
+pub mod activations;
+pub mod cli;
+
+pub type Error = anyhow::Error;
@@ -1,31 +1,21 @@
 use clap::Parser;
-use cli::Cli;
-use xdg::BaseDirectories;
-
-mod activations;
-mod cli;
-
-pub type Error = anyhow::Error;
+use flox_activations::activations::default_runtime_dir;
+use flox_activations::cli::{self, Cli};
+use flox_activations::Error;
 
 fn main() -> Result<(), Error> {
     let args = Cli::parse();
-    eprintln!("{args:?}");
 
     let runtime_dir = match args.runtime_dir {
         Some(runtime_dir) => runtime_dir,
-        None => {
-            let dirs = BaseDirectories::with_prefix("flox")?;
-            match dirs.get_runtime_directory() {
-                Ok(runtime_dir) => runtime_dir.to_path_buf(),
-                Err(_) => dirs.get_cache_home(),
-            }
-        },
+        None => default_runtime_dir()?,
     };
 
     match args.command {
         cli::Command::StartOrAttach(args) => args.handle(runtime_dir)?,
         cli::Command::SetReady(args) => args.handle(runtime_dir)?,
         cli::Command::Attach(args) => args.handle(runtime_dir)?,
+        cli::Command::Detach(args) => args.handle(runtime_dir)?,
     }
     Ok(())
 }
@@ -53,6 +53,11 @@ pub struct Flox {
 
     /// Feature flags
     pub features: Features,
+
+    /// Fail instead of locking environments
+    /// whose lockfile is missing or out of date,
+    /// see [crate::models::environment::CoreEnvironment::lock]
+    pub locked: bool,
 }
 
 impl Flox {}
@@ -271,6 +276,7 @@ pub mod test_helpers {
             catalog_client: MockClient::default().into(),
             installable_locker: Default::default(),
             features: Default::default(),
+            locked: false,
         };
 
         (flox, tempdir_handle)
@@ -0,0 +1,1015 @@
+//! Auditing locked packages for known vulnerabilities
+//!
+//! Packages are matched by `pname` and version against a local database of
+//! vulnerabilities, so that environments can be audited offline, e.g. in CI.
+//! Databases can be in the [OSV](https://ossf.github.io/osv-schema/) format,
+//! either a single record, a list of records or an object with a `vulns` list,
+//! or in the format of the [NVD CVE API 2.0](https://nvd.nist.gov/developers/vulnerabilities).
+//!
+//! Versions are compared like Nix's `builtins.compareVersions`,
+//! which matches the versions of packages in nixpkgs.
+//! OSV records of other package ecosystems, e.g. `crates.io`,
+//! are only matched if their ecosystem is selected explicitly,
+//! see [VulnerabilityDatabase::retain_ecosystems].
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use super::lockfile::{LockedManifestCatalog, LockedPackage};
+use crate::data::System;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("failed to read vulnerability database '{0}'")]
+    ReadDatabase(String, #[source] std::io::Error),
+    #[error("failed to parse vulnerability database")]
+    ParseDatabase(#[source] serde_json::Error),
+    #[error("unrecognized vulnerability database format, expected OSV or NVD JSON")]
+    UnknownFormat,
+    #[error(
+        "ecosystem '{0}' uses distribution specific versions, \
+         which can't be compared to the versions of packages in nixpkgs"
+    )]
+    DistributionEcosystem(String),
+}
+
+/// OSV ecosystems of Linux distributions.
+///
+/// Their versions contain epochs and distribution revisions,
+/// e.g. `1:1.1.1n-0+deb11u4`, and are not comparable to nixpkgs versions.
+const DISTRIBUTION_ECOSYSTEMS: &[&str] = &[
+    "AlmaLinux",
+    "Alpine",
+    "Azure Linux",
+    "Chainguard",
+    "Debian",
+    "Mageia",
+    "openEuler",
+    "openSUSE",
+    "Photon OS",
+    "Red Hat",
+    "Rocky Linux",
+    "SUSE",
+    "Ubuntu",
+    "Wolfi",
+];
+
+/// How severe a vulnerability is, as reported by the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The database doesn't assign a severity to the vulnerability
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Interpret a severity label used by a database, e.g. `HIGH` or `MODERATE`
+    fn from_label(label: &str) -> Severity {
+        match label.to_ascii_lowercase().as_str() {
+            "low" => Severity::Low,
+            "medium" | "moderate" => Severity::Medium,
+            "high" => Severity::High,
+            "critical" => Severity::Critical,
+            _ => Severity::Unknown,
+        }
+    }
+
+    /// Interpret a CVSS base score
+    fn from_score(score: f64) -> Severity {
+        match score {
+            score if score >= 9.0 => Severity::Critical,
+            score if score >= 7.0 => Severity::High,
+            score if score >= 4.0 => Severity::Medium,
+            score if score > 0.0 => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Unknown => write!(f, "unknown"),
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(Severity::Unknown),
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!(
+                "unknown severity '{s}', expected one of 'unknown', 'low', 'medium', 'high' or 'critical'"
+            )),
+        }
+    }
+}
+
+/// A known vulnerability and the package versions it affects
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Vulnerability {
+    /// The identifier of the vulnerability, e.g. `CVE-2024-1234`
+    pub id: String,
+    /// Other identifiers of the same vulnerability
+    pub aliases: Vec<String>,
+    pub summary: Option<String>,
+    pub severity: Severity,
+    #[serde(skip)]
+    affected: Vec<AffectedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AffectedPackage {
+    name: String,
+    /// The OSV ecosystem of the package without a release, e.g. `crates.io`.
+    /// `None` for packages that are not specific to an ecosystem.
+    ecosystem: Option<String>,
+    /// Versions that are affected, regardless of `ranges`
+    versions: Vec<String>,
+    ranges: Vec<VersionRange>,
+}
+
+/// A range of versions, unbounded if `start` or `end` is `None`
+#[derive(Debug, Clone, PartialEq, Default)]
+struct VersionRange {
+    start: Option<VersionBound>,
+    end: Option<VersionBound>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct VersionBound {
+    version: String,
+    inclusive: bool,
+}
+
+impl VersionRange {
+    fn contains(&self, version: &str) -> bool {
+        let after_start = match &self.start {
+            Some(start) => match compare_versions(version, &start.version) {
+                Ordering::Greater => true,
+                Ordering::Equal => start.inclusive,
+                Ordering::Less => false,
+            },
+            None => true,
+        };
+        let before_end = match &self.end {
+            Some(end) => match compare_versions(version, &end.version) {
+                Ordering::Less => true,
+                Ordering::Equal => end.inclusive,
+                Ordering::Greater => false,
+            },
+            None => true,
+        };
+        after_start && before_end
+    }
+}
+
+impl Vulnerability {
+    /// Whether the vulnerability affects `version` of the package `pname`
+    fn affects(&self, pname: &str, version: &str) -> bool {
+        self.affected.iter().any(|affected| {
+            affected.name.eq_ignore_ascii_case(pname)
+                && (affected
+                    .versions
+                    .iter()
+                    .any(|affected_version| affected_version == version)
+                    || affected.ranges.iter().any(|range| range.contains(version)))
+        })
+    }
+}
+
+/// A collection of known vulnerabilities
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VulnerabilityDatabase {
+    pub vulnerabilities: Vec<Vulnerability>,
+}
+
+impl VulnerabilityDatabase {
+    /// Read a database in the OSV or NVD JSON format
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, AuditError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AuditError::ReadDatabase(path.display().to_string(), e))?;
+        Self::from_str(&contents)
+    }
+
+    /// Only match packages of the OSV `ecosystems`,
+    /// in addition to packages that are not specific to an ecosystem.
+    ///
+    /// Packages of other ecosystems may share names with packages in nixpkgs,
+    /// e.g. `openssl` on `crates.io`, so they are ignored by default.
+    /// Distribution ecosystems can't be selected, see [DISTRIBUTION_ECOSYSTEMS].
+    pub fn retain_ecosystems(&mut self, ecosystems: &[String]) -> Result<(), AuditError> {
+        if let Some(ecosystem) = ecosystems
+            .iter()
+            .find(|ecosystem| DISTRIBUTION_ECOSYSTEMS.contains(&ecosystem.as_str()))
+        {
+            return Err(AuditError::DistributionEcosystem(ecosystem.clone()));
+        }
+
+        for vulnerability in &mut self.vulnerabilities {
+            vulnerability
+                .affected
+                .retain(|affected| match &affected.ecosystem {
+                    Some(ecosystem) => ecosystems.contains(ecosystem),
+                    None => true,
+                });
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for VulnerabilityDatabase {
+    type Err = AuditError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse<T: serde::de::DeserializeOwned>(
+            value: serde_json::Value,
+        ) -> Result<T, AuditError> {
+            serde_json::from_value(value).map_err(AuditError::ParseDatabase)
+        }
+
+        let value: serde_json::Value =
+            serde_json::from_str(s).map_err(AuditError::ParseDatabase)?;
+        let has_key = |key| {
+            value
+                .as_object()
+                .is_some_and(|object| object.contains_key(key))
+        };
+
+        let vulnerabilities = if value.is_array() {
+            let records: Vec<osv::Record> = parse(value)?;
+            records.into_iter().map(Vulnerability::from).collect()
+        } else if has_key("vulnerabilities") {
+            let response: nvd::Response = parse(value)?;
+            response
+                .vulnerabilities
+                .into_iter()
+                .map(|item| Vulnerability::from(item.cve))
+                .collect()
+        } else if has_key("vulns") {
+            let list: osv::List = parse(value)?;
+            list.vulns.into_iter().map(Vulnerability::from).collect()
+        } else if has_key("affected") {
+            let record: osv::Record = parse(value)?;
+            vec![record.into()]
+        } else {
+            return Err(AuditError::UnknownFormat);
+        };
+
+        Ok(VulnerabilityDatabase { vulnerabilities })
+    }
+}
+
+/// Deserialization of the [OSV schema](https://ossf.github.io/osv-schema/)
+mod osv {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    pub struct List {
+        #[serde(default)]
+        pub vulns: Vec<Record>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Record {
+        pub id: String,
+        #[serde(default)]
+        pub aliases: Vec<String>,
+        pub summary: Option<String>,
+        pub details: Option<String>,
+        #[serde(default)]
+        pub severity: Vec<Severity>,
+        #[serde(default)]
+        pub affected: Vec<Affected>,
+        pub database_specific: Option<DatabaseSpecific>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Severity {
+        pub score: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct DatabaseSpecific {
+        pub severity: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Affected {
+        pub package: Option<Package>,
+        #[serde(default)]
+        pub ranges: Vec<Range>,
+        #[serde(default)]
+        pub versions: Vec<String>,
+        pub database_specific: Option<DatabaseSpecific>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Package {
+        pub name: String,
+        pub ecosystem: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Range {
+        #[serde(rename = "type")]
+        pub kind: String,
+        #[serde(default)]
+        pub events: Vec<Event>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Event {
+        Introduced(String),
+        Fixed(String),
+        LastAffected(String),
+        Limit(String),
+    }
+}
+
+impl From<osv::Record> for Vulnerability {
+    fn from(record: osv::Record) -> Self {
+        // Prefer the severity label assigned by the database,
+        // scores are only used if they are plain numbers rather than CVSS vectors.
+        let label = record
+            .database_specific
+            .as_ref()
+            .and_then(|specific| specific.severity.as_deref())
+            .or_else(|| {
+                record.affected.iter().find_map(|affected| {
+                    affected
+                        .database_specific
+                        .as_ref()
+                        .and_then(|specific| specific.severity.as_deref())
+                })
+            });
+        let severity = match label {
+            Some(label) => Severity::from_label(label),
+            None => record
+                .severity
+                .iter()
+                .filter_map(|severity| severity.score.parse().ok())
+                .map(Severity::from_score)
+                .max()
+                .unwrap_or(Severity::Unknown),
+        };
+
+        let affected = record
+            .affected
+            .into_iter()
+            .filter_map(|affected| {
+                let package = affected.package?;
+                // Ecosystems may be qualified with a release, e.g. `Debian:11`
+                let ecosystem =
+                    package
+                        .ecosystem
+                        .map(|ecosystem| match ecosystem.split_once(':') {
+                            Some((ecosystem, _release)) => ecosystem.to_string(),
+                            None => ecosystem,
+                        });
+                // Versions of distributions are not comparable to nixpkgs versions
+                if ecosystem
+                    .as_deref()
+                    .is_some_and(|ecosystem| DISTRIBUTION_ECOSYSTEMS.contains(&ecosystem))
+                {
+                    return None;
+                }
+                let ranges = affected
+                    .ranges
+                    .iter()
+                    // Git ranges refer to commits rather than versions
+                    .filter(|range| range.kind != "GIT")
+                    .flat_map(|range| osv_ranges(&range.events))
+                    .collect();
+                Some(AffectedPackage {
+                    name: package.name,
+                    ecosystem,
+                    versions: affected.versions,
+                    ranges,
+                })
+            })
+            .collect();
+
+        Vulnerability {
+            id: record.id,
+            aliases: record.aliases,
+            summary: record.summary.or(record.details),
+            severity,
+            affected,
+        }
+    }
+}
+
+/// Turn a sequence of OSV range events into version ranges.
+///
+/// Each `introduced` event opens a range,
+/// which is closed by the next `fixed` or `last_affected` event.
+fn osv_ranges(events: &[osv::Event]) -> Vec<VersionRange> {
+    let mut ranges = Vec::new();
+    let mut open: Option<VersionRange> = None;
+    for event in events {
+        match event {
+            osv::Event::Introduced(version) => {
+                ranges.extend(open.take());
+                let start = (version != "0").then(|| VersionBound {
+                    version: version.clone(),
+                    inclusive: true,
+                });
+                open = Some(VersionRange { start, end: None });
+            },
+            osv::Event::Fixed(version) | osv::Event::Limit(version) => {
+                let mut range = open.take().unwrap_or_default();
+                range.end = Some(VersionBound {
+                    version: version.clone(),
+                    inclusive: false,
+                });
+                ranges.push(range);
+            },
+            osv::Event::LastAffected(version) => {
+                let mut range = open.take().unwrap_or_default();
+                range.end = Some(VersionBound {
+                    version: version.clone(),
+                    inclusive: true,
+                });
+                ranges.push(range);
+            },
+        }
+    }
+    ranges.extend(open);
+    ranges
+}
+
+/// Deserialization of responses of the
+/// [NVD CVE API 2.0](https://nvd.nist.gov/developers/vulnerabilities)
+mod nvd {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    pub struct Response {
+        pub vulnerabilities: Vec<Item>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Item {
+        pub cve: Cve,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Cve {
+        pub id: String,
+        #[serde(default)]
+        pub descriptions: Vec<Description>,
+        #[serde(default)]
+        pub metrics: Metrics,
+        #[serde(default)]
+        pub configurations: Vec<Configuration>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Description {
+        pub lang: String,
+        pub value: String,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Metrics {
+        #[serde(default)]
+        pub cvss_metric_v40: Vec<Metric>,
+        #[serde(default)]
+        pub cvss_metric_v31: Vec<Metric>,
+        #[serde(default)]
+        pub cvss_metric_v30: Vec<Metric>,
+        #[serde(default)]
+        pub cvss_metric_v2: Vec<Metric>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Metric {
+        pub cvss_data: CvssData,
+        /// Set on the metric rather than `cvssData` for CVSS v2
+        pub base_severity: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CvssData {
+        pub base_score: Option<f64>,
+        pub base_severity: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Configuration {
+        #[serde(default)]
+        pub nodes: Vec<Node>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Node {
+        #[serde(default)]
+        pub cpe_match: Vec<CpeMatch>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CpeMatch {
+        pub vulnerable: bool,
+        pub criteria: String,
+        pub version_start_including: Option<String>,
+        pub version_start_excluding: Option<String>,
+        pub version_end_including: Option<String>,
+        pub version_end_excluding: Option<String>,
+    }
+}
+
+impl From<nvd::Cve> for Vulnerability {
+    fn from(cve: nvd::Cve) -> Self {
+        let metrics = &cve.metrics;
+        let severity = [
+            &metrics.cvss_metric_v40,
+            &metrics.cvss_metric_v31,
+            &metrics.cvss_metric_v30,
+            &metrics.cvss_metric_v2,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|metric| {
+            let label = metric
+                .cvss_data
+                .base_severity
+                .as_deref()
+                .or(metric.base_severity.as_deref());
+            match (label, metric.cvss_data.base_score) {
+                (Some(label), _) => Severity::from_label(label),
+                (None, Some(score)) => Severity::from_score(score),
+                (None, None) => Severity::Unknown,
+            }
+        })
+        .next()
+        .unwrap_or(Severity::Unknown);
+
+        let affected = cve
+            .configurations
+            .iter()
+            .flat_map(|configuration| &configuration.nodes)
+            .flat_map(|node| &node.cpe_match)
+            .filter(|cpe_match| cpe_match.vulnerable)
+            .filter_map(nvd_affected_package)
+            .collect();
+
+        let summary = cve
+            .descriptions
+            .iter()
+            .find(|description| description.lang == "en")
+            .map(|description| description.value.clone());
+
+        Vulnerability {
+            id: cve.id,
+            aliases: vec![],
+            summary,
+            severity,
+            affected,
+        }
+    }
+}
+
+/// Interpret a CPE match of the form
+/// `cpe:2.3:<part>:<vendor>:<product>:<version>:...` as an affected package,
+/// matching packages by the product name.
+fn nvd_affected_package(cpe_match: &nvd::CpeMatch) -> Option<AffectedPackage> {
+    let mut components = cpe_match.criteria.split(':').skip(4);
+    let name = components.next()?.to_string();
+    let version = components.next().unwrap_or("*");
+
+    if !matches!(version, "*" | "-" | "") {
+        return Some(AffectedPackage {
+            name,
+            ecosystem: None,
+            versions: vec![version.to_string()],
+            ranges: vec![],
+        });
+    }
+
+    let bound = |version: &Option<String>, inclusive: bool| {
+        version
+            .clone()
+            .map(|version| VersionBound { version, inclusive })
+    };
+    let range = VersionRange {
+        start: bound(&cpe_match.version_start_including, true)
+            .or_else(|| bound(&cpe_match.version_start_excluding, false)),
+        end: bound(&cpe_match.version_end_including, true)
+            .or_else(|| bound(&cpe_match.version_end_excluding, false)),
+    };
+    Some(AffectedPackage {
+        name,
+        ecosystem: None,
+        versions: vec![],
+        ranges: vec![range],
+    })
+}
+
+/// Split a version into components like Nix,
+/// at `.` and `-` and between runs of digits and other characters.
+fn version_components(version: &str) -> Vec<&str> {
+    let mut components = Vec::new();
+    let mut rest = version;
+    while let Some(start) = rest.find(|c| c != '.' && c != '-') {
+        rest = &rest[start..];
+        let is_digit = rest.starts_with(|c: char| c.is_ascii_digit());
+        let end = rest
+            .find(|c: char| c == '.' || c == '-' || c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        components.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    components
+}
+
+/// Whether component `a` is older than component `b`, following Nix
+fn component_less_than(a: &str, b: &str) -> bool {
+    let a_number = a.parse::<u64>().ok();
+    let b_number = b.parse::<u64>().ok();
+    match (a_number, b_number) {
+        (Some(a), Some(b)) => a < b,
+        _ if a.is_empty() && b_number.is_some() => true,
+        _ if a == "pre" && b != "pre" => true,
+        _ if b == "pre" => false,
+        // Non-numeric components are older than numeric ones
+        (None, Some(_)) => true,
+        (Some(_), None) => false,
+        (None, None) => a < b,
+    }
+}
+
+/// Compare two versions like Nix's `builtins.compareVersions`
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let a = version_components(a);
+    let b = version_components(b);
+    for i in 0..a.len().max(b.len()) {
+        let a = a.get(i).copied().unwrap_or("");
+        let b = b.get(i).copied().unwrap_or("");
+        if component_less_than(a, b) {
+            return Ordering::Less;
+        }
+        if component_less_than(b, a) {
+            return Ordering::Greater;
+        }
+    }
+    Ordering::Equal
+}
+
+/// A vulnerability affecting a locked package
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditFinding {
+    pub install_id: String,
+    pub pname: String,
+    pub version: String,
+    /// The systems the vulnerable version is locked for
+    pub systems: Vec<System>,
+    pub vulnerability: Vulnerability,
+}
+
+impl Display for AuditFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}] {} {}",
+            self.vulnerability.severity, self.vulnerability.id, self.install_id, self.version
+        )?;
+        if let Some(summary) = &self.vulnerability.summary {
+            write!(f, ": {summary}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of [audit_lockfile]
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct AuditReport {
+    /// Known vulnerabilities of locked packages, from most to least severe
+    pub findings: Vec<AuditFinding>,
+    /// Install IDs of packages marked as insecure by the catalog,
+    /// which are only locked if `options.allow.insecure` is set
+    pub insecure_packages: Vec<String>,
+}
+
+/// Match the packages in `lockfile` against the vulnerabilities in `database`
+///
+/// Catalog and flake packages are matched by their `pname` and version,
+/// packages installed from store paths are not audited.
+pub fn audit_lockfile(
+    lockfile: &LockedManifestCatalog,
+    database: &VulnerabilityDatabase,
+) -> AuditReport {
+    let mut findings: BTreeMap<(&str, &str, &str), AuditFinding> = BTreeMap::new();
+    let mut insecure_packages = Vec::new();
+
+    for package in &lockfile.packages {
+        let (pname, version, system) = match package {
+            LockedPackage::Catalog(pkg) => {
+                if pkg.insecure == Some(true) && !insecure_packages.contains(&pkg.install_id) {
+                    insecure_packages.push(pkg.install_id.clone());
+                }
+                (pkg.pname.as_str(), pkg.version.as_str(), &pkg.system)
+            },
+            LockedPackage::Flake(pkg) => {
+                let installable = &pkg.locked_installable;
+                let Some(version) = installable.version.as_deref() else {
+                    continue;
+                };
+                let pname = installable.pname.as_deref().unwrap_or(&installable.name);
+                (pname, version, &installable.system)
+            },
+            LockedPackage::StorePath(_) => continue,
+        };
+
+        for vulnerability in &database.vulnerabilities {
+            if !vulnerability.affects(pname, version) {
+                continue;
+            }
+            findings
+                .entry((package.install_id(), version, &vulnerability.id))
+                .or_insert_with(|| AuditFinding {
+                    install_id: package.install_id().to_string(),
+                    pname: pname.to_string(),
+                    version: version.to_string(),
+                    systems: vec![],
+                    vulnerability: vulnerability.clone(),
+                })
+                .systems
+                .push(system.clone());
+        }
+    }
+
+    let mut findings = findings
+        .into_values()
+        .map(|mut finding| {
+            finding.systems.sort();
+            finding
+        })
+        .collect::<Vec<_>>();
+    findings.sort_by_key(|finding| Reverse(finding.vulnerability.severity));
+    insecure_packages.sort();
+
+    AuditReport {
+        findings,
+        insecure_packages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::models::lockfile::test_helpers::fake_catalog_package_lock;
+
+    #[test]
+    fn compare_versions_like_nix() {
+        let cases = [
+            ("1.0", "2.3", Ordering::Less),
+            ("2.1", "2.3", Ordering::Less),
+            ("2.3", "2.3", Ordering::Equal),
+            ("2.5", "2.3", Ordering::Greater),
+            ("3.1", "2.3", Ordering::Greater),
+            ("2.3.1", "2.3", Ordering::Greater),
+            ("2.3.1", "2.3a", Ordering::Greater),
+            ("2.3pre1", "2.3", Ordering::Less),
+            ("2.3pre3", "2.3pre12", Ordering::Less),
+            ("2.3a", "2.3c", Ordering::Less),
+            ("2.3pre1", "2.3c", Ordering::Less),
+            ("2.3pre1", "2.3q", Ordering::Less),
+            ("1.1.1w", "1.1.1t", Ordering::Greater),
+            ("3.0.13", "3.0.9", Ordering::Greater),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(compare_versions(a, b), expected, "comparing {a} to {b}");
+        }
+    }
+
+    #[test]
+    fn osv_ranges_and_versions() {
+        let mut database = VulnerabilityDatabase::from_str(indoc! {r#"
+            [{
+                "id": "OSV-2024-1",
+                "aliases": ["CVE-2024-1"],
+                "summary": "bad things",
+                "database_specific": { "severity": "MODERATE" },
+                "affected": [{
+                    "package": { "ecosystem": "OSS-Fuzz", "name": "openssl" },
+                    "ranges": [
+                        { "type": "GIT", "events": [{ "introduced": "abc" }] },
+                        { "type": "ECOSYSTEM", "events": [
+                            { "introduced": "0" }, { "fixed": "1.1.1w" },
+                            { "introduced": "3.0.0" }, { "last_affected": "3.0.12" }
+                        ]}
+                    ],
+                    "versions": ["3.1.0"]
+                }]
+            }]
+        "#})
+        .unwrap();
+        database
+            .retain_ecosystems(&["OSS-Fuzz".to_string()])
+            .unwrap();
+        let vulnerability = &database.vulnerabilities[0];
+
+        assert_eq!(vulnerability.severity, Severity::Medium);
+        assert!(vulnerability.affects("openssl", "1.1.1t"));
+        assert!(!vulnerability.affects("openssl", "1.1.1w"));
+        assert!(vulnerability.affects("openssl", "3.0.12"));
+        assert!(!vulnerability.affects("openssl", "3.0.13"));
+        assert!(vulnerability.affects("openssl", "3.1.0"));
+        assert!(!vulnerability.affects("libressl", "1.1.1t"));
+    }
+
+    #[test]
+    fn nvd_cpe_matches() {
+        let database = VulnerabilityDatabase::from_str(indoc! {r#"
+            {
+                "vulnerabilities": [{
+                    "cve": {
+                        "id": "CVE-2024-2",
+                        "descriptions": [{ "lang": "en", "value": "worse things" }],
+                        "metrics": {
+                            "cvssMetricV31": [{
+                                "cvssData": { "baseScore": 9.8, "baseSeverity": "CRITICAL" }
+                            }]
+                        },
+                        "configurations": [{
+                            "nodes": [{
+                                "cpeMatch": [
+                                    {
+                                        "vulnerable": true,
+                                        "criteria": "cpe:2.3:a:gnu:hello:*:*:*:*:*:*:*:*",
+                                        "versionStartIncluding": "2.10",
+                                        "versionEndExcluding": "2.12.1"
+                                    },
+                                    {
+                                        "vulnerable": true,
+                                        "criteria": "cpe:2.3:a:gnu:hello:2.9:*:*:*:*:*:*:*"
+                                    },
+                                    {
+                                        "vulnerable": false,
+                                        "criteria": "cpe:2.3:o:linux:linux_kernel:-:*:*:*:*:*:*:*"
+                                    }
+                                ]
+                            }]
+                        }]
+                    }
+                }]
+            }
+        "#})
+        .unwrap();
+        let vulnerability = &database.vulnerabilities[0];
+
+        assert_eq!(vulnerability.severity, Severity::Critical);
+        assert_eq!(vulnerability.summary.as_deref(), Some("worse things"));
+        assert!(vulnerability.affects("hello", "2.12"));
+        assert!(vulnerability.affects("hello", "2.9"));
+        assert!(!vulnerability.affects("hello", "2.12.1"));
+        assert!(!vulnerability.affects("hello", "2.8"));
+        assert!(!vulnerability.affects("linux_kernel", "6.1"));
+    }
+
+    #[test]
+    fn audit_groups_findings_by_system() {
+        let database = VulnerabilityDatabase::from_str(indoc! {r#"
+            { "vulns": [
+                {
+                    "id": "LOW-1",
+                    "severity": [{ "type": "CVSS_V3", "score": "2.0" }],
+                    "affected": [{ "package": { "name": "hello" }, "versions": ["2.12"] }]
+                },
+                {
+                    "id": "HIGH-1",
+                    "database_specific": { "severity": "HIGH" },
+                    "affected": [{ "package": { "name": "hello" }, "versions": ["2.12"] }]
+                }
+            ]}
+        "#})
+        .unwrap();
+
+        let (_, _, mut hello) = fake_catalog_package_lock("hello", None);
+        hello.version = "2.12".to_string();
+        hello.insecure = Some(true);
+        let mut hello_linux = hello.clone();
+        hello_linux.system = "x86_64-linux".to_string();
+        let (_, _, mut curl) = fake_catalog_package_lock("curl", None);
+        curl.version = "2.12".to_string();
+
+        let lockfile = LockedManifestCatalog {
+            packages: vec![hello_linux.into(), hello.into(), curl.into()],
+            ..Default::default()
+        };
+        let report = audit_lockfile(&lockfile, &database);
+
+        let summary = report
+            .findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.vulnerability.id.as_str(),
+                    finding.vulnerability.severity,
+                    finding.systems.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let systems = vec!["aarch64-darwin".to_string(), "x86_64-linux".to_string()];
+        assert_eq!(summary, vec![
+            ("HIGH-1", Severity::High, systems.clone()),
+            ("LOW-1", Severity::Low, systems),
+        ]);
+        assert_eq!(report.insecure_packages, vec![
+            "hello_install_id".to_string()
+        ]);
+    }
+
+    /// Packages of other ecosystems are only matched if their ecosystem is selected
+    #[test]
+    fn osv_ecosystems_are_opt_in() {
+        let database = VulnerabilityDatabase::from_str(indoc! {r#"
+            [{
+                "id": "RUSTSEC-2023-1",
+                "affected": [{
+                    "package": { "ecosystem": "crates.io", "name": "openssl" },
+                    "ranges": [{ "type": "SEMVER", "events": [
+                        { "introduced": "0" }, { "fixed": "0.10.55" }
+                    ]}]
+                }]
+            }]
+        "#})
+        .unwrap();
+
+        let mut default = database.clone();
+        default.retain_ecosystems(&[]).unwrap();
+        assert!(!default.vulnerabilities[0].affects("openssl", "0.10.48"));
+
+        let mut crates = database;
+        crates
+            .retain_ecosystems(&["crates.io".to_string()])
+            .unwrap();
+        assert!(crates.vulnerabilities[0].affects("openssl", "0.10.48"));
+    }
+
+    /// Versions of distributions are never compared to nixpkgs versions
+    #[test]
+    fn osv_distribution_ecosystems_are_skipped() {
+        let mut database = VulnerabilityDatabase::from_str(indoc! {r#"
+            [{
+                "id": "DSA-5417-1",
+                "affected": [{
+                    "package": { "ecosystem": "Debian:11", "name": "openssl" },
+                    "ranges": [{ "type": "ECOSYSTEM", "events": [
+                        { "introduced": "0" }, { "fixed": "1.1.1n-0+deb11u5" }
+                    ]}],
+                    "versions": ["1.1.1n-0+deb11u4"]
+                }]
+            }]
+        "#})
+        .unwrap();
+        database.retain_ecosystems(&[]).unwrap();
+        assert!(!database.vulnerabilities[0].affects("openssl", "1.1.1n"));
+        assert!(!database.vulnerabilities[0].affects("openssl", "3.0.13"));
+
+        assert!(matches!(
+            database.retain_ecosystems(&["Debian".to_string()]),
+            Err(AuditError::DistributionEcosystem(ecosystem)) if ecosystem == "Debian"
+        ));
+    }
+
+    #[test]
+    fn unknown_database_format() {
+        assert!(matches!(
+            VulnerabilityDatabase::from_str(r#"{ "packages": [] }"#),
+            Err(AuditError::UnknownFormat)
+        ));
+    }
+}
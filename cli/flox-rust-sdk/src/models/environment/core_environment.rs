@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -7,8 +8,10 @@ use std::str::FromStr;
 
 use log::debug;
 use pollster::FutureExt;
+use tempfile::TempPath;
 use thiserror::Error;
 
+use super::include::{IncludeError, IncludeFetcher};
 use super::{
     copy_dir_recursive,
     CanonicalizeError,
@@ -22,7 +25,9 @@ use super::{
 use crate::data::CanonicalPath;
 use crate::flox::Flox;
 use crate::models::container_builder::ContainerBuilder;
+use crate::models::environment_diff::LockfileDiff;
 use crate::models::lockfile::{
+    Compose,
     LockedManifest,
     LockedManifestCatalog,
     LockedManifestError,
@@ -39,8 +44,8 @@ use crate::models::manifest::{
     TomlEditError,
     TypedManifest,
     TypedManifestCatalog,
-    MANIFEST_VERSION_KEY,
 };
+use crate::models::migration::{migrate_manifest, MigrationError};
 use crate::models::pkgdb::{
     call_pkgdb,
     error_codes,
@@ -49,7 +54,7 @@ use crate::models::pkgdb::{
     PkgDbError,
     PKGDB_BIN,
 };
-use crate::providers::catalog::{self, ClientTrait};
+use crate::providers::catalog::ClientTrait;
 use crate::providers::flox_cpp_utils::InstallableLocker;
 use crate::providers::services::{maybe_make_service_config_file, ServiceError};
 
@@ -68,6 +73,8 @@ pub struct CoreEnvironment<State = ReadOnly> {
     ///
     /// Commonly /.../.flox/env/
     env_dir: PathBuf,
+    /// Fetches environments included by the manifest
+    include_fetcher: IncludeFetcher,
     _state: State,
 }
 
@@ -148,7 +155,10 @@ impl<State> CoreEnvironment<State> {
         // itself have the same contents
         let already_locked = match (manifest, &lockfile) {
             (TypedManifest::Catalog(manifest), LockedManifest::Catalog(lock)) => {
-                *manifest == lock.manifest
+                match &lock.compose {
+                    Some(compose) => *manifest == compose.composer,
+                    None => *manifest == lock.manifest,
+                }
             },
             (TypedManifest::Pkgdb(_), LockedManifest::Catalog(_)) => {
                 return Err(CoreEnvironmentError::LockingVersion0NotSupported);
@@ -230,7 +240,21 @@ impl<State> CoreEnvironment<State> {
     /// The caller is responsible for skipping calls to lock when an environment
     /// is already locked.
     /// For that reason, this always writes the lockfile to disk.
+    ///
+    /// If [Flox::locked] is set, the lockfile is never written.
+    /// An up to date lockfile is returned as is,
+    /// and a missing or outdated lockfile is an error
+    /// rather than resolving packages that differ from the committed lockfile.
     pub fn lock(&mut self, flox: &Flox) -> Result<LockedManifest, CoreEnvironmentError> {
+        if flox.locked {
+            return match self.lockfile_if_up_to_date()? {
+                Some(lockfile) => Ok(lockfile),
+                None => Err(CoreEnvironmentError::LockfileOutdated(
+                    self.outdated_lockfile()?,
+                )),
+            };
+        }
+
         let manifest = self.manifest()?;
 
         let lockfile = match manifest {
@@ -239,11 +263,7 @@ impl<State> CoreEnvironment<State> {
             },
             TypedManifest::Catalog(manifest) => {
                 tracing::debug!("using catalog client to lock");
-                LockedManifest::Catalog(self.lock_with_catalog_client(
-                    &flox.catalog_client,
-                    &flox.installable_locker,
-                    *manifest,
-                )?)
+                LockedManifest::Catalog(self.lock_with_catalog_client(flox, *manifest)?)
             },
         };
 
@@ -263,15 +283,65 @@ impl<State> CoreEnvironment<State> {
         Ok(lockfile)
     }
 
+    /// Determine why the lockfile is not up to date with the manifest,
+    /// listing the package descriptors that invalidate their locked resolution.
+    fn outdated_lockfile(&self) -> Result<OutdatedLockfile, CoreEnvironmentError> {
+        let Ok(lockfile_path) = CanonicalPath::new(self.lockfile_path()) else {
+            return Ok(OutdatedLockfile::Missing);
+        };
+        let TypedManifest::Catalog(manifest) = self.manifest()? else {
+            return Err(CoreEnvironmentError::LockingVersion0NotSupported);
+        };
+        let lockfile = LockedManifest::read_from_file(&lockfile_path)
+            .map_err(CoreEnvironmentError::LockedManifest)?;
+
+        let locked_manifest = match &lockfile {
+            LockedManifest::Catalog(lockfile) => match &lockfile.compose {
+                Some(compose) => compose.composer.clone(),
+                None => lockfile.manifest.clone(),
+            },
+            // No package of a version 0 lockfile can be reused
+            LockedManifest::Pkgdb(_) => Default::default(),
+        };
+        let locked_install = &locked_manifest.install;
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for (install_id, descriptor) in manifest.install.iter() {
+            match locked_install.get(install_id) {
+                None => added.push(install_id.clone()),
+                Some(locked_descriptor)
+                    if descriptor.invalidates_existing_resolution(locked_descriptor) =>
+                {
+                    changed.push(install_id.clone())
+                },
+                Some(_) => {},
+            }
+        }
+        let removed = locked_install
+            .keys()
+            .filter(|install_id| !manifest.install.contains_key(*install_id))
+            .cloned()
+            .collect();
+        let include_changed = manifest.include != locked_manifest.include;
+
+        Ok(OutdatedLockfile::ManifestChanged {
+            added,
+            changed,
+            removed,
+            include_changed,
+        })
+    }
+
     /// Lock the environment with the catalog client
     ///
-    /// If a lockfile exists, it is used as a base.
+    /// If a lockfile exists, it is used as a base,
+    /// including the manifests of included environments pinned by it.
     /// If the manifest should be locked without a base,
     /// remove the lockfile before calling this function or use [Self::upgrade].
     fn lock_with_catalog_client(
         &self,
-        client: &catalog::Client,
-        installable_locker: &impl InstallableLocker,
+        flox: &Flox,
         manifest: TypedManifestCatalog,
     ) -> Result<LockedManifestCatalog, CoreEnvironmentError> {
         let existing_lockfile = 'lockfile: {
@@ -292,14 +362,29 @@ impl<State> CoreEnvironment<State> {
             }
         };
 
-        LockedManifestCatalog::lock_manifest(
+        let (manifest, compose) = self
+            .include_fetcher
+            .compose(
+                flox,
+                &manifest,
+                existing_lockfile
+                    .as_ref()
+                    .and_then(|lockfile| lockfile.compose.as_ref()),
+                false,
+            )
+            .map_err(CoreEnvironmentError::Include)?;
+
+        let mut lockfile = LockedManifestCatalog::lock_manifest(
             &manifest,
             existing_lockfile.as_ref(),
-            client,
-            installable_locker,
+            &flox.catalog_client,
+            &flox.installable_locker,
         )
         .block_on()
-        .map_err(CoreEnvironmentError::LockedManifest)
+        .map_err(CoreEnvironmentError::LockedManifest)?;
+        lockfile.compose = compose;
+
+        Ok(lockfile)
     }
 
     /// Build the environment.
@@ -332,7 +417,14 @@ impl<State> CoreEnvironment<State> {
             .map_err(CoreEnvironmentError::LockedManifest)?;
 
         let mut pkgdb_cmd = Command::new(Path::new(&*PKGDB_BIN));
-        pkgdb_cmd.arg("buildenv").arg(lockfile_path);
+        pkgdb_cmd.arg("buildenv");
+        // The lockfile for the current system is removed when dropped,
+        // so keep it until pkgdb returns
+        let system_lockfile = maybe_write_lockfile_for_system(flox, &lockfile)?;
+        match &system_lockfile {
+            Some(system_lockfile_path) => pkgdb_cmd.arg(system_lockfile_path),
+            None => pkgdb_cmd.arg(lockfile_path),
+        };
 
         let service_config_path = if let LockedManifest::Catalog(ref lockfile) = lockfile {
             maybe_make_service_config_file(flox, lockfile)?
@@ -362,6 +454,42 @@ impl<State> CoreEnvironment<State> {
     }
 }
 
+/// Write a copy of `lockfile` with the system specific `vars`, `hook` and
+/// `profile` sections of its manifest merged for the current system,
+/// as pkgdb only builds the common sections.
+///
+/// Returns `None` if the manifest doesn't have system specific sections,
+/// in which case the lockfile can be built as is.
+/// The written lockfile is deleted when the returned [TempPath] is dropped.
+fn maybe_write_lockfile_for_system(
+    flox: &Flox,
+    lockfile: &LockedManifest,
+) -> Result<Option<TempPath>, CoreEnvironmentError> {
+    let LockedManifest::Catalog(lockfile) = lockfile else {
+        return Ok(None);
+    };
+    if !lockfile.manifest.has_system_overrides() {
+        return Ok(None);
+    }
+
+    let system_lockfile = LockedManifest::Catalog(LockedManifestCatalog {
+        manifest: lockfile.manifest.copy_for_system(&flox.system),
+        ..lockfile.clone()
+    });
+    let mut file = tempfile::NamedTempFile::new_in(&flox.temp_dir)
+        .map_err(CoreEnvironmentError::WriteLockfile)?;
+    file.write_all(system_lockfile.to_string().as_bytes())
+        .map_err(CoreEnvironmentError::WriteLockfile)?;
+    let path = file.into_temp_path();
+    debug!(
+        "wrote lockfile for system {} to {}",
+        flox.system,
+        path.display()
+    );
+
+    Ok(Some(path))
+}
+
 impl CoreEnvironment<()> {
     /// Creates a [ContainerBuilder] from the environment.
     ///
@@ -383,6 +511,7 @@ impl CoreEnvironment<()> {
     /// so force the caller to handle locking by taking lockfile_path as an
     /// argument.
     pub fn build_container(
+        flox: &Flox,
         lockfile_path: CanonicalPath,
         name: &str,
         tag: &str,
@@ -393,14 +522,23 @@ impl CoreEnvironment<()> {
             ));
         }
 
+        let lockfile = LockedManifest::read_from_file(&lockfile_path)
+            .map_err(CoreEnvironmentError::LockedManifest)?;
+
         let mut pkgdb_cmd = Command::new(Path::new(&*PKGDB_BIN));
         pkgdb_cmd
             .arg("buildenv")
             .arg("--container")
             .arg(name)
             .arg("--container-tag")
-            .arg(tag)
-            .arg(lockfile_path);
+            .arg(tag);
+        // The lockfile for the current system is removed when dropped,
+        // so keep it until pkgdb returns
+        let system_lockfile = maybe_write_lockfile_for_system(flox, &lockfile)?;
+        match &system_lockfile {
+            Some(system_lockfile_path) => pkgdb_cmd.arg(system_lockfile_path),
+            None => pkgdb_cmd.arg(lockfile_path),
+        };
 
         // Locking flakes may require using `ssh` for private flakes,
         // so don't clear PATH
@@ -448,10 +586,20 @@ impl CoreEnvironment<ReadOnly> {
     pub fn new(env_dir: impl AsRef<Path>) -> Self {
         CoreEnvironment {
             env_dir: env_dir.as_ref().to_path_buf(),
+            include_fetcher: IncludeFetcher::default(),
             _state: ReadOnly {},
         }
     }
 
+    /// Resolve environments included by the manifest with `include_fetcher`
+    ///
+    /// Without an explicit [IncludeFetcher],
+    /// only absolute paths and FloxHub environments can be included.
+    pub fn with_include_fetcher(mut self, include_fetcher: IncludeFetcher) -> Self {
+        self.include_fetcher = include_fetcher;
+        self
+    }
+
     /// Install packages to the environment atomically
     ///
     /// Returns the new manifest content if the environment was modified. Also
@@ -645,27 +793,81 @@ impl CoreEnvironment<ReadOnly> {
     /// First resolve a new lockfile with upgraded packages using either pkgdb or the catalog client.
     /// Then verify the new lockfile by building the environment.
     /// Finally replace the existing environment with the new, upgraded one.
+    ///
+    /// If [Flox::locked] is set, upgrading fails as it writes a new lockfile,
+    /// use [Self::dry_upgrade] to preview upgrades instead.
     pub fn upgrade(
         &mut self,
         flox: &Flox,
         groups_or_iids: &[&str],
     ) -> Result<UpgradeResult, CoreEnvironmentError> {
+        if flox.locked {
+            return Err(CoreEnvironmentError::UpgradeLocked);
+        }
+
+        let (lockfile, mut result) = self.resolve_upgrade(flox, groups_or_iids)?;
+
+        let store_path =
+            self.transact_with_lockfile_contents(serde_json::json!(&lockfile).to_string(), flox)?;
+        result.store_path = Some(store_path);
+
+        Ok(result)
+    }
+
+    /// Resolve the upgrades of packages in this environment
+    /// without modifying or building the environment.
+    ///
+    /// The returned [UpgradeResult] has no store path.
+    pub fn dry_upgrade(
+        &mut self,
+        flox: &Flox,
+        groups_or_iids: &[&str],
+    ) -> Result<UpgradeResult, CoreEnvironmentError> {
+        let (_, result) = self.resolve_upgrade(flox, groups_or_iids)?;
+        Ok(result)
+    }
+
+    /// Resolve a new lockfile with upgraded packages
+    /// and describe the upgrades compared to the existing lockfile.
+    fn resolve_upgrade(
+        &mut self,
+        flox: &Flox,
+        groups_or_iids: &[&str],
+    ) -> Result<(LockedManifest, UpgradeResult), CoreEnvironmentError> {
         tracing::debug!(to_upgrade = groups_or_iids.join(","), "upgrading");
         let manifest = self.manifest()?;
 
-        let (lockfile, upgraded) = match manifest {
+        let (lockfile, upgraded, includes, diff) = match manifest {
             TypedManifest::Pkgdb(_) => {
                 return Err(CoreEnvironmentError::LockingVersion0NotSupported);
             },
             TypedManifest::Catalog(catalog) => {
-                Self::ensure_valid_upgrade(groups_or_iids, &catalog)?;
+                let existing_lockfile = match self.existing_lockfile()? {
+                    Some(LockedManifest::Catalog(lockfile)) => Some(lockfile),
+                    _ => None,
+                };
+                // Included environments are only refreshed when upgrading the whole environment
+                let existing_compose = existing_lockfile
+                    .as_ref()
+                    .and_then(|lockfile| lockfile.compose.clone());
+                let (merged, compose) = self
+                    .include_fetcher
+                    .compose(
+                        flox,
+                        &catalog,
+                        existing_compose.as_ref(),
+                        groups_or_iids.is_empty(),
+                    )
+                    .map_err(CoreEnvironmentError::Include)?;
+
+                Self::ensure_valid_upgrade(groups_or_iids, &merged)?;
                 tracing::debug!("using catalog client to upgrade");
 
-                let (lockfile, upgraded) = self.upgrade_with_catalog_client(
+                let (mut lockfile, upgraded) = self.upgrade_with_catalog_client(
                     &flox.catalog_client,
                     &flox.installable_locker,
                     groups_or_iids,
-                    &catalog,
+                    &merged,
                 )?;
 
                 let upgraded = {
@@ -679,17 +881,23 @@ impl CoreEnvironment<ReadOnly> {
                     install_ids
                 };
 
-                (LockedManifest::Catalog(lockfile), upgraded)
+                let includes = changed_includes(existing_compose.as_ref(), compose.as_ref());
+                lockfile.compose = compose;
+
+                let diff = existing_lockfile
+                    .map(|existing_lockfile| LockfileDiff::new(&existing_lockfile, &lockfile))
+                    .unwrap_or_default();
+
+                (LockedManifest::Catalog(lockfile), upgraded, includes, diff)
             },
         };
 
-        let store_path =
-            self.transact_with_lockfile_contents(serde_json::json!(&lockfile).to_string(), flox)?;
-
-        Ok(UpgradeResult {
+        Ok((lockfile, UpgradeResult {
             packages: upgraded,
-            store_path: Some(store_path),
-        })
+            includes,
+            diff,
+            store_path: None,
+        }))
     }
 
     fn ensure_valid_upgrade(
@@ -822,17 +1030,16 @@ impl CoreEnvironment<ReadOnly> {
         };
 
         // todo: handle flake diffs
-        // Iterate over the two sorted maps in lockstep
-        // Since BTreeMap is ordered and we must have the same packages before
-        // and after upgrading, we can zip the two iterators together knowing
-        // that we'll visit the same install_id from each map at the same time.
+        // Match packages by install_id and system.
+        // Packages may only be added or removed by an upgrade
+        // if an included environment changed.
         let package_diff = previous_packages
             .iter()
-            .zip(pkgs_after_upgrade.iter())
-            // Extract LockedPackage
-            .flat_map(|((_prev_id, prev_map), (_curr_id, curr_map))| {
-                let curr_iter = curr_map.iter().map(|(_sys, pkg)| pkg);
-                prev_map.iter().map(|(_sys, pkg)| pkg).zip(curr_iter)
+            .flat_map(|(id, prev_map)| {
+                let curr_map = pkgs_after_upgrade.get(id);
+                prev_map.iter().filter_map(move |(system, prev_pkg)| {
+                    Some((prev_pkg, curr_map?.get(system)?))
+                })
             })
             // Keep anything that has been upgraded, using a change in
             // derivation to define upgraded for both flake and catalog packages.
@@ -845,10 +1052,13 @@ impl CoreEnvironment<ReadOnly> {
             })
             .collect::<Vec<_>>();
 
-        let final_lockfile = if package_diff.is_empty() {
-            existing_lockfile.unwrap_or(upgraded_lockfile)
-        } else {
-            upgraded_lockfile
+        let final_lockfile = match existing_lockfile {
+            Some(existing_lockfile)
+                if package_diff.is_empty() && existing_lockfile.manifest == *manifest =>
+            {
+                existing_lockfile
+            },
+            _ => upgraded_lockfile,
         };
 
         Ok((final_lockfile, package_diff))
@@ -865,6 +1075,7 @@ impl CoreEnvironment<ReadOnly> {
 
         Ok(CoreEnvironment {
             env_dir: tempdir.as_ref().to_path_buf(),
+            include_fetcher: self.include_fetcher.clone(),
             _state: ReadWrite {},
         })
     }
@@ -996,9 +1207,11 @@ impl CoreEnvironment<ReadOnly> {
         Ok(store_path)
     }
 
+    /// Migrate the manifest to the latest version and lock it again.
+    ///
     /// Should not be called with
     /// !migration_info.needs_manifest_migration && !migration_info.needs_upgrade
-    pub fn migrate_to_v1(
+    pub fn migrate(
         &mut self,
         flox: &Flox,
         mut migration_info: MigrationInfo,
@@ -1014,7 +1227,7 @@ impl CoreEnvironment<ReadOnly> {
         let mut temp_env = self.writable(&tempdir)?;
 
         if migration_info.needs_manifest_migration {
-            Self::migrate_manifest_contents_to_v1(&mut migration_info.raw_manifest)?;
+            Self::migrate_manifest_contents(&mut migration_info.raw_manifest)?;
 
             debug!("migration transaction: updating manifest");
             temp_env.update_manifest(migration_info.raw_manifest.to_string())?;
@@ -1039,46 +1252,32 @@ impl CoreEnvironment<ReadOnly> {
         Ok(store_path)
     }
 
-    /// Migrate a v0 [RawManifest] to a v1 [RawManifest] by inserting
-    /// `version = 1` and moving `hook.script` to `hook.on-activate` if
-    /// `hook.on-activate` doesn't already exist.
+    /// Migrate a [RawManifest] to the latest manifest version
+    /// by running all pending [crate::models::migration::MANIFEST_MIGRATIONS].
     ///
-    /// `raw_manifest` is expected to be a v0 manifest.
-    /// Return an error if the resulting manifest is not a valid v1 manifest.
+    /// Return an error if the resulting manifest is not a valid manifest.
     /// Note that the modifications are still made even if an error is returned to allow
     /// [Self::migrate_and_edit_unsafe] to use the invalid manifest.
-    fn migrate_manifest_contents_to_v1(
+    fn migrate_manifest_contents(
         raw_manifest: &mut RawManifest,
     ) -> Result<(), CoreEnvironmentError> {
-        // // Insert `version = 1`
-        raw_manifest.insert(MANIFEST_VERSION_KEY, toml_edit::value(1));
-
-        // Migrate `hook.script` to `hook.on-activate`
-        let hook = raw_manifest.get_mut("hook").and_then(|s| s.as_table_mut());
-        if let Some(hook) = hook {
-            if hook.get("on-activate").is_none() {
-                // Rename `hook.script` to `hook.on-activate`, preserving
-                // comments and formatting
-                if let Some((script_key, script_item)) = hook.remove_entry("script") {
-                    // Unit tests cover this is safe to unwrap
-                    let mut on_activate = toml_edit::Key::from_str("on-activate").unwrap();
-                    let mut on_activate_key = on_activate.as_mut();
-                    let decor = on_activate_key.leaf_decor_mut();
-                    *decor = script_key.leaf_decor().clone();
-                    let dotted_decor = on_activate_key.dotted_decor_mut();
-                    *dotted_decor = script_key.dotted_decor().clone();
-                    // Does not preserve order of hooks,
-                    // but we only have one field in the hook section.
-                    hook.insert_formatted(&on_activate, script_item);
+        match migrate_manifest(raw_manifest) {
+            Ok(migrations) => {
+                for migration in migrations {
+                    debug!(
+                        "migrated manifest from version {} to {}: {}",
+                        migration.from,
+                        migration.to(),
+                        migration.description
+                    );
                 }
-            }
+                Ok(())
+            },
+            Err(MigrationError::InvalidManifest(err)) => {
+                Err(CoreEnvironmentError::MigrateManifest(err))
+            },
+            Err(err) => Err(CoreEnvironmentError::MigrationVersion(err)),
         }
-
-        // Make sure it parses
-        raw_manifest
-            .to_typed()
-            .map_err(CoreEnvironmentError::MigrateManifest)?;
-        Ok(())
     }
 
     /// Replace manifest with provided `contents` and perform migration in a
@@ -1101,7 +1300,7 @@ impl CoreEnvironment<ReadOnly> {
         let mut raw_manifest = RawManifest::from_str(&contents)
             .map_err(|e| CoreEnvironmentError::ModifyToml(TomlEditError::ParseManifest(e)))?;
 
-        let migrate_result = Self::migrate_manifest_contents_to_v1(&mut raw_manifest);
+        let migrate_result = Self::migrate_manifest_contents(&mut raw_manifest);
 
         debug!("migration transaction: updating manifest");
         temp_env.update_manifest(raw_manifest.to_string())?;
@@ -1236,9 +1435,106 @@ impl EditResult {
 #[derive(Debug)]
 pub struct UpgradeResult {
     pub packages: Vec<String>,
+    /// Names of included environments whose manifest changed
+    pub includes: Vec<String>,
+    /// Changes to the locked packages on each system
+    pub diff: LockfileDiff,
     pub store_path: Option<PathBuf>,
 }
 
+/// Names of the included environments in `after`
+/// that were not included with the same contents in `before`
+fn changed_includes(before: Option<&Compose>, after: Option<&Compose>) -> Vec<String> {
+    let Some(after) = after else {
+        return Vec::new();
+    };
+    after
+        .include
+        .iter()
+        .filter(|include| {
+            !before.is_some_and(|before| {
+                before
+                    .include
+                    .iter()
+                    .any(|prev| prev.name == include.name && prev.hash == include.hash)
+            })
+        })
+        .map(|include| include.name.clone())
+        .collect()
+}
+
+/// Why an environment can't be used without locking it again,
+/// see [CoreEnvironment::lock]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutdatedLockfile {
+    /// The environment has not been locked
+    Missing,
+    /// The manifest was modified since the environment was locked
+    ManifestChanged {
+        /// Install IDs of packages added to the manifest
+        added: Vec<String>,
+        /// Install IDs of packages whose descriptor changed
+        /// in a way that invalidates their locked resolution
+        changed: Vec<String>,
+        /// Install IDs of packages removed from the manifest
+        removed: Vec<String>,
+        /// Whether the `[include]` section changed,
+        /// which requires locking the included environments again
+        include_changed: bool,
+    },
+}
+
+impl Display for OutdatedLockfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (added, changed, removed, include_changed) = match self {
+            OutdatedLockfile::Missing => return write!(f, "The environment has not been locked."),
+            OutdatedLockfile::ManifestChanged {
+                added,
+                changed,
+                removed,
+                include_changed,
+            } => (added, changed, removed, *include_changed),
+        };
+
+        write!(
+            f,
+            "The manifest was modified since the environment was locked."
+        )?;
+        if added.is_empty() && changed.is_empty() && removed.is_empty() && !include_changed {
+            return write!(
+                f,
+                "\nNo packages would be resolved again, \
+                 but sections of the manifest other than 'install' changed, e.g. 'options'."
+            );
+        }
+        if !added.is_empty() || !changed.is_empty() {
+            write!(f, "\nThe following packages would be resolved again:")?;
+        }
+        for install_id in added {
+            write!(f, "\n  - '{install_id}' (added)")?;
+        }
+        for install_id in changed {
+            write!(f, "\n  - '{install_id}' (changed)")?;
+        }
+        if !removed.is_empty() {
+            write!(
+                f,
+                "\nThe following packages would be removed from the lockfile:"
+            )?;
+        }
+        for install_id in removed {
+            write!(f, "\n  - '{install_id}'")?;
+        }
+        if include_changed {
+            write!(
+                f,
+                "\nThe 'include' section changed, so included environments would be locked again."
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum CoreEnvironmentError {
     // region: immutable manifest errors
@@ -1290,9 +1586,18 @@ pub enum CoreEnvironmentError {
     #[error(transparent)]
     LockedManifest(LockedManifestError),
 
+    #[error(transparent)]
+    Include(IncludeError),
+
     #[error(transparent)]
     BadLockfilePath(CanonicalizeError),
 
+    #[error("lockfile is out of date")]
+    LockfileOutdated(OutdatedLockfile),
+
+    #[error("can't upgrade packages without locking the environment")]
+    UpgradeLocked,
+
     // todo: refactor upgrade to use `LockedManifest`
     #[error("failed to upgrade environment")]
     UpgradeFailedCatalog(#[source] UpgradeError),
@@ -1315,8 +1620,10 @@ pub enum CoreEnvironmentError {
     #[error("unsupported system to build container: {0}")]
     ContainerizeUnsupportedSystem(String),
 
-    #[error("could not automatically migrate manifest to version 1")]
+    #[error("could not automatically migrate manifest to the latest version")]
     MigrateManifest(#[source] toml_edit::de::Error),
+    #[error("could not migrate manifest")]
+    MigrationVersion(#[source] MigrationError),
 
     #[error("failed to create version 1 lock")]
     LockForMigration(#[source] Box<CoreEnvironmentError>),
@@ -1475,12 +1782,6 @@ mod tests {
     use std::os::unix::fs::PermissionsExt;
     use std::str::FromStr;
 
-    use catalog::{
-        Client,
-        MsgAttrPathNotFoundNotFoundForAllSystems,
-        GENERATED_DATA,
-        MANUALLY_GENERATED,
-    };
     use catalog_api_v1::types::{MessageLevel, ResolvedPackageDescriptor, SystemEnum};
     use chrono::{DateTime, Utc};
     use flox_core::Version;
@@ -1490,7 +1791,6 @@ mod tests {
     use test_helpers::{new_core_environment_from_env_files, new_core_environment_with_lockfile};
     use tests::test_helpers::MANIFEST_INCOMPATIBLE_SYSTEM;
 
-    use self::catalog::{CatalogPage, MockClient, ResolvedPackageGroup};
     use self::test_helpers::new_core_environment;
     use super::*;
     use crate::flox::test_helpers::flox_instance;
@@ -1498,10 +1798,22 @@ mod tests {
     use crate::models::lockfile::test_helpers::fake_catalog_package_lock;
     use crate::models::lockfile::ResolutionFailures;
     use crate::models::manifest::{
+        IncludeDescriptor,
+        IncludeDescriptorLocal,
         ManifestPackageDescriptorCatalog,
+        ManifestVariable,
         RawManifest,
         DEFAULT_GROUP_NAME,
     };
+    use crate::providers::catalog::{
+        CatalogPage,
+        Client,
+        MockClient,
+        MsgAttrPathNotFoundNotFoundForAllSystems,
+        ResolvedPackageGroup,
+        GENERATED_DATA,
+        MANUALLY_GENERATED,
+    };
     use crate::providers::flox_cpp_utils::InstallableLockerMock;
     use crate::providers::services::SERVICE_CONFIG_FILENAME;
 
@@ -1684,6 +1996,7 @@ mod tests {
             version: Version,
             packages: vec![foo_locked.into()],
             manifest: manifest.clone(),
+            compose: None,
         };
 
         let lockfile_str = serde_json::to_string_pretty(&lockfile).unwrap();
@@ -1736,6 +2049,227 @@ mod tests {
         assert!(upgraded_packages.len() == 1);
     }
 
+    /// The lockfile written for the current system is removed once it is dropped
+    #[test]
+    fn lockfile_for_system_is_removed_when_dropped() {
+        let (flox, _temp_dir_handle) = flox_instance();
+        let manifest: TypedManifestCatalog = toml::from_str(&formatdoc! {r#"
+            version = 1
+
+            [vars]
+            FOO = "common"
+
+            [vars.{system}]
+            FOO = "system"
+        "#, system = flox.system})
+        .unwrap();
+        let lockfile = LockedManifest::Catalog(lockfile::LockedManifestCatalog {
+            version: Version,
+            packages: vec![],
+            manifest,
+            compose: None,
+        });
+
+        let path = maybe_write_lockfile_for_system(&flox, &lockfile)
+            .unwrap()
+            .expect("manifest has system overrides");
+        let written: LockedManifestCatalog =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            written.manifest.vars.0["FOO"],
+            ManifestVariable::from("system")
+        );
+
+        let path_buf = path.to_path_buf();
+        drop(path);
+        assert!(!path_buf.exists());
+    }
+
+    /// Upgrading fails without modifying the environment if locking is disabled
+    #[test]
+    fn upgrade_fails_when_locked() {
+        let (mut flox, _temp_dir_handle) = flox_instance();
+        flox.locked = true;
+
+        let (foo_iid, foo_descriptor, foo_locked) = fake_catalog_package_lock("foo", None);
+        let mut manifest = TypedManifestCatalog::default();
+        manifest.install.insert(foo_iid, foo_descriptor);
+        let lockfile = lockfile::LockedManifestCatalog {
+            version: Version,
+            packages: vec![foo_locked.into()],
+            manifest: manifest.clone(),
+            compose: None,
+        };
+        let lockfile_contents = serde_json::to_string_pretty(&lockfile).unwrap();
+        let mut env_view = new_core_environment_with_lockfile(
+            &flox,
+            &toml::to_string(&manifest).unwrap(),
+            &lockfile_contents,
+        );
+
+        let err = env_view.upgrade(&flox, &[]).unwrap_err();
+        assert!(matches!(err, CoreEnvironmentError::UpgradeLocked));
+        assert_eq!(
+            fs::read_to_string(env_view.lockfile_path()).unwrap(),
+            lockfile_contents
+        );
+    }
+
+    #[test]
+    fn outdated_lockfile_without_package_changes_mentions_other_sections() {
+        let outdated = OutdatedLockfile::ManifestChanged {
+            added: vec![],
+            changed: vec![],
+            removed: vec![],
+            include_changed: false,
+        };
+        assert_eq!(outdated.to_string(), indoc! {"
+            The manifest was modified since the environment was locked.
+            No packages would be resolved again, but sections of the manifest other than 'install' changed, e.g. 'options'."
+        });
+    }
+
+    #[test]
+    fn outdated_lockfile_mentions_removed_packages_and_includes() {
+        let outdated = OutdatedLockfile::ManifestChanged {
+            added: vec![],
+            changed: vec![],
+            removed: vec!["hello".to_string()],
+            include_changed: true,
+        };
+        assert_eq!(outdated.to_string(), indoc! {"
+            The manifest was modified since the environment was locked.
+            The following packages would be removed from the lockfile:
+              - 'hello'
+            The 'include' section changed, so included environments would be locked again."
+        });
+    }
+
+    /// Removing packages and changing includes are reported as such,
+    /// rather than as changes to other sections of the manifest
+    #[test]
+    fn lock_reports_removed_packages_and_include_changes_when_locked() {
+        let (mut flox, _temp_dir_handle) = flox_instance();
+        flox.locked = true;
+
+        let (hello_iid, hello_descriptor, hello_locked) = fake_catalog_package_lock("hello", None);
+        let mut manifest = TypedManifestCatalog::default();
+        manifest.install.insert(hello_iid.clone(), hello_descriptor);
+        let lockfile = LockedManifestCatalog {
+            version: Version::<1>,
+            manifest: manifest.clone(),
+            packages: vec![hello_locked.into()],
+            compose: None,
+        };
+        let mut env_view = new_core_environment_with_lockfile(
+            &flox,
+            &toml::to_string(&manifest).unwrap(),
+            &serde_json::to_string_pretty(&lockfile).unwrap(),
+        );
+
+        manifest.install.remove(&hello_iid);
+        manifest
+            .include
+            .environments
+            .push(IncludeDescriptor::Local(IncludeDescriptorLocal {
+                dir: "../other".into(),
+                name: None,
+            }));
+        fs::write(
+            env_view.manifest_path(),
+            toml::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        let err = env_view.ensure_locked(&flox).unwrap_err();
+        let CoreEnvironmentError::LockfileOutdated(outdated) = err else {
+            panic!("expected LockfileOutdated error, got {err:?}");
+        };
+        assert_eq!(outdated, OutdatedLockfile::ManifestChanged {
+            added: vec![],
+            changed: vec![],
+            removed: vec![hello_iid],
+            include_changed: true,
+        });
+    }
+
+    /// A dry upgrade reports version changes without modifying the environment
+    #[test]
+    fn dry_upgrade_reports_versions_without_modifying_environment() {
+        let (mut flox, _temp_dir_handle) = flox_instance();
+        // Previewing upgrades doesn't write a lockfile, so it is allowed
+        flox.locked = true;
+
+        let mut manifest = TypedManifestCatalog::default();
+        let (foo_iid, foo_descriptor, mut foo_locked) = fake_catalog_package_lock("foo", None);
+        foo_locked.version = "0.9".to_string();
+        manifest.install.insert(foo_iid.clone(), foo_descriptor);
+        let lockfile = lockfile::LockedManifestCatalog {
+            version: Version,
+            packages: vec![foo_locked.into()],
+            manifest: manifest.clone(),
+            compose: None,
+        };
+        let manifest_contents = toml::to_string(&manifest).unwrap();
+        let lockfile_contents = serde_json::to_string_pretty(&lockfile).unwrap();
+        let mut env_view =
+            new_core_environment_with_lockfile(&flox, &manifest_contents, &lockfile_contents);
+
+        let mut mock_client = MockClient::new(None::<&str>).unwrap();
+        mock_client.push_resolve_response(vec![ResolvedPackageGroup {
+            name: DEFAULT_GROUP_NAME.to_string(),
+            page: Some(CatalogPage {
+                packages: Some(vec![ResolvedPackageDescriptor {
+                    attr_path: "foo".to_string(),
+                    broken: Some(false),
+                    derivation: "new derivation".to_string(),
+                    description: Some("description".to_string()),
+                    insecure: Some(false),
+                    install_id: foo_iid.clone(),
+                    license: None,
+                    locked_url: "locked-url".to_string(),
+                    name: "foo".to_string(),
+                    outputs: vec![],
+                    outputs_to_install: None,
+                    pname: "foo".to_string(),
+                    rev: "rev".to_string(),
+                    rev_count: 42,
+                    rev_date: DateTime::<Utc>::MIN_UTC,
+                    scrape_date: DateTime::<Utc>::MIN_UTC,
+                    stabilities: None,
+                    unfree: None,
+                    version: "1.0".to_string(),
+                    system: SystemEnum::Aarch64Darwin,
+                }]),
+                msgs: vec![],
+                page: 1,
+                url: "url".to_string(),
+                complete: true,
+            }),
+            msgs: vec![],
+        }]);
+        flox.catalog_client = mock_client.into();
+
+        let result = env_view.dry_upgrade(&flox, &[]).unwrap();
+
+        assert_eq!(result.packages, vec![foo_iid.clone()]);
+        assert!(result.store_path.is_none());
+        let changes = &result.diff.systems[&SystemEnum::Aarch64Darwin.to_string()].changed;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].install_id, foo_iid);
+        assert_eq!(changes[0].old_version.as_deref(), Some("0.9"));
+        assert_eq!(changes[0].new_version.as_deref(), Some("1.0"));
+
+        assert_eq!(
+            fs::read_to_string(env_view.manifest_path()).unwrap(),
+            manifest_contents
+        );
+        assert_eq!(
+            fs::read_to_string(env_view.lockfile_path()).unwrap(),
+            lockfile_contents
+        );
+    }
+
     /// replacing an environment should fail if a backup exists
     #[test]
     fn detects_existing_backup() {
@@ -1824,8 +2358,70 @@ mod tests {
             .exists());
     }
 
+    /// With [Flox::locked] set, an up to date lockfile is returned as is,
+    /// and an outdated or missing lockfile is an error
+    /// listing the descriptors that invalidate the lock.
     #[test]
-    fn migrate_to_v1_error_for_dropped_field() {
+    fn lock_fails_for_outdated_lockfile_when_locked() {
+        let (mut flox, _temp_dir_handle) = flox_instance();
+        flox.locked = true;
+
+        let (hello_iid, hello_descriptor, hello_locked) = fake_catalog_package_lock("hello", None);
+        let mut manifest = TypedManifestCatalog::default();
+        manifest.install.insert(hello_iid.clone(), hello_descriptor);
+        let lockfile = LockedManifestCatalog {
+            version: Version::<1>,
+            manifest: manifest.clone(),
+            packages: vec![hello_locked.into()],
+            compose: None,
+        };
+        let lockfile_contents = serde_json::to_string_pretty(&lockfile).unwrap();
+        let mut env_view = new_core_environment_with_lockfile(
+            &flox,
+            &toml::to_string(&manifest).unwrap(),
+            &lockfile_contents,
+        );
+
+        env_view.lock(&flox).unwrap();
+
+        let (curl_iid, curl_descriptor, _) = fake_catalog_package_lock("curl", None);
+        manifest.install.insert(curl_iid.clone(), curl_descriptor);
+        if let Some(ManifestPackageDescriptor::Catalog(descriptor)) =
+            manifest.install.get_mut(&hello_iid)
+        {
+            descriptor.version = Some("2.10".to_string());
+        }
+        fs::write(
+            env_view.manifest_path(),
+            toml::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        let err = env_view.ensure_locked(&flox).unwrap_err();
+        let CoreEnvironmentError::LockfileOutdated(outdated) = err else {
+            panic!("expected LockfileOutdated error, got {err:?}");
+        };
+        assert_eq!(outdated, OutdatedLockfile::ManifestChanged {
+            added: vec![curl_iid],
+            changed: vec![hello_iid],
+            removed: vec![],
+            include_changed: false,
+        });
+        assert_eq!(
+            fs::read_to_string(env_view.lockfile_path()).unwrap(),
+            lockfile_contents
+        );
+
+        fs::remove_file(env_view.lockfile_path()).unwrap();
+        let err = env_view.lock(&flox).unwrap_err();
+        assert!(matches!(
+            err,
+            CoreEnvironmentError::LockfileOutdated(OutdatedLockfile::Missing)
+        ));
+    }
+
+    #[test]
+    fn migrate_error_for_dropped_field() {
         let (flox, _temp_dir_handle) = flox_instance();
         let contents = indoc! {r#"
             [options]
@@ -1837,7 +2433,7 @@ mod tests {
         let raw_manifest = RawManifest::from_str(contents).unwrap();
 
         let err = environment
-            .migrate_to_v1(&flox, MigrationInfo {
+            .migrate(&flox, MigrationInfo {
                 raw_manifest,
                 needs_manifest_migration: true,
                 needs_upgrade: false,
@@ -1852,7 +2448,7 @@ mod tests {
     }
 
     #[test]
-    fn migrate_to_v1_error_for_locking() {
+    fn migrate_error_for_locking() {
         let (mut flox, _temp_dir_handle) = flox_instance();
         // The v0 lockfile should get ignored,
         // but create it just to keep this more realistic
@@ -1877,7 +2473,7 @@ mod tests {
         let raw_manifest = RawManifest::from_str(&manifest_contents).unwrap();
 
         let err = environment
-            .migrate_to_v1(&flox, MigrationInfo {
+            .migrate(&flox, MigrationInfo {
                 raw_manifest,
                 needs_manifest_migration: true,
                 needs_upgrade: true,
@@ -1914,113 +2510,6 @@ mod tests {
         }
     }
 
-    /// [CoreEnvironment::migrate_manifest_contents_to_v1] migrates a manifest
-    /// with `script` in a `[hook]` table correctly, maintaining comments and
-    /// formatting.
-    #[test]
-    fn migrate_script_hook_table() {
-        let contents = formatdoc! {r#"
-            [vars]
-            foo = "bar"
-
-            # comment 1
-            [hook] # comment 2
-            # comment 3
-             script = "echo hello" # comment 4
-            # comment 5
-
-            [options]
-            "#};
-        let mut raw_manifest = RawManifest::from_str(&contents).unwrap();
-        CoreEnvironment::migrate_manifest_contents_to_v1(&mut raw_manifest).unwrap();
-        assert_eq!(raw_manifest.to_string(), formatdoc! {r#"
-                version = 1
-                [vars]
-                foo = "bar"
-
-                # comment 1
-                [hook] # comment 2
-                # comment 3
-                 on-activate = "echo hello" # comment 4
-                # comment 5
-
-                [options]
-                "#
-        });
-    }
-
-    /// [CoreEnvironment::migrate_manifest_contents_to_v1] migrates a manifest
-    /// with hook.script as a dotted key correctly, maintaining comments and
-    /// formatting.
-    #[test]
-    fn migrate_script_hook_dotted_decor() {
-        let contents = formatdoc! {r#"
-            vars.foo = "bar"
-
-            # comment 1
-            hook . script = "echo hello" # comment 2
-            # comment 3
-
-            options.allow.unfree = false
-            "#};
-        let mut raw_manifest = RawManifest::from_str(&contents).unwrap();
-        CoreEnvironment::migrate_manifest_contents_to_v1(&mut raw_manifest).unwrap();
-        assert_eq!(raw_manifest.to_string(), formatdoc! {r#"
-                vars.foo = "bar"
-
-                # comment 1
-                hook . on-activate = "echo hello" # comment 2
-                # comment 3
-
-                options.allow.unfree = false
-                version = 1
-                "#
-        });
-    }
-
-    /// If a manifest contains both `hook.script` and `hook.on-activate`,
-    /// [CoreEnvironment::migrate_manifest_contents_to_v1] returns an error.
-    #[test]
-    fn migrate_script_skip_for_on_activate() {
-        let contents = formatdoc! {r#"
-            [hook]
-            script = "echo foo"
-            on-activate = "echo bar"
-            "#};
-        let mut raw_manifest = RawManifest::from_str(&contents).unwrap();
-        let err = CoreEnvironment::migrate_manifest_contents_to_v1(&mut raw_manifest).unwrap_err();
-        assert_eq!(raw_manifest.to_string(), formatdoc! {r#"
-                version = 1
-                [hook]
-                script = "echo foo"
-                on-activate = "echo bar"
-                "#
-        });
-        if let CoreEnvironmentError::MigrateManifest(e) = err {
-            assert!(e.message().contains("unknown field `script`"));
-        } else {
-            panic!("expected MigrateManifest error");
-        }
-    }
-
-    /// Even if a manifest fails validation, it is still modified by
-    /// [CoreEnvironment::migrate_manifest_contents_to_v1].
-    #[test]
-    fn migrate_script_modifies_on_error() {
-        let contents = formatdoc! {r#"
-            [hook]
-            script = "echo hello"
-            on-activate = "echo hello"
-            "#};
-        let mut raw_manifest = RawManifest::from_str(&contents).unwrap();
-        assert!(raw_manifest.get("version").is_none());
-        CoreEnvironment::migrate_manifest_contents_to_v1(&mut raw_manifest).unwrap_err();
-        assert_eq!(
-            raw_manifest.get("version").unwrap().as_integer().unwrap(),
-            1
-        );
-    }
-
     #[test]
     fn v0_does_not_need_relock() {
         let (flox, _temp_dir_handle) = flox_instance();
@@ -2093,6 +2582,7 @@ mod tests {
                     priority: None,
                     version: None,
                     systems: None,
+                    outputs: None,
                 }),
             );
         }
@@ -21,16 +21,22 @@ use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use flox_core::Version;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use thiserror::Error;
 
 use super::core_environment::CoreEnvironment;
 use super::{copy_dir_recursive, ENV_DIR_NAME};
-use crate::flox::EnvironmentName;
-use crate::models::environment::MANIFEST_FILENAME;
+use crate::data::System;
+use crate::flox::{EnvironmentName, Flox, FLOX_VERSION};
+use crate::models::environment::{LOCKFILE_FILENAME, MANIFEST_FILENAME};
+use crate::models::environment_diff::EnvironmentDiff;
+use crate::models::environment_ref::GenerationTag;
+use crate::models::lockfile::{LockedManifest, LockedManifestCatalog};
+use crate::models::manifest::{RawManifest, TypedManifest, TypedManifestCatalog};
 use crate::providers::git::{
     GitCommandError,
     GitCommandOptions,
@@ -111,6 +117,118 @@ impl<S> Generations<S> {
         return Ok(manifest_osstr.to_string_lossy().to_string());
     }
 
+    /// Read the lockfile of a given generation and return its contents as a string
+    ///
+    /// Returns `None` if the generation does not contain a lockfile.
+    pub fn lockfile(&self, generation: usize) -> Result<Option<String>, GenerationsError> {
+        let metadata = self.metadata()?;
+        if !metadata.generations.contains_key(&generation.into()) {
+            return Err(GenerationsError::GenerationNotFound(generation));
+        }
+
+        // `git show` on a tree lists its entries
+        let env_tree = self
+            .repo
+            .show(&format!("{}:{}/{}", self.branch, generation, ENV_DIR_NAME))
+            .map_err(GenerationsError::ShowLockfile)?;
+        if !env_tree
+            .to_string_lossy()
+            .lines()
+            .any(|entry| entry == LOCKFILE_FILENAME)
+        {
+            return Ok(None);
+        }
+
+        let lockfile_osstr = self
+            .repo
+            .show(&format!(
+                "{}:{}/{}/{}",
+                self.branch, generation, ENV_DIR_NAME, LOCKFILE_FILENAME
+            ))
+            .map_err(GenerationsError::ShowLockfile)?;
+
+        Ok(Some(lockfile_osstr.to_string_lossy().to_string()))
+    }
+
+    /// Write the manifest and lockfile of a given generation to `path`
+    /// and return a [CoreEnvironment] for them.
+    ///
+    /// Unlike [Generations::get_generation], the generation is read
+    /// directly from the repository, without a writable checkout of the branch.
+    pub fn copy_generation(
+        &self,
+        generation: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<CoreEnvironment, GenerationsError> {
+        let path = path.as_ref();
+        let manifest = self.manifest(generation)?;
+        let lockfile = self.lockfile(generation)?;
+
+        fs::create_dir_all(path).map_err(GenerationsError::WriteManifest)?;
+        fs::write(path.join(MANIFEST_FILENAME), manifest)
+            .map_err(GenerationsError::WriteManifest)?;
+        if let Some(lockfile) = lockfile {
+            fs::write(path.join(LOCKFILE_FILENAME), lockfile)
+                .map_err(GenerationsError::WriteLockfile)?;
+        }
+
+        Ok(CoreEnvironment::new(path))
+    }
+
+    /// Compare two generations
+    ///
+    /// Returns a summary of the changes to packages, variables, hooks and services
+    /// from generation `from` to generation `to`.
+    /// Locked package versions are compared for the given `system`.
+    /// Generations with a v0 manifest can not be compared.
+    pub fn diff(
+        &self,
+        from: usize,
+        to: usize,
+        system: &System,
+    ) -> Result<EnvironmentDiff, GenerationsError> {
+        let (from_manifest, from_lockfile) = self.typed_generation(from)?;
+        let (to_manifest, to_lockfile) = self.typed_generation(to)?;
+
+        Ok(EnvironmentDiff::new(
+            &from_manifest,
+            from_lockfile.as_ref(),
+            &to_manifest,
+            to_lockfile.as_ref(),
+            system,
+        ))
+    }
+
+    /// Read and parse the manifest and lockfile of a generation
+    ///
+    /// Lockfiles of v0 environments are ignored.
+    fn typed_generation(
+        &self,
+        generation: usize,
+    ) -> Result<(TypedManifestCatalog, Option<LockedManifestCatalog>), GenerationsError> {
+        let manifest = self
+            .manifest(generation)?
+            .parse::<RawManifest>()
+            .map_err(GenerationsError::ParseManifest)?
+            .to_typed()
+            .map_err(GenerationsError::ParseManifest)?;
+        let TypedManifest::Catalog(manifest) = manifest else {
+            return Err(GenerationsError::UnsupportedManifestVersion(generation));
+        };
+
+        let lockfile = match self.lockfile(generation)? {
+            Some(contents) => {
+                match serde_json::from_str(&contents).map_err(GenerationsError::ParseLockfile)? {
+                    LockedManifest::Catalog(lockfile) => Some(lockfile),
+                    LockedManifest::Pkgdb(_) => None,
+                }
+            },
+            None => None,
+        };
+
+        Ok((*manifest, lockfile))
+    }
+
     /// Read the manifest of the current generation and return its contents as a string
     pub fn current_gen_manifest(&self) -> Result<String, GenerationsError> {
         let metadata = self.metadata()?;
@@ -209,6 +327,11 @@ impl Generations<ReadWrite> {
     ///   When a generation needs to be used again after being modified,
     ///   it is recommended to create a new [Generations<ReadWrite>] instance first.
     pub fn get_generation(&self, generation: usize) -> Result<CoreEnvironment, GenerationsError> {
+        let metadata = self.metadata()?;
+        if !metadata.generations.contains_key(&generation.into()) {
+            return Err(GenerationsError::GenerationNotFound(generation));
+        }
+
         let environment = CoreEnvironment::new(
             self.repo
                 .path()
@@ -247,10 +370,10 @@ impl Generations<ReadWrite> {
         &mut self,
         environment: &mut CoreEnvironment,
         generation: usize,
-        description: String,
+        mut generation_metadata: SingleGenerationMetadata,
         set_current: bool,
     ) -> Result<(), GenerationsError> {
-        let mut generation_metadata = SingleGenerationMetadata::new(description.clone());
+        let description = generation_metadata.description.clone();
 
         let mut metadata = self.metadata()?;
 
@@ -308,7 +431,7 @@ impl Generations<ReadWrite> {
     pub fn add_generation(
         &mut self,
         environment: &mut CoreEnvironment,
-        description: String,
+        metadata: SingleGenerationMetadata,
     ) -> Result<(), GenerationsError> {
         // Returns the highest numbered generation so we know which number to assign
         // the new one. We don't support rollbacks or checking out specific generations
@@ -324,24 +447,24 @@ impl Generations<ReadWrite> {
             .max()
             .unwrap_or_default();
 
-        self.register_generation(environment, *max + 1, description, true)
+        self.register_generation(environment, *max + 1, metadata, true)
     }
 
     /// Switch to a provided generation.
     ///
     /// Fails if the generation does not exist.
+    /// Marks the generation as last active now.
     ///
     /// This method will not perform any validation of the generation switched to.
     /// If validation (e.g. proving that the environment builds) is required,
-    /// it should first be realized using [Self::realize_generation].
-    #[allow(unused)]
-    fn set_current_generation(&mut self, generation: usize) -> Result<(), GenerationsError> {
+    /// it should first be realized using [Self::get_generation].
+    pub fn set_current_generation(&mut self, generation: usize) -> Result<(), GenerationsError> {
         let mut metadata = self.metadata()?;
 
-        let generation_metadata = metadata.generations.contains_key(&generation.into());
-        if !generation_metadata {
+        let Some(generation_metadata) = metadata.generations.get_mut(&generation.into()) else {
             return Err(GenerationsError::GenerationNotFound(generation));
-        }
+        };
+        generation_metadata.last_active = Some(Utc::now());
 
         metadata.current_gen = Some(generation.into());
 
@@ -349,14 +472,133 @@ impl Generations<ReadWrite> {
 
         self.repo
             .add(&[Path::new(GENERATIONS_METADATA_FILE)])
-            .unwrap();
+            .map_err(GenerationsError::StageChanges)?;
         self.repo
             .commit(&format!("Set current generation to {}", generation))
-            .unwrap();
-        self.repo.push("origin", false).unwrap();
+            .map_err(GenerationsError::CommitChanges)?;
+        self.repo
+            .push("origin", false)
+            .map_err(GenerationsError::CompleteTransaction)?;
 
         Ok(())
     }
+
+    /// Tag a generation with a name, see [GenerationTag].
+    ///
+    /// Fails if the generation does not exist.
+    /// If the tag already refers to another generation, it is moved to `generation`.
+    pub fn tag_generation(
+        &mut self,
+        tag: GenerationTag,
+        generation: usize,
+    ) -> Result<(), GenerationsError> {
+        let mut metadata = self.metadata()?;
+
+        if !metadata.generations.contains_key(&generation.into()) {
+            return Err(GenerationsError::GenerationNotFound(generation));
+        }
+
+        metadata.tags.insert(tag.clone(), generation.into());
+
+        write_metadata_file(metadata, self.repo.path())?;
+
+        self.repo
+            .add(&[Path::new(GENERATIONS_METADATA_FILE)])
+            .map_err(GenerationsError::StageChanges)?;
+        self.repo
+            .commit(&format!("Tag generation {generation} as '{tag}'"))
+            .map_err(GenerationsError::CommitChanges)?;
+        self.repo
+            .push("origin", false)
+            .map_err(GenerationsError::CompleteTransaction)?;
+
+        Ok(())
+    }
+
+    /// Remove a tag
+    ///
+    /// Fails if the tag does not exist.
+    /// Returns the generation the tag referred to.
+    pub fn remove_tag(&mut self, tag: &GenerationTag) -> Result<GenerationId, GenerationsError> {
+        let mut metadata = self.metadata()?;
+
+        let Some(generation) = metadata.tags.remove(tag) else {
+            return Err(GenerationsError::TagNotFound(tag.clone()));
+        };
+
+        write_metadata_file(metadata, self.repo.path())?;
+
+        self.repo
+            .add(&[Path::new(GENERATIONS_METADATA_FILE)])
+            .map_err(GenerationsError::StageChanges)?;
+        self.repo
+            .commit(&format!("Remove tag '{tag}' from generation {generation}"))
+            .map_err(GenerationsError::CommitChanges)?;
+        self.repo
+            .push("origin", false)
+            .map_err(GenerationsError::CompleteTransaction)?;
+
+        Ok(generation)
+    }
+
+    /// Remove generations that are not retained by the given [RetentionPolicy]
+    ///
+    /// The current generation and tagged generations are never removed.
+    /// Removed generations are deleted from the metadata file
+    /// together with their generation folders.
+    ///
+    /// Returns the removed generations.
+    /// If no generation is removed, no commit is created.
+    pub fn prune(
+        &mut self,
+        policy: &RetentionPolicy,
+    ) -> Result<Vec<GenerationId>, GenerationsError> {
+        let mut metadata = self.metadata()?;
+
+        let pruned = metadata.generations_to_prune(policy, Utc::now());
+        if pruned.is_empty() {
+            return Ok(pruned);
+        }
+
+        for generation in &pruned {
+            metadata.generations.remove(generation);
+        }
+        write_metadata_file(metadata, self.repo.path())?;
+
+        let generation_paths = pruned
+            .iter()
+            .map(|generation| PathBuf::from(generation.to_string()))
+            .collect::<Vec<_>>();
+        self.repo
+            .rm(
+                &generation_paths
+                    .iter()
+                    .map(|p| p.as_path())
+                    .collect::<Vec<_>>(),
+                true,
+                true,
+                false,
+            )
+            .map_err(GenerationsError::RemoveGenerations)?;
+        self.repo
+            .add(&[Path::new(GENERATIONS_METADATA_FILE)])
+            .map_err(GenerationsError::StageChanges)?;
+
+        self.repo
+            .commit(&format!(
+                "Prune generations {}",
+                pruned
+                    .iter()
+                    .map(|generation| generation.to_string())
+                    .join(", ")
+            ))
+            .map_err(GenerationsError::CommitChanges)?;
+        self.repo
+            .push("origin", false)
+            .map_err(GenerationsError::CompleteTransaction)?;
+
+        Ok(pruned)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -388,6 +630,8 @@ pub enum GenerationsError {
     GenerationNotFound(usize),
     #[error("no generations found in environment")]
     NoGenerations,
+    #[error("tag '{0}' not found")]
+    TagNotFound(GenerationTag),
     // endregion
 
     // region: repo/transaction
@@ -399,13 +643,25 @@ pub enum GenerationsError {
     CommitChanges(#[source] GitCommandError),
     #[error("could not complete transaction")]
     CompleteTransaction(#[source] GitRemoteCommandError),
+    #[error("could not remove generations")]
+    RemoveGenerations(#[source] GitCommandError),
     // endregion
 
     // region: manifest errors
     #[error("could not write manifest file")]
     WriteManifest(#[source] std::io::Error),
+    #[error("could not write lockfile")]
+    WriteLockfile(#[source] std::io::Error),
     #[error("could not show manifest file")]
     ShowManifest(#[source] GitCommandError),
+    #[error("could not show lockfile")]
+    ShowLockfile(#[source] GitCommandError),
+    #[error("could not parse manifest")]
+    ParseManifest(#[source] toml_edit::de::Error),
+    #[error("could not parse lockfile")]
+    ParseLockfile(#[source] serde_json::Error),
+    #[error("generation {0} uses a deprecated manifest version and can not be compared")]
+    UnsupportedManifestVersion(usize),
     // endregion
 }
 
@@ -468,11 +724,92 @@ pub struct AllGenerationsMetadata {
     /// Entries in this map must match up 1-to-1 with the generation folders
     /// in the environment branch.
     pub generations: BTreeMap<GenerationId, SingleGenerationMetadata>,
+    /// Named references to generations, see [GenerationTag]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<GenerationTag, GenerationId>,
     /// Schema version of the metadata file, not yet utilized
     #[serde(default)]
     version: Version<1>,
 }
 
+impl AllGenerationsMetadata {
+    /// Return the generations that are not retained by the given [RetentionPolicy]
+    /// at time `now`, in ascending order.
+    ///
+    /// Never includes the current generation or tagged generations.
+    pub fn generations_to_prune(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Vec<GenerationId> {
+        if policy.keep_last.is_none() && policy.max_age.is_none() {
+            return Vec::new();
+        }
+
+        let newest = self
+            .generations
+            .keys()
+            .rev()
+            .take(policy.keep_last.unwrap_or(0))
+            .collect::<Vec<_>>();
+
+        self.generations
+            .iter()
+            .filter(|(id, generation)| {
+                let is_current = self.current_gen.as_ref() == Some(*id);
+                let is_tagged = self.tags.values().any(|tagged| tagged == *id);
+                let is_newest = newest.contains(id);
+                let is_recent = policy
+                    .max_age
+                    .is_some_and(|max_age| generation.created > now - max_age);
+                !(is_current || is_tagged || is_newest || is_recent)
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Return the generation a tag refers to, if the tag exists
+    pub fn resolve_tag(&self, tag: &GenerationTag) -> Option<GenerationId> {
+        self.tags.get(tag).cloned()
+    }
+
+    /// Return the tags referring to a generation
+    pub fn tags_of(&self, generation: &GenerationId) -> Vec<&GenerationTag> {
+        self.tags
+            .iter()
+            .filter(|(_, tagged)| *tagged == generation)
+            .map(|(tag, _)| tag)
+            .collect()
+    }
+
+    /// Return the generation preceding the current generation,
+    /// i.e. the highest numbered generation lower than the current one.
+    ///
+    /// Returns `None` if there is no current generation
+    /// or the current generation is the oldest one.
+    pub fn previous_generation(&self) -> Option<GenerationId> {
+        let current_gen = self.current_gen.as_ref()?;
+        self.generations
+            .range(..current_gen)
+            .next_back()
+            .map(|(id, _)| id.clone())
+    }
+}
+
+/// Which generations to keep when pruning generations
+///
+/// A generation is kept if it is one of the `keep_last` newest generations
+/// or if it was created less than `max_age` ago.
+/// If neither is set, all generations are kept.
+/// The current generation and tagged generations are always kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Number of newest generations to keep
+    pub keep_last: Option<usize>,
+    /// Keep generations created within this duration
+    pub max_age: Option<Duration>,
+}
+
 /// Metadata for a single generation of an environment
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -488,10 +825,31 @@ pub struct SingleGenerationMetadata {
 
     /// log message(s) describing the change from the previous generation
     pub description: String,
-    // todo: do we still need to track this?
-    //       do we now?
-    // /// store path of the built generation
-    // path: PathBuf,
+
+    /// FloxHub user that created this generation
+    ///
+    /// `None` for generations created without being logged in
+    /// or by versions of flox that did not record provenance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// Version of flox that created this generation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flox_version: Option<String>,
+
+    /// The command that created this generation, e.g. `install` or `edit`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    /// The system this generation was created and built on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<System>,
+
+    /// Store path of the built generation
+    ///
+    /// `None` if the generation was not built when it was created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_path: Option<PathBuf>,
 }
 
 impl SingleGenerationMetadata {
@@ -501,8 +859,36 @@ impl SingleGenerationMetadata {
             created: Utc::now(),
             last_active: None,
             description,
+            author: None,
+            flox_version: None,
+            command: None,
+            system: None,
+            store_path: None,
         }
     }
+
+    /// Create metadata for a generation created by `command`
+    ///
+    /// Records the logged in FloxHub user, the running flox version
+    /// and the current system as provenance of the generation.
+    pub fn from_command(flox: &Flox, command: &str, description: String) -> Self {
+        Self {
+            author: flox
+                .floxhub_token
+                .as_ref()
+                .map(|token| token.handle().to_string()),
+            flox_version: Some(FLOX_VERSION.to_string()),
+            command: Some(command.to_string()),
+            system: Some(flox.system.clone()),
+            ..Self::new(description)
+        }
+    }
+
+    /// Set the store path of the built generation
+    pub fn with_store_path(mut self, store_path: Option<PathBuf>) -> Self {
+        self.store_path = store_path;
+        self
+    }
 }
 
 #[derive(
@@ -525,6 +911,399 @@ pub struct GenerationId(usize);
 
 #[cfg(test)]
 mod tests {
-    // todo: tests for this will be easier with the `init` method implemented
-    // in https://github.com/flox/flox/pull/563
+    use indoc::indoc;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::flox::test_helpers::flox_instance;
+    use crate::models::environment_diff::PackageVersion;
+
+    /// Create a generations branch with `n` generations
+    /// each containing a manifest with a distinct comment.
+    fn generations_with(n: usize) -> (Generations<ReadWrite>, TempDir) {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let mut options = GitCommandOptions::default();
+        options.add_config_flag("user.name", "Flox Test");
+        options.add_config_flag("user.email", "test@flox.invalid");
+
+        fs::create_dir_all(tempdir.path().join("checkout")).unwrap();
+        let generations = Generations::init(
+            options,
+            tempdir.path().join("checkout"),
+            tempdir.path().join("bare"),
+            "test".to_string(),
+            &"test".parse().unwrap(),
+        )
+        .unwrap();
+
+        let mut generations = generations.writable(tempdir.path()).unwrap();
+
+        for i in 1..=n {
+            add_generation(
+                &mut generations,
+                tempdir.path(),
+                &format!("# generation {i}"),
+                None,
+            );
+        }
+
+        (generations, tempdir)
+    }
+
+    /// Add a generation with the given manifest and lockfile contents
+    fn add_generation(
+        generations: &mut Generations<ReadWrite>,
+        tempdir: &Path,
+        manifest: &str,
+        lockfile: Option<&str>,
+    ) {
+        let env_path = tempfile::tempdir_in(tempdir).unwrap().into_path();
+        fs::write(env_path.join(MANIFEST_FILENAME), manifest).unwrap();
+        if let Some(lockfile) = lockfile {
+            fs::write(env_path.join(LOCKFILE_FILENAME), lockfile).unwrap();
+        }
+        generations
+            .add_generation(
+                &mut CoreEnvironment::new(env_path),
+                SingleGenerationMetadata::new("change".to_string()),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn set_current_generation_updates_metadata() {
+        let (mut generations, _tempdir) = generations_with(3);
+
+        let before = generations.metadata().unwrap();
+        assert_eq!(before.current_gen, Some(3.into()));
+
+        generations.set_current_generation(1).unwrap();
+
+        let after = generations.metadata().unwrap();
+        assert_eq!(after.current_gen, Some(1.into()));
+        assert!(
+            after.generations[&1.into()].last_active >= before.generations[&3.into()].last_active
+        );
+        assert_eq!(
+            generations.current_gen_manifest().unwrap(),
+            "# generation 1"
+        );
+    }
+
+    #[test]
+    fn set_current_generation_fails_for_missing_generation() {
+        let (mut generations, _tempdir) = generations_with(1);
+
+        let err = generations.set_current_generation(2).unwrap_err();
+        assert!(matches!(err, GenerationsError::GenerationNotFound(2)));
+        assert_eq!(generations.metadata().unwrap().current_gen, Some(1.into()));
+    }
+
+    #[test]
+    fn get_generation_fails_for_missing_generation() {
+        let (generations, _tempdir) = generations_with(1);
+
+        assert!(generations.get_generation(1).is_ok());
+        assert!(matches!(
+            generations.get_generation(2),
+            Err(GenerationsError::GenerationNotFound(2))
+        ));
+    }
+
+    #[test]
+    fn previous_generation_is_highest_below_current() {
+        let (mut generations, _tempdir) = generations_with(3);
+
+        let metadata = generations.metadata().unwrap();
+        assert_eq!(metadata.previous_generation(), Some(2.into()));
+
+        generations.set_current_generation(1).unwrap();
+        let metadata = generations.metadata().unwrap();
+        assert_eq!(metadata.previous_generation(), None);
+    }
+
+    #[test]
+    fn copy_generation_reads_without_checkout() {
+        let (generations, _tempdir) = generations_with(2);
+        let generations = Generations::new(generations.repo.clone(), generations.branch.clone());
+        let copy_dir = tempfile::tempdir().unwrap();
+
+        let copy = generations
+            .copy_generation(1, copy_dir.path().join(ENV_DIR_NAME))
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(copy.manifest_path()).unwrap(),
+            generations.manifest(1).unwrap()
+        );
+        assert!(matches!(
+            generations.copy_generation(3, copy_dir.path().join("missing")),
+            Err(GenerationsError::GenerationNotFound(3))
+        ));
+    }
+
+    #[test]
+    fn lockfile_is_optional() {
+        let (mut generations, tempdir) = generations_with(1);
+        add_generation(
+            &mut generations,
+            tempdir.path(),
+            "version = 1",
+            Some(r#"{"lockfile-version": 1, "manifest": {"version": 1}, "packages": []}"#),
+        );
+
+        assert_eq!(generations.lockfile(1).unwrap(), None);
+        assert!(generations.lockfile(2).unwrap().is_some());
+        assert!(matches!(
+            generations.lockfile(3),
+            Err(GenerationsError::GenerationNotFound(3))
+        ));
+    }
+
+    #[test]
+    fn diff_compares_manifests() {
+        let (mut generations, tempdir) = generations_with(0);
+        add_generation(
+            &mut generations,
+            tempdir.path(),
+            indoc! {r#"
+                version = 1
+                [install]
+                hello.pkg-path = "hello"
+            "#},
+            None,
+        );
+        add_generation(
+            &mut generations,
+            tempdir.path(),
+            indoc! {r#"
+                version = 1
+                [install]
+                cowsay.pkg-path = "cowsay"
+                [vars]
+                FOO = "bar"
+            "#},
+            None,
+        );
+
+        let diff = generations.diff(1, 2, &"x86_64-linux".to_string()).unwrap();
+        let installed = |packages: &[PackageVersion]| {
+            packages
+                .iter()
+                .map(|package| package.install_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(installed(&diff.packages.added), vec!["cowsay"]);
+        assert_eq!(installed(&diff.packages.removed), vec!["hello"]);
+        assert_eq!(diff.vars.added, vec!["FOO"]);
+    }
+
+    #[test]
+    fn diff_fails_for_v0_manifests() {
+        let (generations, _tempdir) = generations_with(2);
+
+        assert!(matches!(
+            generations.diff(1, 2, &"x86_64-linux".to_string()),
+            Err(GenerationsError::UnsupportedManifestVersion(1))
+        ));
+    }
+
+    /// Metadata with generations 1..=n, generation `i` created `n - i` days before `now`
+    fn metadata_with(n: usize, current: usize, now: DateTime<Utc>) -> AllGenerationsMetadata {
+        let mut metadata = AllGenerationsMetadata {
+            current_gen: Some(current.into()),
+            ..Default::default()
+        };
+        for i in 1..=n {
+            let mut generation = SingleGenerationMetadata::new(format!("change {i}"));
+            generation.created = now - Duration::days((n - i) as i64);
+            metadata.generations.insert(i.into(), generation);
+        }
+        metadata
+    }
+
+    #[test]
+    fn generations_to_prune_keeps_all_without_policy() {
+        let now = Utc::now();
+        let metadata = metadata_with(5, 5, now);
+
+        assert!(metadata
+            .generations_to_prune(&RetentionPolicy::default(), now)
+            .is_empty());
+    }
+
+    #[test]
+    fn generations_to_prune_keeps_last_and_current() {
+        let now = Utc::now();
+        let metadata = metadata_with(5, 1, now);
+
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            max_age: None,
+        };
+        assert_eq!(metadata.generations_to_prune(&policy, now), vec![
+            2.into(),
+            3.into()
+        ]);
+    }
+
+    #[test]
+    fn generations_to_prune_keeps_recent_or_last() {
+        let now = Utc::now();
+        let metadata = metadata_with(5, 5, now);
+
+        // generations 4 and 5 are younger than 2 days
+        let policy = RetentionPolicy {
+            keep_last: None,
+            max_age: Some(Duration::days(2)),
+        };
+        assert_eq!(metadata.generations_to_prune(&policy, now), vec![
+            1.into(),
+            2.into(),
+            3.into()
+        ]);
+
+        // generations are kept if they match either criterion
+        let policy = RetentionPolicy {
+            keep_last: Some(3),
+            max_age: Some(Duration::days(2)),
+        };
+        assert_eq!(metadata.generations_to_prune(&policy, now), vec![
+            1.into(),
+            2.into()
+        ]);
+    }
+
+    #[test]
+    fn prune_removes_generations() {
+        let (mut generations, _tempdir) = generations_with(4);
+        generations.set_current_generation(2).unwrap();
+
+        let pruned = generations
+            .prune(&RetentionPolicy {
+                keep_last: Some(1),
+                max_age: None,
+            })
+            .unwrap();
+        assert_eq!(pruned, vec![1.into(), 3.into()]);
+
+        let metadata = generations.metadata().unwrap();
+        assert_eq!(metadata.current_gen, Some(2.into()));
+        assert_eq!(
+            metadata.generations.keys().cloned().collect::<Vec<_>>(),
+            vec![2.into(), 4.into()]
+        );
+        assert!(!generations.git().path().join("1").exists());
+        assert!(generations.get_generation(1).is_err());
+        assert!(generations.manifest(4).is_ok());
+
+        // pruning again is a no-op
+        let pruned = generations
+            .prune(&RetentionPolicy {
+                keep_last: Some(1),
+                max_age: None,
+            })
+            .unwrap();
+        assert!(pruned.is_empty());
+    }
+
+    #[test]
+    fn generations_to_prune_keeps_tagged() {
+        let now = Utc::now();
+        let mut metadata = metadata_with(5, 5, now);
+        metadata.tags.insert("stable".parse().unwrap(), 2.into());
+
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            max_age: None,
+        };
+        assert_eq!(metadata.generations_to_prune(&policy, now), vec![
+            1.into(),
+            3.into(),
+            4.into()
+        ]);
+    }
+
+    #[test]
+    fn tag_generation_updates_metadata() {
+        let (mut generations, _tempdir) = generations_with(3);
+        let stable: GenerationTag = "stable".parse().unwrap();
+
+        generations.tag_generation(stable.clone(), 1).unwrap();
+        let metadata = generations.metadata().unwrap();
+        assert_eq!(metadata.resolve_tag(&stable), Some(1.into()));
+        assert_eq!(metadata.tags_of(&1.into()), vec![&stable]);
+
+        // tags can be moved to another generation
+        generations.tag_generation(stable.clone(), 2).unwrap();
+        let metadata = generations.metadata().unwrap();
+        assert_eq!(metadata.resolve_tag(&stable), Some(2.into()));
+        assert!(metadata.tags_of(&1.into()).is_empty());
+
+        assert!(matches!(
+            generations.tag_generation(stable.clone(), 4),
+            Err(GenerationsError::GenerationNotFound(4))
+        ));
+
+        assert_eq!(generations.remove_tag(&stable).unwrap(), 2.into());
+        assert_eq!(generations.metadata().unwrap().resolve_tag(&stable), None);
+        assert!(matches!(
+            generations.remove_tag(&stable),
+            Err(GenerationsError::TagNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn metadata_without_provenance_still_loads() {
+        let metadata: AllGenerationsMetadata = serde_json::from_str(indoc! {r#"
+            {
+              "currentGen": "1",
+              "generations": {
+                "1": {
+                  "created": 1700000000,
+                  "lastActive": 1700000000,
+                  "description": "Add first generation"
+                }
+              },
+              "version": 1
+            }
+        "#})
+        .unwrap();
+
+        let generation = &metadata.generations[&1.into()];
+        assert_eq!(generation.description, "Add first generation");
+        assert_eq!(generation.author, None);
+        assert_eq!(generation.flox_version, None);
+        assert_eq!(generation.command, None);
+        assert_eq!(generation.system, None);
+        assert_eq!(generation.store_path, None);
+    }
+
+    #[test]
+    fn add_generation_records_provenance() {
+        let (flox, _flox_tempdir) = flox_instance();
+        let (mut generations, tempdir) = generations_with(1);
+
+        let env_path = tempfile::tempdir_in(tempdir.path()).unwrap().into_path();
+        fs::write(env_path.join(MANIFEST_FILENAME), "# generation 2").unwrap();
+        let metadata =
+            SingleGenerationMetadata::from_command(&flox, "install", "change".to_string())
+                .with_store_path(Some(PathBuf::from("/nix/store/fake-environment")));
+        generations
+            .add_generation(&mut CoreEnvironment::new(env_path), metadata)
+            .unwrap();
+
+        let generation = generations.metadata().unwrap().generations[&2.into()].clone();
+        assert_eq!(generation.command.as_deref(), Some("install"));
+        assert_eq!(
+            generation.flox_version.as_deref(),
+            Some(FLOX_VERSION.as_str())
+        );
+        assert_eq!(generation.system, Some(flox.system.clone()));
+        assert_eq!(
+            generation.store_path,
+            Some(PathBuf::from("/nix/store/fake-environment"))
+        );
+    }
 }
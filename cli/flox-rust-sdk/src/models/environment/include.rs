@@ -0,0 +1,326 @@
+//! Composition of environments using the `[include]` section of a manifest
+//!
+//! Included environments are merged into the including environment
+//! (see [TypedManifestCatalog::merge_includes]) before the merged manifest is locked.
+//! The manifests of included environments are pinned in the lockfile
+//! so that changes to an included environment only take effect
+//! when the including environment is upgraded.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use thiserror::Error;
+
+use super::generations::{GenerationId, GenerationsError};
+use super::managed_environment::ManagedEnvironment;
+use super::remote_environment::RemoteEnvironment;
+use super::{
+    DotFlox,
+    Environment,
+    EnvironmentError,
+    EnvironmentPointer,
+    ManagedPointer,
+    ENV_DIR_NAME,
+    MANIFEST_FILENAME,
+};
+use crate::flox::{EnvironmentRef, Flox};
+use crate::models::lockfile::{Compose, LockedInclude};
+use crate::models::manifest::{
+    IncludeDescriptor,
+    IncludeDescriptorLocal,
+    IncludeDescriptorRemote,
+    TypedManifest,
+    TypedManifestCatalog,
+};
+
+/// Fetches the manifests of environments included by a manifest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IncludeFetcher {
+    /// The directory relative `dir` includes are resolved against,
+    /// i.e. the directory containing the `.flox` directory of the including environment.
+    ///
+    /// Environments without a project directory, e.g. generations of a managed environment,
+    /// can only include environments by absolute path or from FloxHub.
+    pub base_directory: Option<PathBuf>,
+}
+
+impl IncludeFetcher {
+    /// Create an [IncludeFetcher] for the environment in `dot_flox_path`,
+    /// resolving relative `dir` includes against the directory containing it.
+    pub fn for_dot_flox(dot_flox_path: impl AsRef<Path>) -> Self {
+        Self {
+            base_directory: dot_flox_path.as_ref().parent().map(Path::to_path_buf),
+        }
+    }
+
+    /// Merge the environments included by `manifest` into it.
+    ///
+    /// Included environments that are pinned in `locked` with the same descriptor
+    /// are reused unless `refresh` is set, all others are fetched.
+    ///
+    /// Returns the merged manifest and the [Compose] record to store in the lockfile,
+    /// or `None` if `manifest` doesn't include any environments.
+    pub fn compose(
+        &self,
+        flox: &Flox,
+        manifest: &TypedManifestCatalog,
+        locked: Option<&Compose>,
+        refresh: bool,
+    ) -> Result<(TypedManifestCatalog, Option<Compose>), IncludeError> {
+        if manifest.include.environments.is_empty() {
+            return Ok((manifest.clone(), None));
+        }
+
+        let mut includes = Vec::new();
+        let mut names = HashSet::new();
+        for descriptor in &manifest.include.environments {
+            let pinned = locked
+                .filter(|_| !refresh)
+                .and_then(|compose| compose.include.iter().find(|i| &i.descriptor == descriptor));
+
+            let include = match pinned {
+                Some(pinned) => {
+                    debug!("using pinned include '{}'", pinned.name);
+                    pinned.clone()
+                },
+                None => self.fetch(flox, descriptor)?,
+            };
+
+            if !names.insert(include.name.clone()) {
+                return Err(IncludeError::DuplicateName(include.name));
+            }
+            includes.push(include);
+        }
+
+        let merged = manifest.merge_includes(includes.iter().map(|include| &include.manifest));
+        let compose = Compose {
+            composer: manifest.clone(),
+            include: includes,
+        };
+        Ok((merged, Some(compose)))
+    }
+
+    /// Fetch the current manifest of an included environment
+    pub fn fetch(
+        &self,
+        flox: &Flox,
+        descriptor: &IncludeDescriptor,
+    ) -> Result<LockedInclude, IncludeError> {
+        let (name, contents, generation) = match descriptor {
+            IncludeDescriptor::Local(local) => self.fetch_local(flox, local)?,
+            IncludeDescriptor::Remote(remote) => self.fetch_remote(flox, remote)?,
+        };
+        let name = descriptor
+            .explicit_name()
+            .map(ToString::to_string)
+            .unwrap_or(name);
+
+        let manifest = match toml::from_str(&contents) {
+            Ok(TypedManifest::Catalog(manifest)) => *manifest,
+            Ok(TypedManifest::Pkgdb(_)) => return Err(IncludeError::Version0(name)),
+            Err(err) => return Err(IncludeError::ParseManifest(name, err)),
+        };
+        if !manifest.include.environments.is_empty() {
+            return Err(IncludeError::NestedInclude(name));
+        }
+
+        Ok(LockedInclude {
+            name,
+            descriptor: descriptor.clone(),
+            manifest,
+            generation,
+            hash: blake3::hash(contents.as_bytes()).to_hex().to_string(),
+        })
+    }
+
+    /// Read the manifest of an environment in a local directory
+    fn fetch_local(
+        &self,
+        flox: &Flox,
+        local: &IncludeDescriptorLocal,
+    ) -> Result<(String, String, Option<GenerationId>), IncludeError> {
+        let dir = match &self.base_directory {
+            _ if local.dir.is_absolute() => local.dir.clone(),
+            Some(base_directory) => base_directory.join(&local.dir),
+            None => return Err(IncludeError::RelativeDir(local.dir.clone())),
+        };
+
+        let open_error =
+            |err: EnvironmentError| IncludeError::OpenLocal(dir.clone(), Box::new(err));
+        let dot_flox = DotFlox::open_in(&dir).map_err(open_error)?;
+
+        let contents = match &dot_flox.pointer {
+            EnvironmentPointer::Path(_) => {
+                let manifest_path = dot_flox.path.join(ENV_DIR_NAME).join(MANIFEST_FILENAME);
+                fs::read_to_string(manifest_path)
+                    .map_err(|err| open_error(EnvironmentError::ReadManifest(err)))?
+            },
+            EnvironmentPointer::Managed(pointer) => {
+                let environment =
+                    ManagedEnvironment::open(flox, (**pointer).clone(), &dot_flox.path)
+                        .map_err(|err| open_error(err.into()))?;
+                environment.manifest_contents(flox).map_err(open_error)?
+            },
+        };
+
+        Ok((dot_flox.pointer.name().to_string(), contents, None))
+    }
+
+    /// Read the manifest of the current or tagged generation of a FloxHub environment
+    fn fetch_remote(
+        &self,
+        flox: &Flox,
+        remote: &IncludeDescriptorRemote,
+    ) -> Result<(String, String, Option<GenerationId>), IncludeError> {
+        let env_ref = &remote.remote;
+        let pointer = ManagedPointer::new(
+            env_ref.owner().clone(),
+            env_ref.name().clone(),
+            &flox.floxhub,
+        );
+        let environment = RemoteEnvironment::new(flox, pointer)
+            .map_err(|err| IncludeError::OpenRemote(env_ref.clone(), Box::new(err.into())))?;
+
+        let generation = match env_ref.tag() {
+            Some(tag) => environment
+                .resolve_tag(tag)
+                .map_err(|err| IncludeError::OpenRemote(env_ref.clone(), Box::new(err)))?,
+            None => environment
+                .generations()
+                .metadata()
+                .and_then(|metadata| metadata.current_gen.ok_or(GenerationsError::NoGenerations))
+                .map_err(|err| IncludeError::ReadGeneration(env_ref.clone(), Box::new(err)))?,
+        };
+        let contents = environment
+            .generations()
+            .manifest(*generation)
+            .map_err(|err| IncludeError::ReadGeneration(env_ref.clone(), Box::new(err)))?;
+
+        Ok((env_ref.name().to_string(), contents, Some(generation)))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IncludeError {
+    #[error(
+        "cannot include '{}': relative paths are only supported for environments in a local directory",
+        .0.display()
+    )]
+    RelativeDir(PathBuf),
+
+    #[error("cannot include environment in '{}'", .0.display())]
+    OpenLocal(PathBuf, #[source] Box<EnvironmentError>),
+
+    #[error("cannot include environment '{0}'")]
+    OpenRemote(EnvironmentRef, #[source] Box<EnvironmentError>),
+
+    #[error("cannot read manifest of included environment '{0}'")]
+    ReadGeneration(EnvironmentRef, #[source] Box<GenerationsError>),
+
+    #[error("cannot parse manifest of included environment '{0}'")]
+    ParseManifest(String, #[source] toml::de::Error),
+
+    #[error("cannot include environment '{0}': version 0 manifests can not be included")]
+    Version0(String),
+
+    #[error("cannot include environment '{0}': it includes other environments itself")]
+    NestedInclude(String),
+
+    #[error(
+        "multiple included environments are named '{0}'\n\
+         Use the 'name' field to give each included environment a unique name."
+    )]
+    DuplicateName(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::{formatdoc, indoc};
+
+    use super::*;
+    use crate::flox::test_helpers::flox_instance;
+    use crate::models::environment::path_environment::test_helpers::new_path_environment;
+
+    fn including_manifest(dir: &Path) -> TypedManifestCatalog {
+        toml::from_str(&formatdoc! {r#"
+            version = 1
+            [include]
+            environments = [{{ dir = "{}" }}]
+        "#, dir.display()})
+        .unwrap()
+    }
+
+    #[test]
+    fn compose_reuses_pinned_includes_until_refreshed() {
+        let (flox, _temp_dir_handle) = flox_instance();
+        let base = new_path_environment(&flox, indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "base"
+        "#});
+        let manifest = including_manifest(base.path.parent().unwrap());
+        let fetcher = IncludeFetcher::default();
+
+        let (merged, compose) = fetcher.compose(&flox, &manifest, None, false).unwrap();
+        let compose = compose.unwrap();
+        assert_eq!(merged.vars.0["FOO"], "base".into());
+        assert_eq!(compose.composer, manifest);
+        assert_eq!(compose.include[0].name, "name");
+        assert_eq!(compose.include[0].generation, None);
+
+        fs::write(
+            base.path.join(ENV_DIR_NAME).join(MANIFEST_FILENAME),
+            "version = 1\n[vars]\nFOO = \"changed\"\n",
+        )
+        .unwrap();
+
+        let (merged, pinned) = fetcher
+            .compose(&flox, &manifest, Some(&compose), false)
+            .unwrap();
+        assert_eq!(merged.vars.0["FOO"], "base".into());
+        assert_eq!(pinned.unwrap(), compose);
+
+        let (merged, refreshed) = fetcher
+            .compose(&flox, &manifest, Some(&compose), true)
+            .unwrap();
+        assert_eq!(merged.vars.0["FOO"], "changed".into());
+        assert_ne!(refreshed.unwrap().include[0].hash, compose.include[0].hash);
+    }
+
+    #[test]
+    fn relative_dir_is_resolved_against_base_directory() {
+        let (flox, _temp_dir_handle) = flox_instance();
+        let base = new_path_environment(&flox, "version = 1");
+        let base_dir = base.path.parent().unwrap();
+        let manifest = including_manifest(Path::new(base_dir.file_name().unwrap()));
+
+        let err = IncludeFetcher::default()
+            .compose(&flox, &manifest, None, false)
+            .unwrap_err();
+        assert!(matches!(err, IncludeError::RelativeDir(_)));
+
+        let fetcher = IncludeFetcher {
+            base_directory: base_dir.parent().map(Path::to_path_buf),
+        };
+        let (_, compose) = fetcher.compose(&flox, &manifest, None, false).unwrap();
+        assert_eq!(compose.unwrap().include.len(), 1);
+    }
+
+    #[test]
+    fn nested_includes_are_rejected() {
+        let (flox, _temp_dir_handle) = flox_instance();
+        let base = new_path_environment(&flox, "version = 1");
+        let nested = new_path_environment(
+            &flox,
+            &toml::to_string(&including_manifest(base.path.parent().unwrap())).unwrap(),
+        );
+        let manifest = including_manifest(nested.path.parent().unwrap());
+
+        let err = IncludeFetcher::default()
+            .compose(&flox, &manifest, None, false)
+            .unwrap_err();
+        assert!(matches!(err, IncludeError::NestedInclude(_)));
+    }
+}
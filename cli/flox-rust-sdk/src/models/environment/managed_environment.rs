@@ -5,9 +5,17 @@ use flox_core::Version;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use url::Url;
 
 use super::core_environment::{CoreEnvironment, UpgradeResult};
-use super::generations::{Generations, GenerationsError};
+use super::generations::{
+    GenerationId,
+    Generations,
+    GenerationsError,
+    RetentionPolicy,
+    SingleGenerationMetadata,
+};
+use super::include::IncludeFetcher;
 use super::path_environment::PathEnvironment;
 use super::{
     gcroots_dir,
@@ -42,10 +50,15 @@ use crate::models::env_registry::{
     EnvRegistryError,
 };
 use crate::models::environment::copy_dir_recursive;
-use crate::models::environment_ref::{EnvironmentName, EnvironmentOwner};
-use crate::models::floxmeta::{floxmeta_git_options, FloxMeta, FloxMetaError};
+use crate::models::environment_ref::{EnvironmentName, EnvironmentOwner, GenerationTag};
+use crate::models::floxmeta::{floxmeta_git_options, FloxMeta, FloxMetaError, FloxmetaRemote};
 use crate::models::lockfile::LockedManifest;
-use crate::models::manifest::{PackageToInstall, TypedManifest};
+use crate::models::manifest::{
+    ManifestMergeConflicts,
+    PackageToInstall,
+    RawManifest,
+    TypedManifest,
+};
 use crate::providers::git::{
     GitCommandBranchHashError,
     GitCommandError,
@@ -64,6 +77,9 @@ pub struct ManagedEnvironment {
     out_link: PathBuf,
     pointer: ManagedPointer,
     floxmeta: FloxMeta,
+    /// Generation used instead of the current generation,
+    /// see [ManagedEnvironment::pin_generation]
+    generation: Option<GenerationId>,
 }
 
 #[derive(Debug, Error)]
@@ -172,6 +188,42 @@ pub enum ManagedEnvironmentError {
     #[error("could not read manifest")]
     ReadManifest(#[source] GenerationsError),
 
+    #[error("could not read generations metadata")]
+    ReadGenerationsMetadata(#[source] GenerationsError),
+
+    #[error("could not switch to generation {0}")]
+    SwitchGeneration(GenerationId, #[source] GenerationsError),
+
+    #[error("there is no generation before the current generation {0}")]
+    NoPreviousGeneration(GenerationId),
+
+    #[error("could not prune generations")]
+    PruneGenerations(#[source] GenerationsError),
+
+    #[error("could not update tag '{0}'")]
+    TagGeneration(GenerationTag, #[source] GenerationsError),
+
+    #[error("environment does not have a generation tagged '{0}'")]
+    TagNotFound(GenerationTag),
+
+    #[error("environment is pinned to generation {0} and can not be modified")]
+    Pinned(GenerationId),
+
+    #[error("could not read generation {0}")]
+    ReadGeneration(GenerationId, #[source] GenerationsError),
+
+    #[error("could not read generations to merge")]
+    ReadMergeGenerations(#[source] GenerationsError),
+
+    #[error("could not parse manifest to merge")]
+    ParseMergeManifest(#[source] toml_edit::de::Error),
+
+    #[error("could not merge local and upstream changes")]
+    MergeConflicts(#[source] ManifestMergeConflicts),
+
+    #[error("could not build merged environment")]
+    BuildMerged(#[source] CoreEnvironmentError),
+
     #[error("could not canonicalize environment path")]
     CanonicalizePath(#[source] CanonicalizeError),
 
@@ -210,6 +262,12 @@ impl GenerationLock {
 impl Environment for ManagedEnvironment {
     /// This will lock if there is an out of sync local checkout
     fn lockfile(&mut self, flox: &Flox) -> Result<LockedManifest, EnvironmentError> {
+        if let Some(generation) = self.pinned_checkout(flox)? {
+            return generation
+                .existing_lockfile()?
+                .ok_or(EnvironmentError::MissingLockfile);
+        }
+
         let mut local_checkout = self.local_env_or_copy_current_generation(flox)?;
         self.ensure_locked(flox, &mut local_checkout)
     }
@@ -220,13 +278,20 @@ impl Environment for ManagedEnvironment {
         flox: &Flox,
         tag: &str,
     ) -> Result<ContainerBuilder, EnvironmentError> {
-        let mut local_checkout = self.local_env_or_copy_current_generation(flox)?;
-        self.ensure_locked(flox, &mut local_checkout)?;
+        let local_checkout = match self.pinned_checkout(flox)? {
+            Some(generation) => generation,
+            None => {
+                let mut local_checkout = self.local_env_or_copy_current_generation(flox)?;
+                self.ensure_locked(flox, &mut local_checkout)?;
+                local_checkout
+            },
+        };
 
         let lockfile_path = CanonicalPath::new(local_checkout.lockfile_path())
             .expect("a locked environment must have a lockfile");
 
-        let builder = CoreEnvironment::build_container(lockfile_path, self.name().as_ref(), tag)?;
+        let builder =
+            CoreEnvironment::build_container(flox, lockfile_path, self.name().as_ref(), tag)?;
         Ok(builder)
     }
 
@@ -236,6 +301,8 @@ impl Environment for ManagedEnvironment {
         packages: &[PackageToInstall],
         flox: &Flox,
     ) -> Result<InstallationAttempt, EnvironmentError> {
+        self.ensure_not_pinned()?;
+
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
@@ -253,8 +320,10 @@ impl Environment for ManagedEnvironment {
             ))?
         }
 
-        let metadata = format!("installed packages: {:?}", &packages);
+        let description = format!("installed packages: {:?}", &packages);
         let result = local_checkout.install(packages, flox)?;
+        let metadata = SingleGenerationMetadata::from_command(flox, "install", description)
+            .with_store_path(result.store_path.clone());
 
         generations
             .add_generation(&mut local_checkout, metadata)
@@ -273,6 +342,8 @@ impl Environment for ManagedEnvironment {
         packages: Vec<String>,
         flox: &Flox,
     ) -> Result<UninstallationAttempt, EnvironmentError> {
+        self.ensure_not_pinned()?;
+
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
@@ -289,8 +360,10 @@ impl Environment for ManagedEnvironment {
             ))?
         }
 
-        let metadata = format!("uninstalled packages: {:?}", &packages);
+        let description = format!("uninstalled packages: {:?}", &packages);
         let result = local_checkout.uninstall(packages, flox)?;
+        let metadata = SingleGenerationMetadata::from_command(flox, "uninstall", description)
+            .with_store_path(result.store_path.clone());
 
         generations
             .add_generation(&mut local_checkout, metadata)
@@ -305,6 +378,8 @@ impl Environment for ManagedEnvironment {
 
     /// Atomically edit this environment, ensuring that it still builds
     fn edit(&mut self, flox: &Flox, contents: String) -> Result<EditResult, EnvironmentError> {
+        self.ensure_not_pinned()?;
+
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
@@ -315,8 +390,11 @@ impl Environment for ManagedEnvironment {
         let result = local_checkout.edit(flox, contents)?;
 
         if result != EditResult::Unchanged {
+            let metadata =
+                SingleGenerationMetadata::from_command(flox, "edit", "manually edited".to_string())
+                    .with_store_path(result.store_path());
             generations
-                .add_generation(&mut local_checkout, "manually edited".to_string())
+                .add_generation(&mut local_checkout, metadata)
                 .map_err(ManagedEnvironmentError::CommitGeneration)?;
             self.lock_pointer()?;
             if let Some(ref store_path) = result.store_path() {
@@ -333,6 +411,8 @@ impl Environment for ManagedEnvironment {
         flox: &Flox,
         groups_or_iids: &[&str],
     ) -> Result<UpgradeResult, EnvironmentError> {
+        self.ensure_not_pinned()?;
+
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
@@ -352,7 +432,15 @@ impl Environment for ManagedEnvironment {
 
         let result = local_checkout.upgrade(flox, groups_or_iids)?;
 
-        let metadata = format!("upgraded packages: {}", result.packages.join(", "));
+        let mut description = format!("upgraded packages: {}", result.packages.join(", "));
+        if !result.includes.is_empty() {
+            description.push_str(&format!(
+                "; updated includes: {}",
+                result.includes.join(", ")
+            ));
+        }
+        let metadata = SingleGenerationMetadata::from_command(flox, "upgrade", description)
+            .with_store_path(result.store_path.clone());
 
         generations
             .add_generation(&mut local_checkout, metadata)
@@ -367,8 +455,39 @@ impl Environment for ManagedEnvironment {
         Ok(result)
     }
 
+    /// Resolve the upgrades of packages in this environment
+    /// without modifying the environment or its generations
+    fn dry_upgrade(
+        &mut self,
+        flox: &Flox,
+        groups_or_iids: &[&str],
+    ) -> Result<UpgradeResult, EnvironmentError> {
+        if let Some(mut generation) = self.pinned_checkout(flox)? {
+            return Ok(generation.dry_upgrade(flox, groups_or_iids)?);
+        }
+
+        let remote = self.get_current_generation(flox)?;
+        let mut local_checkout = self.local_env_or_copy_current_generation(flox)?;
+
+        if !Self::validate_checkout(&local_checkout, &remote)? {
+            Err(EnvironmentError::ManagedEnvironment(
+                ManagedEnvironmentError::CheckoutOutOfSync,
+            ))?
+        }
+
+        Ok(local_checkout.dry_upgrade(flox, groups_or_iids)?)
+    }
+
     /// Extract the current content of the manifest
     fn manifest_contents(&self, flox: &Flox) -> Result<String, EnvironmentError> {
+        if let Some(generation) = self.pinned_generation()? {
+            let manifest = self
+                .generations()
+                .manifest(*generation)
+                .map_err(ManagedEnvironmentError::ReadManifest)?;
+            return Ok(manifest);
+        }
+
         let local_checkout = self.local_env_or_copy_current_generation(flox)?;
         let manifest = local_checkout.manifest_contents()?;
         Ok(manifest)
@@ -382,6 +501,16 @@ impl Environment for ManagedEnvironment {
 
     /// This will lock if there is an out of sync local checkout
     fn activation_path(&mut self, flox: &Flox) -> Result<PathBuf, EnvironmentError> {
+        if let Some(generation) = self.pinned_generation()? {
+            // Generations don't change, so an existing build can be reused
+            let out_link = self.generation_out_link(&generation);
+            if !out_link.exists() {
+                let store_path = self.build(flox)?;
+                self.link(store_path)?;
+            }
+            return Ok(out_link);
+        }
+
         let mut local_checkout = self.local_env_or_copy_current_generation(flox)?;
 
         self.ensure_locked(flox, &mut local_checkout)?;
@@ -443,6 +572,10 @@ impl Environment for ManagedEnvironment {
     ///
     /// Path will not share a common prefix with the path returned by [`ManagedEnvironment::lockfile_path`]
     fn manifest_path(&self, flox: &Flox) -> Result<PathBuf, EnvironmentError> {
+        if let Some(generation) = self.pinned_checkout(flox)? {
+            return Ok(generation.manifest_path());
+        }
+
         let path = self
             .local_env_or_copy_current_generation(flox)?
             .manifest_path();
@@ -453,6 +586,10 @@ impl Environment for ManagedEnvironment {
     ///
     /// Path will not share a common prefix with the path returned by [`ManagedEnvironment::manifest_path`]
     fn lockfile_path(&self, flox: &Flox) -> Result<PathBuf, EnvironmentError> {
+        if let Some(generation) = self.pinned_checkout(flox)? {
+            return Ok(generation.lockfile_path());
+        }
+
         let path = self
             .local_env_or_copy_current_generation(flox)?
             .lockfile_path();
@@ -480,16 +617,18 @@ impl Environment for ManagedEnvironment {
                 .map_err(|e| ManagedEnvironmentError::DeleteEnvironmentLink(out_link_path, e))?;
         }
 
-        deregister(flox, &self.path, &EnvironmentPointer::Managed(self.pointer))?;
+        deregister(flox, &self.path, &EnvironmentPointer::from(self.pointer))?;
 
         Ok(())
     }
 
-    fn migrate_to_v1(
+    fn migrate(
         &mut self,
         flox: &Flox,
         migration_info: MigrationInfo,
     ) -> Result<(), EnvironmentError> {
+        self.ensure_not_pinned()?;
+
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
@@ -508,7 +647,7 @@ impl Environment for ManagedEnvironment {
             ))?
         }
 
-        let metadata = match (
+        let description = match (
             migration_info.needs_manifest_migration,
             migration_info.needs_upgrade,
         ) {
@@ -518,10 +657,14 @@ impl Environment for ManagedEnvironment {
             _ => unreachable!("called with invalid migration metadata"),
         };
 
-        let store_path = temporary.migrate_to_v1(flox, migration_info)?;
+        let store_path = temporary.migrate(flox, migration_info)?;
+
+        let metadata =
+            SingleGenerationMetadata::from_command(flox, "migrate", description.to_string())
+                .with_store_path(Some(store_path.clone()));
 
         generations
-            .add_generation(&mut temporary, metadata.to_string())
+            .add_generation(&mut temporary, metadata)
             .map_err(ManagedEnvironmentError::CommitGeneration)?;
         self.lock_pointer()?;
         self.link(store_path)?;
@@ -558,14 +701,26 @@ impl ManagedEnvironment {
         }
     }
 
+    /// Build the pinned generation if the environment is pinned,
+    /// or the local checkout otherwise
     pub fn build(&mut self, flox: &Flox) -> Result<PathBuf, EnvironmentError> {
-        let mut local_checkout = self.local_env_or_copy_current_generation(flox)?;
+        let mut local_checkout = match self.pinned_checkout(flox)? {
+            Some(generation) => generation,
+            None => self.local_env_or_copy_current_generation(flox)?,
+        };
 
         Ok(local_checkout.build(flox)?)
     }
 
+    /// Link `store_path` to the out link of the pinned generation
+    /// if the environment is pinned,
+    /// or to the out link of the environment otherwise
     pub fn link(&mut self, store_path: impl AsRef<Path>) -> Result<(), EnvironmentError> {
-        CoreEnvironment::link(&self.out_link, store_path)?;
+        let out_link = match self.pinned_generation()? {
+            Some(generation) => self.generation_out_link(&generation),
+            None => self.out_link.clone(),
+        };
+        CoreEnvironment::link(out_link, store_path)?;
 
         Ok(())
     }
@@ -646,11 +801,15 @@ impl ManagedEnvironment {
             },
             Err(FloxMetaError::CloneBranch(GitRemoteCommandError::RefNotFound(_)))
             | Err(FloxMetaError::FetchBranch(GitRemoteCommandError::RefNotFound(_))) => {
+                let upstream = match &pointer.git_remote {
+                    Some(git_remote) => git_remote.to_string(),
+                    None => flox.floxhub.base_url().to_string(),
+                };
                 return Err(ManagedEnvironmentError::UpstreamNotFound {
                     env_ref: pointer.into(),
-                    upstream: flox.floxhub.base_url().to_string(),
+                    upstream,
                     user: flox.floxhub_token.as_ref().map(|t| t.handle().to_string()),
-                })
+                });
             },
             Err(e) => Err(ManagedEnvironmentError::OpenFloxmeta(e))?,
         };
@@ -684,7 +843,7 @@ impl ManagedEnvironment {
         ensure_registered(
             flox,
             &dot_flox_path,
-            &EnvironmentPointer::Managed(pointer.clone()),
+            &EnvironmentPointer::from(pointer.clone()),
         )?;
 
         let env = ManagedEnvironment {
@@ -692,6 +851,7 @@ impl ManagedEnvironment {
             out_link,
             pointer,
             floxmeta,
+            generation: None,
         };
 
         Ok(env)
@@ -873,6 +1033,8 @@ impl ManagedEnvironment {
         flox: &Flox,
         contents: String,
     ) -> Result<Result<EditResult, CoreEnvironmentError>, EnvironmentError> {
+        self.ensure_not_pinned()?;
+
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
@@ -898,8 +1060,17 @@ impl ManagedEnvironment {
 
         debug!("Environment changed, create and lock generation");
 
+        // Only `flox pull` edits without building, to add the current system
+        let store_path = result.as_ref().ok().and_then(|result| result.store_path());
+        let metadata = SingleGenerationMetadata::from_command(
+            flox,
+            "pull",
+            "added the current system".to_string(),
+        )
+        .with_store_path(store_path);
+
         generations
-            .add_generation(&mut temporary, "manually edited".to_string())
+            .add_generation(&mut temporary, metadata)
             .map_err(ManagedEnvironmentError::CommitGeneration)?;
         self.lock_pointer()?;
 
@@ -939,8 +1110,15 @@ impl ManagedEnvironment {
 
         debug!("Environment changed, create and lock generation");
 
+        let metadata = SingleGenerationMetadata::from_command(
+            flox,
+            "pull",
+            "migrated to version 1 and added the current system".to_string(),
+        )
+        .with_store_path(result.as_ref().ok().cloned());
+
         generations
-            .add_generation(&mut temporary, "manually edited".to_string())
+            .add_generation(&mut temporary, metadata)
             .map_err(ManagedEnvironmentError::CommitGeneration)?;
         self.lock_pointer()?;
 
@@ -986,18 +1164,23 @@ impl ManagedEnvironment {
             .map_err(ManagedEnvironmentError::Build)?;
 
         // TODO: should use self.link but that returns an EnvironmentError
-        CoreEnvironment::link(&self.out_link, store_path).map_err(ManagedEnvironmentError::Link)?;
+        CoreEnvironment::link(&self.out_link, &store_path)
+            .map_err(ManagedEnvironmentError::Link)?;
 
         let mut generations = self
             .generations()
             .writable(flox.temp_dir.clone())
             .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?;
 
+        let metadata = SingleGenerationMetadata::from_command(
+            flox,
+            "edit",
+            "Synchronized manual changes to generation".to_string(),
+        )
+        .with_store_path(Some(store_path));
+
         generations
-            .add_generation(
-                &mut local_checkout,
-                "Synchronized manual changes to generation".to_string(),
-            )
+            .add_generation(&mut local_checkout, metadata)
             .map_err(ManagedEnvironmentError::CommitGeneration)?;
 
         self.lock_pointer()?;
@@ -1040,11 +1223,248 @@ impl ManagedEnvironment {
         )
         .map_err(ManagedEnvironmentError::CreateLocalEnvironmentView)?;
 
-        let local_checkout = CoreEnvironment::new(env_dir);
+        let local_checkout = CoreEnvironment::new(env_dir)
+            .with_include_fetcher(IncludeFetcher::for_dot_flox(&self.path));
 
         Ok(local_checkout)
     }
 
+    /// Switch the environment to a given generation.
+    ///
+    /// The generation is built before it is set as the current generation,
+    /// so that a generation which fails to build leaves the environment unchanged.
+    /// Switching generations is refused if the local checkout has changes
+    /// that are not yet committed to a generation.
+    ///
+    /// After switching, `.flox/env` is reset to the new current generation
+    /// and the environment is relinked.
+    pub fn switch_generation(
+        &mut self,
+        flox: &Flox,
+        generation: GenerationId,
+    ) -> Result<(), ManagedEnvironmentError> {
+        self.ensure_not_pinned()?;
+
+        let mut generations = self
+            .generations()
+            .writable(flox.temp_dir.clone())
+            .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?;
+
+        let remote = generations
+            .get_current_generation()
+            .map_err(ManagedEnvironmentError::CreateGenerationFiles)?;
+
+        let local_checkout = self.local_env_or_copy_current_generation(flox)?;
+
+        if !Self::validate_checkout(&local_checkout, &remote)? {
+            Err(ManagedEnvironmentError::CheckoutOutOfSync)?
+        }
+
+        let mut target = generations
+            .get_generation(*generation)
+            .map_err(|e| ManagedEnvironmentError::SwitchGeneration(generation.clone(), e))?;
+
+        let store_path = target.build(flox).map_err(ManagedEnvironmentError::Build)?;
+
+        generations
+            .set_current_generation(*generation)
+            .map_err(|e| ManagedEnvironmentError::SwitchGeneration(generation.clone(), e))?;
+        self.lock_pointer()?;
+
+        self.reset_local_env_to_current_generation(flox)?;
+
+        // TODO: should use self.link but that returns an EnvironmentError
+        CoreEnvironment::link(&self.out_link, store_path).map_err(ManagedEnvironmentError::Link)?;
+
+        Ok(())
+    }
+
+    /// Switch the environment to the generation preceding the current one.
+    ///
+    /// Returns the generation that was switched to.
+    /// See [ManagedEnvironment::switch_generation] for details.
+    pub fn rollback(&mut self, flox: &Flox) -> Result<GenerationId, ManagedEnvironmentError> {
+        let metadata = self
+            .generations()
+            .metadata()
+            .map_err(ManagedEnvironmentError::ReadGenerationsMetadata)?;
+
+        let current_gen = metadata.current_gen.clone().unwrap_or_default();
+        let Some(previous) = metadata.previous_generation() else {
+            return Err(ManagedEnvironmentError::NoPreviousGeneration(current_gen));
+        };
+
+        self.switch_generation(flox, previous.clone())?;
+
+        Ok(previous)
+    }
+
+    /// Remove generations of the environment that are not retained by `policy`
+    ///
+    /// The current generation is never removed.
+    /// Like other changes to a managed environment,
+    /// the change has to be pushed to be reflected upstream.
+    ///
+    /// Returns the removed generations.
+    pub fn prune_generations(
+        &mut self,
+        flox: &Flox,
+        policy: &RetentionPolicy,
+    ) -> Result<Vec<GenerationId>, ManagedEnvironmentError> {
+        let mut generations = self
+            .generations()
+            .writable(flox.temp_dir.clone())
+            .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?;
+
+        let pruned = generations
+            .prune(policy)
+            .map_err(ManagedEnvironmentError::PruneGenerations)?;
+
+        if !pruned.is_empty() {
+            self.lock_pointer()?;
+        }
+
+        Ok(pruned)
+    }
+
+    /// Tag `generation` with `tag`, moving the tag if it already exists
+    ///
+    /// Like other changes to a managed environment,
+    /// the change has to be pushed to be reflected upstream.
+    pub fn tag_generation(
+        &mut self,
+        flox: &Flox,
+        tag: GenerationTag,
+        generation: GenerationId,
+    ) -> Result<(), ManagedEnvironmentError> {
+        let mut generations = self
+            .generations()
+            .writable(flox.temp_dir.clone())
+            .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?;
+
+        generations
+            .tag_generation(tag.clone(), *generation)
+            .map_err(|e| ManagedEnvironmentError::TagGeneration(tag, e))?;
+        self.lock_pointer()?;
+
+        Ok(())
+    }
+
+    /// Remove `tag` from the environment
+    ///
+    /// Returns the generation the tag referred to.
+    pub fn remove_tag(
+        &mut self,
+        flox: &Flox,
+        tag: &GenerationTag,
+    ) -> Result<GenerationId, ManagedEnvironmentError> {
+        let mut generations = self
+            .generations()
+            .writable(flox.temp_dir.clone())
+            .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?;
+
+        let generation = generations.remove_tag(tag).map_err(|e| match e {
+            GenerationsError::TagNotFound(tag) => ManagedEnvironmentError::TagNotFound(tag),
+            e => ManagedEnvironmentError::TagGeneration(tag.clone(), e),
+        })?;
+        self.lock_pointer()?;
+
+        Ok(generation)
+    }
+
+    /// Return the generation `tag` refers to
+    pub fn resolve_tag(
+        &self,
+        tag: &GenerationTag,
+    ) -> Result<GenerationId, ManagedEnvironmentError> {
+        self.generations()
+            .metadata()
+            .map_err(ManagedEnvironmentError::ReadGenerationsMetadata)?
+            .resolve_tag(tag)
+            .ok_or_else(|| ManagedEnvironmentError::TagNotFound(tag.clone()))
+    }
+
+    /// Use `generation` instead of the current generation of the environment
+    ///
+    /// The environment and its generations are not modified,
+    /// the generation is read and built into a separate out link.
+    /// A pinned environment can not be modified.
+    pub fn pin_generation(
+        &mut self,
+        generation: GenerationId,
+    ) -> Result<(), ManagedEnvironmentError> {
+        let metadata = self
+            .generations()
+            .metadata()
+            .map_err(ManagedEnvironmentError::ReadGenerationsMetadata)?;
+        if !metadata.generations.contains_key(&generation) {
+            return Err(ManagedEnvironmentError::ReadGeneration(
+                generation.clone(),
+                GenerationsError::GenerationNotFound(*generation),
+            ));
+        }
+
+        self.generation = Some(generation);
+        Ok(())
+    }
+
+    /// Return the generation used instead of the current generation, if any
+    ///
+    /// That is either the generation set with [ManagedEnvironment::pin_generation],
+    /// or the generation tagged with the tag of the pointer.
+    pub fn pinned_generation(&self) -> Result<Option<GenerationId>, ManagedEnvironmentError> {
+        if let Some(generation) = &self.generation {
+            return Ok(Some(generation.clone()));
+        }
+
+        self.pointer
+            .tag
+            .as_ref()
+            .map(|tag| self.resolve_tag(tag))
+            .transpose()
+    }
+
+    /// Fail if the environment is pinned to a generation,
+    /// as changes would be based on the pinned rather than the current generation.
+    fn ensure_not_pinned(&self) -> Result<(), ManagedEnvironmentError> {
+        match self.pinned_generation()? {
+            Some(generation) => Err(ManagedEnvironmentError::Pinned(generation)),
+            None => Ok(()),
+        }
+    }
+
+    /// Return a temporary checkout of the pinned generation
+    /// if the environment is pinned
+    fn pinned_checkout(
+        &self,
+        flox: &Flox,
+    ) -> Result<Option<CoreEnvironment>, ManagedEnvironmentError> {
+        let Some(generation) = self.pinned_generation()? else {
+            return Ok(None);
+        };
+
+        let checkout_path = tempfile::tempdir_in(&flox.temp_dir)
+            .unwrap()
+            .into_path()
+            .join(ENV_DIR_NAME);
+        let checkout = self
+            .generations()
+            .copy_generation(*generation, checkout_path)
+            .map_err(|e| ManagedEnvironmentError::ReadGeneration(generation, e))?;
+
+        Ok(Some(checkout))
+    }
+
+    /// Path of the out link for builds of `generation`
+    ///
+    /// The link is next to the out link of the environment,
+    /// so that the out link of the environment is not replaced.
+    fn generation_out_link(&self, generation: &GenerationId) -> PathBuf {
+        let mut file_name = self.out_link.file_name().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{generation}"));
+        self.out_link.with_file_name(file_name)
+    }
+
     /// Return a [CoreEnvironment] for an existing local checkout
     /// or create one from the current generation.
     ///
@@ -1067,7 +1487,8 @@ impl ManagedEnvironment {
             .map_err(ManagedEnvironmentError::CreateLocalEnvironmentView)?;
         }
 
-        let local = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let local = CoreEnvironment::new(self.path.join(ENV_DIR_NAME))
+            .with_include_fetcher(IncludeFetcher::for_dot_flox(&self.path));
         Ok(local)
     }
 
@@ -1147,7 +1568,8 @@ impl ManagedEnvironment {
         &self.pointer
     }
 
-    fn generations(&self) -> Generations {
+    /// Return a read-only view of the generations of this environment
+    pub fn generations(&self) -> Generations {
         Generations::new(
             self.floxmeta.git.clone(),
             branch_name(&self.pointer, &self.path),
@@ -1280,6 +1702,19 @@ pub enum PullResult {
     UpToDate,
     /// The environment was reset to the latest upstream version
     Updated,
+    /// Local changes were merged with the latest upstream version
+    /// into a new generation that has not been pushed yet
+    Merged,
+}
+
+/// Where to push a new environment to, see [ManagedEnvironment::push_new]
+struct PushNewOptions {
+    owner: EnvironmentOwner,
+    name: EnvironmentName,
+    /// Push to this git repository instead of FloxHub
+    git_remote: Option<Url>,
+    /// Overwrite an existing environment with the same name
+    force: bool,
 }
 
 impl ManagedEnvironment {
@@ -1292,13 +1727,18 @@ impl ManagedEnvironment {
     /// By default, if an environment with the same name already exists in the owner's repository,
     /// the push will fail, unless `force` is set to `true`.
     ///
-    /// If access to a remote repository requires authentication,
+    /// If `git_remote` is set, the environment is pushed to that git repository
+    /// instead of FloxHub.
+    ///
+    /// If access to a remote FloxHub repository requires authentication,
     /// the FloxHub token must be set in the flox instance.
     /// The caller is responsible for ensuring that the token is present and valid.
+    /// Authentication with plain git remotes is left to git.
     pub fn push_new(
         flox: &Flox,
         path_environment: PathEnvironment,
         owner: EnvironmentOwner,
+        git_remote: Option<Url>,
         force: bool,
     ) -> Result<Self, ManagedEnvironmentError> {
         // path of the original .flox directory
@@ -1317,11 +1757,22 @@ impl ManagedEnvironment {
             .map_err(ManagedEnvironmentError::Lock)?;
 
         // Ensure the environment builds before we push it
-        core_environment
+        let store_path = core_environment
             .build(flox)
             .map_err(ManagedEnvironmentError::Build)?;
 
-        Self::push_new_without_building(flox, owner, name, force, dot_flox_path, core_environment)
+        Self::push_new_without_building(
+            flox,
+            PushNewOptions {
+                owner,
+                name,
+                git_remote,
+                force,
+            },
+            dot_flox_path,
+            core_environment,
+            Some(store_path),
+        )
     }
 
     /// Push an environment and open the resulting [ManagedEnvironment],
@@ -1331,13 +1782,21 @@ impl ManagedEnvironment {
     /// some tests need an environment that has build errors.
     fn push_new_without_building(
         flox: &Flox,
-        owner: EnvironmentOwner,
-        name: EnvironmentName,
-        force: bool,
+        options: PushNewOptions,
         dot_flox_path: CanonicalPath,
         mut core_environment: CoreEnvironment,
+        store_path: Option<PathBuf>,
     ) -> Result<Self, ManagedEnvironmentError> {
-        let pointer = ManagedPointer::new(owner, name.clone(), &flox.floxhub);
+        let PushNewOptions {
+            owner,
+            name,
+            git_remote,
+            force,
+        } = options;
+        let mut pointer = ManagedPointer::new(owner, name.clone(), &flox.floxhub);
+        if let Some(git_remote) = git_remote {
+            pointer = pointer.with_git_remote(git_remote);
+        }
 
         let checkedout_floxmeta_path = tempfile::tempdir_in(&flox.temp_dir).unwrap().into_path();
         let temp_floxmeta_path = tempfile::tempdir_in(&flox.temp_dir).unwrap().into_path();
@@ -1345,9 +1804,10 @@ impl ManagedEnvironment {
         // Caller decides whether to set token
         let token = flox.floxhub_token.as_ref();
 
-        let git_url = flox.floxhub.git_url();
+        let remote = FloxmetaRemote::from_pointer(&pointer)
+            .map_err(|e| ManagedEnvironmentError::OpenFloxmeta(FloxMetaError::FloxhubError(e)))?;
 
-        let options = floxmeta_git_options(git_url, &pointer.owner, token);
+        let options = floxmeta_git_options(&remote, token);
 
         // Initialize a new branch for this environment in a new, temporary,
         // bare repo. This acts like part of the bare repo that backs a user's
@@ -1372,18 +1832,22 @@ impl ManagedEnvironment {
 
         // Add this environment as a new generation, which involves pushing to
         // the fake remote.
+        let metadata = SingleGenerationMetadata::from_command(
+            flox,
+            "push",
+            "Add first generation".to_string(),
+        )
+        .with_store_path(store_path);
+
         generations
-            .add_generation(&mut core_environment, "Add first generation".to_string())
+            .add_generation(&mut core_environment, metadata)
             .map_err(ManagedEnvironmentError::CommitGeneration)?;
 
         temp_floxmeta_git
-            .add_remote(
-                "upstream",
-                &format!("{}/{}/floxmeta", &git_url, &pointer.owner),
-            )
+            .add_remote("upstream", &remote.url())
             .unwrap();
 
-        // Push the branch for this environment to FloxHub
+        // Push the branch for this environment to its remote
         match temp_floxmeta_git.push_ref("upstream", "HEAD", force) {
             Err(GitRemoteCommandError::AccessDenied) => Err(ManagedEnvironmentError::AccessDenied)?,
             Err(GitRemoteCommandError::Diverged) => Err(ManagedEnvironmentError::Diverged)?,
@@ -1465,7 +1929,7 @@ impl ManagedEnvironment {
                 .map_err(ManagedEnvironmentError::Git)?;
 
             if !consistent_history {
-                Err(ManagedEnvironmentError::Diverged)?;
+                self.merge_diverged(flox, "push")?;
             }
         }
         self.floxmeta
@@ -1489,9 +1953,10 @@ impl ManagedEnvironment {
     /// Pull new generation data from floxhub
     ///
     /// Requires the local checkout to be synched with the current generation.
-    /// If the environment has diverged, the pull will fail.
+    /// If the environment has diverged, local and upstream changes are merged
+    /// into a new generation, see [ManagedEnvironment::merge_diverged].
     ///
-    /// If `force == true`, the pull will proceed even if the environment has diverged.
+    /// If `force == true`, the pull will discard local changes instead of merging.
     pub fn pull(
         &mut self,
         flox: &Flox,
@@ -1530,7 +1995,10 @@ impl ManagedEnvironment {
             Err(GitRemoteCommandError::RefNotFound(_)) => {
                 Err(ManagedEnvironmentError::UpstreamNotFound {
                     env_ref: self.pointer.clone().into(),
-                    upstream: self.pointer.floxhub_url.to_string(),
+                    upstream: match &self.pointer.git_remote {
+                        Some(git_remote) => git_remote.to_string(),
+                        None => self.pointer.floxhub_url.to_string(),
+                    },
                     user: flox.floxhub_token.as_ref().map(|t| t.handle().to_string()),
                 })?
             },
@@ -1547,7 +2015,8 @@ impl ManagedEnvironment {
                 .branch_contains_commit(&project_branch, &sync_branch)
                 .map_err(ManagedEnvironmentError::Git)?;
             if !consistent_history {
-                Err(ManagedEnvironmentError::Diverged)?;
+                self.merge_diverged(flox, "pull")?;
+                return Ok(PullResult::Merged);
             }
 
             let sync_branch_commit = self.floxmeta.git.branch_hash(&sync_branch).ok();
@@ -1575,6 +2044,137 @@ impl ManagedEnvironment {
         Ok(PullResult::Updated)
     }
 
+    /// Merge diverged local and upstream generations
+    ///
+    /// Performs a three-way merge of the manifests of the current local
+    /// and the current upstream generation,
+    /// using the current generation of their last common commit as the base.
+    /// The merged manifest is locked and built on top of the current upstream generation.
+    /// Only if that succeeds, the local branch is reset to the upstream branch
+    /// and the merged environment is added as a new generation.
+    ///
+    /// If the manifests contain conflicting changes,
+    /// [ManagedEnvironmentError::MergeConflicts] is returned
+    /// and the environment is left unchanged.
+    /// If either branch already contains the other,
+    /// or the upstream branch does not exist, e.g. because it was deleted,
+    /// there is nothing to merge and [ManagedEnvironmentError::Diverged] is returned.
+    fn merge_diverged(
+        &mut self,
+        flox: &Flox,
+        command: &str,
+    ) -> Result<(), ManagedEnvironmentError> {
+        let sync_branch = remote_branch_name(&self.pointer);
+        let project_branch = branch_name(&self.pointer, &self.path);
+
+        if !self
+            .floxmeta
+            .git
+            .has_branch(&sync_branch)
+            .map_err(ManagedEnvironmentError::GitBranchHash)?
+        {
+            return Err(ManagedEnvironmentError::Diverged);
+        }
+
+        let Some(merge_base) = self
+            .floxmeta
+            .git
+            .merge_base(&project_branch, &sync_branch)
+            .map_err(ManagedEnvironmentError::Git)?
+        else {
+            return Err(ManagedEnvironmentError::Diverged);
+        };
+
+        for branch in [&project_branch, &sync_branch] {
+            let head = self
+                .floxmeta
+                .git
+                .branch_hash(branch)
+                .map_err(ManagedEnvironmentError::GitBranchHash)?;
+            if head == merge_base {
+                return Err(ManagedEnvironmentError::Diverged);
+            }
+        }
+
+        // Read the manifest of the current generation at a revision,
+        // an empty manifest if there are no generations at that revision.
+        let read_manifest = |rev: &str| {
+            let generations = Generations::new(self.floxmeta.git.clone(), rev.to_string());
+            let contents = match generations.current_gen_manifest() {
+                Ok(contents) => contents,
+                Err(GenerationsError::NoGenerations) => return Ok(RawManifest::default()),
+                Err(err) => return Err(ManagedEnvironmentError::ReadMergeGenerations(err)),
+            };
+            contents
+                .parse::<RawManifest>()
+                .map_err(ManagedEnvironmentError::ParseMergeManifest)
+        };
+
+        let base = read_manifest(&merge_base)?;
+        let ours = read_manifest(&project_branch)?;
+        let theirs = read_manifest(&sync_branch)?;
+
+        let merged = RawManifest::merge(&base, &ours, &theirs)
+            .map_err(ManagedEnvironmentError::MergeConflicts)?;
+
+        // Apply the merged manifest to a copy of the current upstream generation,
+        // which locks and builds the merged environment.
+        let upstream_generations = Generations::new(self.floxmeta.git.clone(), sync_branch.clone());
+        let local_gen = self
+            .generations()
+            .metadata()
+            .map_err(ManagedEnvironmentError::ReadMergeGenerations)?
+            .current_gen;
+        let upstream_gen = upstream_generations
+            .metadata()
+            .map_err(ManagedEnvironmentError::ReadMergeGenerations)?
+            .current_gen;
+        let mut merged_env = upstream_generations
+            .writable(&flox.temp_dir)
+            .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?
+            .get_current_generation()
+            .map_err(ManagedEnvironmentError::CreateGenerationFiles)?;
+        let result = merged_env
+            .edit(flox, merged.to_string())
+            .map_err(ManagedEnvironmentError::BuildMerged)?;
+
+        // Reset the local branch to the upstream branch
+        self.floxmeta
+            .git
+            .push_ref(
+                ".",
+                format!("refs/heads/{sync_branch}:refs/heads/{project_branch}"),
+                true,
+            )
+            .map_err(ManagedEnvironmentError::ApplyUpdates)?;
+
+        if result != EditResult::Unchanged {
+            let description = match (local_gen, upstream_gen) {
+                (Some(local_gen), Some(upstream_gen)) => format!(
+                    "merged local generation {local_gen} with upstream generation {upstream_gen}"
+                ),
+                _ => "merged local and upstream changes".to_string(),
+            };
+            let metadata = SingleGenerationMetadata::from_command(flox, command, description)
+                .with_store_path(result.store_path());
+
+            self.generations()
+                .writable(&flox.temp_dir)
+                .map_err(ManagedEnvironmentError::CreateFloxmetaDir)?
+                .add_generation(&mut merged_env, metadata)
+                .map_err(ManagedEnvironmentError::CommitGeneration)?;
+        }
+
+        self.lock_pointer()?;
+        self.reset_local_env_to_current_generation(flox)?;
+        if let Some(store_path) = result.store_path() {
+            CoreEnvironment::link(&self.out_link, store_path)
+                .map_err(ManagedEnvironmentError::Link)?;
+        }
+
+        Ok(())
+    }
+
     /// Detach the environment from the remote repository.
     ///
     /// And return a [PathEnvironment] representing
@@ -1611,7 +2211,7 @@ impl ManagedEnvironment {
         deregister(
             flox,
             &self.path,
-            &EnvironmentPointer::Managed(self.pointer.clone()),
+            &EnvironmentPointer::from(self.pointer.clone()),
         )?;
 
         // create the metadata for a path environment
@@ -1660,6 +2260,7 @@ pub mod test_helpers {
                 &floxhub,
             ),
             floxmeta: unusable_mock_floxmeta(),
+            generation: None,
         }
     }
 
@@ -1681,11 +2282,15 @@ pub mod test_helpers {
     ) -> ManagedEnvironment {
         ManagedEnvironment::push_new_without_building(
             flox,
-            owner,
-            "name".parse().unwrap(),
-            false,
+            PushNewOptions {
+                owner,
+                name: "name".parse().unwrap(),
+                git_remote: None,
+                force: false,
+            },
             CanonicalPath::new(tempdir_in(&flox.temp_dir).unwrap().into_path()).unwrap(),
             new_core_environment(flox, contents),
+            None,
         )
         .unwrap()
     }
@@ -1705,11 +2310,15 @@ pub mod test_helpers {
     ) -> ManagedEnvironment {
         ManagedEnvironment::push_new_without_building(
             flox,
-            owner,
-            "name".parse().unwrap(),
-            false,
+            PushNewOptions {
+                owner,
+                name: "name".parse().unwrap(),
+                git_remote: None,
+                force: false,
+            },
             CanonicalPath::new(tempdir_in(&flox.temp_dir).unwrap().into_path()).unwrap(),
             new_core_environment_from_env_files(flox, env_files_dir),
+            None,
         )
         .unwrap()
     }
@@ -1759,6 +2368,8 @@ mod test {
             floxhub_git_url_override: Some(
                 Url::from_directory_path(mock_floxhub_git_path).unwrap(),
             ),
+            git_remote: None,
+            tag: None,
             version: Version::<1> {},
         }
     }
@@ -1816,7 +2427,7 @@ mod test {
         test_pointer: &ManagedPointer,
         branch: &str,
     ) -> FloxMeta {
-        let user_floxmeta_dir = floxmeta_dir(flox, &test_pointer.owner);
+        let user_floxmeta_dir = floxmeta_dir(flox, test_pointer);
         fs::create_dir_all(&user_floxmeta_dir).unwrap();
         GitCommandProvider::clone_branch(
             format!("file://{}", remote_path.to_string_lossy()),
@@ -2245,7 +2856,7 @@ mod test {
 
         // Create a mock floxmeta (note clone is used instead of clone_branch,
         // which is used in create_floxmeta, because we need both branches)
-        let user_floxmeta_dir = floxmeta_dir(&flox, &test_pointer.owner);
+        let user_floxmeta_dir = floxmeta_dir(&flox, &test_pointer);
         fs::create_dir_all(&user_floxmeta_dir).unwrap();
         <GitCommandProvider as GitProvider>::clone(
             format!("file://{}", remote_path.to_string_lossy()),
@@ -2512,6 +3123,7 @@ mod test {
                 priority: None,
                 version: None,
                 systems: None,
+                outputs: None,
             }
             .into(),
         );
@@ -2541,6 +3153,77 @@ mod test {
         assert_eq!(lockfile_in_generation, lockfile);
     }
 
+    /// Pinning a generation reads it without changing the environment
+    #[test]
+    fn pinned_generation_does_not_modify_environment() {
+        let owner = EnvironmentOwner::from_str("owner").unwrap();
+        let (mut flox, _temp_dir_handle) = flox_instance_with_optional_floxhub(Some(&owner));
+
+        let client = MockClient::new(None::<&str>).unwrap();
+        flox.catalog_client = client.into();
+
+        let first_manifest =
+            toml_edit::ser::to_string_pretty(&TypedManifestCatalog::default()).unwrap();
+        let mut managed_env = test_helpers::mock_managed_environment(&flox, &first_manifest, owner);
+
+        let second_manifest = indoc! {"
+            version = 1
+
+            # second generation
+        "};
+        // Only the new generation is relevant here, not whether it builds
+        let _ = managed_env
+            .edit_unsafe(&flox, second_manifest.to_string())
+            .unwrap();
+
+        let branch = branch_name(&managed_env.pointer, &managed_env.path);
+        let rev_before = managed_env.floxmeta.git.branch_hash(&branch).unwrap();
+
+        managed_env.pin_generation(1.into()).unwrap();
+
+        assert_eq!(
+            managed_env.manifest_contents(&flox).unwrap(),
+            first_manifest
+        );
+        let err = managed_env
+            .edit(&flox, second_manifest.to_string())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            EnvironmentError::ManagedEnvironment(ManagedEnvironmentError::Pinned(_))
+        ));
+
+        let metadata = managed_env.generations().metadata().unwrap();
+        assert_eq!(metadata.current_gen, Some(2.into()));
+        assert_eq!(
+            managed_env.floxmeta.git.branch_hash(&branch).unwrap(),
+            rev_before
+        );
+    }
+
+    /// Generations created while pulling record `pull` as their command
+    #[test]
+    fn edit_unsafe_records_pull_provenance() {
+        let owner = EnvironmentOwner::from_str("owner").unwrap();
+        let (mut flox, _temp_dir_handle) = flox_instance_with_optional_floxhub(Some(&owner));
+
+        let client = MockClient::new(None::<&str>).unwrap();
+        flox.catalog_client = client.into();
+
+        let first_manifest =
+            toml_edit::ser::to_string_pretty(&TypedManifestCatalog::default()).unwrap();
+        let mut managed_env = test_helpers::mock_managed_environment(&flox, &first_manifest, owner);
+
+        let _ = managed_env
+            .edit_unsafe(&flox, "version = 1\n\n# pulled\n".to_string())
+            .unwrap();
+
+        let metadata = managed_env.generations().metadata().unwrap();
+        let generation = &metadata.generations[&2.into()];
+        assert_eq!(generation.command.as_deref(), Some("pull"));
+        assert_eq!(generation.system.as_ref(), Some(&flox.system));
+    }
+
     /// Validate should return true if the manifest in two environments is the same
     #[test]
     fn test_validate_local_same_manifest() {
@@ -2635,6 +3318,8 @@ mod test {
             name: EnvironmentName::from_str("name").unwrap(),
             floxhub_url: Url::from_str("https://hub.flox.dev").unwrap(),
             floxhub_git_url_override: None,
+            git_remote: None,
+            tag: None,
             version: Version::<1>,
         };
         let reg = EnvRegistry {
@@ -2644,7 +3329,7 @@ mod test {
                 path: path.to_path_buf(),
                 envs: vec![RegisteredEnv {
                     created_at: 0,
-                    pointer: EnvironmentPointer::Managed(pointer.clone()),
+                    pointer: EnvironmentPointer::from(pointer.clone()),
                 }],
                 activations: HashSet::new(),
             }],
@@ -2692,6 +3377,39 @@ mod test {
         ));
     }
 
+    /// Without an upstream branch there is nothing to merge
+    #[test]
+    fn merge_diverged_fails_without_upstream_branch() {
+        let (flox, _temp_dir_handle) = flox_instance();
+        let dot_flox_path = flox.temp_dir.join(DOT_FLOX);
+        std::fs::create_dir_all(&dot_flox_path).unwrap();
+
+        // create a mock remote
+        let (test_pointer, remote_path, remote) = create_mock_remote(flox.temp_dir.join("remote"));
+
+        let branch = remote_branch_name(&test_pointer);
+        remote.checkout(&branch, true).unwrap();
+        commit_file(&remote, "file 1");
+
+        // create a mock floxmeta
+        let floxmeta = create_floxmeta(&flox, &remote_path, &test_pointer, &branch);
+
+        let mut env = ManagedEnvironment::open_with(
+            floxmeta,
+            &flox,
+            test_pointer,
+            CanonicalPath::new(dot_flox_path).unwrap(),
+            flox.temp_dir.join("out_link"),
+        )
+        .unwrap();
+
+        // mimic an upstream environment that was deleted
+        env.floxmeta.git.delete_branch(&branch, true).unwrap();
+
+        let result = env.merge_diverged(&flox, "push");
+        assert!(matches!(result, Err(ManagedEnvironmentError::Diverged)));
+    }
+
     #[test]
     fn deregisters_on_delete() {
         let (flox, _temp_dir_handle) = flox_instance();
@@ -3,7 +3,6 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::{fs, io};
 
-use core_environment::UpgradeResult;
 pub use flox_core::{path_hash, Version};
 use log::debug;
 use serde::{Deserialize, Serialize};
@@ -15,9 +14,10 @@ use self::managed_environment::ManagedEnvironmentError;
 use self::remote_environment::RemoteEnvironmentError;
 use super::container_builder::ContainerBuilder;
 use super::env_registry::EnvRegistryError;
-use super::environment_ref::{EnvironmentName, EnvironmentOwner};
+use super::environment_ref::{EnvironmentName, EnvironmentOwner, GenerationTag};
 use super::lockfile::{LockedManifest, LockedManifestError};
 use super::manifest::{ManifestError, PackageToInstall, RawManifest, TomlEditError, TypedManifest};
+use super::migration;
 use crate::data::{CanonicalPath, CanonicalizeError};
 use crate::flox::{Flox, Floxhub};
 use crate::providers::git::{
@@ -29,9 +29,17 @@ use crate::providers::git::{
 use crate::utils::copy_file_without_permissions;
 
 mod core_environment;
-pub use core_environment::{test_helpers, CoreEnvironment, CoreEnvironmentError, EditResult};
+pub use core_environment::{
+    test_helpers,
+    CoreEnvironment,
+    CoreEnvironmentError,
+    EditResult,
+    OutdatedLockfile,
+    UpgradeResult,
+};
 
 pub mod generations;
+pub mod include;
 pub mod managed_environment;
 pub mod path_environment;
 pub mod remote_environment;
@@ -100,16 +108,16 @@ pub struct UninstallationAttempt {
 }
 
 /// Stores information about which of the manifest and lockfile need to be
-/// migrated from v0 to v1
+/// migrated to the latest version
 ///
 /// This struct should never be created if neither manifest nor lockfile need to
 /// be migrated.
 #[derive(Clone, Debug)]
 pub struct MigrationInfo {
-    /// The manifest is v0 and needs to be migrated to v1
+    /// The manifest has pending [migration::MANIFEST_MIGRATIONS]
     pub needs_manifest_migration: bool,
-    /// The current lockfile is v0,
-    /// or the manifest is v0 and the lockfile is v1.
+    /// The current lockfile is outdated,
+    /// or the manifest needs to be migrated.
     /// In either case, a migration requires changing the locked packages the
     /// user already has.
     pub needs_upgrade: bool,
@@ -149,6 +157,14 @@ pub trait Environment: Send {
         groups_or_iids: &[&str],
     ) -> Result<UpgradeResult, EnvironmentError>;
 
+    /// Resolve the upgrades of packages in this environment
+    /// without modifying the environment
+    fn dry_upgrade(
+        &mut self,
+        flox: &Flox,
+        groups_or_iids: &[&str],
+    ) -> Result<UpgradeResult, EnvironmentError>;
+
     /// Return the lockfile.
     ///
     /// Some implementations error if the lock does not already exist, while
@@ -229,46 +245,42 @@ pub trait Environment: Send {
         Ok(false)
     }
 
+    /// Check whether the manifest or lockfile were written by an older
+    /// version of flox and need to be migrated.
+    ///
     /// Possible actions depending on (version of manifest, version of lockfile)
-    /// 0, None - manifest migration
-    /// 0, 0 - manifest migration, upgrade
-    /// 0, 1 - manifest migration, upgrade (unlikely state)
-    /// 1, None - None
-    /// 1, 0 - upgrade
-    /// 1, 1 - None
-    fn needs_migration_to_v1(
-        &self,
-        flox: &Flox,
-    ) -> Result<Option<MigrationInfo>, EnvironmentError> {
+    /// old, None - manifest migration
+    /// old, any - manifest migration, upgrade
+    /// latest, None - None
+    /// latest, old - upgrade
+    /// latest, latest - None
+    fn needs_migration(&self, flox: &Flox) -> Result<Option<MigrationInfo>, EnvironmentError> {
         let raw_manifest = RawManifest::from_str(&self.manifest_contents(flox)?).map_err(|e| {
             EnvironmentError::Core(CoreEnvironmentError::ModifyToml(
                 TomlEditError::ParseManifest(e),
             ))
         })?;
-        let manifest = raw_manifest.to_typed().map_err(|e| {
+        // Make sure the manifest is valid for its version
+        raw_manifest.to_typed().map_err(|e| {
             EnvironmentError::Core(CoreEnvironmentError::ModifyToml(
                 TomlEditError::ParseManifest(e),
             ))
         })?;
-        let needs_manifest_migration = match manifest {
-            TypedManifest::Pkgdb(_) => true,
-            TypedManifest::Catalog(_) => false,
-        };
+        let needs_manifest_migration = !migration::pending_manifest_migrations(&raw_manifest)
+            .map_err(|e| EnvironmentError::Core(CoreEnvironmentError::MigrationVersion(e)))?
+            .is_empty();
 
         let lockfile_path = self.lockfile_path(flox)?;
         let needs_upgrade = if let Ok(canonical_path) = CanonicalPath::new(lockfile_path) {
-            // v0 manifest with any lockfile needs to be upgraded.
-            // Having a v1 lockfile would be an unlikely state,
+            // A migrated manifest with any lockfile needs to be upgraded.
+            // Having a latest lockfile would be an unlikely state,
             // but just treat it as needing an upgrade.
             if needs_manifest_migration {
                 true
             } else {
                 let lockfile = LockedManifest::read_from_file(&canonical_path)
                     .map_err(EnvironmentError::LockedManifest)?;
-                match lockfile {
-                    LockedManifest::Pkgdb(_) => true,
-                    LockedManifest::Catalog(_) => false,
-                }
+                migration::lockfile_needs_upgrade(&lockfile)
             }
         } else {
             // No existing lockfile, so no upgrade
@@ -287,7 +299,7 @@ pub trait Environment: Send {
     }
 
     /// This will lock
-    fn migrate_to_v1(
+    fn migrate(
         &mut self,
         flox: &Flox,
         migration_info: MigrationInfo,
@@ -306,7 +318,7 @@ pub trait Environment: Send {
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub enum EnvironmentPointer {
     /// Identifies an environment whose source of truth lies outside of the project itself
-    Managed(ManagedPointer),
+    Managed(Box<ManagedPointer>),
     /// Identifies an environment whose source of truth lies inside the project
     Path(PathPointer),
 }
@@ -321,6 +333,12 @@ pub struct PathPointer {
     version: Version<1>,
 }
 
+impl From<ManagedPointer> for EnvironmentPointer {
+    fn from(pointer: ManagedPointer) -> Self {
+        EnvironmentPointer::Managed(Box::new(pointer))
+    }
+}
+
 impl PathPointer {
     /// Create a new [PathPointer] with the given name.
     pub fn new(name: EnvironmentName) -> Self {
@@ -345,6 +363,22 @@ pub struct ManagedPointer {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[cfg_attr(test, proptest(value = "None"))]
     pub floxhub_git_url_override: Option<Url>,
+    /// A plain git repository hosting the environment instead of FloxHub,
+    /// e.g. a self-hosted bare repository or a `file://` url.
+    ///
+    /// If set, generations are pushed to and pulled from this repository
+    /// without FloxHub authentication.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub git_remote: Option<Url>,
+    /// Tag of the generation to use instead of the current generation,
+    /// see [GenerationTag].
+    ///
+    /// The tag is resolved whenever the environment is opened,
+    /// so the environment follows the tag when it is moved upstream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub tag: Option<GenerationTag>,
     version: Version<1>,
 }
 
@@ -356,9 +390,23 @@ impl ManagedPointer {
             owner,
             floxhub_url: floxhub.base_url().clone(),
             floxhub_git_url_override: floxhub.git_url_override().cloned(),
+            git_remote: None,
+            tag: None,
             version: Version::<1>,
         }
     }
+
+    /// Host the environment in the plain git repository at `url` instead of FloxHub
+    pub fn with_git_remote(mut self, url: Url) -> Self {
+        self.git_remote = Some(url);
+        self
+    }
+
+    /// Use the generation tagged `tag` instead of the current generation
+    pub fn with_tag(mut self, tag: GenerationTag) -> Self {
+        self.tag = Some(tag);
+        self
+    }
 }
 
 impl EnvironmentPointer {
@@ -775,22 +823,26 @@ mod test {
     }"#;
 
     static MANAGED_ENV_POINTER: Lazy<EnvironmentPointer> = Lazy::new(|| {
-        EnvironmentPointer::Managed(ManagedPointer {
+        EnvironmentPointer::from(ManagedPointer {
             name: EnvironmentName::from_str("name").unwrap(),
             owner: EnvironmentOwner::from_str("owner").unwrap(),
             floxhub_url: DEFAULT_FLOXHUB_URL.clone(),
             floxhub_git_url_override: None,
+            git_remote: None,
+            tag: None,
             version: Version::<1> {},
         })
     });
 
     #[test]
     fn serializes_managed_environment_pointer() {
-        let managed_pointer = EnvironmentPointer::Managed(ManagedPointer {
+        let managed_pointer = EnvironmentPointer::from(ManagedPointer {
             name: EnvironmentName::from_str("name").unwrap(),
             owner: EnvironmentOwner::from_str("owner").unwrap(),
             floxhub_url: DEFAULT_FLOXHUB_URL.clone(),
             floxhub_git_url_override: None,
+            git_remote: None,
+            tag: None,
             version: Version::<1> {},
         });
 
@@ -1018,7 +1070,7 @@ mod test {
         let (flox, _temp_dir_handle) = flox_instance();
         let environment = new_path_environment(&flox, "");
         assert!(matches!(
-            environment.needs_migration_to_v1(&flox),
+            environment.needs_migration(&flox),
             Ok(Some(MigrationInfo {
                 needs_manifest_migration: true,
                 needs_upgrade: false,
@@ -1035,7 +1087,7 @@ mod test {
         let environment =
             new_path_environment_from_env_files(&flox, MANUALLY_GENERATED.join("hello_v0"));
         assert!(matches!(
-            environment.needs_migration_to_v1(&flox),
+            environment.needs_migration(&flox),
             Ok(Some(MigrationInfo {
                 needs_manifest_migration: true,
                 needs_upgrade: true,
@@ -1065,7 +1117,7 @@ mod test {
             TypedManifest::Pkgdb(_),
         ));
         assert!(matches!(
-            environment.needs_migration_to_v1(&flox),
+            environment.needs_migration(&flox),
             Ok(Some(MigrationInfo {
                 needs_manifest_migration: true,
                 needs_upgrade: true,
@@ -1079,7 +1131,7 @@ mod test {
     fn needs_manifest_migration_1_none() {
         let (flox, _temp_dir_handle) = flox_instance();
         let environment = new_path_environment(&flox, "version = 1");
-        assert!(environment.needs_migration_to_v1(&flox).unwrap().is_none());
+        assert!(environment.needs_migration(&flox).unwrap().is_none());
     }
 
     /// When manifest is v1 and lockfile is v0, we need upgrade
@@ -1090,7 +1142,7 @@ mod test {
             new_path_environment_from_env_files(&flox, MANUALLY_GENERATED.join("hello_v0"));
         fs::write(environment.manifest_path(&flox).unwrap(), "version = 1").unwrap();
         assert!(matches!(
-            environment.needs_migration_to_v1(&flox),
+            environment.needs_migration(&flox),
             Ok(Some(MigrationInfo {
                 needs_manifest_migration: false,
                 needs_upgrade: true,
@@ -1106,7 +1158,7 @@ mod test {
         let environment = new_path_environment(&flox, "version = 1");
         let mut env_view = CoreEnvironment::new(environment.path.join(ENV_DIR_NAME));
         env_view.lock(&flox).unwrap();
-        assert!(environment.needs_migration_to_v1(&flox).unwrap().is_none());
+        assert!(environment.needs_migration(&flox).unwrap().is_none());
     }
 
     #[test]
@@ -13,6 +13,7 @@
 //! `ENVIRONMENT_DIR_NAME` contains the environment definition
 //! and is modified using [CoreEnvironment].
 
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::fs::{self};
 use std::io::Write;
@@ -22,6 +23,7 @@ use indoc::formatdoc;
 use log::debug;
 
 use super::core_environment::{CoreEnvironment, UpgradeResult};
+use super::include::IncludeFetcher;
 use super::{
     path_hash,
     services_socket_path,
@@ -72,7 +74,7 @@ pub struct PathEnvironment {
     pub pointer: PathPointer,
 }
 
-/// A profile script or list of packages to install when initializing an environment
+/// A profile script, variables or list of packages to install when initializing an environment
 #[derive(Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
 pub struct InitCustomization {
     pub hook_on_activate: Option<String>,
@@ -82,6 +84,7 @@ pub struct InitCustomization {
     pub profile_tcsh: Option<String>,
     pub profile_zsh: Option<String>,
     pub packages: Option<Vec<CatalogPackage>>,
+    pub vars: Option<BTreeMap<String, String>>,
 }
 
 impl PartialEq for PathEnvironment {
@@ -141,7 +144,13 @@ impl PathEnvironment {
     /// This method should only be used to create [CoreEnvironment]s for a [PathEnvironment].
     /// To modify the environment, use the [PathEnvironment] methods instead.
     pub(super) fn into_core_environment(self) -> CoreEnvironment {
+        self.env_view()
+    }
+
+    /// Get a [CoreEnvironment] for the `env/` directory of this environment
+    fn env_view(&self) -> CoreEnvironment {
         CoreEnvironment::new(self.path.join(ENV_DIR_NAME))
+            .with_include_fetcher(IncludeFetcher::for_dot_flox(&self.path))
     }
 
     pub fn rename(&mut self, new_name: EnvironmentName) -> Result<(), EnvironmentError> {
@@ -173,7 +182,7 @@ impl PathEnvironment {
 impl Environment for PathEnvironment {
     /// This will lock the environment if it is not already locked.
     fn lockfile(&mut self, flox: &Flox) -> Result<LockedManifest, EnvironmentError> {
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let mut env_view = self.env_view();
         Ok(env_view.ensure_locked(flox)?)
     }
 
@@ -183,12 +192,13 @@ impl Environment for PathEnvironment {
         flox: &Flox,
         tag: &str,
     ) -> Result<ContainerBuilder, EnvironmentError> {
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let mut env_view = self.env_view();
         env_view.ensure_locked(flox)?;
         let lockfile_path = CanonicalPath::new(env_view.lockfile_path())
             .expect("a locked environment must have a lockfile");
 
-        let builder = CoreEnvironment::build_container(lockfile_path, self.name().as_ref(), tag)?;
+        let builder =
+            CoreEnvironment::build_container(flox, lockfile_path, self.name().as_ref(), tag)?;
         Ok(builder)
     }
 
@@ -205,7 +215,7 @@ impl Environment for PathEnvironment {
         packages: &[PackageToInstall],
         flox: &Flox,
     ) -> Result<InstallationAttempt, EnvironmentError> {
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let mut env_view = self.env_view();
         let result = env_view.install(packages, flox)?;
         if let Some(ref store_path) = result.store_path {
             self.link(flox, store_path)?;
@@ -224,7 +234,7 @@ impl Environment for PathEnvironment {
         packages: Vec<String>,
         flox: &Flox,
     ) -> Result<UninstallationAttempt, EnvironmentError> {
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let mut env_view = self.env_view();
         let result = env_view.uninstall(packages, flox)?;
         if let Some(ref store_path) = result.store_path {
             self.link(flox, store_path)?;
@@ -235,7 +245,7 @@ impl Environment for PathEnvironment {
 
     /// Atomically edit this environment, ensuring that it still builds
     fn edit(&mut self, flox: &Flox, contents: String) -> Result<EditResult, EnvironmentError> {
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let mut env_view = self.env_view();
         let result = env_view.edit(flox, contents)?;
         if result != EditResult::Unchanged {
             if let Some(ref store_path) = result.store_path() {
@@ -252,7 +262,7 @@ impl Environment for PathEnvironment {
         groups_or_iids: &[&str],
     ) -> Result<UpgradeResult, EnvironmentError> {
         tracing::debug!(to_upgrade = groups_or_iids.join(","), "upgrading");
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let mut env_view = self.env_view();
         let result = env_view.upgrade(flox, groups_or_iids)?;
         if let Some(ref store_path) = result.store_path {
             self.link(flox, store_path)?;
@@ -261,6 +271,17 @@ impl Environment for PathEnvironment {
         Ok(result)
     }
 
+    /// Resolve the upgrades of packages in this environment
+    /// without modifying the environment
+    fn dry_upgrade(
+        &mut self,
+        flox: &Flox,
+        groups_or_iids: &[&str],
+    ) -> Result<UpgradeResult, EnvironmentError> {
+        let mut env_view = self.env_view();
+        Ok(env_view.dry_upgrade(flox, groups_or_iids)?)
+    }
+
     /// Read the environment definition file as a string
     fn manifest_contents(&self, flox: &Flox) -> Result<String, EnvironmentError> {
         fs::read_to_string(self.manifest_path(flox)?).map_err(EnvironmentError::ReadManifest)
@@ -268,7 +289,7 @@ impl Environment for PathEnvironment {
 
     /// Return the deserialized manifest
     fn manifest(&self, _flox: &Flox) -> Result<TypedManifest, EnvironmentError> {
-        let env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+        let env_view = self.env_view();
         env_view.manifest().map_err(EnvironmentError::Core)
     }
 
@@ -294,7 +315,7 @@ impl Environment for PathEnvironment {
         let out_link = self.out_link(&flox.system)?;
 
         if self.needs_rebuild(flox)? {
-            let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
+            let mut env_view = self.env_view();
             env_view.ensure_locked(flox)?;
             let store_path = env_view.build(flox)?;
             self.link(flox, store_path)?;
@@ -351,13 +372,13 @@ impl Environment for PathEnvironment {
         Ok(self.path.join(ENV_DIR_NAME).join(LOCKFILE_FILENAME))
     }
 
-    fn migrate_to_v1(
+    fn migrate(
         &mut self,
         flox: &Flox,
         migration_info: MigrationInfo,
     ) -> Result<(), EnvironmentError> {
-        let mut env_view = CoreEnvironment::new(self.path.join(ENV_DIR_NAME));
-        let store_path = env_view.migrate_to_v1(flox, migration_info)?;
+        let mut env_view = self.env_view();
+        let store_path = env_view.migrate(flox, migration_info)?;
         self.link(flox, store_path)?;
         Ok(())
     }
@@ -426,7 +447,7 @@ impl PathEnvironment {
 
         // Build environment if customization installs at least one package
         if matches!(customization.packages.as_deref(), Some([_, ..])) {
-            let mut env_view = CoreEnvironment::new(environment.path.join(ENV_DIR_NAME));
+            let mut env_view = environment.env_view();
             env_view.lock(flox)?;
             let store_path = env_view.build(flox)?;
             environment.link(flox, store_path)?;
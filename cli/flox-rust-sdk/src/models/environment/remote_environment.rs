@@ -1,11 +1,13 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use flox_core::path_hash;
 use log::debug;
 use tempfile::TempDir;
 use thiserror::Error;
 
 use super::core_environment::UpgradeResult;
+use super::generations::{GenerationId, Generations, RetentionPolicy};
 use super::managed_environment::{remote_branch_name, ManagedEnvironment, ManagedEnvironmentError};
 use super::{
     gcroots_dir,
@@ -24,12 +26,15 @@ use super::{
 };
 use crate::flox::{EnvironmentOwner, EnvironmentRef, Flox};
 use crate::models::container_builder::ContainerBuilder;
-use crate::models::environment_ref::EnvironmentName;
+use crate::models::environment_ref::{EnvironmentName, GenerationTag};
 use crate::models::floxmeta::{FloxMeta, FloxMetaError};
 use crate::models::lockfile::LockedManifest;
 use crate::models::manifest::{PackageToInstall, TypedManifest};
 
 const REMOTE_ENVIRONMENT_BASE_DIR: &str = "remote";
+/// Directory within [REMOTE_ENVIRONMENT_BASE_DIR] containing environments
+/// hosted in plain git repositories
+const GIT_REMOTES_DIR_NAME: &str = "git";
 
 #[derive(Debug, Error)]
 pub enum RemoteEnvironmentError {
@@ -60,12 +65,18 @@ pub enum RemoteEnvironmentError {
 
     #[error("could not set a new install prefix")]
     WriteNewOutlink(#[source] std::io::Error),
+
+    #[error("environment is pinned to generation {0} and can not be modified")]
+    Pinned(GenerationId),
 }
 
 #[derive(Debug)]
 pub struct RemoteEnvironment {
     inner: ManagedEnvironment,
     out_link: PathBuf,
+    /// Generation used instead of the current generation,
+    /// see [RemoteEnvironment::pin_generation]
+    pinned: Option<GenerationId>,
 }
 
 impl RemoteEnvironment {
@@ -99,7 +110,17 @@ impl RemoteEnvironment {
         )
         .unwrap();
 
-        let inner_out_link = gcroots_dir(flox, &pointer.owner).join(remote_branch_name(&pointer));
+        // Environments in plain git repositories may share owner and name
+        // with an environment on FloxHub
+        let out_link_name = match &pointer.git_remote {
+            Some(git_remote) => format!(
+                "{}-{}",
+                remote_branch_name(&pointer),
+                path_hash(git_remote.as_str())
+            ),
+            None => remote_branch_name(&pointer),
+        };
+        let inner_out_link = gcroots_dir(flox, &pointer.owner).join(out_link_name);
         let mut inner =
             ManagedEnvironment::open_with(floxmeta, flox, pointer, dot_flox_path, inner_out_link)
                 .map_err(RemoteEnvironmentError::OpenManagedEnvironment)?;
@@ -112,18 +133,29 @@ impl RemoteEnvironment {
 
         let out_link = path.join(GCROOTS_DIR_NAME);
 
-        Ok(Self { inner, out_link })
+        Ok(Self {
+            inner,
+            out_link,
+            pinned: None,
+        })
     }
 
     /// Pull a remote environment into a flox-provided managed environment
-    /// in `<FLOX_CACHE_DIR>/remote/<owner>/<name>`
+    /// in `<FLOX_CACHE_DIR>/remote/<owner>/<name>`,
+    /// or `<FLOX_CACHE_DIR>/remote/git/<hash of the url>/<owner>/<name>`
+    /// for environments hosted in plain git repositories.
     ///
     /// This function provides the sensible default directory to [RemoteEnvironment::new_in].
     /// The directory will be created by [RemoteEnvironment::new_in].
     pub fn new(flox: &Flox, pointer: ManagedPointer) -> Result<Self, RemoteEnvironmentError> {
-        let path = flox
-            .cache_dir
-            .join(REMOTE_ENVIRONMENT_BASE_DIR)
+        let base_dir = flox.cache_dir.join(REMOTE_ENVIRONMENT_BASE_DIR);
+        let base_dir = match &pointer.git_remote {
+            Some(git_remote) => base_dir
+                .join(GIT_REMOTES_DIR_NAME)
+                .join(path_hash(git_remote.as_str())),
+            None => base_dir,
+        };
+        let path = base_dir
             .join(pointer.owner.as_ref())
             .join(pointer.name.as_ref());
 
@@ -142,6 +174,120 @@ impl RemoteEnvironment {
         self.inner.pointer()
     }
 
+    /// Return a read-only view of the generations of this environment
+    pub fn generations(&self) -> Generations {
+        self.inner.generations()
+    }
+
+    /// Use `generation` instead of the current generation of the environment
+    ///
+    /// A pinned environment can not be modified.
+    /// The generation is built into a separate out link,
+    /// see [ManagedEnvironment::pin_generation],
+    /// so neither the cached environment nor its out link are changed.
+    pub fn pin_generation(&mut self, generation: GenerationId) -> Result<(), EnvironmentError> {
+        self.inner.pin_generation(generation.clone())?;
+        self.pinned = Some(generation);
+
+        Ok(())
+    }
+
+    /// Return the generation `tag` refers to
+    pub fn resolve_tag(&self, tag: &GenerationTag) -> Result<GenerationId, EnvironmentError> {
+        Ok(self.inner.resolve_tag(tag)?)
+    }
+
+    /// Tag `generation` with `tag` and push the change upstream
+    pub fn tag_generation(
+        &mut self,
+        flox: &Flox,
+        tag: GenerationTag,
+        generation: GenerationId,
+    ) -> Result<(), EnvironmentError> {
+        self.ensure_not_pinned()?;
+        self.inner.tag_generation(flox, tag, generation)?;
+        self.inner
+            .push(flox, false)
+            .map_err(RemoteEnvironmentError::UpdateUpstream)?;
+
+        Ok(())
+    }
+
+    /// Remove `tag` and push the change upstream
+    ///
+    /// Returns the generation the tag referred to.
+    pub fn remove_tag(
+        &mut self,
+        flox: &Flox,
+        tag: &GenerationTag,
+    ) -> Result<GenerationId, EnvironmentError> {
+        self.ensure_not_pinned()?;
+        let generation = self.inner.remove_tag(flox, tag)?;
+        self.inner
+            .push(flox, false)
+            .map_err(RemoteEnvironmentError::UpdateUpstream)?;
+
+        Ok(generation)
+    }
+
+    /// Fail if the environment is pinned to a generation,
+    /// as changes would be based on the pinned rather than the current generation.
+    fn ensure_not_pinned(&self) -> Result<(), RemoteEnvironmentError> {
+        match &self.pinned {
+            Some(generation) => Err(RemoteEnvironmentError::Pinned(generation.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Switch the environment to a given generation and push the change upstream
+    pub fn switch_generation(
+        &mut self,
+        flox: &Flox,
+        generation: GenerationId,
+    ) -> Result<(), EnvironmentError> {
+        self.ensure_not_pinned()?;
+        self.inner.switch_generation(flox, generation)?;
+        self.inner
+            .push(flox, false)
+            .map_err(|e| RemoteEnvironmentError::UpdateUpstream(e).into())
+            .and_then(|_| Self::update_out_link(flox, &self.out_link, &mut self.inner))?;
+
+        Ok(())
+    }
+
+    /// Switch the environment to the previous generation and push the change upstream
+    ///
+    /// Returns the generation that was switched to.
+    pub fn rollback(&mut self, flox: &Flox) -> Result<GenerationId, EnvironmentError> {
+        self.ensure_not_pinned()?;
+        let generation = self.inner.rollback(flox)?;
+        self.inner
+            .push(flox, false)
+            .map_err(|e| RemoteEnvironmentError::UpdateUpstream(e).into())
+            .and_then(|_| Self::update_out_link(flox, &self.out_link, &mut self.inner))?;
+
+        Ok(generation)
+    }
+
+    /// Remove generations not retained by `policy` and push the change upstream
+    ///
+    /// Returns the removed generations.
+    pub fn prune_generations(
+        &mut self,
+        flox: &Flox,
+        policy: &RetentionPolicy,
+    ) -> Result<Vec<GenerationId>, EnvironmentError> {
+        self.ensure_not_pinned()?;
+        let pruned = self.inner.prune_generations(flox, policy)?;
+        if !pruned.is_empty() {
+            self.inner
+                .push(flox, false)
+                .map_err(RemoteEnvironmentError::UpdateUpstream)?;
+        }
+
+        Ok(pruned)
+    }
+
     /// Update the out link to point to the current version of the environment
     ///
     /// The inner out link points to the latest version of the managed environment.
@@ -191,6 +337,7 @@ impl Environment for RemoteEnvironment {
         packages: &[PackageToInstall],
         flox: &Flox,
     ) -> Result<InstallationAttempt, EnvironmentError> {
+        self.ensure_not_pinned()?;
         let result = self.inner.install(packages, flox)?;
         self.inner
             .push(flox, false)
@@ -206,6 +353,7 @@ impl Environment for RemoteEnvironment {
         packages: Vec<String>,
         flox: &Flox,
     ) -> Result<UninstallationAttempt, EnvironmentError> {
+        self.ensure_not_pinned()?;
         let result = self.inner.uninstall(packages, flox)?;
         self.inner
             .push(flox, false)
@@ -217,6 +365,7 @@ impl Environment for RemoteEnvironment {
 
     /// Atomically edit this environment, ensuring that it still builds
     fn edit(&mut self, flox: &Flox, contents: String) -> Result<EditResult, EnvironmentError> {
+        self.ensure_not_pinned()?;
         let result = self.inner.edit(flox, contents)?;
         if result == EditResult::Unchanged {
             return Ok(result);
@@ -235,6 +384,7 @@ impl Environment for RemoteEnvironment {
         flox: &Flox,
         groups_or_iids: &[&str],
     ) -> Result<UpgradeResult, EnvironmentError> {
+        self.ensure_not_pinned()?;
         let result = self.inner.upgrade(flox, groups_or_iids)?;
         self.inner
             .push(flox, false)
@@ -244,6 +394,16 @@ impl Environment for RemoteEnvironment {
         Ok(result)
     }
 
+    /// Resolve the upgrades of packages in this environment
+    /// without modifying the environment
+    fn dry_upgrade(
+        &mut self,
+        flox: &Flox,
+        groups_or_iids: &[&str],
+    ) -> Result<UpgradeResult, EnvironmentError> {
+        self.inner.dry_upgrade(flox, groups_or_iids)
+    }
+
     /// Extract the current content of the manifest
     fn manifest_contents(&self, flox: &Flox) -> Result<String, EnvironmentError> {
         self.inner.manifest_contents(flox)
@@ -255,6 +415,10 @@ impl Environment for RemoteEnvironment {
     }
 
     fn activation_path(&mut self, flox: &Flox) -> Result<PathBuf, EnvironmentError> {
+        if self.pinned.is_some() {
+            return self.inner.activation_path(flox);
+        }
+
         Self::update_out_link(flox, &self.out_link, &mut self.inner)?;
         Ok(self.out_link.clone())
     }
@@ -309,12 +473,13 @@ impl Environment for RemoteEnvironment {
         self.inner.delete(flox)
     }
 
-    fn migrate_to_v1(
+    fn migrate(
         &mut self,
         flox: &Flox,
         migration_info: MigrationInfo,
     ) -> Result<(), EnvironmentError> {
-        self.inner.migrate_to_v1(flox, migration_info)?;
+        self.ensure_not_pinned()?;
+        self.inner.migrate(flox, migration_info)?;
         self.inner
             .push(flox, false)
             .map_err(|e| RemoteEnvironmentError::UpdateUpstream(e).into())
@@ -0,0 +1,685 @@
+//! Semantic comparison of two versions of an environment
+//!
+//! Compares the manifests and, where available, the lockfiles
+//! of two versions of an environment, e.g. two generations,
+//! and summarizes the changes to packages, variables, hooks and services.
+//!
+//! [LockfileDiff] compares only the locked packages of two lockfiles,
+//! in more detail and for every system.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use super::lockfile::{LockedManifestCatalog, LockedPackage};
+use super::manifest::{ManifestVariable, TypedManifestCatalog};
+use crate::data::System;
+
+/// A summary of the changes between two versions of an environment
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EnvironmentDiff {
+    pub packages: PackagesDiff,
+    pub vars: KeysDiff,
+    pub hooks: KeysDiff,
+    pub services: KeysDiff,
+}
+
+/// Packages added, removed or changed between two versions of an environment
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PackagesDiff {
+    pub added: Vec<PackageVersion>,
+    pub removed: Vec<PackageVersion>,
+    pub changed: Vec<PackageChange>,
+}
+
+/// A package and the version it is locked to, if known
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PackageVersion {
+    pub install_id: String,
+    pub version: Option<String>,
+}
+
+/// A package whose descriptor or locked version changed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PackageChange {
+    pub install_id: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+}
+
+/// Names of entries added, removed or changed in a table of the manifest
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KeysDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl KeysDiff {
+    /// Compare the keys and values of two maps
+    fn new<V: PartialEq>(old: &BTreeMap<String, V>, new: &BTreeMap<String, V>) -> Self {
+        let mut diff = KeysDiff::default();
+        for (key, old_value) in old {
+            match new.get(key) {
+                None => diff.removed.push(key.clone()),
+                Some(new_value) if new_value != old_value => diff.changed.push(key.clone()),
+                Some(_) => {},
+            }
+        }
+        for key in new.keys() {
+            if !old.contains_key(key) {
+                diff.added.push(key.clone());
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl PackagesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl EnvironmentDiff {
+    /// Compare two versions of an environment.
+    ///
+    /// Packages are compared by install id.
+    /// Locked versions are read from the lockfiles for the given `system`,
+    /// if a lockfile is not provided, versions are reported as unknown.
+    /// A package is considered changed if either its descriptor in the manifest
+    /// or its locked version changed.
+    pub fn new(
+        old_manifest: &TypedManifestCatalog,
+        old_lockfile: Option<&LockedManifestCatalog>,
+        new_manifest: &TypedManifestCatalog,
+        new_lockfile: Option<&LockedManifestCatalog>,
+        system: &System,
+    ) -> Self {
+        let locked_version = |lockfile: Option<&LockedManifestCatalog>, install_id: &str| {
+            lockfile?
+                .packages
+                .iter()
+                .find(|package| package.install_id() == install_id && package.system() == system)
+                .and_then(|package| package.version())
+                .map(String::from)
+        };
+
+        let mut packages = PackagesDiff::default();
+        for (install_id, old_descriptor) in old_manifest.install.iter() {
+            let old_version = locked_version(old_lockfile, install_id);
+            let Some(new_descriptor) = new_manifest.install.get(install_id) else {
+                packages.removed.push(PackageVersion {
+                    install_id: install_id.clone(),
+                    version: old_version,
+                });
+                continue;
+            };
+            let new_version = locked_version(new_lockfile, install_id);
+            if old_descriptor != new_descriptor || old_version != new_version {
+                packages.changed.push(PackageChange {
+                    install_id: install_id.clone(),
+                    old_version,
+                    new_version,
+                });
+            }
+        }
+        for install_id in new_manifest.install.keys() {
+            if !old_manifest.install.contains_key(install_id) {
+                packages.added.push(PackageVersion {
+                    install_id: install_id.clone(),
+                    version: locked_version(new_lockfile, install_id),
+                });
+            }
+        }
+
+        EnvironmentDiff {
+            packages,
+            vars: KeysDiff::new(&variables(old_manifest), &variables(new_manifest)),
+            hooks: KeysDiff::new(&hook_scripts(old_manifest), &hook_scripts(new_manifest)),
+            services: KeysDiff::new(&old_manifest.services.0, &new_manifest.services.0),
+        }
+    }
+
+    /// Whether there are no changes between the two versions
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+            && self.vars.is_empty()
+            && self.hooks.is_empty()
+            && self.services.is_empty()
+    }
+}
+
+/// Changes to the locked packages between two lockfiles, per system
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LockfileDiff {
+    /// Systems without changes are omitted
+    pub systems: BTreeMap<System, LockedPackagesDiff>,
+}
+
+/// Locked packages added, removed or changed on a single system
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LockedPackagesDiff {
+    pub added: Vec<PackageVersion>,
+    pub removed: Vec<PackageVersion>,
+    pub changed: Vec<LockedPackageChange>,
+}
+
+/// A package that is locked differently in two lockfiles
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LockedPackageChange {
+    pub install_id: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// The package is built from a different derivation,
+    /// even if its version didn't change, e.g. because a dependency changed.
+    pub derivation_changed: bool,
+    /// Outputs added or removed by the package,
+    /// or whose selection for installation changed
+    pub outputs: KeysDiff,
+}
+
+impl LockedPackagesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl LockfileDiff {
+    /// Compare the locked packages of two lockfiles by system and install id.
+    pub fn new(old: &LockedManifestCatalog, new: &LockedManifestCatalog) -> Self {
+        let old_packages = packages_by_system(old);
+        let new_packages = packages_by_system(new);
+        let empty = BTreeMap::new();
+
+        let systems: BTreeSet<&System> = old_packages
+            .keys()
+            .chain(new_packages.keys())
+            .copied()
+            .collect();
+        let mut diff = LockfileDiff::default();
+        for system in systems {
+            let old = old_packages.get(system).unwrap_or(&empty);
+            let new = new_packages.get(system).unwrap_or(&empty);
+            let packages = LockedPackagesDiff::new(old, new);
+            if !packages.is_empty() {
+                diff.systems.insert(system.clone(), packages);
+            }
+        }
+        diff
+    }
+
+    /// Whether the lockfiles lock the same packages on all systems
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+}
+
+impl LockedPackagesDiff {
+    fn new(old: &BTreeMap<&str, &LockedPackage>, new: &BTreeMap<&str, &LockedPackage>) -> Self {
+        let version = |package: &LockedPackage| package.version().map(String::from);
+
+        let mut diff = LockedPackagesDiff::default();
+        for (install_id, old_package) in old {
+            let Some(new_package) = new.get(install_id) else {
+                diff.removed.push(PackageVersion {
+                    install_id: install_id.to_string(),
+                    version: version(old_package),
+                });
+                continue;
+            };
+            let change = LockedPackageChange {
+                install_id: install_id.to_string(),
+                old_version: version(old_package),
+                new_version: version(new_package),
+                derivation_changed: old_package.derivation() != new_package.derivation(),
+                outputs: KeysDiff::new(&outputs(old_package), &outputs(new_package)),
+            };
+            if change.old_version != change.new_version
+                || change.derivation_changed
+                || !change.outputs.is_empty()
+            {
+                diff.changed.push(change);
+            }
+        }
+        for (install_id, new_package) in new {
+            if !old.contains_key(install_id) {
+                diff.added.push(PackageVersion {
+                    install_id: install_id.to_string(),
+                    version: version(new_package),
+                });
+            }
+        }
+        diff
+    }
+}
+
+/// Group the locked packages of a lockfile by system and install id
+fn packages_by_system(
+    lockfile: &LockedManifestCatalog,
+) -> BTreeMap<&System, BTreeMap<&str, &LockedPackage>> {
+    let mut packages: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    for package in &lockfile.packages {
+        packages
+            .entry(package.system())
+            .or_default()
+            .insert(package.install_id(), package);
+    }
+    packages
+}
+
+/// The outputs of a locked package and whether each of them is installed.
+///
+/// Store paths don't have named outputs.
+fn outputs(package: &LockedPackage) -> BTreeMap<String, bool> {
+    let (outputs, outputs_to_install, requested_outputs_to_install) = match package {
+        LockedPackage::Catalog(pkg) => (
+            &pkg.outputs,
+            &pkg.outputs_to_install,
+            &pkg.requested_outputs_to_install,
+        ),
+        LockedPackage::Flake(pkg) => (
+            &pkg.locked_installable.outputs,
+            &pkg.locked_installable.outputs_to_install,
+            &pkg.locked_installable.requested_outputs_to_install,
+        ),
+        LockedPackage::StorePath(_) => return BTreeMap::new(),
+    };
+    let installed = requested_outputs_to_install
+        .as_ref()
+        .or(outputs_to_install.as_ref());
+    outputs
+        .keys()
+        .map(|name| {
+            let is_installed = match installed {
+                Some(installed) => installed.contains(name),
+                None => true,
+            };
+            (name.clone(), is_installed)
+        })
+        .collect()
+}
+
+/// Collect the variables of a manifest, prefixing variables that are only set
+/// on specific systems with the system, e.g. `aarch64-darwin.FOO`.
+fn variables(manifest: &TypedManifestCatalog) -> BTreeMap<String, &ManifestVariable> {
+    let common = manifest
+        .vars
+        .0
+        .iter()
+        .map(|(name, var)| (name.clone(), var));
+    let system_specific = manifest.vars.1.iter().flat_map(|(system, vars)| {
+        vars.0
+            .iter()
+            .map(move |(name, var)| (format!("{system}.{name}"), var))
+    });
+    common.chain(system_specific).collect()
+}
+
+/// Collect the `[hook]` and `[profile]` scripts of a manifest
+/// keyed by their location in the manifest, e.g. `hook.on-activate`.
+fn hook_scripts(manifest: &TypedManifestCatalog) -> BTreeMap<String, serde_json::Value> {
+    let sections = [
+        ("hook", serde_json::to_value(&manifest.hook)),
+        ("profile", serde_json::to_value(&manifest.profile)),
+    ];
+
+    let mut scripts = BTreeMap::new();
+    for (section, value) in sections {
+        let Ok(serde_json::Value::Object(entries)) = value else {
+            continue;
+        };
+        for (key, script) in entries {
+            scripts.insert(format!("{section}.{key}"), script);
+        }
+    }
+    scripts
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::models::lockfile::test_helpers::fake_catalog_package_lock;
+    use crate::models::lockfile::LockedPackage;
+
+    fn manifest(contents: &str) -> TypedManifestCatalog {
+        toml::from_str(contents).unwrap()
+    }
+
+    fn lockfile(packages: &[(&str, &str)]) -> LockedManifestCatalog {
+        LockedManifestCatalog {
+            packages: packages
+                .iter()
+                .map(|(name, version)| {
+                    let (_, _, mut locked) = fake_catalog_package_lock(name, None);
+                    locked.install_id = name.to_string();
+                    locked.version = version.to_string();
+                    LockedPackage::from(locked)
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn identical_environments_have_empty_diff() {
+        let manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            [vars]
+            FOO = "foo"
+        "#});
+        let lockfile = lockfile(&[("hello", "2.12")]);
+
+        let diff = EnvironmentDiff::new(
+            &manifest,
+            Some(&lockfile),
+            &manifest,
+            Some(&lockfile),
+            &"aarch64-darwin".to_string(),
+        );
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn packages_added_removed_and_changed() {
+        let old_manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            curl.pkg-path = "curl"
+            vim.pkg-path = "vim"
+        "#});
+        let new_manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            curl.pkg-path = "curl"
+            vim.pkg-path = "neovim"
+            cowsay.pkg-path = "cowsay"
+        "#});
+        let old_lockfile = lockfile(&[("hello", "2.12"), ("curl", "8.4.0"), ("vim", "9.0")]);
+        let new_lockfile = lockfile(&[
+            ("hello", "2.12"),
+            ("curl", "8.5.0"),
+            ("vim", "0.9.5"),
+            ("cowsay", "3.7.0"),
+        ]);
+
+        let diff = EnvironmentDiff::new(
+            &old_manifest,
+            Some(&old_lockfile),
+            &new_manifest,
+            Some(&new_lockfile),
+            &"aarch64-darwin".to_string(),
+        );
+
+        assert_eq!(diff.packages, PackagesDiff {
+            added: vec![PackageVersion {
+                install_id: "cowsay".to_string(),
+                version: Some("3.7.0".to_string()),
+            }],
+            removed: vec![],
+            changed: vec![
+                PackageChange {
+                    install_id: "curl".to_string(),
+                    old_version: Some("8.4.0".to_string()),
+                    new_version: Some("8.5.0".to_string()),
+                },
+                PackageChange {
+                    install_id: "vim".to_string(),
+                    old_version: Some("9.0".to_string()),
+                    new_version: Some("0.9.5".to_string()),
+                },
+            ],
+        });
+    }
+
+    #[test]
+    fn versions_are_unknown_without_lockfile_or_for_other_systems() {
+        let old_manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+        "#});
+        let new_manifest = manifest(indoc! {r#"
+            version = 1
+        "#});
+
+        let diff = EnvironmentDiff::new(
+            &old_manifest,
+            Some(&lockfile(&[("hello", "2.12")])),
+            &new_manifest,
+            None,
+            &"x86_64-linux".to_string(),
+        );
+
+        assert_eq!(diff.packages.removed, vec![PackageVersion {
+            install_id: "hello".to_string(),
+            version: None,
+        }]);
+    }
+
+    #[test]
+    fn vars_hooks_and_services_changes() {
+        let old_manifest = manifest(indoc! {r#"
+            version = 1
+            [vars]
+            KEEP = "same"
+            CHANGE = "old"
+            REMOVE = "gone"
+            [hook]
+            on-activate = "echo old"
+            [services]
+            web.command = "python -m http.server"
+        "#});
+        let new_manifest = manifest(indoc! {r#"
+            version = 1
+            [vars]
+            KEEP = "same"
+            CHANGE = "new"
+            ADD = "added"
+            [hook]
+            on-activate = "echo new"
+            [profile]
+            common = "echo profile"
+            [services]
+            db.command = "postgres"
+        "#});
+
+        let diff = EnvironmentDiff::new(
+            &old_manifest,
+            None,
+            &new_manifest,
+            None,
+            &"x86_64-linux".to_string(),
+        );
+
+        assert!(diff.packages.is_empty());
+        assert_eq!(diff.vars, KeysDiff {
+            added: vec!["ADD".to_string()],
+            removed: vec!["REMOVE".to_string()],
+            changed: vec!["CHANGE".to_string()],
+        });
+        assert_eq!(diff.hooks, KeysDiff {
+            added: vec!["profile.common".to_string()],
+            removed: vec![],
+            changed: vec!["hook.on-activate".to_string()],
+        });
+        assert_eq!(diff.services, KeysDiff {
+            added: vec!["db".to_string()],
+            removed: vec!["web".to_string()],
+            changed: vec![],
+        });
+    }
+
+    #[test]
+    fn system_specific_changes_are_prefixed_with_system() {
+        let old_manifest = manifest(indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "common"
+        "#});
+        let new_manifest = manifest(indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "common"
+            [vars.aarch64-darwin]
+            FOO = "darwin"
+            [hook.aarch64-darwin]
+            on-activate = "echo darwin"
+        "#});
+
+        let diff = EnvironmentDiff::new(
+            &old_manifest,
+            None,
+            &new_manifest,
+            None,
+            &"aarch64-darwin".to_string(),
+        );
+
+        assert_eq!(diff.vars, KeysDiff {
+            added: vec!["aarch64-darwin.FOO".to_string()],
+            removed: vec![],
+            changed: vec![],
+        });
+        assert_eq!(diff.hooks, KeysDiff {
+            added: vec!["hook.aarch64-darwin".to_string()],
+            removed: vec![],
+            changed: vec![],
+        });
+    }
+
+    /// A catalog package locked for `system` with the given outputs
+    fn locked_package(
+        install_id: &str,
+        version: &str,
+        derivation: &str,
+        system: &str,
+        outputs: &[&str],
+    ) -> LockedPackage {
+        let (_, _, mut locked) = fake_catalog_package_lock(install_id, None);
+        locked.install_id = install_id.to_string();
+        locked.version = version.to_string();
+        locked.derivation = derivation.to_string();
+        locked.system = system.to_string();
+        locked.outputs = outputs
+            .iter()
+            .map(|output| {
+                (
+                    output.to_string(),
+                    format!("/nix/store/{derivation}-{output}"),
+                )
+            })
+            .collect();
+        LockedPackage::from(locked)
+    }
+
+    fn lockfile_with(packages: Vec<LockedPackage>) -> LockedManifestCatalog {
+        LockedManifestCatalog {
+            packages,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn identical_lockfiles_have_empty_diff() {
+        let lockfile = lockfile_with(vec![
+            locked_package("hello", "2.12", "hello-drv", "aarch64-darwin", &["out"]),
+            locked_package("hello", "2.12", "hello-drv-linux", "x86_64-linux", &["out"]),
+        ]);
+        assert!(LockfileDiff::new(&lockfile, &lockfile).is_empty());
+    }
+
+    #[test]
+    fn lockfile_diff_reports_changes_per_system() {
+        let old = lockfile_with(vec![
+            locked_package("hello", "2.12", "hello-drv", "aarch64-darwin", &["out"]),
+            locked_package("curl", "8.4.0", "curl-drv", "aarch64-darwin", &["out"]),
+            locked_package("curl", "8.4.0", "curl-drv-linux", "x86_64-linux", &["out"]),
+            locked_package("vim", "9.0", "vim-drv", "x86_64-linux", &["out"]),
+        ]);
+        let new = lockfile_with(vec![
+            locked_package("hello", "2.12", "hello-drv", "aarch64-darwin", &["out"]),
+            locked_package("curl", "8.5.0", "curl-drv-2", "aarch64-darwin", &["out"]),
+            locked_package("curl", "8.4.0", "curl-drv-linux-2", "x86_64-linux", &[
+                "out", "dev",
+            ]),
+            locked_package("cowsay", "3.7.0", "cowsay-drv", "x86_64-linux", &["out"]),
+        ]);
+
+        let diff = LockfileDiff::new(&old, &new);
+
+        assert_eq!(
+            diff.systems,
+            BTreeMap::from([
+                ("aarch64-darwin".to_string(), LockedPackagesDiff {
+                    added: vec![],
+                    removed: vec![],
+                    changed: vec![LockedPackageChange {
+                        install_id: "curl".to_string(),
+                        old_version: Some("8.4.0".to_string()),
+                        new_version: Some("8.5.0".to_string()),
+                        derivation_changed: true,
+                        outputs: KeysDiff::default(),
+                    }],
+                }),
+                ("x86_64-linux".to_string(), LockedPackagesDiff {
+                    added: vec![PackageVersion {
+                        install_id: "cowsay".to_string(),
+                        version: Some("3.7.0".to_string()),
+                    }],
+                    removed: vec![PackageVersion {
+                        install_id: "vim".to_string(),
+                        version: Some("9.0".to_string()),
+                    }],
+                    changed: vec![LockedPackageChange {
+                        install_id: "curl".to_string(),
+                        old_version: Some("8.4.0".to_string()),
+                        new_version: Some("8.4.0".to_string()),
+                        derivation_changed: true,
+                        outputs: KeysDiff {
+                            added: vec!["dev".to_string()],
+                            removed: vec![],
+                            changed: vec![],
+                        },
+                    }],
+                }),
+            ])
+        );
+    }
+
+    #[test]
+    fn lockfile_diff_reports_changed_output_selection() {
+        let old = lockfile_with(vec![locked_package(
+            "curl",
+            "8.4.0",
+            "curl-drv",
+            "x86_64-linux",
+            &["out", "dev"],
+        )]);
+        let mut new = old.clone();
+        let LockedPackage::Catalog(ref mut curl) = new.packages[0] else {
+            unreachable!()
+        };
+        curl.requested_outputs_to_install = Some(vec!["out".to_string()]);
+
+        let diff = LockfileDiff::new(&old, &new);
+        let changed = &diff.systems["x86_64-linux"].changed;
+        assert_eq!(changed.len(), 1);
+        assert!(!changed[0].derivation_changed);
+        assert_eq!(changed[0].outputs, KeysDiff {
+            added: vec![],
+            removed: vec![],
+            changed: vec!["dev".to_string()],
+        });
+    }
+}
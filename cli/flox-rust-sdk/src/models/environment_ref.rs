@@ -65,16 +65,60 @@ impl FromStr for EnvironmentName {
     }
 }
 
+/// A name for a generation of an environment, e.g. `stable`
+///
+/// Tags are referred to as `<owner>/<name>@<tag>`.
+/// Tags may only contain alphanumeric characters, '.', '_', and '-',
+/// and to tell them apart from generation numbers, they may not be purely numeric.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    AsRef,
+    Display,
+    DeserializeFromStr,
+    SerializeDisplay,
+)]
+pub struct GenerationTag(String);
+
+impl FromStr for GenerationTag {
+    type Err = EnvironmentRefError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty()
+            || s.chars().all(|c| c.is_ascii_digit())
+            || !s
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ['.', '_', '-'].contains(&c))
+        {
+            Err(EnvironmentRefError::InvalidTag(s.to_string()))?
+        }
+
+        Ok(GenerationTag(s.to_string()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct EnvironmentRef {
     owner: EnvironmentOwner,
     name: EnvironmentName,
+    /// Tag of the generation to use instead of the current generation
+    #[cfg_attr(test, proptest(value = "None"))]
+    tag: Option<GenerationTag>,
 }
 
 impl Display for EnvironmentRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}/{}", self.owner, self.name)
+        write!(f, "{}/{}", self.owner, self.name)?;
+        if let Some(tag) = &self.tag {
+            write!(f, "@{tag}")?;
+        }
+        Ok(())
     }
 }
 
@@ -85,9 +129,14 @@ impl FromStr for EnvironmentRef {
         let (owner, name) = s
             .split_once('/')
             .ok_or(EnvironmentRefError::InvalidOwner(s.to_string()))?;
+        let (name, tag) = match name.split_once('@') {
+            Some((name, tag)) => (name, Some(GenerationTag::from_str(tag)?)),
+            None => (name, None),
+        };
         Ok(Self {
             owner: EnvironmentOwner::from_str(owner)?,
             name: EnvironmentName::from_str(name)?,
+            tag,
         })
     }
 }
@@ -97,6 +146,7 @@ impl From<ManagedPointer> for EnvironmentRef {
         Self {
             owner: pointer.owner,
             name: pointer.name,
+            tag: None,
         }
     }
 }
@@ -108,6 +158,9 @@ pub enum EnvironmentRefError {
 
     #[error("Owner '{0}' is invalid.\nEnvironment owners may only contain alphanumeric characters, '.', '_', and '-'.")]
     InvalidOwner(String),
+
+    #[error("Tag '{0}' is invalid.\nGeneration tags may only contain alphanumeric characters, '.', '_', and '-', and may not be a number.")]
+    InvalidTag(String),
 }
 
 impl EnvironmentRef {
@@ -119,15 +172,24 @@ impl EnvironmentRef {
         &self.name
     }
 
+    pub fn tag(&self) -> Option<&GenerationTag> {
+        self.tag.as_ref()
+    }
+
     pub fn new(owner: impl AsRef<str>, name: impl AsRef<str>) -> Result<Self, EnvironmentRefError> {
         Ok(Self {
             owner: EnvironmentOwner::from_str(owner.as_ref())?,
             name: EnvironmentName::from_str(name.as_ref())?,
+            tag: None,
         })
     }
 
     pub fn new_from_parts(owner: EnvironmentOwner, name: EnvironmentName) -> Self {
-        Self { owner, name }
+        Self {
+            owner,
+            name,
+            tag: None,
+        }
     }
 }
 
@@ -154,7 +216,68 @@ mod test {
         type Strategy = BoxedStrategy<Self>;
 
         fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
-            "[^ /]".prop_map(|s| EnvironmentName(s.to_string())).boxed()
+            // '@' is valid in names, but starts a tag in an [EnvironmentRef]
+            "[^ /@]"
+                .prop_map(|s| EnvironmentName(s.to_string()))
+                .boxed()
         }
     }
+
+    #[test]
+    fn parse_ref_with_tag() {
+        let env_ref = EnvironmentRef::from_str("owner/name@stable").unwrap();
+        assert_eq!(env_ref.owner().as_ref(), "owner");
+        assert_eq!(env_ref.name().as_ref(), "name");
+        assert_eq!(
+            env_ref.tag().map(|tag| tag.to_string()),
+            Some("stable".to_string())
+        );
+        assert_eq!(env_ref.to_string(), "owner/name@stable");
+
+        let env_ref = EnvironmentRef::from_str("owner/name").unwrap();
+        assert_eq!(env_ref.tag(), None);
+        assert_eq!(env_ref.to_string(), "owner/name");
+
+        assert!(matches!(
+            EnvironmentRef::from_str("owner/name@"),
+            Err(EnvironmentRefError::InvalidTag(_))
+        ));
+        assert!(matches!(
+            EnvironmentRef::from_str("owner/name@42"),
+            Err(EnvironmentRefError::InvalidTag(_))
+        ));
+    }
+
+    #[test]
+    fn parse_tags() {
+        for tag in ["stable", "v1.2", "release_2024-01", "1a", "-"] {
+            assert_eq!(GenerationTag::from_str(tag).unwrap().to_string(), tag);
+        }
+        for tag in [
+            "",
+            "42",
+            "with space",
+            "a/b",
+            "a@b",
+            "a:b",
+            "a+b",
+            "ünïcode",
+            "a\tb",
+        ] {
+            assert!(
+                matches!(
+                    GenerationTag::from_str(tag),
+                    Err(EnvironmentRefError::InvalidTag(_))
+                ),
+                "'{tag}' should be invalid"
+            );
+        }
+    }
+
+    /// Environments named after directories containing '@' can still be opened
+    #[test]
+    fn names_may_contain_at() {
+        assert!(EnvironmentName::from_str("name@host").is_ok());
+        assert!(EnvironmentOwner::from_str("owner@host").is_ok());
+    }
 }
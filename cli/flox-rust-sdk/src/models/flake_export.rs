@@ -0,0 +1,624 @@
+//! Export a locked environment as a standalone Nix flake
+//!
+//! The generated `flake.nix` pins the same nixpkgs revisions and flakes as the
+//! lockfile and provides a `devShells.<system>.default` for every system
+//! of the environment, setting `[vars]` and running the `hook.on-activate`,
+//! `profile.common` and `profile.bash` scripts.
+//! The export is deterministic, so that it can be regenerated and compared
+//! whenever the lockfile changes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde_json::json;
+use thiserror::Error;
+
+use super::lockfile::{LockedManifestCatalog, LockedPackage};
+
+/// The files of an exported flake
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlakeExport {
+    pub flake_nix: String,
+    pub flake_lock: String,
+    /// Install IDs of packages installed from store paths,
+    /// which can't be referenced by a pure flake
+    pub skipped_packages: Vec<String>,
+    /// Names of secret variables, which are not exported
+    pub skipped_secrets: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum FlakeExportError {
+    #[error(
+        "the environment does not contain any packages from the catalog, which are required to provide nixpkgs for the development shell"
+    )]
+    NoNixpkgs,
+}
+
+/// A flake input of the exported flake
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Input {
+    name: String,
+    url: String,
+    /// The locked `flake.lock` node, if the input can be locked without fetching it
+    lock: Option<GitHubRef>,
+    /// Whether the input is a nixpkgs revision (as opposed to a flake providing packages)
+    is_nixpkgs: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct GitHubRef {
+    owner: String,
+    repo: String,
+    rev: String,
+    last_modified: Option<i64>,
+}
+
+impl GitHubRef {
+    /// Parse `https://github.com/<owner>/<repo>?rev=<rev>`
+    /// and `github:<owner>/<repo>/<rev>` URLs
+    fn parse(url: &str) -> Option<Self> {
+        let url = url.split_once('#').map_or(url, |(url, _fragment)| url);
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+        let (owner, repo, rev) = if let Some(path) = path.strip_prefix("https://github.com/") {
+            let (owner, repo) = path.split_once('/')?;
+            let rev = query
+                .split('&')
+                .find_map(|param| param.strip_prefix("rev="))?;
+            (owner, repo.trim_end_matches(".git"), rev)
+        } else if let Some(path) = path.strip_prefix("github:") {
+            let mut parts = path.splitn(3, '/');
+            (parts.next()?, parts.next()?, parts.next()?)
+        } else {
+            return None;
+        };
+
+        let is_commit = rev.len() == 40 && rev.chars().all(|c| c.is_ascii_hexdigit());
+        if owner.is_empty() || repo.is_empty() || repo.contains('/') || !is_commit {
+            return None;
+        }
+
+        Some(GitHubRef {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            rev: rev.to_string(),
+            last_modified: None,
+        })
+    }
+
+    fn url(&self) -> String {
+        format!("github:{}/{}/{}", self.owner, self.repo, self.rev)
+    }
+}
+
+/// Generate a `flake.nix` and `flake.lock` reproducing the locked environment
+pub fn export_flake(
+    name: &str,
+    lockfile: &LockedManifestCatalog,
+) -> Result<FlakeExport, FlakeExportError> {
+    let mut inputs = BTreeMap::<String, Input>::new();
+    // system -> package expressions
+    let mut packages = BTreeMap::<String, BTreeSet<(String, String)>>::new();
+    // system -> names of the nixpkgs inputs used by the system
+    let mut nixpkgs_by_system = BTreeMap::<String, BTreeSet<String>>::new();
+    let mut skipped_packages = BTreeSet::new();
+
+    for package in &lockfile.packages {
+        match package {
+            LockedPackage::Catalog(package) => {
+                let mut github = GitHubRef::parse(&package.locked_url);
+                if let Some(github) = &mut github {
+                    github.last_modified = Some(package.rev_date.timestamp());
+                }
+                let short_rev = package.rev.get(..12).unwrap_or(&package.rev);
+                let input_name = format!("nixpkgs-{short_rev}");
+                let input = inputs.entry(input_name.clone()).or_insert_with(|| Input {
+                    name: input_name.clone(),
+                    url: github
+                        .as_ref()
+                        .map(GitHubRef::url)
+                        .unwrap_or_else(|| package.locked_url.clone()),
+                    lock: github,
+                    is_nixpkgs: true,
+                });
+
+                let attr_path = package.attr_path.split('.').collect::<Vec<_>>();
+                let expressions = package_expressions(
+                    &input.name,
+                    &attr_path,
+                    package.requested_outputs_to_install.as_deref(),
+                );
+                packages
+                    .entry(package.system.clone())
+                    .or_default()
+                    .extend(expressions.map(|expr| (package.install_id.clone(), expr)));
+                nixpkgs_by_system
+                    .entry(package.system.clone())
+                    .or_default()
+                    .insert(input.name.clone());
+            },
+            LockedPackage::Flake(package) => {
+                let installable = &package.locked_installable;
+                let url = installable
+                    .locked_url
+                    .split_once('#')
+                    .map_or(installable.locked_url.as_str(), |(url, _)| url);
+                let existing = inputs
+                    .values()
+                    .find(|input| !input.is_nixpkgs && input.url == url)
+                    .map(|input| input.name.clone());
+                let input_name = existing.unwrap_or_else(|| {
+                    let input_name = format!("flake-{}", package.install_id);
+                    inputs.insert(input_name.clone(), Input {
+                        name: input_name.clone(),
+                        url: url.to_string(),
+                        // the last modification date of flakes isn't locked
+                        lock: None,
+                        is_nixpkgs: false,
+                    });
+                    input_name
+                });
+
+                let attr_path = installable
+                    .locked_flake_attr_path
+                    .split('.')
+                    .collect::<Vec<_>>();
+                let expressions = package_expressions(
+                    &format!("inputs.{}", nix_attr(&input_name)),
+                    &attr_path,
+                    installable.requested_outputs_to_install.as_deref(),
+                );
+                packages
+                    .entry(installable.system.clone())
+                    .or_default()
+                    .extend(expressions.map(|expr| (package.install_id.clone(), expr)));
+            },
+            LockedPackage::StorePath(package) => {
+                skipped_packages.insert(package.install_id.clone());
+            },
+        }
+    }
+
+    let default_nixpkgs = inputs
+        .values()
+        .find(|input| input.is_nixpkgs)
+        .map(|input| input.name.clone())
+        .ok_or(FlakeExportError::NoNixpkgs)?;
+
+    let systems = match &lockfile.manifest.options.systems {
+        Some(systems) => systems.iter().cloned().collect::<BTreeSet<_>>(),
+        None => packages.keys().cloned().collect(),
+    };
+
+    let flake_nix = render_flake_nix(
+        name,
+        lockfile,
+        &inputs,
+        &systems,
+        &packages,
+        &nixpkgs_by_system,
+        &default_nixpkgs,
+    );
+    let flake_lock = render_flake_lock(&inputs);
+
+    Ok(FlakeExport {
+        flake_nix,
+        flake_lock,
+        skipped_packages: skipped_packages.into_iter().collect(),
+        skipped_secrets: lockfile
+            .manifest
+            .vars
+            .secrets()
+            .map(|(name, _)| name.clone())
+            .collect(),
+    })
+}
+
+/// Nix expressions for the outputs of a package
+fn package_expressions(
+    scope: &str,
+    attr_path: &[&str],
+    outputs: Option<&[String]>,
+) -> impl Iterator<Item = String> {
+    let package = std::iter::once(scope.to_string())
+        .chain(attr_path.iter().map(|attr| nix_attr(attr)))
+        .collect::<Vec<_>>()
+        .join(".");
+    let expressions = match outputs {
+        Some(outputs) if !outputs.is_empty() => outputs
+            .iter()
+            .map(|output| format!("{package}.{}", nix_attr(output)))
+            .collect(),
+        _ => vec![package],
+    };
+    expressions.into_iter()
+}
+
+fn render_flake_nix(
+    name: &str,
+    lockfile: &LockedManifestCatalog,
+    inputs: &BTreeMap<String, Input>,
+    systems: &BTreeSet<String>,
+    packages: &BTreeMap<String, BTreeSet<(String, String)>>,
+    nixpkgs_by_system: &BTreeMap<String, BTreeSet<String>>,
+    default_nixpkgs: &str,
+) -> String {
+    let manifest = &lockfile.manifest;
+    let allow_unfree = manifest.options.allow.unfree.unwrap_or(true);
+    let allow_broken = manifest.options.allow.broken.unwrap_or(false)
+        || lockfile
+            .packages
+            .iter()
+            .any(|package| package.broken() == Some(true));
+    let allow_insecure = manifest.options.allow.insecure.unwrap_or(false)
+        || lockfile.packages.iter().any(|package| {
+            package
+                .as_catalog_package_ref()
+                .is_some_and(|package| package.insecure == Some(true))
+        });
+
+    let mut nix = String::new();
+    let _ = writeln!(
+        nix,
+        "# Generated by `flox export flake` from the lockfile of environment {}.",
+        nix_string(name)
+    );
+    let _ = writeln!(
+        nix,
+        "# Do not edit, changes are overwritten when the environment is exported again."
+    );
+    let _ = writeln!(nix, "{{");
+    let _ = writeln!(
+        nix,
+        "  description = {};",
+        nix_string(&format!("Flox environment {name}"))
+    );
+    let _ = writeln!(nix);
+    let _ = writeln!(nix, "  inputs = {{");
+    for input in inputs.values() {
+        let _ = writeln!(
+            nix,
+            "    {}.url = {};",
+            nix_attr(&input.name),
+            nix_string(&input.url)
+        );
+    }
+    let _ = writeln!(nix, "  }};");
+    let _ = writeln!(nix);
+    let _ = writeln!(nix, "  outputs = inputs: let");
+    let _ = writeln!(nix, "    config = {{");
+    let _ = writeln!(nix, "      allowUnfree = {allow_unfree};");
+    let _ = writeln!(nix, "      allowBroken = {allow_broken};");
+    if allow_insecure {
+        let _ = writeln!(nix, "      allowInsecurePredicate = _: true;");
+    }
+    let _ = writeln!(nix, "    }};");
+    let _ = writeln!(nix, "  in {{");
+    let _ = writeln!(nix, "    devShells = {{");
+
+    for system in systems {
+        let manifest = manifest.copy_for_system(system);
+        let mut nixpkgs = nixpkgs_by_system.get(system).cloned().unwrap_or_default();
+        let shell_nixpkgs = nixpkgs
+            .first()
+            .cloned()
+            .unwrap_or(default_nixpkgs.to_string());
+        nixpkgs.insert(shell_nixpkgs.clone());
+
+        let _ = writeln!(nix, "      {}.default = let", nix_attr(system));
+        for input in &nixpkgs {
+            let _ = writeln!(
+                nix,
+                "        {} = import inputs.{} {{ system = {}; inherit config; }};",
+                nix_attr(input),
+                nix_attr(input),
+                nix_string(system)
+            );
+        }
+        let _ = writeln!(nix, "      in {}.mkShell {{", nix_attr(&shell_nixpkgs));
+
+        let _ = writeln!(nix, "        packages = [");
+        for (install_id, expression) in packages.get(system).into_iter().flatten() {
+            let _ = writeln!(nix, "          # {install_id}");
+            let _ = writeln!(nix, "          {expression}");
+        }
+        let _ = writeln!(nix, "        ];");
+
+        let vars = manifest.vars.static_values();
+        if !vars.is_empty() {
+            let _ = writeln!(nix, "        env = {{");
+            for (name, value) in &vars {
+                let _ = writeln!(nix, "          {} = {};", nix_attr(name), nix_string(value));
+            }
+            let _ = writeln!(nix, "        }};");
+        }
+
+        let scripts = [
+            ("hook.on-activate", &manifest.hook.on_activate),
+            ("profile.common", &manifest.profile.common),
+            ("profile.bash", &manifest.profile.bash),
+        ]
+        .into_iter()
+        .filter_map(|(name, script)| Some((name, script.as_deref()?)))
+        .collect::<Vec<_>>();
+        if !scripts.is_empty() {
+            let _ = writeln!(nix, "        shellHook = ''");
+            for (index, (name, script)) in scripts.iter().enumerate() {
+                if index > 0 {
+                    let _ = writeln!(nix);
+                }
+                let _ = writeln!(nix, "          # {name}");
+                for line in script.trim_end().lines() {
+                    if line.is_empty() {
+                        let _ = writeln!(nix);
+                    } else {
+                        let _ = writeln!(nix, "          {}", nix_indented_string_line(line));
+                    }
+                }
+            }
+            let _ = writeln!(nix, "        '';");
+        }
+
+        let _ = writeln!(nix, "      }};");
+    }
+
+    let _ = writeln!(nix, "    }};");
+    let _ = writeln!(nix, "  }};");
+    let _ = writeln!(nix, "}}");
+    nix
+}
+
+/// Render a `flake.lock` in the format written by Nix
+///
+/// Inputs that can't be locked without fetching them are omitted
+/// and locked by Nix when the flake is first used.
+fn render_flake_lock(inputs: &BTreeMap<String, Input>) -> String {
+    let mut nodes = serde_json::Map::new();
+    let mut root_inputs = serde_json::Map::new();
+
+    for input in inputs.values() {
+        let Some(github) = &input.lock else {
+            continue;
+        };
+        let mut locked = json!({
+            "owner": github.owner,
+            "repo": github.repo,
+            "rev": github.rev,
+            "type": "github",
+        });
+        if let Some(last_modified) = github.last_modified {
+            locked["lastModified"] = json!(last_modified);
+        }
+        nodes.insert(
+            input.name.clone(),
+            json!({
+                "locked": locked,
+                "original": {
+                    "owner": github.owner,
+                    "repo": github.repo,
+                    "rev": github.rev,
+                    "type": "github",
+                },
+            }),
+        );
+        root_inputs.insert(input.name.clone(), json!(input.name));
+    }
+    nodes.insert("root".to_string(), json!({ "inputs": root_inputs }));
+
+    let lock = json!({
+        "nodes": nodes,
+        "root": "root",
+        "version": 7,
+    });
+    let mut rendered =
+        serde_json::to_string_pretty(&lock).expect("flake.lock is serializable to JSON");
+    rendered.push('\n');
+    rendered
+}
+
+/// Quote an attribute name unless it is a valid Nix identifier
+fn nix_attr(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '\''));
+    if is_identifier {
+        name.to_string()
+    } else {
+        nix_string(name)
+    }
+}
+
+/// Render a double quoted Nix string
+fn nix_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+/// Escape a line for use in an indented (`''`) Nix string
+fn nix_indented_string_line(line: &str) -> String {
+    line.replace("''", "'''").replace("${", "''${")
+}
+
+#[cfg(test)]
+mod tests {
+    use flox_core::Version;
+    use indoc::{formatdoc, indoc};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::models::lockfile::test_helpers::{
+        fake_catalog_package_lock,
+        fake_flake_installable_lock,
+    };
+    use crate::models::lockfile::LockedPackageStorePath;
+    use crate::models::manifest::{ManifestPackageDescriptorStorePath, TypedManifestCatalog};
+
+    const REV: &str = "27e30d177e57d912d614c88c622dcfdb2e6e6515";
+
+    fn lockfile(manifest: &str, packages: Vec<LockedPackage>) -> LockedManifestCatalog {
+        LockedManifestCatalog {
+            version: Version::<1>,
+            manifest: toml::from_str::<TypedManifestCatalog>(manifest).unwrap(),
+            packages,
+            compose: None,
+        }
+    }
+
+    fn catalog_package(name: &str) -> LockedPackage {
+        let (_, _, mut package) = fake_catalog_package_lock(name, None);
+        package.install_id = name.to_string();
+        package.locked_url = format!("https://github.com/flox/nixpkgs?rev={REV}");
+        package.rev = REV.to_string();
+        package.into()
+    }
+
+    #[test]
+    fn parses_github_urls() {
+        let expected = GitHubRef {
+            owner: "flox".to_string(),
+            repo: "nixpkgs".to_string(),
+            rev: REV.to_string(),
+            last_modified: None,
+        };
+        assert_eq!(
+            GitHubRef::parse(&format!("https://github.com/flox/nixpkgs?rev={REV}")),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            GitHubRef::parse(&format!("github:flox/nixpkgs/{REV}?narHash=abc#hello")),
+            Some(expected)
+        );
+        assert_eq!(GitHubRef::parse("github:flox/nixpkgs/main"), None);
+        assert_eq!(GitHubRef::parse("git+https://example.com/repo"), None);
+    }
+
+    #[test]
+    fn exports_packages_vars_and_hooks() {
+        let mut python = catalog_package("python311Packages.pip");
+        if let LockedPackage::Catalog(ref mut python) = python {
+            python.install_id = "pip".to_string();
+            python.requested_outputs_to_install = Some(vec!["out".to_string(), "man".to_string()]);
+        }
+        let (_, _, flake) = fake_flake_installable_lock("hello");
+
+        let lockfile = lockfile(
+            indoc! {r#"
+                version = 1
+                [vars]
+                GREETING = "hello ${USER}"
+                TOKEN = { command = "pass show token" }
+                [hook]
+                on-activate = "echo ''activated''"
+                [profile]
+                bash = "alias ll='ls -l'"
+                [options]
+                systems = ["aarch64-darwin"]
+            "#},
+            vec![catalog_package("hello"), python, flake.into()],
+        );
+
+        let export = export_flake("my-env", &lockfile).unwrap();
+        let short_rev = &REV[..12];
+        assert_eq!(export.flake_nix, formatdoc! {r#"
+            # Generated by `flox export flake` from the lockfile of environment "my-env".
+            # Do not edit, changes are overwritten when the environment is exported again.
+            {{
+              description = "Flox environment my-env";
+
+              inputs = {{
+                flake-hello_install_id.url = "github:nowhere/exciting/affeaffeaffeaffeaffeaffeaffeaffeaffeaffe";
+                nixpkgs-{short_rev}.url = "github:flox/nixpkgs/{REV}";
+              }};
+
+              outputs = inputs: let
+                config = {{
+                  allowUnfree = true;
+                  allowBroken = false;
+                }};
+              in {{
+                devShells = {{
+                  aarch64-darwin.default = let
+                    nixpkgs-{short_rev} = import inputs.nixpkgs-{short_rev} {{ system = "aarch64-darwin"; inherit config; }};
+                  in nixpkgs-{short_rev}.mkShell {{
+                    packages = [
+                      # hello
+                      nixpkgs-{short_rev}.hello
+                      # hello_install_id
+                      inputs.flake-hello_install_id.packages.aarch64-darwin.hello
+                      # pip
+                      nixpkgs-{short_rev}.python311Packages.pip.man
+                      # pip
+                      nixpkgs-{short_rev}.python311Packages.pip.out
+                    ];
+                    env = {{
+                      GREETING = "hello \${{USER}}";
+                    }};
+                    shellHook = ''
+                      # hook.on-activate
+                      echo '''activated'''
+
+                      # profile.bash
+                      alias ll='ls -l'
+                    '';
+                  }};
+                }};
+              }};
+            }}
+        "#});
+        assert_eq!(export.skipped_secrets, vec!["TOKEN".to_string()]);
+
+        let lock: serde_json::Value = serde_json::from_str(&export.flake_lock).unwrap();
+        let input_name = format!("nixpkgs-{short_rev}");
+        assert_eq!(
+            lock["nodes"]["root"]["inputs"],
+            json!({ &input_name: &input_name })
+        );
+        assert_eq!(
+            lock["nodes"][&input_name]["locked"],
+            json!({
+                "lastModified": 1630368000,
+                "owner": "flox",
+                "repo": "nixpkgs",
+                "rev": REV,
+                "type": "github",
+            })
+        );
+        assert_eq!(lock["version"], json!(7));
+    }
+
+    #[test]
+    fn skips_store_paths_and_requires_nixpkgs() {
+        let store_path = LockedPackageStorePath::from_parts(
+            "local".to_string(),
+            &ManifestPackageDescriptorStorePath {
+                store_path: "/nix/store/some-path".to_string(),
+                systems: None,
+                priority: None,
+            },
+            "aarch64-darwin".to_string(),
+        );
+        let manifest = "version = 1";
+
+        let export = export_flake(
+            "env",
+            &lockfile(manifest, vec![
+                catalog_package("hello"),
+                store_path.clone().into(),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(export.skipped_packages, vec!["local".to_string()]);
+
+        let err = export_flake("env", &lockfile(manifest, vec![store_path.into()])).unwrap_err();
+        assert!(matches!(err, FlakeExportError::NoNixpkgs));
+    }
+}
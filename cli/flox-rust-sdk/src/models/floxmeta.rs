@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 
+use flox_core::path_hash;
 use log::debug;
 use thiserror::Error;
 use url::Url;
@@ -18,6 +19,9 @@ use crate::providers::git::{
 };
 
 pub const FLOXMETA_DIR_NAME: &str = "meta";
+/// Directory within [FLOXMETA_DIR_NAME] containing floxmeta repositories
+/// of environments hosted in plain git repositories
+const GIT_REMOTES_DIR_NAME: &str = "git";
 
 #[derive(Debug)]
 pub struct FloxMeta {
@@ -58,25 +62,14 @@ impl FloxMeta {
     ) -> Result<Self, FloxMetaError> {
         let token = flox.floxhub_token.as_ref();
 
-        let floxhub = Floxhub::new(
-            pointer.floxhub_url.to_owned(),
-            pointer.floxhub_git_url_override.clone(),
-        )
-        .map_err(FloxMetaError::FloxhubError)?;
+        let remote = FloxmetaRemote::from_pointer(pointer).map_err(FloxMetaError::FloxhubError)?;
 
-        let git_url = floxhub.git_url();
-
-        let git_options = floxmeta_git_options(git_url, &pointer.owner, token);
+        let git_options = floxmeta_git_options(&remote, token);
         let branch = remote_branch_name(pointer);
 
-        let git = GitCommandProvider::clone_branch_with(
-            git_options,
-            format!("{}/{}/floxmeta", git_url, pointer.owner),
-            path,
-            branch,
-            true,
-        )
-        .map_err(FloxMetaError::CloneBranch)?;
+        let git =
+            GitCommandProvider::clone_branch_with(git_options, remote.url(), path, branch, true)
+                .map_err(FloxMetaError::CloneBranch)?;
 
         Ok(FloxMeta { git })
     }
@@ -89,7 +82,7 @@ impl FloxMeta {
     ///
     /// Like [`FloxmetaV2::clone_to`], but uses the system path for floxmeta repositories in XDG_DATA_HOME
     pub fn clone(flox: &Flox, pointer: &ManagedPointer) -> Result<Self, FloxMetaError> {
-        Self::clone_to(floxmeta_dir(flox, &pointer.owner), flox, pointer)
+        Self::clone_to(floxmeta_dir(flox, pointer), flox, pointer)
     }
 
     /// Open a floxmeta repository at a given path
@@ -112,15 +105,9 @@ impl FloxMeta {
     ) -> Result<Self, FloxMetaError> {
         let token = flox.floxhub_token.as_ref();
 
-        let floxhub = Floxhub::new(
-            pointer.floxhub_url.to_owned(),
-            pointer.floxhub_git_url_override.clone(),
-        )
-        .map_err(FloxMetaError::FloxhubError)?;
-
-        let git_url = floxhub.git_url();
+        let remote = FloxmetaRemote::from_pointer(pointer).map_err(FloxMetaError::FloxhubError)?;
 
-        let git_options = floxmeta_git_options(git_url, &pointer.owner, token);
+        let git_options = floxmeta_git_options(&remote, token);
 
         if !user_floxmeta_dir.as_ref().exists() {
             Err(FloxMetaError::NotFound(pointer.owner.to_string()))?
@@ -144,7 +131,7 @@ impl FloxMeta {
     ///
     /// Like [`FloxmetaV2::open_at`], but uses the system path for floxmeta repositories in XDG_DATA_HOME.
     pub fn open(flox: &Flox, pointer: &ManagedPointer) -> Result<Self, FloxMetaError> {
-        let user_floxmeta_dir = floxmeta_dir(flox, &pointer.owner);
+        let user_floxmeta_dir = floxmeta_dir(flox, pointer);
         Self::open_at(user_floxmeta_dir, flox, pointer)
     }
 
@@ -155,15 +142,9 @@ impl FloxMeta {
     ) -> Result<Self, FloxMetaError> {
         let token = flox.floxhub_token.as_ref();
 
-        let floxhub = Floxhub::new(
-            pointer.floxhub_url.to_owned(),
-            pointer.floxhub_git_url_override.clone(),
-        )
-        .map_err(FloxMetaError::FloxhubError)?;
-
-        let git_url = floxhub.git_url();
+        let remote = FloxmetaRemote::from_pointer(pointer).map_err(FloxMetaError::FloxhubError)?;
 
-        let git_options = floxmeta_git_options(git_url, &pointer.owner, token);
+        let git_options = floxmeta_git_options(&remote, token);
 
         let git = GitCommandProvider::init_with(git_options, user_floxmeta_dir, false).unwrap();
         git.rename_branch(&remote_branch_name(pointer)).unwrap();
@@ -172,19 +153,60 @@ impl FloxMeta {
     }
 }
 
+/// The remote repository hosting the floxmeta repository of a managed environment
+#[derive(Debug, Clone, PartialEq)]
+pub enum FloxmetaRemote {
+    /// The floxmeta repository of an owner on a FloxHub instance,
+    /// authenticated with a FloxHub token
+    Floxhub {
+        git_url: Url,
+        owner: EnvironmentOwner,
+    },
+    /// A plain git repository, e.g. a self-hosted bare repository or a `file://` url.
+    ///
+    /// Authentication is left to git, e.g. using ssh keys.
+    Git(Url),
+}
+
+impl FloxmetaRemote {
+    /// Determine the remote hosting the environment of a [ManagedPointer]
+    pub fn from_pointer(pointer: &ManagedPointer) -> Result<Self, FloxhubError> {
+        if let Some(git_remote) = &pointer.git_remote {
+            return Ok(FloxmetaRemote::Git(git_remote.clone()));
+        }
+
+        let floxhub = Floxhub::new(
+            pointer.floxhub_url.to_owned(),
+            pointer.floxhub_git_url_override.clone(),
+        )?;
+
+        Ok(FloxmetaRemote::Floxhub {
+            git_url: floxhub.git_url().clone(),
+            owner: pointer.owner.clone(),
+        })
+    }
+
+    /// The url of the remote floxmeta repository
+    pub fn url(&self) -> String {
+        match self {
+            FloxmetaRemote::Floxhub { git_url, owner } => format!("{git_url}/{owner}/floxmeta"),
+            FloxmetaRemote::Git(url) => url.to_string(),
+        }
+    }
+}
+
 /// Returns the git options for interacting with floxmeta repositories
 ///
 /// * Disable global and system config
 ///   to avoid user config interfering with flox operations
 /// * Set required user config (name and email)
-/// * Configure a dynamic origin for the FloxHub repository
-///   to allow cloning and fetching from different FloxHub hosts per user.
-///   The FloxHub host is derived from the FloxHub url in the environment pointer.
-/// * Set authentication with the FloxHub token using an inline credential helper
-///   if a token is provided.
+/// * Configure a dynamic origin for the remote floxmeta repository
+///   to allow cloning and fetching from different remotes per environment.
+///   The remote is derived from the environment pointer.
+/// * For FloxHub remotes, set authentication with the FloxHub token
+///   using an inline credential helper if a token is provided.
 pub fn floxmeta_git_options(
-    floxhub_git_url: &Url,
-    floxhub_owner: &str,
+    remote: &FloxmetaRemote,
     floxhub_token: Option<&FloxhubToken>,
 ) -> GitCommandOptions {
     let mut options = GitCommandOptions::default();
@@ -198,18 +220,20 @@ pub fn floxmeta_git_options(
     options.add_env_var("GIT_CONFIG_GLOBAL", "/dev/null");
     options.add_env_var("GIT_CONFIG_SYSTEM", "/dev/null");
 
-    // provides a "dynamic" remote "dynamicorigin".
+    // Floxmeta repositories are stored per FloxHub owner locally,
+    // but may contain environments from different remotes.
+    // Rather than storing remotes in the local repository,
+    // provide a "dynamic" remote "dynamicorigin",
+    // pointing to the remote of the environment the current operation operates on.
     //
-    // either the FloxHub url from the environment pointer
-    // or the default FloxHub url if the current operation does not operate on a managed environment.
-    //
-    // Local floxmeta repositories may contain environments from different FloxHub hosts.
-    // The dynamic origin allows to fetch from different FloxHub hosts per environment
+    // The dynamic origin allows to fetch from different remotes per environment
     // and reduces the amount of state stored in the local floxmeta repository.
-    options.add_config_flag(
-        "remote.dynamicorigin.url",
-        format!("{floxhub_git_url}/{floxhub_owner}/floxmeta"),
-    );
+    options.add_config_flag("remote.dynamicorigin.url", remote.url());
+
+    let FloxmetaRemote::Floxhub { git_url, .. } = remote else {
+        debug!("using plain git remote, skipping FloxHub authentication");
+        return options;
+    };
 
     let token = if let Some(token) = floxhub_token {
         debug!("using configured FloxHub token");
@@ -226,17 +250,26 @@ pub fn floxmeta_git_options(
     // to enforce authentication failures and avoid fallback to pinentry
     options.add_env_var("FLOX_FLOXHUB_TOKEN", token);
     options.add_config_flag(
-        &format!("credential.{floxhub_git_url}.helper"),
+        &format!("credential.{git_url}.helper"),
         r#"!f(){ echo "username=oauth"; echo "password=$FLOX_FLOXHUB_TOKEN"; }; f"#,
     );
 
     options
 }
 
-pub(super) fn floxmeta_dir(flox: &Flox, owner: &EnvironmentOwner) -> PathBuf {
-    flox.data_dir
-        .join(FLOXMETA_DIR_NAME)
-        .join(owner.to_string())
+/// The path of the local floxmeta repository for the environment of `pointer`
+///
+/// Environments on FloxHub are stored in a repository per owner.
+/// Environments hosted in plain git repositories are stored
+/// in a repository per remote url.
+pub(super) fn floxmeta_dir(flox: &Flox, pointer: &ManagedPointer) -> PathBuf {
+    let floxmeta_dir = flox.data_dir.join(FLOXMETA_DIR_NAME);
+    match &pointer.git_remote {
+        Some(git_remote) => floxmeta_dir
+            .join(GIT_REMOTES_DIR_NAME)
+            .join(path_hash(git_remote.as_str())),
+        None => floxmeta_dir.join(pointer.owner.to_string()),
+    }
 }
 
 pub mod test_helpers {
@@ -303,4 +336,36 @@ mod tests {
         FloxMeta::open_at(tempdir.path().join("dest"), &flox, &pointer)
             .expect("Opening a floxmeta repo should succeed");
     }
+
+    /// Test whether a floxmeta repository can be cloned from a plain git remote
+    /// and is stored separately from FloxHub floxmeta repositories.
+    #[test]
+    fn clone_repo_from_git_remote() {
+        let (flox, tempdir) = flox_instance();
+        let source_path = tempdir.path().join("source");
+        let git_remote = Url::from_directory_path(source_path.join("floxtest/floxmeta")).unwrap();
+
+        let pointer = ManagedPointer::new(
+            "floxtest".parse().unwrap(),
+            "test".parse().unwrap(),
+            &flox.floxhub,
+        )
+        .with_git_remote(git_remote.clone());
+
+        assert_eq!(
+            FloxmetaRemote::from_pointer(&pointer).unwrap(),
+            FloxmetaRemote::Git(git_remote.clone())
+        );
+        let floxhub_pointer =
+            ManagedPointer::new(pointer.owner.clone(), pointer.name.clone(), &flox.floxhub);
+        assert_ne!(
+            floxmeta_dir(&flox, &pointer),
+            floxmeta_dir(&flox, &floxhub_pointer)
+        );
+
+        create_fake_floxmeta(&source_path, &flox, &pointer);
+
+        FloxMeta::clone(&flox, &pointer).expect("Cloning a floxmeta repo should succeed");
+        FloxMeta::open(&flox, &pointer).expect("Opening a floxmeta repo should succeed");
+    }
 }
@@ -0,0 +1,604 @@
+//! Checks for common mistakes in manifests and lockfiles
+//!
+//! Unlike errors found while parsing or locking a manifest,
+//! the problems reported here don't prevent an environment from being used,
+//! but are likely to cause surprising behavior.
+//! Each [Diagnostic] has a [Severity] so that callers can decide
+//! which problems to reject, e.g. when checking environments in CI.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::Serialize;
+
+use super::lockfile::{LockedManifestCatalog, LockedPackage, DEFAULT_SYSTEMS_STR};
+use super::manifest::{ManifestPackageDescriptor, TypedManifestCatalog, DEFAULT_PRIORITY};
+use crate::data::System;
+
+/// How severe a problem found by [lint] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Likely intentional, but worth a look
+    Info,
+    /// Likely to cause surprising behavior
+    Warning,
+    /// The environment does not behave as described by the manifest
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "unknown severity '{s}', expected one of 'info', 'warning' or 'error'"
+            )),
+        }
+    }
+}
+
+/// The kinds of problems detected by [lint]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    /// A package is restricted to systems not enabled in `options.systems`
+    UnsupportedSystem,
+    /// A `pkg-group` contains only a single package
+    SinglePackageGroup,
+    /// Packages with the same name and priority are installed side by side
+    PriorityConflict,
+    /// The command of a service is not provided by an installed package
+    ServiceMissingPackage,
+    /// A variable from `[vars]` is reassigned by a hook or profile script
+    VarShadowedByHook,
+    /// The lockfile is missing or was locked from a different manifest
+    StaleLockfile,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Lint::UnsupportedSystem => "unsupported-system",
+            Lint::SinglePackageGroup => "single-package-group",
+            Lint::PriorityConflict => "priority-conflict",
+            Lint::ServiceMissingPackage => "service-missing-package",
+            Lint::VarShadowedByHook => "var-shadowed-by-hook",
+            Lint::StaleLockfile => "stale-lockfile",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A problem found by [lint]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub lint: Lint,
+    /// The manifest entry the problem was found in, e.g. `install.hello.systems`,
+    /// or `None` if the problem concerns the environment as a whole
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.lint)?;
+        if let Some(path) = &self.path {
+            write!(f, " {path}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check a manifest and, if it has been locked, its lockfile for common mistakes
+///
+/// Services are checked against the packages locked for `system`.
+/// Diagnostics are ordered from most to least severe.
+pub fn lint(
+    manifest: &TypedManifestCatalog,
+    lockfile: Option<&LockedManifestCatalog>,
+    system: &System,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    diagnostics.extend(lint_stale_lockfile(manifest, lockfile));
+    diagnostics.extend(lint_unsupported_systems(manifest));
+    diagnostics.extend(lint_single_package_groups(manifest));
+    if let Some(lockfile) = lockfile {
+        diagnostics.extend(lint_priority_conflicts(lockfile));
+    }
+    diagnostics.extend(lint_service_packages(manifest, lockfile, system));
+    diagnostics.extend(lint_shadowed_vars(manifest));
+
+    diagnostics.sort_by_key(|diagnostic| Reverse(diagnostic.severity));
+    diagnostics
+}
+
+fn lint_stale_lockfile(
+    manifest: &TypedManifestCatalog,
+    lockfile: Option<&LockedManifestCatalog>,
+) -> Option<Diagnostic> {
+    let message = match lockfile {
+        None => "the environment has not been locked",
+        Some(lockfile) => {
+            let locked_manifest = match &lockfile.compose {
+                Some(compose) => &compose.composer,
+                None => &lockfile.manifest,
+            };
+            if locked_manifest == manifest {
+                return None;
+            }
+            "the lockfile was created from a different version of the manifest"
+        },
+    };
+
+    Some(Diagnostic {
+        severity: Severity::Error,
+        lint: Lint::StaleLockfile,
+        path: None,
+        message: message.to_string(),
+    })
+}
+
+fn lint_unsupported_systems(manifest: &TypedManifestCatalog) -> Vec<Diagnostic> {
+    let enabled_systems = manifest
+        .options
+        .systems
+        .as_deref()
+        .unwrap_or(&*DEFAULT_SYSTEMS_STR);
+
+    let mut diagnostics = Vec::new();
+    for (install_id, descriptor) in manifest.install.iter() {
+        let systems = match descriptor {
+            ManifestPackageDescriptor::Catalog(descriptor) => &descriptor.systems,
+            ManifestPackageDescriptor::FlakeRef(descriptor) => &descriptor.systems,
+            ManifestPackageDescriptor::StorePath(descriptor) => &descriptor.systems,
+        };
+        let unsupported = systems
+            .iter()
+            .flatten()
+            .filter(|system| !enabled_systems.contains(system))
+            .map(|system| format!("'{system}'"))
+            .collect::<Vec<_>>();
+        if unsupported.is_empty() {
+            continue;
+        }
+
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            lint: Lint::UnsupportedSystem,
+            path: Some(format!("install.{install_id}.systems")),
+            message: format!(
+                "package '{install_id}' requests {} which {} not enabled in 'options.systems'",
+                unsupported.join(", "),
+                if unsupported.len() == 1 { "is" } else { "are" },
+            ),
+        });
+    }
+    diagnostics
+}
+
+fn lint_single_package_groups(manifest: &TypedManifestCatalog) -> Vec<Diagnostic> {
+    let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (install_id, descriptor) in manifest.install.iter() {
+        let Some(group) = descriptor
+            .as_catalog_descriptor_ref()
+            .and_then(|descriptor| descriptor.pkg_group.as_deref())
+        else {
+            continue;
+        };
+        groups.entry(group).or_default().push(install_id);
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(group, install_ids)| match install_ids.as_slice() {
+            [install_id] => Some(Diagnostic {
+                severity: Severity::Info,
+                lint: Lint::SinglePackageGroup,
+                path: Some(format!("install.{install_id}.pkg-group")),
+                message: format!(
+                    "package group '{group}' only contains '{install_id}', \
+                     so it is resolved independently of all other packages"
+                ),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Packages with the same name likely provide the same files.
+/// If they also have the same priority, building the environment fails
+/// or picks one of the files arbitrarily.
+fn lint_priority_conflicts(lockfile: &LockedManifestCatalog) -> Vec<Diagnostic> {
+    let mut candidates: BTreeMap<(&str, u64), BTreeSet<&str>> = BTreeMap::new();
+    for package in &lockfile.packages {
+        let (name, priority) = match package {
+            LockedPackage::Catalog(package) => (package.pname.as_str(), package.priority),
+            LockedPackage::Flake(package) => {
+                let Some(pname) = package.locked_installable.pname.as_deref() else {
+                    continue;
+                };
+                let priority = package
+                    .locked_installable
+                    .priority
+                    .unwrap_or(DEFAULT_PRIORITY);
+                (pname, priority)
+            },
+            LockedPackage::StorePath(_) => continue,
+        };
+        candidates
+            .entry((name, priority))
+            .or_default()
+            .insert(package.install_id());
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(_, install_ids)| install_ids.len() > 1)
+        .map(|((name, priority), install_ids)| {
+            let last = install_ids.last().expect("at least two install ids");
+            Diagnostic {
+                severity: Severity::Warning,
+                lint: Lint::PriorityConflict,
+                path: Some(format!("install.{last}.priority")),
+                message: format!(
+                    "packages {} all provide '{name}' with priority {priority} \
+                     and may contain conflicting files",
+                    install_ids
+                        .iter()
+                        .map(|id| format!("'{id}'"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Check that the program run by each service is provided by an installed package.
+///
+/// If the packages locked for `system` have been built,
+/// their `bin` directories are searched for the program.
+/// Otherwise the program is matched against the names of installed packages,
+/// which is less reliable and thus reported with a lower severity.
+fn lint_service_packages(
+    manifest: &TypedManifestCatalog,
+    lockfile: Option<&LockedManifestCatalog>,
+    system: &System,
+) -> Vec<Diagnostic> {
+    let locked_packages = lockfile
+        .iter()
+        .flat_map(|lockfile| &lockfile.packages)
+        .filter(|package| package.system() == system)
+        .collect::<Vec<_>>();
+    let built_outputs = locked_packages
+        .iter()
+        .flat_map(|package| package_output_paths(package))
+        .filter(|path| Path::new(path).exists())
+        .collect::<Vec<_>>();
+
+    let mut diagnostics = Vec::new();
+    for (name, service) in manifest.services.iter() {
+        if service
+            .systems
+            .as_ref()
+            .is_some_and(|systems| !systems.contains(system))
+        {
+            continue;
+        }
+        let Some(program) = service_program(&service.command) else {
+            continue;
+        };
+
+        let (provided, severity) = if built_outputs.is_empty() {
+            let provided = manifest.install.iter().any(|(install_id, descriptor)| {
+                let pkg_path = descriptor
+                    .as_catalog_descriptor_ref()
+                    .map(|descriptor| descriptor.pkg_path.as_str());
+                let pname = locked_packages
+                    .iter()
+                    .find(|package| package.install_id() == install_id)
+                    .and_then(|package| package.as_catalog_package_ref())
+                    .map(|package| package.pname.as_str());
+                [Some(install_id.as_str()), pkg_path, pname]
+                    .into_iter()
+                    .flatten()
+                    .map(|name| name.rsplit('.').next().unwrap_or(name))
+                    .any(|name| program.starts_with(name) || name.starts_with(program))
+            });
+            (provided, Severity::Info)
+        } else {
+            let provided = built_outputs
+                .iter()
+                .any(|output| Path::new(output).join("bin").join(program).exists());
+            (provided, Severity::Warning)
+        };
+
+        if !provided {
+            diagnostics.push(Diagnostic {
+                severity,
+                lint: Lint::ServiceMissingPackage,
+                path: Some(format!("services.{name}.command")),
+                message: format!(
+                    "service '{name}' runs '{program}', which is not provided by an installed package"
+                ),
+            });
+        }
+    }
+    diagnostics
+}
+
+/// The store paths of the outputs of a locked package
+fn package_output_paths(package: &LockedPackage) -> Vec<&str> {
+    match package {
+        LockedPackage::Catalog(package) => package.outputs.values().map(String::as_str).collect(),
+        LockedPackage::Flake(package) => package
+            .locked_installable
+            .outputs
+            .values()
+            .map(String::as_str)
+            .collect(),
+        LockedPackage::StorePath(package) => vec![package.store_path.as_str()],
+    }
+}
+
+/// Extract the program run by a service command,
+/// skipping leading variable assignments and `exec`.
+///
+/// Returns `None` for programs referenced by path or through variables,
+/// which can't be attributed to a package.
+fn service_program(command: &str) -> Option<&str> {
+    let program = command
+        .split_whitespace()
+        .find(|word| *word != "exec" && !word.contains('='))?;
+    if program.contains(['/', '$', '`', '(']) {
+        return None;
+    }
+    Some(program)
+}
+
+fn lint_shadowed_vars(manifest: &TypedManifestCatalog) -> Vec<Diagnostic> {
+    let scripts = [
+        ("hook.on-activate", &manifest.hook.on_activate),
+        ("hook.on-deactivate", &manifest.hook.on_deactivate),
+        ("profile.common", &manifest.profile.common),
+        ("profile.bash", &manifest.profile.bash),
+        ("profile.zsh", &manifest.profile.zsh),
+        ("profile.fish", &manifest.profile.fish),
+        ("profile.tcsh", &manifest.profile.tcsh),
+    ];
+
+    let mut diagnostics = Vec::new();
+    for var in manifest.vars.0.keys() {
+        let var_pattern = regex::escape(var);
+        // POSIX shell assignments, fish's `set` and tcsh's `setenv`
+        let assignment = Regex::new(&format!(
+            r"(?m)^\s*(?:(?:export|declare(?:\s+-\w+)*|local)\s+)?{var_pattern}=|\bset\s+(?:-\w+\s+)*{var_pattern}\b|\bsetenv\s+{var_pattern}\b"
+        ))
+        .expect("variable names are escaped");
+
+        let Some((script_name, _)) = scripts.iter().find(|(_, script)| {
+            script
+                .as_deref()
+                .is_some_and(|script| assignment.is_match(script))
+        }) else {
+            continue;
+        };
+
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            lint: Lint::VarShadowedByHook,
+            path: Some(format!("vars.{var}")),
+            message: format!("variable '{var}' is set in [vars] but reassigned in '{script_name}'"),
+        });
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use flox_core::Version;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::models::lockfile::test_helpers::fake_catalog_package_lock;
+    use crate::models::lockfile::{LockedPackageCatalog, LockedPackageStorePath};
+
+    const SYSTEM: &str = "x86_64-linux";
+
+    fn manifest(contents: &str) -> TypedManifestCatalog {
+        toml::from_str(contents).unwrap()
+    }
+
+    fn lints(diagnostics: &[Diagnostic]) -> Vec<(Lint, Option<&str>)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.lint, diagnostic.path.as_deref()))
+            .collect()
+    }
+
+    fn lockfile(
+        manifest: &TypedManifestCatalog,
+        packages: Vec<LockedPackage>,
+    ) -> LockedManifestCatalog {
+        LockedManifestCatalog {
+            version: Version::<1>,
+            manifest: manifest.clone(),
+            packages,
+            compose: None,
+        }
+    }
+
+    fn catalog_package(install_id: &str, pname: &str, priority: u64) -> LockedPackageCatalog {
+        let (_, _, mut package) = fake_catalog_package_lock(pname, None);
+        package.install_id = install_id.to_string();
+        package.priority = priority;
+        package.system = SYSTEM.to_string();
+        package
+    }
+
+    #[test]
+    fn reports_stale_and_missing_lockfiles() {
+        let locked = manifest("version = 1");
+        let current = manifest(indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "bar"
+        "#});
+
+        let diagnostics = lint(
+            &current,
+            Some(&lockfile(&locked, vec![])),
+            &SYSTEM.to_string(),
+        );
+        assert_eq!(lints(&diagnostics), vec![(Lint::StaleLockfile, None)]);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+
+        let diagnostics = lint(&current, None, &SYSTEM.to_string());
+        assert_eq!(lints(&diagnostics), vec![(Lint::StaleLockfile, None)]);
+
+        let diagnostics = lint(
+            &locked,
+            Some(&lockfile(&locked, vec![])),
+            &SYSTEM.to_string(),
+        );
+        assert_eq!(diagnostics, vec![]);
+    }
+
+    #[test]
+    fn reports_manifest_problems() {
+        let manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.systems = ["aarch64-darwin"]
+            curl.pkg-path = "curl"
+            curl.pkg-group = "tools"
+            [vars]
+            FOO = "bar"
+            BAR = "baz"
+            [hook]
+            on-activate = """
+              export FOO="other"
+              echo "$BAR"
+            """
+            [profile]
+            fish = "set -gx BAR qux"
+            [options]
+            systems = ["x86_64-linux"]
+        "#});
+
+        let diagnostics = lint(
+            &manifest,
+            Some(&lockfile(&manifest, vec![])),
+            &SYSTEM.to_string(),
+        );
+        assert_eq!(lints(&diagnostics), vec![
+            (Lint::UnsupportedSystem, Some("install.hello.systems")),
+            (Lint::VarShadowedByHook, Some("vars.BAR")),
+            (Lint::VarShadowedByHook, Some("vars.FOO")),
+            (Lint::SinglePackageGroup, Some("install.curl.pkg-group")),
+        ]);
+        assert_eq!(
+            diagnostics[1].message,
+            "variable 'BAR' is set in [vars] but reassigned in 'profile.fish'"
+        );
+    }
+
+    #[test]
+    fn reports_priority_conflicts() {
+        let manifest = manifest("version = 1");
+        let lockfile = lockfile(&manifest, vec![
+            LockedPackage::Catalog(catalog_package("python", "python3", 5)),
+            LockedPackage::Catalog(catalog_package("python_alt", "python3", 5)),
+            LockedPackage::Catalog(catalog_package("python_low", "python3", 6)),
+            LockedPackage::StorePath(LockedPackageStorePath {
+                install_id: "python_store".to_string(),
+                store_path: "/nix/store/python3".to_string(),
+                system: SYSTEM.to_string(),
+                priority: 5,
+            }),
+        ]);
+
+        let diagnostics = lint_priority_conflicts(&lockfile);
+        assert_eq!(lints(&diagnostics), vec![(
+            Lint::PriorityConflict,
+            Some("install.python_alt.priority")
+        )]);
+        assert_eq!(
+            diagnostics[0].message,
+            "packages 'python', 'python_alt' all provide 'python3' with priority 5 \
+             and may contain conflicting files"
+        );
+    }
+
+    #[test]
+    fn reports_services_without_packages() {
+        let manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            postgres.pkg-path = "postgresql_16"
+            [services]
+            db.command = "PGPORT=5432 exec postgres -D data"
+            web.command = "nginx -c nginx.conf"
+            script.command = "$FLOX_ENV_PROJECT/serve.sh"
+            other-system.command = "redis-server"
+            other-system.systems = ["aarch64-darwin"]
+        "#});
+        let lockfile = lockfile(&manifest, vec![]);
+
+        let diagnostics = lint_service_packages(&manifest, Some(&lockfile), &SYSTEM.to_string());
+        assert_eq!(lints(&diagnostics), vec![(
+            Lint::ServiceMissingPackage,
+            Some("services.web.command")
+        )]);
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn checks_built_packages_for_service_programs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let out = tempdir.path().join("out");
+        std::fs::create_dir_all(out.join("bin")).unwrap();
+        std::fs::write(out.join("bin").join("nginx"), "").unwrap();
+
+        let manifest = manifest(indoc! {r#"
+            version = 1
+            [install]
+            nginx.pkg-path = "nginx"
+            [services]
+            web.command = "nginx -c nginx.conf"
+            proxy.command = "nginx-proxy"
+        "#});
+        let mut package = catalog_package("nginx", "nginx", 5);
+        package.outputs = [("out".to_string(), out.to_string_lossy().to_string())].into();
+        let lockfile = lockfile(&manifest, vec![LockedPackage::Catalog(package)]);
+
+        let diagnostics = lint_service_packages(&manifest, Some(&lockfile), &SYSTEM.to_string());
+        assert_eq!(lints(&diagnostics), vec![(
+            Lint::ServiceMissingPackage,
+            Some("services.proxy.command")
+        )]);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+}
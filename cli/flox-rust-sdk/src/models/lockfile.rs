@@ -19,11 +19,15 @@ use flox_core::Version;
 use log::debug;
 use thiserror::Error;
 
+use super::environment::generations::GenerationId;
 use super::manifest::{
     Allows,
+    IncludeDescriptor,
     ManifestPackageDescriptor,
     ManifestPackageDescriptorCatalog,
     ManifestPackageDescriptorFlake,
+    ManifestPackageDescriptorStorePath,
+    SelectedOutputs,
     TypedManifestCatalog,
     DEFAULT_GROUP_NAME,
     DEFAULT_PRIORITY,
@@ -47,8 +51,9 @@ use crate::providers::flox_cpp_utils::{
     InstallableLocker,
     LockedInstallable,
 };
+use crate::providers::nix::store_path_is_available;
 
-static DEFAULT_SYSTEMS_STR: Lazy<[String; 4]> = Lazy::new(|| {
+pub(crate) static DEFAULT_SYSTEMS_STR: Lazy<[String; 4]> = Lazy::new(|| {
     [
         "aarch64-darwin".to_string(),
         "aarch64-linux".to_string(),
@@ -127,6 +132,36 @@ pub struct LockedManifestCatalog {
     pub manifest: TypedManifestCatalog,
     /// locked packages
     pub packages: Vec<LockedPackage>,
+    /// The manifests that were merged into `manifest`,
+    /// if the original manifest includes other environments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub compose: Option<Compose>,
+}
+
+/// The inputs of a manifest that includes other environments
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Compose {
+    /// The manifest that includes the other environments, as written by the user
+    pub composer: TypedManifestCatalog,
+    /// The included environments, in order of increasing precedence
+    pub include: Vec<LockedInclude>,
+}
+
+/// An included environment pinned to the manifest it had when it was locked
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LockedInclude {
+    /// The name of the included environment
+    pub name: String,
+    /// The descriptor in the `[include]` section this environment was included by
+    pub descriptor: IncludeDescriptor,
+    /// The manifest of the included environment
+    pub manifest: TypedManifestCatalog,
+    /// The generation of the included environment, if it is a FloxHub environment
+    pub generation: Option<GenerationId>,
+    /// A hash of the contents of the included manifest
+    pub hash: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, derive_more::From)]
@@ -135,6 +170,7 @@ pub struct LockedManifestCatalog {
 pub enum LockedPackage {
     Catalog(LockedPackageCatalog),
     Flake(LockedPackageFlake),
+    StorePath(LockedPackageStorePath),
 }
 
 impl LockedPackage {
@@ -149,6 +185,7 @@ impl LockedPackage {
         match self {
             LockedPackage::Catalog(pkg) => &pkg.install_id,
             LockedPackage::Flake(pkg) => &pkg.install_id,
+            LockedPackage::StorePath(pkg) => &pkg.install_id,
         }
     }
 
@@ -156,6 +193,7 @@ impl LockedPackage {
         match self {
             LockedPackage::Catalog(pkg) => &pkg.system,
             LockedPackage::Flake(pkg) => &pkg.locked_installable.system,
+            LockedPackage::StorePath(pkg) => &pkg.system,
         }
     }
 
@@ -163,6 +201,7 @@ impl LockedPackage {
         match self {
             LockedPackage::Catalog(pkg) => pkg.broken,
             LockedPackage::Flake(pkg) => pkg.locked_installable.broken,
+            LockedPackage::StorePath(_) => None,
         }
     }
 
@@ -170,13 +209,25 @@ impl LockedPackage {
         match self {
             LockedPackage::Catalog(pkg) => pkg.unfree,
             LockedPackage::Flake(pkg) => pkg.locked_installable.unfree,
+            LockedPackage::StorePath(_) => None,
         }
     }
 
+    /// The derivation of the package,
+    /// for store paths the store path itself identifies the package.
     pub fn derivation(&self) -> &str {
         match self {
             LockedPackage::Catalog(pkg) => &pkg.derivation,
             LockedPackage::Flake(pkg) => &pkg.locked_installable.derivation,
+            LockedPackage::StorePath(pkg) => &pkg.store_path,
+        }
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            LockedPackage::Catalog(pkg) => Some(&pkg.version),
+            LockedPackage::Flake(pkg) => pkg.locked_installable.version.as_deref(),
+            LockedPackage::StorePath(_) => None,
         }
     }
 }
@@ -191,6 +242,7 @@ pub struct LockedPackageCatalog {
     pub broken: Option<bool>,
     pub derivation: String,
     pub description: Option<String>,
+    pub insecure: Option<bool>,
     pub install_id: String,
     pub license: Option<String>,
     pub locked_url: String,
@@ -219,6 +271,8 @@ pub struct LockedPackageCatalog {
     // a `uint64_t` instead of a `size_t`. Using a `u64` here matches those semantics, though in
     // reality it's likely not an issue.
     pub priority: u64,
+    /// List of output names to install as requested by the user
+    pub requested_outputs_to_install: Option<Vec<String>>,
     // endregion
 }
 
@@ -237,8 +291,7 @@ impl LockedPackageCatalog {
             broken,
             derivation,
             description,
-            // TODO: we should add this to LockedPackageCatalog
-            insecure: _,
+            insecure,
             install_id,
             license,
             locked_url,
@@ -273,6 +326,7 @@ impl LockedPackageCatalog {
             broken,
             derivation,
             description,
+            insecure,
             install_id,
             license,
             locked_url,
@@ -290,6 +344,8 @@ impl LockedPackageCatalog {
             system: system.to_string(),
             priority,
             group,
+            // selected from the resolved outputs by [LockedManifestCatalog::select_outputs]
+            requested_outputs_to_install: None,
         }
     }
 }
@@ -320,6 +376,66 @@ impl LockedPackageFlake {
     }
 }
 
+/// A package installed from an existing store path.
+///
+/// Store paths are not resolved,
+/// locking only verifies that the store path exists
+/// and records it for each system the package is installed on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct LockedPackageStorePath {
+    pub install_id: String,
+    pub store_path: String,
+    pub system: System,
+    pub priority: u64,
+}
+
+/// The directory of the Nix store
+const NIX_STORE_DIR: &str = "/nix/store";
+
+/// Whether `path` is syntactically a store path, i.e. `/nix/store/<hash>-<name>`.
+///
+/// This mirrors the validation of store paths by Nix,
+/// but doesn't check whether the store path exists.
+fn is_store_path(path: &str) -> bool {
+    const HASH_LEN: usize = 32;
+    const HASH_CHARS: &str = "0123456789abcdfghijklmnpqrsvwxyz";
+
+    let Some(base_name) = path
+        .strip_prefix(NIX_STORE_DIR)
+        .and_then(|path| path.strip_prefix('/'))
+    else {
+        return false;
+    };
+    let Some((hash, name)) = base_name.split_once('-') else {
+        return false;
+    };
+    hash.len() == HASH_LEN
+        && hash.chars().all(|c| HASH_CHARS.contains(c))
+        && !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-._?=".contains(c))
+}
+
+impl LockedPackageStorePath {
+    /// Construct a [LockedPackageStorePath] from a [ManifestPackageDescriptorStorePath]
+    /// for a given system.
+    pub fn from_parts(
+        install_id: String,
+        descriptor: &ManifestPackageDescriptorStorePath,
+        system: System,
+    ) -> Self {
+        LockedPackageStorePath {
+            install_id,
+            store_path: descriptor.store_path.clone(),
+            system,
+            priority: descriptor.priority.unwrap_or(DEFAULT_PRIORITY),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 struct FlakeInstallableToLock {
     install_id: String,
@@ -517,6 +633,9 @@ impl LockedManifestCatalog {
 
                     Ok(PackageToList::Flake(descriptor, locked_package))
                 },
+                LockedPackage::StorePath(locked_package) => {
+                    Ok(PackageToList::StorePath(locked_package))
+                },
             })
             .collect::<Result<Vec<_>, LockedManifestError>>()
     }
@@ -530,6 +649,8 @@ impl LockedManifestCatalog {
     /// Already locked flake installables will not be locked again,
     /// and copied from the seed lockfile as is.
     ///
+    /// Store paths are not resolved, but checked to exist and locked as is.
+    ///
     /// Catalog and flake installables are locked separately, usinf largely symmetric logic.
     /// Keeping the locking of each kind separate keeps the existing methods simpler
     /// and allows for potential parallelization in the future.
@@ -547,6 +668,8 @@ impl LockedManifestCatalog {
         let (already_locked_installables, installables_to_lock) =
             Self::split_locked_flake_installables(flake_installables, seed_lockfile);
 
+        let locked_store_paths = Self::lock_store_paths(manifest, seed_lockfile).await?;
+
         // The manifest could have been edited since locking packages,
         // in which case there may be packages that aren't allowed.
         Self::check_packages_are_allowed(
@@ -561,10 +684,17 @@ impl LockedManifestCatalog {
 
         if groups_to_lock.is_empty() && installables_to_lock.is_empty() {
             debug!("All packages are already locked, skipping resolution");
+            Self::select_outputs(&mut already_locked_packages, manifest)?;
             return Ok(LockedManifestCatalog {
                 version: Version::<1>,
                 manifest: manifest.clone(),
-                packages: [already_locked_packages, already_locked_installables].concat(),
+                packages: [
+                    already_locked_packages,
+                    already_locked_installables,
+                    locked_store_paths,
+                ]
+                .concat(),
+                compose: None,
             });
         }
 
@@ -579,7 +709,7 @@ impl LockedManifestCatalog {
         };
 
         // unpack locked packages from response
-        let locked_packages: Vec<LockedPackage> =
+        let mut locked_packages: Vec<LockedPackage> =
             Self::locked_packages_from_resolution(manifest, resolved)?
                 .map(Into::into)
                 .collect();
@@ -601,6 +731,13 @@ impl LockedManifestCatalog {
             &manifest.options.allow,
         )?;
 
+        Self::select_outputs(
+            already_locked_packages
+                .iter_mut()
+                .chain(&mut locked_packages),
+            manifest,
+        )?;
+
         let lockfile = LockedManifestCatalog {
             version: Version::<1>,
             manifest: manifest.clone(),
@@ -609,8 +746,10 @@ impl LockedManifestCatalog {
                 locked_packages,
                 already_locked_installables,
                 locked_installables,
+                locked_store_paths,
             ]
             .concat(),
+            compose: None,
         };
 
         Ok(lockfile)
@@ -655,6 +794,17 @@ impl LockedManifestCatalog {
                     ));
                 }
             }
+
+            // Don't allow insecure by default
+            if !allow.insecure.unwrap_or(false) {
+                // Assume a package isn't insecure,
+                // packages locked before the flag was recorded don't have it
+                if package.insecure.unwrap_or(false) {
+                    return Err(LockedManifestError::InsecureNotAllowed(
+                        package.install_id.to_owned(),
+                    ));
+                }
+            }
         }
 
         Ok(())
@@ -696,6 +846,47 @@ impl LockedManifestCatalog {
         }
     }
 
+    /// Record the outputs selected by the manifest for each locked catalog package.
+    ///
+    /// Errors if a package doesn't provide a selected output.
+    /// Packages without selected outputs install all of their outputs.
+    fn select_outputs<'a>(
+        locked_packages: impl IntoIterator<Item = &'a mut LockedPackage>,
+        manifest: &TypedManifestCatalog,
+    ) -> Result<(), LockedManifestError> {
+        for locked_package in locked_packages {
+            let LockedPackage::Catalog(package) = locked_package else {
+                continue;
+            };
+
+            let selected = manifest
+                .catalog_pkg_descriptor_with_id(&package.install_id)
+                .and_then(|descriptor| descriptor.outputs);
+
+            package.requested_outputs_to_install = match selected {
+                None => None,
+                Some(SelectedOutputs::All(_)) => Some(package.outputs.keys().cloned().collect()),
+                Some(SelectedOutputs::Specific(outputs)) => {
+                    let missing = outputs
+                        .iter()
+                        .filter(|output| !package.outputs.contains_key(*output))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if !missing.is_empty() {
+                        return Err(LockedManifestError::MissingPackageOutputs {
+                            install_id: package.install_id.clone(),
+                            system: package.system.clone(),
+                            missing,
+                            available: package.outputs.keys().cloned().collect(),
+                        });
+                    }
+                    Some(outputs)
+                },
+            };
+        }
+        Ok(())
+    }
+
     /// Transform a lockfile into a mapping that is easier to query:
     /// Lockfile -> { (install_id, system): (package_descriptor, locked_package) }
     fn make_seed_mapping(
@@ -763,8 +954,7 @@ impl LockedManifestCatalog {
                 version: manifest_descriptor.version.clone(),
                 allow_pre_releases: manifest.options.semver.allow_pre_releases,
                 allow_broken: manifest.options.allow.broken,
-                // TODO: add support for insecure
-                allow_insecure: None,
+                allow_insecure: manifest.options.allow.insecure,
                 allow_unfree: manifest.options.allow.unfree,
                 allowed_licenses: maybe_licenses.clone(),
                 systems: vec![],
@@ -1169,6 +1359,85 @@ impl LockedManifestCatalog {
         Ok(ok.into_iter())
     }
 
+    /// Lock the store path descriptors of a manifest for each of their systems.
+    ///
+    /// Store paths are "locked" by definition,
+    /// but they have to be paths in the Nix store
+    /// that exist locally or can be substituted at the time of locking.
+    /// Store paths that are already locked in the seed lockfile
+    /// are not queried again.
+    async fn lock_store_paths(
+        manifest: &TypedManifestCatalog,
+        seed_lockfile: Option<&LockedManifestCatalog>,
+    ) -> Result<Vec<LockedPackage>, LockedManifestError> {
+        let mut locked = Vec::new();
+        for (install_id, descriptor) in manifest.install.iter() {
+            let Some(descriptor) = descriptor.as_store_path_descriptor_ref() else {
+                continue;
+            };
+
+            if !is_store_path(&descriptor.store_path) {
+                return Err(LockedManifestError::InvalidStorePath {
+                    install_id: install_id.clone(),
+                    store_path: descriptor.store_path.clone(),
+                });
+            }
+
+            // Store paths locked in the seed lockfile have already been checked
+            let already_locked = seed_lockfile.is_some_and(|seed| {
+                let is_locked = seed.packages.iter().any(|package| {
+                    matches!(package, LockedPackage::StorePath(_))
+                        && package.install_id() == install_id
+                });
+                let is_unchanged =
+                    seed.manifest
+                        .install
+                        .get(install_id)
+                        .is_some_and(|seed_descriptor| {
+                            !ManifestPackageDescriptor::from(descriptor.clone())
+                                .invalidates_existing_resolution(seed_descriptor)
+                        });
+                is_locked && is_unchanged
+            });
+
+            if !already_locked {
+                let available = store_path_is_available(&descriptor.store_path)
+                    .await
+                    .map_err(|e| LockedManifestError::QueryStorePath {
+                        store_path: descriptor.store_path.clone(),
+                        source: e,
+                    })?;
+                if !available {
+                    return Err(LockedManifestError::StorePathNotFound {
+                        install_id: install_id.clone(),
+                        store_path: descriptor.store_path.clone(),
+                    });
+                }
+            }
+
+            let systems = if let Some(ref systems) = descriptor.systems {
+                systems.as_slice()
+            } else {
+                manifest
+                    .options
+                    .systems
+                    .as_deref()
+                    .unwrap_or(&*DEFAULT_SYSTEMS_STR)
+            };
+            for system in systems {
+                locked.push(
+                    LockedPackageStorePath::from_parts(
+                        install_id.clone(),
+                        descriptor,
+                        system.clone(),
+                    )
+                    .into(),
+                );
+            }
+        }
+        Ok(locked)
+    }
+
     /// Filter out packages from the locked manifest by install_id or group
     ///
     /// This is used to create a seed lockfile to upgrade a subset of packages,
@@ -1318,6 +1587,7 @@ impl TypedLockedManifestPkgdb {
 pub enum PackageToList {
     CatalogOrPkgdb(InstalledPackage),
     Flake(ManifestPackageDescriptorFlake, LockedPackageFlake),
+    StorePath(LockedPackageStorePath),
 }
 
 impl From<InstalledPackage> for PackageToList {
@@ -1390,6 +1660,8 @@ pub enum LockedManifestError {
     BrokenNotAllowed(String),
     #[error("The package '{0}' has an unfree license.\n\nAllow unfree packages by setting 'options.allow.unfree = true' in manifest.toml")]
     UnfreeNotAllowed(String),
+    #[error("The package '{0}' is marked as insecure.\n\nAllow insecure packages by setting 'options.allow.insecure = true' in manifest.toml")]
+    InsecureNotAllowed(String),
 
     #[error(
         "Corrupt manifest; couldn't find flake package descriptor for locked install_id '{0}'"
@@ -1398,8 +1670,41 @@ pub enum LockedManifestError {
 
     #[error(transparent)]
     LockFlakeNixError(FlakeInstallableError),
+    #[error(
+        "store path '{store_path}' of package '{install_id}' is not a path in the Nix store \
+         ('{NIX_STORE_DIR}/<hash>-<name>')"
+    )]
+    InvalidStorePath {
+        install_id: String,
+        store_path: String,
+    },
+    #[error(
+        "store path '{store_path}' of package '{install_id}' does not exist and can't be substituted"
+    )]
+    StorePathNotFound {
+        install_id: String,
+        store_path: String,
+    },
+    #[error("failed to query store path '{store_path}'")]
+    QueryStorePath {
+        store_path: String,
+        #[source]
+        source: std::io::Error,
+    },
     #[error("catalog returned install id not in manifest: {0}")]
     InstallIdNotInManifest(String),
+    #[error(
+        "package '{install_id}' does not provide the output(s) {} on '{system}'\n\
+         Available outputs: {}",
+        missing.iter().map(|output| format!("'{output}'")).join(", "),
+        available.join(", ")
+    )]
+    MissingPackageOutputs {
+        install_id: String,
+        system: System,
+        missing: Vec<String>,
+        available: Vec<String>,
+    },
 }
 
 /// A warning produced by `pkgdb manifest check`
@@ -1424,6 +1729,7 @@ pub mod test_helpers {
             systems: Some(vec![SystemEnum::Aarch64Darwin.to_string()]),
             version: None,
             priority: None,
+            outputs: None,
         }
         .into();
 
@@ -1432,6 +1738,7 @@ pub mod test_helpers {
             broken: None,
             derivation: "derivation".to_string(),
             description: None,
+            insecure: None,
             install_id: install_id.clone(),
             license: None,
             locked_url: "".to_string(),
@@ -1453,6 +1760,7 @@ pub mod test_helpers {
             system: SystemEnum::Aarch64Darwin.to_string(),
             group: group.unwrap_or(DEFAULT_GROUP_NAME).to_string(),
             priority: 5,
+            requested_outputs_to_install: None,
         };
         (install_id, descriptor, locked)
     }
@@ -1541,6 +1849,7 @@ pub mod test_helpers {
 #[cfg(test)]
 pub(crate) mod tests {
     use std::collections::HashMap;
+    use std::path::Path;
     use std::vec;
 
     use catalog::test_helpers::resolved_pkg_group_with_dummy_package;
@@ -1561,9 +1870,10 @@ pub(crate) mod tests {
 
     use self::catalog::PackageResolutionInfo;
     use super::*;
-    use crate::models::manifest::{RawManifest, TypedManifest};
+    use crate::models::manifest::{AllOutputs, RawManifest, TypedManifest};
     use crate::models::search::{SearchLimit, SearchResults};
     use crate::providers::flox_cpp_utils::{FlakeInstallableError, InstallableLockerMock};
+    use crate::providers::nix::NIX_BIN;
 
     /// A mock client that panics if any of its methods are called
     struct PanickingClient;
@@ -1825,6 +2135,7 @@ pub(crate) mod tests {
                 broken: Some(false),
                 derivation: "derivation".to_string(),
                 description: Some("description".to_string()),
+                insecure: Some(false),
                 install_id: "hello_install_id".to_string(),
                 license: Some("license".to_string()),
                 locked_url: "locked_url".to_string(),
@@ -1849,8 +2160,10 @@ pub(crate) mod tests {
                 system: SystemEnum::Aarch64Darwin.to_string(),
                 group: "group".to_string(),
                 priority: 5,
+                requested_outputs_to_install: None,
             }
             .into()],
+            compose: None,
         })
     });
 
@@ -2112,6 +2425,7 @@ pub(crate) mod tests {
                 systems: None,
                 version: None,
                 priority: None,
+                outputs: None,
             }
             .into(),
         );
@@ -2174,6 +2488,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest_before.clone(),
             packages: vec![foo_before_locked.clone().into()],
+            compose: None,
         };
 
         // ---------------------------------------------------------------------
@@ -2206,6 +2521,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest_before.clone(),
             packages: vec![foo_before_locked.into()],
+            compose: None,
         };
 
         // ---------------------------------------------------------------------
@@ -2252,6 +2568,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest_before.clone(),
             packages: vec![foo_before_locked.clone().into()],
+            compose: None,
         };
 
         // ---------------------------------------------------------------------
@@ -2498,6 +2815,7 @@ pub(crate) mod tests {
                 baz_locked.into(),
                 qux_locked.clone().into(),
             ],
+            compose: None,
         };
 
         lockfile.unlock_packages_by_group_or_iid(&[&foo_iid, &baz_iid]);
@@ -2520,6 +2838,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.into(), bar_locked.into()],
+            compose: None,
         };
 
         lockfile.unlock_packages_by_group_or_iid(&["group"]);
@@ -2542,6 +2861,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.into(), bar_locked.into()],
+            compose: None,
         };
 
         lockfile.unlock_packages_by_group_or_iid(&[&foo_iid]);
@@ -2704,6 +3024,7 @@ pub(crate) mod tests {
             packages: [&foo_locked, &bar_locked, &baz_locked]
                 .map(|p| p.clone().into())
                 .to_vec(),
+            compose: None,
         };
 
         manifest
@@ -2798,6 +3119,7 @@ pub(crate) mod tests {
                 foo_locked.clone().into(),
                 foo_locked_second_system.clone().into(),
             ],
+            compose: None,
         };
 
         manifest
@@ -2852,6 +3174,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.into()],
+            compose: None,
         };
 
         manifest
@@ -2904,6 +3227,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![bar_locked.clone().into()],
+            compose: None,
         };
 
         let flake_installables = LockedManifestCatalog::collect_flake_installables(&manifest);
@@ -2936,6 +3260,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![bar_locked.clone().into()],
+            compose: None,
         };
 
         let flake_installables = LockedManifestCatalog::collect_flake_installables(&manifest);
@@ -2975,6 +3300,7 @@ pub(crate) mod tests {
                 foo_locked_system_1.clone().into(),
                 foo_locked_system_2.into(),
             ],
+            compose: None,
         };
 
         let flake_installables = LockedManifestCatalog::collect_flake_installables(&manifest);
@@ -3006,6 +3332,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.clone().into()],
+            compose: None,
         };
 
         // system_2 is added to the manifest
@@ -3049,6 +3376,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.into(), bar_locked.into()],
+            compose: None,
         };
 
         let locked_manifest = LockedManifestCatalog::lock_manifest(
@@ -3083,6 +3411,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![bar_locked.into()],
+            compose: None,
         };
 
         let foo_catalog_descriptor = foo_descriptor.as_catalog_descriptor_ref().unwrap();
@@ -3153,6 +3482,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.into()],
+            compose: None,
         };
 
         let locker_mock = InstallableLockerMock::new();
@@ -3186,6 +3516,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.clone().into()],
+            compose: None,
         };
 
         let mut foo_descriptor_priority_after = foo_descriptor.unwrap_catalog_descriptor().unwrap();
@@ -3215,6 +3546,80 @@ pub(crate) mod tests {
         ]);
     }
 
+    /// Lock `foo` with the outputs `out` and `dev`, selecting `outputs` in the manifest
+    async fn lock_with_selected_outputs(
+        outputs: SelectedOutputs,
+    ) -> Result<LockedManifestCatalog, LockedManifestError> {
+        let (foo_iid, foo_descriptor, mut foo_locked) = fake_catalog_package_lock("foo", None);
+        foo_locked.outputs = [
+            ("out".to_string(), "/nix/store/foo".to_string()),
+            ("dev".to_string(), "/nix/store/foo-dev".to_string()),
+        ]
+        .into();
+
+        let mut manifest = TypedManifestCatalog::default();
+        manifest.options.systems = Some(vec![SystemEnum::Aarch64Darwin.to_string()]);
+        manifest
+            .install
+            .insert(foo_iid.clone(), foo_descriptor.clone());
+
+        let locked = LockedManifestCatalog {
+            version: Version::<1>,
+            manifest: manifest.clone(),
+            packages: vec![foo_locked.into()],
+            compose: None,
+        };
+
+        let mut foo_descriptor = foo_descriptor.unwrap_catalog_descriptor().unwrap();
+        foo_descriptor.outputs = Some(outputs);
+        manifest.install.insert(foo_iid, foo_descriptor.into());
+
+        LockedManifestCatalog::lock_manifest(
+            &manifest,
+            Some(&locked),
+            &PanickingClient,
+            &InstallableLockerMock::new(),
+        )
+        .await
+    }
+
+    /// Outputs selected in the manifest are recorded for locked packages
+    #[tokio::test]
+    async fn lock_manifest_selects_outputs() {
+        let locked = lock_with_selected_outputs(SelectedOutputs::Specific(vec!["dev".to_string()]))
+            .await
+            .unwrap();
+        let LockedPackage::Catalog(foo) = &locked.packages[0] else {
+            panic!("expected a catalog package");
+        };
+        assert_eq!(
+            foo.requested_outputs_to_install,
+            Some(vec!["dev".to_string()])
+        );
+
+        let locked = lock_with_selected_outputs(SelectedOutputs::All(AllOutputs::All))
+            .await
+            .unwrap();
+        let LockedPackage::Catalog(foo) = &locked.packages[0] else {
+            panic!("expected a catalog package");
+        };
+        assert_eq!(
+            foo.requested_outputs_to_install,
+            Some(vec!["dev".to_string(), "out".to_string()])
+        );
+    }
+
+    /// Selecting an output the resolved package doesn't provide is an error
+    #[tokio::test]
+    async fn lock_manifest_rejects_missing_outputs() {
+        let result =
+            lock_with_selected_outputs(SelectedOutputs::Specific(vec!["man".to_string()])).await;
+        assert!(matches!(
+            result,
+            Err(LockedManifestError::MissingPackageOutputs { missing, .. }) if missing == ["man"]
+        ));
+    }
+
     /// [LockedManifestCatalog::lock_manifest] returns an error if an already
     /// locked package is no longer allowed
     #[tokio::test]
@@ -3233,6 +3638,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest: manifest.clone(),
             packages: vec![foo_locked.into()],
+            compose: None,
         };
 
         // Set `options.allow.unfree = false` in the manifest, but not the lockfile
@@ -3308,6 +3714,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: None,
                 broken: None,
+                insecure: None,
                 licenses: vec!["allowed".to_string()]
             }),
             Err(LockedManifestError::LicenseNotAllowed { .. })
@@ -3325,6 +3732,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: None,
                 broken: None,
+                insecure: None,
                 licenses: vec!["allowed".to_string()]
             })
             .is_ok()
@@ -3342,6 +3750,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: None,
                 broken: None,
+                insecure: None,
                 licenses: vec![]
             }),
             Err(LockedManifestError::BrokenNotAllowed { .. })
@@ -3359,6 +3768,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: None,
                 broken: Some(true),
+                insecure: None,
                 licenses: vec![]
             })
             .is_ok()
@@ -3376,6 +3786,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: None,
                 broken: Some(false),
+                insecure: None,
                 licenses: vec![]
             }),
             Err(LockedManifestError::BrokenNotAllowed { .. })
@@ -3393,6 +3804,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: None,
                 broken: None,
+                insecure: None,
                 licenses: vec![]
             })
             .is_ok()
@@ -3410,6 +3822,7 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: Some(true),
                 broken: None,
+                insecure: None,
                 licenses: vec![]
             })
             .is_ok()
@@ -3427,12 +3840,49 @@ pub(crate) mod tests {
             LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
                 unfree: Some(false),
                 broken: None,
+                insecure: None,
                 licenses: vec![]
             }),
             Err(LockedManifestError::UnfreeNotAllowed { .. })
         ));
     }
 
+    /// [LockedManifestCatalog::check_packages_are_allowed] returns an error
+    /// when a package is insecure and `allow.insecure` is unset
+    #[test]
+    fn check_packages_are_allowed_insecure_default() {
+        let (_, _, mut foo_locked) = fake_catalog_package_lock("foo", None);
+        foo_locked.insecure = Some(true);
+
+        assert!(matches!(
+            LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
+                unfree: None,
+                broken: None,
+                insecure: None,
+                licenses: vec![]
+            }),
+            Err(LockedManifestError::InsecureNotAllowed { .. })
+        ));
+    }
+
+    /// [LockedManifestCatalog::check_packages_are_allowed] does not error for
+    /// an insecure package when `allow.insecure = true`
+    #[test]
+    fn check_packages_are_allowed_insecure_true() {
+        let (_, _, mut foo_locked) = fake_catalog_package_lock("foo", None);
+        foo_locked.insecure = Some(true);
+
+        assert!(
+            LockedManifestCatalog::check_packages_are_allowed(&vec![foo_locked], &Allows {
+                unfree: None,
+                broken: None,
+                insecure: Some(true),
+                licenses: vec![]
+            })
+            .is_ok()
+        );
+    }
+
     #[test]
     fn test_list_packages_catalog() {
         let (foo_iid, foo_descriptor, foo_locked) =
@@ -3468,6 +3918,7 @@ pub(crate) mod tests {
                 bar_locked.clone().into(),
                 baz_locked.clone().into(),
             ],
+            compose: None,
         };
 
         let foo_pkg_path = foo_descriptor
@@ -3537,6 +3988,7 @@ pub(crate) mod tests {
             version: Version::<1>,
             manifest,
             packages: vec![foo_locked.clone().into(), baz_locked.clone().into()],
+            compose: None,
         };
 
         let actual = locked
@@ -3570,4 +4022,193 @@ pub(crate) mod tests {
         assert_eq!(installables.len(), 1);
         assert_eq!(installables[0].system.as_str(), "x86_64-linux");
     }
+
+    fn store_path_manifest(store_path: &Path) -> TypedManifestCatalog {
+        let manifest_contents = formatdoc! {r#"
+        version = 1
+
+        [install]
+        foo.store-path = "{store_path}"
+        foo.systems = ["aarch64-darwin", "x86_64-linux"]
+        foo.priority = 3
+        "#, store_path = store_path.display()};
+        let TypedManifest::Catalog(manifest) = toml_edit::de::from_str(&manifest_contents).unwrap()
+        else {
+            panic!("expected a catalog manifest");
+        };
+        *manifest
+    }
+
+    /// A syntactically valid store path
+    const STORE_PATH: &str = "/nix/store/ia1pdwpvhswwnbamqkzbz69ja02bjfqx-hello-2.12.1";
+
+    /// The store path of the `nix` binary, which exists wherever tests run
+    fn existing_store_path() -> String {
+        Path::new(&*NIX_BIN)
+            .ancestors()
+            .find(|path| path.parent() == Some(Path::new(NIX_STORE_DIR)))
+            .expect("nix should be installed in the Nix store")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Store paths are locked for each of their systems without resolution
+    #[tokio::test]
+    async fn lock_manifest_locks_store_paths() {
+        let store_path = existing_store_path();
+        let manifest = store_path_manifest(Path::new(&store_path));
+
+        let locked_manifest = LockedManifestCatalog::lock_manifest(
+            &manifest,
+            None,
+            &PanickingClient,
+            &PanickingLocker,
+        )
+        .await
+        .unwrap();
+
+        let expected = ["aarch64-darwin", "x86_64-linux"]
+            .into_iter()
+            .map(|system| {
+                LockedPackage::from(LockedPackageStorePath {
+                    install_id: "foo".to_string(),
+                    store_path: store_path.clone(),
+                    system: system.to_string(),
+                    priority: 3,
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(locked_manifest.packages, expected);
+
+        let listed = locked_manifest
+            .list_packages(&"x86_64-linux".to_string())
+            .unwrap();
+        assert_eq!(listed, vec![PackageToList::StorePath(
+            LockedPackageStorePath {
+                install_id: "foo".to_string(),
+                store_path: store_path.clone(),
+                system: "x86_64-linux".to_string(),
+                priority: 3,
+            }
+        )]);
+    }
+
+    /// Store paths that neither exist nor can be substituted are rejected when locking
+    #[tokio::test]
+    async fn lock_manifest_fails_for_missing_store_path() {
+        let manifest = store_path_manifest(Path::new(
+            "/nix/store/00000000000000000000000000000000-missing",
+        ));
+
+        let result = LockedManifestCatalog::lock_manifest(
+            &manifest,
+            None,
+            &PanickingClient,
+            &PanickingLocker,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(LockedManifestError::StorePathNotFound { install_id, .. }) if install_id == "foo"
+        ));
+    }
+
+    /// Store paths already locked in the seed lockfile are not queried again,
+    /// changed store paths are
+    #[tokio::test]
+    async fn lock_manifest_skips_query_for_seeded_store_paths() {
+        let missing = Path::new("/nix/store/00000000000000000000000000000000-missing");
+        let manifest = store_path_manifest(missing);
+        let seed = LockedManifestCatalog {
+            version: Version::<1>,
+            manifest: manifest.clone(),
+            packages: vec![LockedPackage::from(LockedPackageStorePath {
+                install_id: "foo".to_string(),
+                store_path: missing.to_string_lossy().to_string(),
+                system: "x86_64-linux".to_string(),
+                priority: 3,
+            })],
+            compose: None,
+        };
+
+        LockedManifestCatalog::lock_manifest(
+            &manifest,
+            Some(&seed),
+            &PanickingClient,
+            &PanickingLocker,
+        )
+        .await
+        .expect("seeded store path should not be queried");
+
+        let changed = store_path_manifest(Path::new(
+            "/nix/store/11111111111111111111111111111111-missing",
+        ));
+        let result = LockedManifestCatalog::lock_manifest(
+            &changed,
+            Some(&seed),
+            &PanickingClient,
+            &PanickingLocker,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(LockedManifestError::StorePathNotFound { install_id, .. }) if install_id == "foo"
+        ));
+    }
+
+    /// Paths outside of the Nix store are rejected when locking
+    #[tokio::test]
+    async fn lock_manifest_fails_for_path_outside_of_store() {
+        let store_path = tempfile::tempdir().unwrap();
+        let manifest = store_path_manifest(store_path.path());
+
+        let result = LockedManifestCatalog::lock_manifest(
+            &manifest,
+            None,
+            &PanickingClient,
+            &PanickingLocker,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(LockedManifestError::InvalidStorePath { install_id, .. }) if install_id == "foo"
+        ));
+    }
+
+    #[test]
+    fn validates_store_paths() {
+        assert!(is_store_path(STORE_PATH));
+        assert!(is_store_path(
+            "/nix/store/00000000000000000000000000000000-missing"
+        ));
+
+        for path in [
+            "/tmp/ia1pdwpvhswwnbamqkzbz69ja02bjfqx-hello-2.12.1",
+            "/nix/store/ia1pdwpvhswwnbamqkzbz69ja02bjfqx-hello-2.12.1/bin/hello",
+            "/nix/store/ia1pdwpvhswwnbamqkzbz69ja02bjfqx",
+            "/nix/store/ia1pdwpvhswwnbamqkzbz69ja02bjfqx-",
+            "/nix/store/short-hello",
+            "/nix/store/ea1pdwpvhswwnbamqkzbz69ja02bjfqx-hello",
+            "/nix/store/ia1pdwpvhswwnbamqkzbz69ja02bjfqx-.hidden",
+            "nix/store/ia1pdwpvhswwnbamqkzbz69ja02bjfqx-hello",
+        ] {
+            assert!(!is_store_path(path), "{path} should not be a store path");
+        }
+    }
+
+    /// Locked store paths roundtrip through the lockfile format
+    #[test]
+    fn store_path_lockfile_roundtrip() {
+        let locked = LockedPackage::from(LockedPackageStorePath {
+            install_id: "foo".to_string(),
+            store_path: "/nix/store/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa-foo".to_string(),
+            system: "x86_64-linux".to_string(),
+            priority: 5,
+        });
+        let json = serde_json::to_value(&locked).unwrap();
+        let parsed: LockedPackage = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, locked);
+    }
 }
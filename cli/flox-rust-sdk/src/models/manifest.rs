@@ -1,5 +1,7 @@
 use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use flox_core::Version;
@@ -8,14 +10,32 @@ use itertools::Itertools;
 use log::debug;
 #[cfg(test)]
 use proptest::prelude::*;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, ObjectValidation, Schema, SchemaObject};
+use schemars::JsonSchema;
 use serde::de::Error;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::skip_serializing_none;
-use toml_edit::{self, Array, DocumentMut, Formatted, InlineTable, Item, Key, Table, Value};
+use toml_edit::{
+    self,
+    Array,
+    DocumentMut,
+    Formatted,
+    InlineTable,
+    Item,
+    Key,
+    Table,
+    TableLike,
+    Value,
+};
 use tracing::trace;
 use url::Url;
 
 use super::environment::path_environment::InitCustomization;
+use super::environment_ref::EnvironmentRef;
+use super::lockfile::DEFAULT_SYSTEMS_STR;
+use super::manifest_schema;
 use crate::data::System;
 use crate::providers::services::ServiceError;
 #[cfg(test)]
@@ -23,6 +43,8 @@ use crate::utils::proptest_btree_map_alphanum_keys;
 
 pub(super) const DEFAULT_GROUP_NAME: &str = "toplevel";
 pub const DEFAULT_PRIORITY: u64 = 5;
+/// Placeholder for redacted sources of secret variables
+pub const REDACTED: &str = "<redacted>";
 
 /// Represents the `[version]` number key in manifest.toml
 pub const MANIFEST_VERSION_KEY: &str = "version";
@@ -44,7 +66,7 @@ pub const MANIFEST_SYSTEMS_KEY: &str = "systems";
 /// A wrapper around a [`toml_edit::DocumentMut`]
 /// that allows modifications of the raw manifest document,
 /// while preserving comments and user formatting.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct RawManifest(toml_edit::DocumentMut);
 impl RawManifest {
     /// Creates a new [RawManifest] instance, populating its configuration from
@@ -96,10 +118,19 @@ impl RawManifest {
             # scripts.
         "#});
 
-        // [sic]: vars not customized using InitCustomization yet
-        vars_table.decor_mut().set_suffix(indoc! {r#"
+        match &customization.vars {
+            Some(vars) if !vars.is_empty() => {
+                vars_table.extend(
+                    vars.iter()
+                        .map(|(name, value)| (name, toml_edit::value(value))),
+                );
+            },
+            _ => {
+                vars_table.decor_mut().set_suffix(indoc! {r#"
 
-            # message = "Howdy""#});
+                    # message = "Howdy""#});
+            },
+        }
 
         manifest.insert(MANIFEST_VARS_KEY, Item::Table(vars_table));
 
@@ -272,7 +303,7 @@ impl RawManifest {
     }
 
     /// Get the version of the manifest.
-    fn get_version(&self) -> Option<i64> {
+    pub(crate) fn get_version(&self) -> Option<i64> {
         self.0.get("version").and_then(Item::as_integer)
     }
 
@@ -293,9 +324,24 @@ impl RawManifest {
     /// could work today, but is still limited by the lack of an optional tag.
     pub fn to_typed(&self) -> Result<TypedManifest, toml_edit::de::Error> {
         match self.get_version() {
-            Some(1) => Ok(TypedManifest::Catalog(toml_edit::de::from_document(
-                self.0.clone(),
-            )?)),
+            Some(1) => match toml_edit::de::from_document(self.0.clone()) {
+                Ok(manifest) => Ok(TypedManifest::Catalog(manifest)),
+                // serde can't tell where e.g. a package descriptor failed to parse,
+                // so look for a more precise error using the manifest schema
+                Err(err) => {
+                    let violation =
+                        toml_edit::de::from_document::<serde_json::Value>(self.0.clone())
+                            .ok()
+                            .and_then(|value| manifest_schema::validate(&value).err());
+                    match violation {
+                        Some(violation) => Err(manifest_schema::located_error(
+                            &self.0.to_string(),
+                            &violation,
+                        )),
+                        None => Err(err),
+                    }
+                },
+            },
             None => Ok(TypedManifest::Pkgdb(toml_edit::de::from_document(
                 self.0.clone(),
             )?)),
@@ -305,6 +351,178 @@ impl RawManifest {
             },
         }
     }
+
+    /// Replace the sources of secret variables in `[vars]` with a placeholder
+    ///
+    /// Secret values are never stored in the manifest,
+    /// but their sources, e.g. commands, may contain sensitive arguments.
+    /// Static variables and formatting are left untouched.
+    pub fn redact_secrets(&mut self) {
+        let Some(vars) = self
+            .0
+            .get_mut("vars")
+            .and_then(|vars| vars.as_table_like_mut())
+        else {
+            return;
+        };
+
+        fn redact_vars(vars: &mut dyn TableLike) {
+            for (name, var) in vars.iter_mut() {
+                let Some(table) = var.as_table_like_mut() else {
+                    continue;
+                };
+                // variables set for a specific system, e.g. `[vars.aarch64-darwin]`
+                if is_supported_system(name.get()) {
+                    redact_vars(table);
+                    continue;
+                }
+                for (_, value) in table.iter_mut() {
+                    if let Some(value) = value.as_value_mut() {
+                        let decor = value.decor().clone();
+                        *value = REDACTED.into();
+                        *value.decor_mut() = decor;
+                    }
+                }
+            }
+        }
+        redact_vars(vars);
+    }
+
+    /// Merge the changes of two manifests derived from a common `base` manifest
+    ///
+    /// Entries are compared by value, ignoring formatting.
+    /// An entry changed on only one side takes the value of that side,
+    /// tables changed on both sides are merged entry by entry,
+    /// so that e.g. packages or variables added on either side are combined.
+    /// Formatting and comments of `ours` are preserved.
+    ///
+    /// Returns [ManifestMergeConflicts] listing all entries
+    /// that were changed differently on both sides.
+    pub fn merge(
+        base: &RawManifest,
+        ours: &RawManifest,
+        theirs: &RawManifest,
+    ) -> Result<RawManifest, ManifestMergeConflicts> {
+        let mut merged = ours.0.clone();
+        let mut conflicts = Vec::new();
+
+        merge_tables(
+            &mut Vec::new(),
+            Some(base.as_table()),
+            ours.as_table(),
+            theirs.as_table(),
+            merged.as_table_mut(),
+            &mut conflicts,
+        );
+
+        if !conflicts.is_empty() {
+            return Err(ManifestMergeConflicts(conflicts));
+        }
+
+        Ok(RawManifest(merged))
+    }
+}
+
+/// Conflicting changes encountered while merging manifests with [RawManifest::merge]
+///
+/// Lists the dotted paths of all entries that were changed differently
+/// by both manifests, e.g. `install.hello` or `vars.FOO`.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("conflicting changes to {}", .0.iter().map(|path| format!("'{path}'")).join(", "))]
+pub struct ManifestMergeConflicts(pub Vec<String>);
+
+/// Merge the entries of the tables `ours` and `theirs` into `merged`
+///
+/// `path` is the path of the tables within the manifest
+/// and is used to report conflicts.
+fn merge_tables(
+    path: &mut Vec<String>,
+    base: Option<&dyn TableLike>,
+    ours: &dyn TableLike,
+    theirs: &dyn TableLike,
+    merged: &mut dyn TableLike,
+    conflicts: &mut Vec<String>,
+) {
+    let keys = ours
+        .iter()
+        .chain(theirs.iter())
+        .map(|(key, _)| key.to_string())
+        .unique()
+        .collect::<Vec<_>>();
+
+    for key in keys {
+        path.push(key.clone());
+        let item = merge_items(
+            path,
+            base.and_then(|base| base.get(&key)),
+            ours.get(&key),
+            theirs.get(&key),
+            conflicts,
+        );
+        path.pop();
+
+        match item {
+            Some(item) => merged.insert(&key, item),
+            None => merged.remove(&key),
+        };
+    }
+}
+
+/// Merge a single entry of a manifest
+///
+/// Returns the merged item or `None` if the entry was removed.
+/// If the entry was changed differently on both sides,
+/// its path is added to `conflicts` and the item of `ours` is returned.
+fn merge_items(
+    path: &mut Vec<String>,
+    base: Option<&Item>,
+    ours: Option<&Item>,
+    theirs: Option<&Item>,
+    conflicts: &mut Vec<String>,
+) -> Option<Item> {
+    let base_value = base.and_then(item_value);
+    let ours_value = ours.and_then(item_value);
+    let theirs_value = theirs.and_then(item_value);
+
+    if ours_value == theirs_value || base_value == theirs_value {
+        return ours.cloned();
+    }
+    if base_value == ours_value {
+        return theirs.cloned();
+    }
+
+    // Both sides changed the entry,
+    // if both are tables their entries may still be merged
+    if let (Some(ours), Some(theirs)) = (ours, theirs) {
+        if let (Some(ours_table), Some(theirs_table)) =
+            (ours.as_table_like(), theirs.as_table_like())
+        {
+            let mut merged = ours.clone();
+            let merged_table = merged
+                .as_table_like_mut()
+                .expect("cloned from a table-like item");
+            merge_tables(
+                path,
+                base.and_then(Item::as_table_like),
+                ours_table,
+                theirs_table,
+                merged_table,
+                conflicts,
+            );
+            return Some(merged);
+        }
+    }
+
+    conflicts.push(path.join("."));
+    ours.cloned()
+}
+
+/// Deserialize an [Item] to compare items independent of their formatting
+fn item_value(item: &Item) -> Option<toml::Value> {
+    let mut table = Table::new();
+    table.insert("value", item.clone());
+    let mut values: toml::Table = toml_edit::de::from_document(DocumentMut::from(table)).ok()?;
+    values.remove("value")
 }
 
 impl FromStr for RawManifest {
@@ -366,10 +584,11 @@ pub enum TypedManifest {
 // the user provided manifest but allow unknown fields when deserializing the
 // lockfile,
 // but that doesn't seem worth the effort at the moment.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(deny_unknown_fields)]
 pub struct TypedManifestCatalog {
+    #[schemars(schema_with = "super::manifest_schema::version_schema::<1>")]
     pub version: Version<1>,
     /// The packages to install in the form of a map from install_id
     /// to package descriptor.
@@ -398,6 +617,11 @@ pub struct TypedManifestCatalog {
     #[serde(default)]
     #[serde(skip_serializing_if = "ManifestBuild::skip_serializing")]
     pub build: ManifestBuild,
+    /// Other environments that are merged into this one
+    #[serde(default)]
+    #[serde(skip_serializing_if = "ManifestInclude::skip_serializing")]
+    #[cfg_attr(test, proptest(value = "ManifestInclude::default()"))]
+    pub include: ManifestInclude,
 }
 
 impl TypedManifestCatalog {
@@ -461,6 +685,74 @@ impl TypedManifestCatalog {
     ) -> Result<bool, ManifestError> {
         pkg_belongs_to_non_empty_toplevel_group(pkg.as_ref(), &self.install.0)
     }
+
+    /// Merge the manifests of included environments into this manifest.
+    ///
+    /// The included manifests are applied in order, followed by this manifest.
+    /// Entries in `install`, `vars`, `services` and `build`
+    /// replace entries with the same key from earlier manifests,
+    /// while `hook` and `profile` scripts are concatenated in the same order
+    /// (see [ManifestHook::merge] and [ManifestProfile::merge]).
+    /// `options` are only taken from this manifest.
+    ///
+    /// The merged manifest does not include any environments.
+    pub fn merge_includes<'a>(
+        &self,
+        includes: impl IntoIterator<Item = &'a TypedManifestCatalog>,
+    ) -> TypedManifestCatalog {
+        let mut manifests: Vec<&TypedManifestCatalog> = includes.into_iter().collect();
+        manifests.push(self);
+
+        let mut merged = TypedManifestCatalog::default();
+        for manifest in manifests {
+            merged.install.0.extend(manifest.install.0.clone());
+            merged.vars.merge(&manifest.vars);
+            merged.services.0.extend(manifest.services.0.clone());
+            merged.build.0.extend(manifest.build.0.clone());
+            merged.hook.merge(&manifest.hook);
+            merged.profile.merge(&manifest.profile);
+        }
+        merged.options = self.options.clone();
+        merged
+    }
+
+    /// Create a copy of this manifest with the system specific
+    /// `vars`, `hook` and `profile` sections for `system` merged
+    /// into the common ones, and those for other systems removed.
+    ///
+    /// Clone rather than merge in place
+    /// to avoid accidental mutation of the original in memory manifest/lockfile.
+    pub fn copy_for_system(&self, system: &System) -> Self {
+        TypedManifestCatalog {
+            vars: self.vars.copy_for_system(system),
+            hook: self.hook.copy_for_system(system),
+            profile: self.profile.copy_for_system(system),
+            ..self.clone()
+        }
+    }
+
+    /// Whether any of the `vars`, `hook` and `profile` sections
+    /// is overridden for specific systems
+    pub fn has_system_overrides(&self) -> bool {
+        !(self.vars.system_overrides().is_empty()
+            && self.hook.system_overrides().is_empty()
+            && self.profile.system_overrides().is_empty())
+    }
+}
+
+/// Append `second` to `first`, making sure each script ends up on its own lines.
+fn concat_scripts(first: Option<String>, second: &Option<String>) -> Option<String> {
+    match (first, second) {
+        (Some(mut first), Some(second)) => {
+            if !first.ends_with('\n') {
+                first.push('\n');
+            }
+            first.push_str(second);
+            Some(first)
+        },
+        (first, None) => first,
+        (None, second) => second.clone(),
+    }
 }
 
 pub(crate) fn pkg_descriptors_in_toplevel_group(
@@ -573,6 +865,7 @@ fn pkg_belongs_to_non_empty_toplevel_group(
     Clone,
     Serialize,
     Deserialize,
+    JsonSchema,
     Default,
     PartialEq,
     derive_more::Deref,
@@ -595,18 +888,18 @@ impl ManifestInstall {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 // todo: this can make the error messages less clear and might call for a custom (de)serialize impl
 #[serde(
     untagged,
-    expecting = "Expected either a catalog package descriptor or flake installable.
+    expecting = "Expected either a catalog package descriptor, flake installable or store path.
 See https://flox.dev/docs/concepts/manifest/#package-descriptors for more information."
 )]
 pub enum ManifestPackageDescriptor {
     Catalog(ManifestPackageDescriptorCatalog),
     FlakeRef(ManifestPackageDescriptorFlake),
-    // TODO: StorePath(ManifestPackageDescriptorStorePath),
+    StorePath(ManifestPackageDescriptorStorePath),
 }
 
 impl ManifestPackageDescriptor {
@@ -622,6 +915,7 @@ impl ManifestPackageDescriptor {
         match (self, other) {
             (Catalog(this), Catalog(other)) => this.invalidates_existing_resolution(other),
             (FlakeRef(this), FlakeRef(other)) => this != other,
+            (StorePath(this), StorePath(other)) => this.store_path != other.store_path,
             // different types of descriptors are always different
             _ => true,
         }
@@ -658,6 +952,14 @@ impl ManifestPackageDescriptor {
             _ => None,
         }
     }
+
+    #[must_use]
+    pub fn as_store_path_descriptor_ref(&self) -> Option<&ManifestPackageDescriptorStorePath> {
+        match self {
+            ManifestPackageDescriptor::StorePath(descriptor) => Some(descriptor),
+            _ => None,
+        }
+    }
 }
 
 impl From<&ManifestPackageDescriptorCatalog> for ManifestPackageDescriptor {
@@ -684,8 +986,14 @@ impl From<ManifestPackageDescriptorFlake> for ManifestPackageDescriptor {
     }
 }
 
+impl From<ManifestPackageDescriptorStorePath> for ManifestPackageDescriptor {
+    fn from(val: ManifestPackageDescriptorStorePath) -> Self {
+        ManifestPackageDescriptor::StorePath(val)
+    }
+}
+
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -702,6 +1010,8 @@ pub struct ManifestPackageDescriptorCatalog {
         )
     )]
     pub(crate) systems: Option<Vec<System>>,
+    /// The outputs of the package to install into the environment
+    pub(crate) outputs: Option<SelectedOutputs>,
 }
 
 impl ManifestPackageDescriptorCatalog {
@@ -712,6 +1022,7 @@ impl ManifestPackageDescriptorCatalog {
     /// * Descriptors are resolved per system,
     ///   changing the supported systems does not invalidate _existing_ resolutions.
     /// * Priority is not used in resolution, so it is ignored.
+    /// * Outputs are selected from the resolved package, so they are ignored.
     pub(super) fn invalidates_existing_resolution(&self, other: &Self) -> bool {
         // unpack to avoid forgetting to update this method when new fields are added
         let ManifestPackageDescriptorCatalog {
@@ -720,14 +1031,33 @@ impl ManifestPackageDescriptorCatalog {
             version,
             systems: _,
             priority: _,
+            outputs: _,
         } = self;
 
         pkg_path != &other.pkg_path || pkg_group != &other.pkg_group || version != &other.version
     }
 }
 
+/// The outputs of a catalog package to install,
+/// either a list of output names or `"all"`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(untagged)]
+pub enum SelectedOutputs {
+    All(AllOutputs),
+    Specific(Vec<String>),
+}
+
+/// The `"all"` keyword of [SelectedOutputs]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum AllOutputs {
+    All,
+}
+
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -745,60 +1075,410 @@ pub struct ManifestPackageDescriptorFlake {
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
 pub struct ManifestPackageDescriptorStorePath {
-    store_path: String,
+    /// An existing store path, e.g. the output of a local build
+    pub store_path: String,
+    #[cfg_attr(test, proptest(strategy = "proptest::option::of(0..10u64)"))]
+    pub(crate) priority: Option<u64>,
+    #[cfg_attr(
+        test,
+        proptest(
+            strategy = "proptest::option::of(proptest::collection::vec(any::<System>(), 1..3))"
+        )
+    )]
+    pub(crate) systems: Option<Vec<System>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+/// The `[vars]` section of a manifest
+///
+/// Variables for specific systems are set in tables named after the system,
+/// e.g. `[vars.aarch64-darwin]`.
+/// Variable names can't contain `-`, so these never clash with variables.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct ManifestVariables(
     #[cfg_attr(
         test,
-        proptest(strategy = "proptest_btree_map_alphanum_keys::<String>(10, 3)")
+        proptest(strategy = "proptest_btree_map_alphanum_keys::<ManifestVariable>(10, 3)")
     )]
-    pub(crate) BTreeMap<String, String>,
+    pub(crate) BTreeMap<String, ManifestVariable>,
+    /// Variables that are only set on specific systems
+    #[cfg_attr(test, proptest(value = "SystemOverrides::default()"))]
+    pub(crate) SystemOverrides<ManifestVariables>,
 );
 
 impl ManifestVariables {
     fn skip_serializing(&self) -> bool {
-        self.0.is_empty()
+        self.0.is_empty() && self.1.is_empty()
+    }
+
+    /// Set the variables of `other`,
+    /// replacing variables of the same name
+    fn merge(&mut self, other: &ManifestVariables) {
+        self.0.extend(other.0.clone());
+        self.1.merge(&other.1, ManifestVariables::merge);
+    }
+
+    /// Create a copy of these variables with the variables for `system` set,
+    /// and those for other systems removed.
+    pub fn copy_for_system(&self, system: &System) -> Self {
+        let mut vars = ManifestVariables(self.0.clone(), SystemOverrides::default());
+        if let Some(system_vars) = self.1.get(system) {
+            vars.merge(system_vars);
+        }
+        vars
+    }
+
+    /// Variables with a value set in the manifest.
+    ///
+    /// Secret variables are not included,
+    /// they have to be resolved using [ManifestVariables::secrets].
+    pub fn static_values(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .filter_map(|(name, variable)| match variable {
+                ManifestVariable::Value(value) => Some((name.clone(), value.clone())),
+                ManifestVariable::Secret(_) => None,
+            })
+            .collect()
+    }
+
+    /// Variables whose values are sourced at activation time
+    pub fn secrets(&self) -> impl Iterator<Item = (&String, &SecretSource)> {
+        self.0.iter().filter_map(|(name, variable)| match variable {
+            ManifestVariable::Secret(source) => Some((name, source)),
+            ManifestVariable::Value(_) => None,
+        })
+    }
+}
+
+impl SystemSpecific for ManifestVariables {
+    fn system_overrides(&self) -> &SystemOverrides<Self> {
+        &self.1
+    }
+}
+
+impl Serialize for ManifestVariables {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len() + self.1.len()))?;
+        for (name, variable) in &self.0 {
+            map.serialize_entry(name, variable)?;
+        }
+        for (system, vars) in self.1.iter() {
+            map.serialize_entry(system, vars)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ManifestVariables {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = BTreeMap::<String, toml::Value>::deserialize(deserializer)?;
+        let mut vars = BTreeMap::new();
+        let mut systems = BTreeMap::new();
+        for (name, value) in entries {
+            if is_supported_system(&name) {
+                let system_vars =
+                    ManifestVariables::deserialize(value).map_err(D::Error::custom)?;
+                systems.insert(name, system_vars);
+            } else {
+                let variable = ManifestVariable::deserialize(value).map_err(D::Error::custom)?;
+                vars.insert(name, variable);
+            }
+        }
+        let systems = SystemOverrides::new(systems).map_err(D::Error::custom)?;
+        Ok(ManifestVariables(vars, systems))
+    }
+}
+
+impl JsonSchema for ManifestVariables {
+    fn schema_name() -> String {
+        "ManifestVariables".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let variables = |gen: &mut SchemaGenerator| -> Schema {
+            SchemaObject {
+                instance_type: Some(InstanceType::Object.into()),
+                object: Some(Box::new(ObjectValidation {
+                    additional_properties: Some(Box::new(gen.subschema_for::<ManifestVariable>())),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }
+            .into()
+        };
+        let mut schema = SchemaObject::from(variables(gen));
+        let object = schema.object();
+        object.properties = DEFAULT_SYSTEMS_STR
+            .iter()
+            .map(|system| (system.clone(), variables(gen)))
+            .collect();
+        schema.into()
+    }
+}
+
+/// The value of a variable in `[vars]`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(untagged)]
+pub enum ManifestVariable {
+    /// A value set in the manifest
+    Value(String),
+    /// A value read at activation time,
+    /// which is never written to the lockfile or the built environment
+    Secret(SecretSource),
+}
+
+impl From<&str> for ManifestVariable {
+    fn from(value: &str) -> Self {
+        ManifestVariable::Value(value.to_string())
     }
 }
 
+/// Where the value of a secret variable is read from.
+///
+/// Relative paths are resolved against the directory containing the `.flox` directory.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(untagged)]
+pub enum SecretSource {
+    File(SecretSourceFile),
+    Dotenv(SecretSourceDotenv),
+    Command(SecretSourceCommand),
+}
+
+/// Read the value from a file, without trailing newlines
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(deny_unknown_fields)]
+pub struct SecretSourceFile {
+    #[cfg_attr(test, proptest(strategy = "any::<String>().prop_map(PathBuf::from)"))]
+    pub file: PathBuf,
+}
+
+/// Read the value from a variable in a dotenv file
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
-#[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
+pub struct SecretSourceDotenv {
+    #[cfg_attr(test, proptest(strategy = "any::<String>().prop_map(PathBuf::from)"))]
+    pub dotenv: PathBuf,
+    /// The variable to read, defaults to the name of the variable in `[vars]`
+    pub key: Option<String>,
+}
+
+/// Use the output of a command run with `sh`, without trailing newlines
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(deny_unknown_fields)]
+pub struct SecretSourceCommand {
+    pub command: String,
+}
+
+// serde doesn't support deny_unknown_fields together with flatten.
+// Unknown fields are rejected by [SystemOverrides] instead,
+// which receives all fields other than the scripts.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[schemars(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
 pub struct ManifestHook {
     /// A script that is run at activation time,
     /// in a flox provided bash shell
-    on_activate: Option<String>,
+    pub(crate) on_activate: Option<String>,
+    /// A script that is run when an activation exits,
+    /// in a flox provided bash shell
+    pub(crate) on_deactivate: Option<String>,
+    /// Scripts that are only run on specific systems,
+    /// e.g. `[hook.aarch64-darwin]`
+    #[serde(flatten)]
+    #[cfg_attr(test, proptest(value = "SystemOverrides::default()"))]
+    pub(crate) systems: SystemOverrides<ManifestHook>,
+}
+
+impl ManifestHook {
+    /// Append the scripts of `other` to these scripts.
+    ///
+    /// `on-deactivate` scripts are prepended instead,
+    /// to tear down in the reverse order of setting up.
+    fn merge(&mut self, other: &ManifestHook) {
+        self.on_activate = concat_scripts(self.on_activate.take(), &other.on_activate);
+        self.on_deactivate =
+            concat_scripts(other.on_deactivate.clone(), &self.on_deactivate.take());
+        self.systems.merge(&other.systems, ManifestHook::merge);
+    }
+
+    /// Create a copy of these hooks with the scripts for `system` merged in,
+    /// and those for other systems removed.
+    pub fn copy_for_system(&self, system: &System) -> Self {
+        let mut hook = ManifestHook {
+            systems: SystemOverrides::default(),
+            ..self.clone()
+        };
+        if let Some(system_hook) = self.systems.get(system) {
+            hook.merge(system_hook);
+        }
+        hook
+    }
+}
+
+impl SystemSpecific for ManifestHook {
+    fn system_overrides(&self) -> &SystemOverrides<Self> {
+        &self.systems
+    }
 }
 
+// serde doesn't support deny_unknown_fields together with flatten.
+// Unknown fields are rejected by [SystemOverrides] instead,
+// which receives all fields other than the scripts.
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
-#[serde(deny_unknown_fields)]
+#[schemars(deny_unknown_fields)]
 pub struct ManifestProfile {
     /// When defined, this hook is run by _all_ shells upon activation
-    common: Option<String>,
+    pub(crate) common: Option<String>,
     /// When defined, this hook is run upon activation in a bash shell
-    bash: Option<String>,
+    pub(crate) bash: Option<String>,
     /// When defined, this hook is run upon activation in a zsh shell
-    zsh: Option<String>,
+    pub(crate) zsh: Option<String>,
     /// When defined, this hook is run upon activation in a fish shell
-    fish: Option<String>,
+    pub(crate) fish: Option<String>,
     /// When defined, this hook is run upon activation in a tcsh shell
-    tcsh: Option<String>,
+    pub(crate) tcsh: Option<String>,
+    /// Scripts that are only run on specific systems,
+    /// e.g. `[profile.x86_64-linux]`
+    #[serde(flatten)]
+    #[cfg_attr(test, proptest(value = "SystemOverrides::default()"))]
+    pub(crate) systems: SystemOverrides<ManifestProfile>,
+}
+
+impl ManifestProfile {
+    /// Append the scripts of `other` to these scripts
+    fn merge(&mut self, other: &ManifestProfile) {
+        self.common = concat_scripts(self.common.take(), &other.common);
+        self.bash = concat_scripts(self.bash.take(), &other.bash);
+        self.zsh = concat_scripts(self.zsh.take(), &other.zsh);
+        self.fish = concat_scripts(self.fish.take(), &other.fish);
+        self.tcsh = concat_scripts(self.tcsh.take(), &other.tcsh);
+        self.systems.merge(&other.systems, ManifestProfile::merge);
+    }
+
+    /// Create a copy of these scripts with the scripts for `system` merged in,
+    /// and those for other systems removed.
+    pub fn copy_for_system(&self, system: &System) -> Self {
+        let mut profile = ManifestProfile {
+            systems: SystemOverrides::default(),
+            ..self.clone()
+        };
+        if let Some(system_profile) = self.systems.get(system) {
+            profile.merge(system_profile);
+        }
+        profile
+    }
+}
+
+impl SystemSpecific for ManifestProfile {
+    fn system_overrides(&self) -> &SystemOverrides<Self> {
+        &self.systems
+    }
+}
+
+/// Whether `system` is one of the systems supported by Flox
+fn is_supported_system(system: &str) -> bool {
+    DEFAULT_SYSTEMS_STR
+        .iter()
+        .any(|supported| supported == system)
+}
+
+/// A section of the manifest that can be extended for specific systems
+pub trait SystemSpecific: Sized {
+    fn system_overrides(&self) -> &SystemOverrides<Self>;
+}
+
+/// Extensions of a manifest section for specific systems,
+/// keyed by the system they apply to.
+///
+/// Systems must be supported by Flox
+/// and the extensions can't themselves be extended for other systems.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash, derive_more::Deref)]
+pub struct SystemOverrides<T>(BTreeMap<System, T>);
+
+impl<T> Default for SystemOverrides<T> {
+    fn default() -> Self {
+        SystemOverrides(BTreeMap::new())
+    }
+}
+
+impl<T: SystemSpecific> SystemOverrides<T> {
+    fn new(overrides: BTreeMap<System, T>) -> Result<Self, String> {
+        for (system, section) in &overrides {
+            if !is_supported_system(system) {
+                return Err(format!(
+                    "unknown field or system `{system}`, expected one of {}",
+                    DEFAULT_SYSTEMS_STR
+                        .iter()
+                        .map(|system| format!("`{system}`"))
+                        .join(", ")
+                ));
+            }
+            if let Some(nested) = section.system_overrides().keys().next() {
+                return Err(format!(
+                    "`{system}` can't be extended for system `{nested}`"
+                ));
+            }
+        }
+        Ok(SystemOverrides(overrides))
+    }
+}
+
+impl<T: Clone + Default> SystemOverrides<T> {
+    /// Merge the extensions of `other` into these extensions using `merge_section`
+    fn merge(&mut self, other: &SystemOverrides<T>, merge_section: impl Fn(&mut T, &T)) {
+        for (system, section) in other.iter() {
+            merge_section(self.0.entry(system.clone()).or_default(), section);
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de> + SystemSpecific> Deserialize<'de> for SystemOverrides<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let overrides = BTreeMap::<System, T>::deserialize(deserializer)?;
+        SystemOverrides::new(overrides).map_err(D::Error::custom)
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for SystemOverrides<T> {
+    fn schema_name() -> String {
+        format!("SystemOverrides_for_{}", T::schema_name())
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            object: Some(Box::new(ObjectValidation {
+                properties: DEFAULT_SYSTEMS_STR
+                    .iter()
+                    .map(|system| (system.clone(), gen.subschema_for::<T>()))
+                    .collect(),
+                additional_properties: Some(Box::new(Schema::Bool(false))),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -821,7 +1501,7 @@ pub struct ManifestOptions {
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(deny_unknown_fields)]
 pub struct Allows {
@@ -829,6 +1509,8 @@ pub struct Allows {
     pub unfree: Option<bool>,
     /// Whether to allow packages that are marked as `broken`
     pub broken: Option<bool>,
+    /// Whether to allow packages that are marked as `insecure`
+    pub insecure: Option<bool>,
     /// A list of license descriptors that are allowed
     #[serde(default)]
     #[cfg_attr(
@@ -839,7 +1521,7 @@ pub struct Allows {
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -855,6 +1537,7 @@ pub struct SemverOptions {
     Clone,
     Serialize,
     Deserialize,
+    JsonSchema,
     Default,
     PartialEq,
     derive_more::Deref,
@@ -878,7 +1561,7 @@ impl ManifestServices {
 }
 
 /// The definition of a service in a manifest
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -899,6 +1582,25 @@ pub struct ManifestServiceDescriptor {
 
 impl ManifestServices {
     pub fn validate(&self) -> Result<(), ServiceError> {
+        for (name, desc) in self.0.iter() {
+            if let Some(system) = desc.vars.iter().flat_map(|vars| vars.1.keys()).next() {
+                return Err(ServiceError::InvalidConfig(formatdoc! {"
+                    Service '{name}' sets variables for system '{system}'.
+
+                    Variables of services can't be set per system,
+                    use 'systems' to limit the service to specific systems instead.
+                "}));
+            }
+            if let Some((var, _)) = desc.vars.iter().flat_map(|vars| vars.secrets()).next() {
+                return Err(ServiceError::InvalidConfig(formatdoc! {"
+                    Service '{name}' sets secret variable '{var}'.
+
+                    Secret variables can only be defined in [vars],
+                    services inherit them from the activated environment.
+                "}));
+            }
+        }
+
         let mut bad_services = vec![];
         for (name, desc) in self.0.iter() {
             let daemonizes = desc.is_daemon.is_some_and(|_self| _self);
@@ -944,7 +1646,7 @@ impl ManifestServices {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -959,6 +1661,7 @@ pub struct ManifestServiceShutdown {
     Clone,
     Serialize,
     Deserialize,
+    JsonSchema,
     Default,
     PartialEq,
     derive_more::Deref,
@@ -980,7 +1683,7 @@ impl ManifestBuild {
 }
 
 /// The definition of a package built from within the environment
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -996,7 +1699,9 @@ pub struct ManifestBuildDescriptor {
 }
 
 /// The definition of a package built from within the environment
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, derive_more::Display)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash, derive_more::Display,
+)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 #[serde(rename_all = "kebab-case")]
 pub enum ManifestBuildSandbox {
@@ -1004,6 +1709,78 @@ pub enum ManifestBuildSandbox {
     Pure,
 }
 
+/// Environments included by a manifest
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ManifestInclude {
+    /// The environments to include, in order of increasing precedence
+    #[serde(default)]
+    pub environments: Vec<IncludeDescriptor>,
+}
+
+impl ManifestInclude {
+    fn skip_serializing(&self) -> bool {
+        self.environments.is_empty()
+    }
+}
+
+/// A reference to an environment that is included by a manifest
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[serde(
+    untagged,
+    expecting = "Expected either a local environment ('dir') or a FloxHub environment ('remote')."
+)]
+pub enum IncludeDescriptor {
+    Local(IncludeDescriptorLocal),
+    Remote(IncludeDescriptorRemote),
+}
+
+/// Include an environment from a directory on the local filesystem
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct IncludeDescriptorLocal {
+    /// The directory containing the `.flox` directory of the environment,
+    /// relative paths are resolved relative to the including environment
+    pub dir: PathBuf,
+    /// A name used to refer to the included environment
+    pub name: Option<String>,
+}
+
+/// Include an environment from FloxHub
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct IncludeDescriptorRemote {
+    /// The environment in the form `owner/name` or `owner/name@tag`
+    #[schemars(with = "String")]
+    pub remote: EnvironmentRef,
+    /// A name used to refer to the included environment
+    pub name: Option<String>,
+}
+
+impl IncludeDescriptor {
+    /// The name explicitly given to the included environment, if any
+    pub fn explicit_name(&self) -> Option<&str> {
+        match self {
+            IncludeDescriptor::Local(local) => local.name.as_deref(),
+            IncludeDescriptor::Remote(remote) => remote.name.as_deref(),
+        }
+    }
+}
+
+impl Display for IncludeDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncludeDescriptor::Local(local) => write!(f, "{}", local.dir.display()),
+            IncludeDescriptor::Remote(remote) => write!(f, "{}", remote.remote),
+        }
+    }
+}
+
 /// Deserialize the manifest as a [serde_json::Value],
 /// then convert it to a [RawManifest] that can then be converted to a [TypedManifest].
 /// This provides more precise errors based on the version of the manifest.
@@ -1017,9 +1794,11 @@ impl<'de> Deserialize<'de> for TypedManifest {
         let value = serde_json::Value::deserialize(deserializer)?;
         let document = toml_edit::ser::to_document(&value)
             .map_err(|err| serde::de::Error::custom(err.to_string()))?;
+        // The document is generated from `value`,
+        // so only the message, not the location, of errors is meaningful
         RawManifest(document)
             .to_typed()
-            .map_err(serde::de::Error::custom)
+            .map_err(|err| serde::de::Error::custom(err.message()))
     }
 }
 
@@ -1074,6 +1853,13 @@ pub enum TomlEditError {
 
     #[error("'{0}' is not a supported attribute in manifest version 1")]
     UnsupportedAttributeV1(String),
+
+    #[error("invalid key '{0}'")]
+    InvalidKey(String, #[source] toml_edit::TomlError),
+    #[error("'{0}' must be a table, but found {1} instead")]
+    NotATable(String, String),
+    #[error("couldn't find '{0}' in the manifest")]
+    KeyNotFound(String),
 }
 
 /// Records the result of trying to install a collection of packages to the
@@ -1514,9 +2300,89 @@ pub fn add_system(toml: &str, system: &str) -> Result<DocumentMut, TomlEditError
     Ok(doc)
 }
 
-#[cfg(test)]
-pub(super) mod test {
-    use pretty_assertions::assert_eq;
+/// Parse a dotted `key` such as `services.web.command`,
+/// returning the keys of the parent tables and the last key.
+fn parse_dotted_key(key: &str) -> Result<(Vec<Key>, Key), TomlEditError> {
+    let mut keys =
+        Key::parse(key).map_err(|err| TomlEditError::InvalidKey(key.to_string(), err))?;
+    // `Key::parse` returns at least one key for valid input
+    let last = keys.pop().expect("parsed key is not empty");
+    Ok((keys, last))
+}
+
+/// Set `key` to `value` in a manifest, creating missing tables as needed.
+///
+/// `key` is a dotted TOML key, e.g. `vars.FOO` or `services.web.command`.
+/// `value` is parsed as a TOML value, e.g. `true` or `["x86_64-linux"]`,
+/// and is used as a string if it isn't one.
+/// If `key` is already set, comments around its value are preserved.
+pub fn set_value(toml: &str, key: &str, value: &str) -> Result<DocumentMut, TomlEditError> {
+    let mut doc = toml
+        .parse::<RawManifest>()
+        .map_err(TomlEditError::ParseManifest)?
+        .0;
+    let (parents, last) = parse_dotted_key(key)?;
+
+    let mut value = value
+        .parse::<Value>()
+        .unwrap_or_else(|_| Value::from(value));
+
+    let mut table = doc.as_table_mut() as &mut dyn TableLike;
+    for (i, parent) in parents.iter().enumerate() {
+        let item = table.entry(parent.get()).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        let type_name = item.type_name().into();
+        table = item
+            .as_table_like_mut()
+            .ok_or_else(|| TomlEditError::NotATable(display_keys(&parents[..=i]), type_name))?;
+    }
+
+    if let Some(existing) = table.get(last.get()).and_then(Item::as_value) {
+        *value.decor_mut() = existing.decor().clone();
+    }
+    debug!("setting '{key}' to '{value}'");
+    table.insert(last.get(), Item::Value(value));
+
+    Ok(doc)
+}
+
+/// Remove `key` from a manifest.
+///
+/// `key` is a dotted TOML key, e.g. `vars.FOO` or `services.web`.
+pub fn unset_value(toml: &str, key: &str) -> Result<DocumentMut, TomlEditError> {
+    let mut doc = toml
+        .parse::<RawManifest>()
+        .map_err(TomlEditError::ParseManifest)?
+        .0;
+    let (parents, last) = parse_dotted_key(key)?;
+
+    let mut table = doc.as_table_mut() as &mut dyn TableLike;
+    for parent in &parents {
+        table = table
+            .get_mut(parent.get())
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| TomlEditError::KeyNotFound(key.to_string()))?;
+    }
+
+    debug!("removing '{key}'");
+    table
+        .remove(last.get())
+        .ok_or_else(|| TomlEditError::KeyNotFound(key.to_string()))?;
+
+    Ok(doc)
+}
+
+/// Render keys as a dotted key for error messages
+fn display_keys(keys: &[Key]) -> String {
+    keys.iter().map(|key| key.display_repr()).join(".")
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use pretty_assertions::assert_eq;
     use proptest::prelude::*;
     use proptest_derive::Arbitrary;
 
@@ -1561,6 +2427,39 @@ pub(super) mod test {
         );
     }
 
+    #[test]
+    fn redact_secrets_hides_secret_sources() {
+        let mut manifest = RawManifest::from_str(indoc! {r#"
+            version = 1
+
+            [vars]
+            STATIC = "visible"
+            TOKEN = { command = "op read op://vault/token" } # from 1password
+            DB.dotenv = ".env"
+            DB.key = "DB_PASSWORD"
+
+            [vars.aarch64-darwin]
+            STATIC = "darwin"
+            KEYCHAIN = { command = "security find-generic-password -w -s token" }
+        "#})
+        .unwrap();
+        manifest.redact_secrets();
+
+        assert_eq!(manifest.to_string(), indoc! {r#"
+            version = 1
+
+            [vars]
+            STATIC = "visible"
+            TOKEN = { command = "<redacted>" } # from 1password
+            DB.dotenv = "<redacted>"
+            DB.key = "<redacted>"
+
+            [vars.aarch64-darwin]
+            STATIC = "darwin"
+            KEYCHAIN = { command = "<redacted>" }
+        "#});
+    }
+
     #[test]
     fn catalog_manifest_rejects_unknown_nested_fields() {
         let manifest = formatdoc! {"
@@ -1575,7 +2474,7 @@ pub(super) mod test {
 
         assert!(
             err.message()
-                .starts_with("unknown field `unknown`, expected one of"),
+                .starts_with("unknown field `unknown` in `options.allow`, expected one of"),
             "unexpected error message: {err}",
         );
     }
@@ -1591,6 +2490,7 @@ pub(super) mod test {
             profile_tcsh: None,
             profile_zsh: None,
             packages: None,
+            vars: None,
         };
 
         let expected_string = indoc! {r#"
@@ -1674,6 +2574,7 @@ pub(super) mod test {
                 version: Some("3.11.6".to_string()),
                 systems: None,
             }]),
+            vars: None,
         };
 
         let expected_string = indoc! {r#"
@@ -1760,6 +2661,7 @@ pub(super) mod test {
             profile_tcsh: None,
             profile_zsh: None,
             packages: None,
+            vars: None,
         };
 
         let expected_string = indoc! {r#"
@@ -1826,6 +2728,86 @@ pub(super) mod test {
         assert_eq!(manifest.to_string(), expected_string.to_string());
     }
 
+    #[test]
+    fn create_documented_manifest_vars() {
+        let systems = [&"x86_64-linux".to_string()];
+        let customization = InitCustomization {
+            vars: Some(BTreeMap::from([
+                ("GREETING".to_string(), "Hello".to_string()),
+                (
+                    "DATABASE_URL".to_string(),
+                    "postgres://localhost".to_string(),
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let expected_string = indoc! {r#"
+            #
+            # This is a Flox environment manifest.
+            # Visit flox.dev/docs/concepts/manifest/
+            # or see flox-edit(1), manifest.toml(5) for more information.
+            #
+            # Flox manifest version managed by Flox CLI
+            version = 1
+
+            # List packages you wish to install in your environment inside
+            # the `[install]` section.
+            [install]
+            # hello.pkg-path = "hello"
+            # nodejs = { version = "^20.15.1", pkg-path = "nodejs" }
+
+            # Set environment variables in the `[vars]` section. These variables may not
+            # reference one another, and are added to the environment without first
+            # expanding them. They are available for use in the `[profile]` and `[hook]`
+            # scripts.
+            [vars]
+            DATABASE_URL = "postgres://localhost"
+            GREETING = "Hello"
+
+            # The `hook.on-activate` script is run by the *bash* shell immediately upon
+            # activating an environment, and will not be invoked if Flox detects that the
+            # environment has previously been activated. Variables set by the script will
+            # be inherited by `[profile]` scripts defined below. Note that any stdout
+            # generated by the script will be redirected to stderr.
+            [hook]
+            # on-activate = '''
+            #   # Set variables, create files and directories
+            #   venv_dir="$(mktemp -d)"
+            #   export venv_dir
+            #
+            #   # Perform initialization steps, e.g. create a python venv
+            #   python -m venv "$venv_dir"
+            # '''
+
+            # Scripts defined in the `[profile]` section are *sourced* by *your shell* and
+            # inherit environment variables set in the `[vars]` section and by `[hook]` scripts.
+            # The `profile.common` script is sourced by all shells and special care should be
+            # taken to ensure compatibility with all shells, after which exactly one of
+            # `profile.{bash,fish,tcsh,zsh}` is sourced by the corresponding shell.
+            [profile]
+            # common = '''
+            #   echo "it's gettin' flox in here"
+            # '''
+
+            # The `[services]` section of the manifest allows you to define services.
+            # Services defined here use the packages provided by the `[install]` section
+            # and any variables you've defined in the `[vars]` section or `hook.on-activate` script.
+            [services]
+            # postgres.command = "postgres --config-file=pg.conf"
+
+            # Additional options can be set in the `[options]` section. Refer to
+            # manifest.toml(5) for a list of available options.
+            [options]
+            systems = ["x86_64-linux"]
+            # Uncomment to disable CUDA detection.
+            # cuda-detection = false
+        "#};
+
+        let manifest = RawManifest::new_documented(systems.as_slice(), &customization);
+        assert_eq!(manifest.to_string(), expected_string.to_string());
+    }
+
     #[test]
     fn create_documented_profile_script() {
         let systems = [&"x86_64-linux".to_string()];
@@ -1842,6 +2824,7 @@ pub(super) mod test {
             profile_tcsh: None,
             profile_zsh: None,
             packages: None,
+            vars: None,
         };
 
         let expected_string = indoc! {r#"
@@ -2055,6 +3038,77 @@ pub(super) mod test {
         assert_eq!(inserted_path, r#"foo."bar.baz".qux"#);
     }
 
+    #[test]
+    fn set_value_preserves_formatting() {
+        let manifest = indoc! {r#"
+            version = 1
+            # variables
+            [vars]
+            FOO = "foo" # comment
+        "#};
+        let toml = set_value(manifest, "vars.FOO", "bar").unwrap();
+        let toml = set_value(&toml.to_string(), "options.systems", r#"["x86_64-linux"]"#).unwrap();
+        let toml = set_value(
+            &toml.to_string(),
+            "services.web.command",
+            "python -m http.server",
+        )
+        .unwrap();
+        assert_eq!(toml.to_string(), indoc! {r#"
+            version = 1
+            # variables
+            [vars]
+            FOO = "bar" # comment
+
+            [options]
+            systems = ["x86_64-linux"]
+
+            [services.web]
+            command = "python -m http.server"
+        "#});
+    }
+
+    #[test]
+    fn set_value_errors_for_non_table_parent() {
+        let manifest = indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "foo"
+        "#};
+        let err = set_value(manifest, "vars.FOO.BAR", "bar").unwrap_err();
+        assert_eq!(
+            err,
+            TomlEditError::NotATable("vars.FOO".to_string(), "string".to_string())
+        );
+        let err = set_value(manifest, "vars.", "bar").unwrap_err();
+        assert!(matches!(err, TomlEditError::InvalidKey(_, _)));
+    }
+
+    #[test]
+    fn unset_value_removes_key() {
+        let manifest = indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "foo"
+            BAR = "bar"
+            [hook]
+            on-activate = "echo hello"
+        "#};
+        let toml = unset_value(manifest, "vars.FOO").unwrap();
+        let toml = unset_value(&toml.to_string(), "hook.on-activate").unwrap();
+        assert_eq!(toml.to_string(), indoc! {r#"
+            version = 1
+            [vars]
+            BAR = "bar"
+            [hook]
+        "#});
+
+        let err = unset_value(manifest, "vars.BAZ").unwrap_err();
+        assert_eq!(err, TomlEditError::KeyNotFound("vars.BAZ".to_string()));
+        let err = unset_value(manifest, "services.web").unwrap_err();
+        assert_eq!(err, TomlEditError::KeyNotFound("services.web".to_string()));
+    }
+
     #[test]
     fn parses_string_descriptor() {
         let parsed: CatalogPackage = "hello".parse().unwrap();
@@ -2326,4 +3380,326 @@ pub(super) mod test {
         assert_eq!(filtered.len(), 1, "{:?}", filtered);
         assert!(filtered.contains_key("postgres"));
     }
+
+    #[test]
+    fn copy_for_system_merges_system_specific_sections() {
+        let manifest: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "common"
+            BAR = "common"
+            [vars.aarch64-darwin]
+            FOO = "darwin"
+            [hook]
+            on-activate = "echo common"
+            [hook.aarch64-darwin]
+            on-activate = "echo darwin"
+            [profile.x86_64-linux]
+            common = "echo linux"
+        "#})
+        .unwrap();
+
+        let expected: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "darwin"
+            BAR = "common"
+            [hook]
+            on-activate = """
+            echo common
+            echo darwin"""
+        "#})
+        .unwrap();
+        assert_eq!(
+            manifest.copy_for_system(&"aarch64-darwin".to_string()),
+            expected
+        );
+
+        let expected: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [vars]
+            FOO = "common"
+            BAR = "common"
+            [hook]
+            on-activate = "echo common"
+            [profile]
+            common = "echo linux"
+        "#})
+        .unwrap();
+        assert_eq!(
+            manifest.copy_for_system(&"x86_64-linux".to_string()),
+            expected
+        );
+    }
+
+    #[test]
+    fn system_specific_sections_reject_unknown_systems() {
+        let err = toml::from_str::<TypedManifestCatalog>(indoc! {r#"
+            version = 1
+            [hook.aarch64-windows]
+            on-activate = "echo windows"
+        "#})
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unknown field or system `aarch64-windows`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn system_specific_sections_cant_be_nested() {
+        let err = toml::from_str::<TypedManifestCatalog>(indoc! {r#"
+            version = 1
+            [vars.aarch64-darwin.x86_64-linux]
+            FOO = "bar"
+        "#})
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`aarch64-darwin` can't be extended for system `x86_64-linux`"),
+            "{err}"
+        );
+    }
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> Result<String, ManifestMergeConflicts> {
+        RawManifest::merge(
+            &base.parse().unwrap(),
+            &ours.parse().unwrap(),
+            &theirs.parse().unwrap(),
+        )
+        .map(|merged| merged.to_string())
+    }
+
+    #[test]
+    fn merge_combines_changes_to_different_entries() {
+        let base = indoc! {r#"
+            version = 1
+
+            [install]
+            hello.pkg-path = "hello"
+
+            [vars]
+            KEEP = "keep"
+            REMOVE = "remove"
+        "#};
+        let ours = indoc! {r#"
+            version = 1
+
+            # our packages
+            [install]
+            hello.pkg-path = "hello"
+            curl.pkg-path = "curl"
+
+            [vars]
+            KEEP = "keep"
+        "#};
+        let theirs = indoc! {r#"
+            version = 1
+
+            [install]
+            hello.pkg-path = "hello"
+            cowsay.pkg-path = "cowsay"
+
+            [vars]
+            KEEP = "keep"
+            REMOVE = "remove"
+            ADD = "add"
+        "#};
+
+        assert_eq!(merge(base, ours, theirs).unwrap(), indoc! {r#"
+            version = 1
+
+            # our packages
+            [install]
+            hello.pkg-path = "hello"
+            curl.pkg-path = "curl"
+            cowsay.pkg-path = "cowsay"
+
+            [vars]
+            KEEP = "keep"
+            ADD = "add"
+        "#});
+    }
+
+    #[test]
+    fn merge_takes_changes_of_either_side() {
+        let base = indoc! {r#"
+            version = 1
+
+            [hook]
+            on-activate = "echo base"
+        "#};
+        let ours = base;
+        let theirs = indoc! {r#"
+            version = 1
+
+            [hook]
+            on-activate = "echo theirs"
+        "#};
+
+        assert_eq!(merge(base, ours, theirs).unwrap(), theirs);
+        assert_eq!(merge(base, theirs, ours).unwrap(), theirs);
+    }
+
+    #[test]
+    fn merge_ignores_formatting_changes() {
+        let base = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+        "#};
+        let ours = indoc! {r#"
+            version = 1
+            [install]
+            hello = { pkg-path = "hello" }
+        "#};
+        let theirs = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            [vars]
+            FOO = "foo"
+        "#};
+
+        assert_eq!(merge(base, ours, theirs).unwrap(), indoc! {r#"
+            version = 1
+            [install]
+            hello = { pkg-path = "hello" }
+            [vars]
+            FOO = "foo"
+        "#});
+    }
+
+    #[test]
+    fn merge_reports_conflicts() {
+        let base = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            [vars]
+            FOO = "base"
+        "#};
+        let ours = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.version = "2.10"
+            [vars]
+            FOO = "ours"
+        "#};
+        let theirs = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.version = "2.12"
+            [vars]
+            FOO = "theirs"
+        "#};
+
+        assert_eq!(
+            merge(base, ours, theirs).unwrap_err(),
+            ManifestMergeConflicts(vec![
+                "install.hello.version".to_string(),
+                "vars.FOO".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_include_descriptors() {
+        let manifest: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [include]
+            environments = [
+                { dir = "../base" },
+                { remote = "owner/toolchain@stable", name = "toolchain" },
+            ]
+        "#})
+        .unwrap();
+
+        assert_eq!(manifest.include.environments, vec![
+            IncludeDescriptor::Local(IncludeDescriptorLocal {
+                dir: PathBuf::from("../base"),
+                name: None,
+            }),
+            IncludeDescriptor::Remote(IncludeDescriptorRemote {
+                remote: "owner/toolchain@stable".parse().unwrap(),
+                name: Some("toolchain".to_string()),
+            }),
+        ]);
+    }
+
+    #[test]
+    fn merge_includes_applies_precedence() {
+        let first: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            ripgrep.pkg-path = "ripgrep"
+            [vars]
+            FOO = "first"
+            BAR = "first"
+            [hook]
+            on-activate = "echo first"
+            on-deactivate = "echo first"
+            [options]
+            systems = ["x86_64-linux"]
+        "#})
+        .unwrap();
+        let second: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.version = "2.12"
+            [vars]
+            FOO = "second"
+            [profile]
+            bash = "echo second"
+        "#})
+        .unwrap();
+        let composer: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [install]
+            ripgrep.pkg-path = "ripgrep"
+            ripgrep.version = "14"
+            [vars]
+            BAR = "composer"
+            [hook]
+            on-activate = "echo composer"
+            on-deactivate = "echo composer"
+            [options]
+            systems = ["aarch64-darwin"]
+            [include]
+            environments = [{ dir = "first" }, { dir = "second" }]
+        "#})
+        .unwrap();
+
+        let merged = composer.merge_includes([&first, &second]);
+
+        let expected: TypedManifestCatalog = toml::from_str(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.version = "2.12"
+            ripgrep.pkg-path = "ripgrep"
+            ripgrep.version = "14"
+            [vars]
+            FOO = "second"
+            BAR = "composer"
+            [hook]
+            on-activate = """
+            echo first
+            echo composer"""
+            on-deactivate = """
+            echo composer
+            echo first"""
+            [profile]
+            bash = "echo second"
+            [options]
+            systems = ["aarch64-darwin"]
+        "#})
+        .unwrap();
+        assert_eq!(merged, expected);
+    }
 }
@@ -0,0 +1,652 @@
+//! JSON Schema of the `manifest.toml` format
+//!
+//! The schema is generated from [TypedManifestCatalog] and its nested types,
+//! and can be used by editors to provide completion and validation of manifests.
+//!
+//! It is also used to find precise errors in manifests that fail to deserialize,
+//! since serde can't report where deserialization of untagged enums
+//! such as package descriptors failed.
+
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use schemars::Map;
+use serde::de::{
+    DeserializeSeed,
+    Deserializer,
+    Error as _,
+    IgnoredAny,
+    MapAccess,
+    SeqAccess,
+    Visitor,
+};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::manifest::TypedManifestCatalog;
+
+/// Field names within this edit distance of an unknown field are suggested as alternatives
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Generate the JSON Schema of a version 1 manifest
+pub fn manifest_schema() -> RootSchema {
+    // TOML has no null values, so optional fields are simply omitted
+    let settings = SchemaSettings::draft07().with(|settings| {
+        settings.option_add_null_type = false;
+    });
+    let mut schema = settings
+        .into_generator()
+        .into_root_schema_for::<TypedManifestCatalog>();
+    let metadata = schema.schema.metadata();
+    metadata.title = Some("Flox manifest".to_string());
+    metadata.description =
+        Some("A Flox environment manifest. See manifest.toml(5) for more information.".to_string());
+    schema
+}
+
+/// Schema of [flox_core::Version], which (de)serializes as the integer `V`
+pub(super) fn version_schema<const V: u8>(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        const_value: Some(V.into()),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// A part of a manifest that doesn't match the manifest schema
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SchemaViolation {
+    #[error("unknown field `{field}`{}, {}", in_path(.path), suggest(.field, .expected))]
+    UnknownField {
+        path: SchemaPath,
+        field: String,
+        expected: Vec<String>,
+    },
+    #[error("missing field `{field}`{}", in_path(.path))]
+    MissingField { path: SchemaPath, field: String },
+    #[error("invalid type for `{path}`: expected {expected}, found {found}")]
+    InvalidType {
+        path: SchemaPath,
+        expected: String,
+        found: String,
+    },
+    #[error("invalid value for `{path}`: expected {expected}")]
+    InvalidValue { path: SchemaPath, expected: String },
+}
+
+impl SchemaViolation {
+    /// The path of the invalid part of the manifest, e.g. `install.hello.version`
+    pub fn path(&self) -> &SchemaPath {
+        match self {
+            SchemaViolation::UnknownField { path, .. }
+            | SchemaViolation::MissingField { path, .. }
+            | SchemaViolation::InvalidType { path, .. }
+            | SchemaViolation::InvalidValue { path, .. } => path,
+        }
+    }
+
+    /// The path of the value that should be highlighted in the manifest,
+    /// i.e. the unknown field itself rather than the table containing it.
+    fn location(&self) -> Vec<PathSegment> {
+        let mut location = self.path().0.clone();
+        if let SchemaViolation::UnknownField { field, .. } = self {
+            location.push(PathSegment::Key(field.clone()));
+        }
+        location
+    }
+}
+
+fn in_path(path: &SchemaPath) -> String {
+    if path.0.is_empty() {
+        String::new()
+    } else {
+        format!(" in `{path}`")
+    }
+}
+
+/// Suggest the field closest to `field`,
+/// or list all expected fields if none is close enough.
+fn suggest(field: &str, expected: &[String]) -> String {
+    let closest = expected
+        .iter()
+        .map(|candidate| (strsim::levenshtein(field, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance);
+
+    match closest {
+        Some((_, candidate)) => format!("did you mean `{candidate}`?"),
+        None if expected.is_empty() => "no fields are allowed here".to_string(),
+        None => format!(
+            "expected one of {}",
+            expected.iter().map(|field| format!("`{field}`")).join(", ")
+        ),
+    }
+}
+
+/// Validate a manifest, converted to JSON, against the manifest schema
+///
+/// Returns the first violation found.
+/// Only the subset of JSON Schema generated for the manifest types is supported.
+pub fn validate(manifest: &Value) -> Result<(), SchemaViolation> {
+    let schema = manifest_schema();
+    let validator = Validator {
+        definitions: &schema.definitions,
+    };
+    validator.validate(&schema.schema, manifest, &mut Vec::new())
+}
+
+/// Turn a violation into a TOML error pointing at the offending value of `toml`.
+///
+/// serde reports where deserialization failed, but the error of an untagged enum
+/// points at the whole value and lacks the schema path and suggestion.
+/// Deserializing `toml` again and failing at the path of the violation
+/// attaches the location of that value to the message of the violation.
+pub(super) fn located_error(toml: &str, violation: &SchemaViolation) -> toml_edit::de::Error {
+    let message = violation.to_string();
+    let location = violation.location();
+    let seed = FailAt {
+        path: &location,
+        message: &message,
+    };
+    match toml_edit::de::Deserializer::parse(toml) {
+        Ok(deserializer) => match seed.deserialize(deserializer) {
+            Err(err) => err,
+            Ok(()) => unreachable!("FailAt never succeeds"),
+        },
+        Err(_) => toml_edit::de::Error::custom(message),
+    }
+}
+
+/// Deserializes the value at `path` and fails there with `message`.
+///
+/// If `path` doesn't exist, it fails at the closest existing parent.
+struct FailAt<'a> {
+    path: &'a [PathSegment],
+    message: &'a str,
+}
+
+impl FailAt<'_> {
+    fn fail<E: serde::de::Error>(self) -> Result<(), E> {
+        Err(E::custom(self.message))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for FailAt<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FailAt<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.message)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        if let Some((PathSegment::Key(key), path)) = self.path.split_first() {
+            while let Some(next) = map.next_key::<String>()? {
+                if next == *key {
+                    return map.next_value_seed(FailAt {
+                        path,
+                        message: self.message,
+                    });
+                }
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        self.fail()
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        if let Some((PathSegment::Index(index), path)) = self.path.split_first() {
+            for _ in 0..*index {
+                if seq.next_element::<IgnoredAny>()?.is_none() {
+                    return self.fail();
+                }
+            }
+            if let Some(()) = seq.next_element_seed(FailAt {
+                path,
+                message: self.message,
+            })? {
+                unreachable!("FailAt never succeeds")
+            }
+        }
+        self.fail()
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, _: bool) -> Result<(), E> {
+        self.fail()
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, _: i64) -> Result<(), E> {
+        self.fail()
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, _: u64) -> Result<(), E> {
+        self.fail()
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, _: f64) -> Result<(), E> {
+        self.fail()
+    }
+
+    fn visit_str<E: serde::de::Error>(self, _: &str) -> Result<(), E> {
+        self.fail()
+    }
+}
+
+/// A segment of the path to a value in the manifest
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The path to a value in the manifest, e.g. `include.environments[1].remote`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaPath(Vec<PathSegment>);
+
+impl Display for SchemaPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => write!(f, "{key}")?,
+                PathSegment::Key(key) => write!(f, ".{key}")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Validator<'a> {
+    definitions: &'a Map<String, Schema>,
+}
+
+impl<'a> Validator<'a> {
+    /// Follow references to definitions
+    fn resolve(&self, schema: &'a SchemaObject) -> Option<&'a SchemaObject> {
+        let Some(reference) = &schema.reference else {
+            return Some(schema);
+        };
+        let name = reference.strip_prefix("#/definitions/")?;
+        match self.definitions.get(name)? {
+            Schema::Object(definition) => self.resolve(definition),
+            Schema::Bool(_) => None,
+        }
+    }
+
+    fn validate_schema(
+        &self,
+        schema: &'a Schema,
+        value: &Value,
+        path: &mut Vec<PathSegment>,
+    ) -> Result<(), SchemaViolation> {
+        match schema {
+            Schema::Bool(true) => Ok(()),
+            Schema::Bool(false) => Err(SchemaViolation::InvalidValue {
+                path: SchemaPath(path.to_vec()),
+                expected: "no value".to_string(),
+            }),
+            Schema::Object(schema) => self.validate(schema, value, path),
+        }
+    }
+
+    fn validate(
+        &self,
+        schema: &'a SchemaObject,
+        value: &Value,
+        path: &mut Vec<PathSegment>,
+    ) -> Result<(), SchemaViolation> {
+        let Some(schema) = self.resolve(schema) else {
+            // Unknown schemas are not generated for the manifest, accept anything
+            return Ok(());
+        };
+
+        if let Some(subschemas) = &schema.subschemas {
+            for subschema in subschemas.all_of.iter().flatten() {
+                self.validate_schema(subschema, value, path)?;
+            }
+            if let Some(alternatives) = subschemas.any_of.as_ref().or(subschemas.one_of.as_ref()) {
+                self.validate_alternatives(alternatives, value, path)?;
+            }
+        }
+
+        if let Some(instance_type) = &schema.instance_type {
+            if !matches_type(instance_type, value) {
+                return Err(SchemaViolation::InvalidType {
+                    path: SchemaPath(path.to_vec()),
+                    expected: describe_type(instance_type),
+                    found: describe_value(value).to_string(),
+                });
+            }
+        }
+
+        if let Some(const_value) = &schema.const_value {
+            if value != const_value {
+                return Err(SchemaViolation::InvalidValue {
+                    path: SchemaPath(path.to_vec()),
+                    expected: const_value.to_string(),
+                });
+            }
+        }
+
+        if let Some(enum_values) = &schema.enum_values {
+            if !enum_values.contains(value) {
+                return Err(SchemaViolation::InvalidValue {
+                    path: SchemaPath(path.to_vec()),
+                    expected: format!("one of {}", enum_values.iter().join(", ")),
+                });
+            }
+        }
+
+        if let (Some(object), Value::Object(map)) = (&schema.object, value) {
+            for field in &object.required {
+                if !map.contains_key(field) {
+                    return Err(SchemaViolation::MissingField {
+                        path: SchemaPath(path.to_vec()),
+                        field: field.clone(),
+                    });
+                }
+            }
+            for (key, value) in map {
+                let field_schema = object
+                    .properties
+                    .get(key)
+                    .or(object.additional_properties.as_deref());
+                match field_schema {
+                    Some(Schema::Bool(false)) => {
+                        return Err(SchemaViolation::UnknownField {
+                            path: SchemaPath(path.to_vec()),
+                            field: key.clone(),
+                            expected: object.properties.keys().cloned().collect(),
+                        });
+                    },
+                    Some(field_schema) => {
+                        path.push(PathSegment::Key(key.clone()));
+                        self.validate_schema(field_schema, value, path)?;
+                        path.pop();
+                    },
+                    None => {},
+                }
+            }
+        }
+
+        if let (Some(array), Value::Array(items)) = (&schema.array, value) {
+            if let Some(SingleOrVec::Single(item_schema)) = &array.items {
+                for (index, item) in items.iter().enumerate() {
+                    path.push(PathSegment::Index(index));
+                    self.validate_schema(item_schema, item, path)?;
+                    path.pop();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate a value that has to match one of multiple alternative schemas,
+    /// e.g. an untagged enum.
+    ///
+    /// If no alternative matches, the violation of the alternative that most
+    /// likely was intended is returned:
+    /// for tables, that's the alternative sharing the most fields with the value,
+    /// otherwise the alternative that matched the most of the value.
+    fn validate_alternatives(
+        &self,
+        alternatives: &'a [Schema],
+        value: &Value,
+        path: &[PathSegment],
+    ) -> Result<(), SchemaViolation> {
+        let mut violations = Vec::new();
+        for alternative in alternatives {
+            match self.validate_schema(alternative, value, &mut path.to_vec()) {
+                Ok(()) => return Ok(()),
+                Err(violation) => violations.push((alternative, violation)),
+            }
+        }
+
+        let shared_fields = |schema: &'a Schema| {
+            let (Schema::Object(schema), Value::Object(map)) = (schema, value) else {
+                return 0;
+            };
+            self.resolve(schema)
+                .and_then(|schema| schema.object.as_ref())
+                .map(|object| {
+                    map.keys()
+                        .filter(|key| object.properties.contains_key(*key))
+                        .count()
+                })
+                .unwrap_or(0)
+        };
+
+        let best = violations
+            .into_iter()
+            .max_by_key(|(alternative, violation)| {
+                (
+                    shared_fields(alternative),
+                    violation.path().to_string().len(),
+                )
+            });
+
+        match best {
+            // none of the alternatives got past the value itself,
+            // so describe what was expected instead
+            Some((
+                _,
+                SchemaViolation::InvalidType {
+                    path: violation_path,
+                    found,
+                    ..
+                },
+            )) if violation_path.0 == path => Err(SchemaViolation::InvalidType {
+                path: violation_path,
+                expected: alternatives
+                    .iter()
+                    .filter_map(|alternative| self.describe_schema(alternative))
+                    .unique()
+                    .join(" or "),
+                found,
+            }),
+            Some((_, violation)) => Err(violation),
+            None => Ok(()),
+        }
+    }
+
+    /// Describe the values matched by a schema, e.g. "a string"
+    fn describe_schema(&self, schema: &'a Schema) -> Option<String> {
+        let Schema::Object(schema) = schema else {
+            return None;
+        };
+        let schema = self.resolve(schema)?;
+        if let Some(enum_values) = &schema.enum_values {
+            return Some(enum_values.iter().join(" or "));
+        }
+        schema.instance_type.as_ref().map(describe_type)
+    }
+}
+
+fn matches_type(instance_type: &SingleOrVec<InstanceType>, value: &Value) -> bool {
+    let matches = |instance_type: &InstanceType| match instance_type {
+        InstanceType::Null => value.is_null(),
+        InstanceType::Boolean => value.is_boolean(),
+        InstanceType::Object => value.is_object(),
+        InstanceType::Array => value.is_array(),
+        InstanceType::Number => value.is_number(),
+        InstanceType::String => value.is_string(),
+        InstanceType::Integer => value.is_i64() || value.is_u64(),
+    };
+    match instance_type {
+        SingleOrVec::Single(instance_type) => matches(instance_type),
+        SingleOrVec::Vec(instance_types) => instance_types.iter().any(matches),
+    }
+}
+
+fn describe_type(instance_type: &SingleOrVec<InstanceType>) -> String {
+    let describe = |instance_type: &InstanceType| match instance_type {
+        InstanceType::Null => "nothing",
+        InstanceType::Boolean => "a boolean",
+        InstanceType::Object => "a table",
+        InstanceType::Array => "an array",
+        InstanceType::Number => "a number",
+        InstanceType::String => "a string",
+        InstanceType::Integer => "an integer",
+    };
+    match instance_type {
+        SingleOrVec::Single(instance_type) => describe(instance_type).to_string(),
+        SingleOrVec::Vec(instance_types) => instance_types.iter().map(describe).join(" or "),
+    }
+}
+
+fn describe_value(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "nothing",
+        Value::Bool(_) => "a boolean",
+        Value::Number(number) if number.is_f64() => "a float",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "a table",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn located(manifest: &str) -> toml_edit::de::Error {
+        located_error(manifest, &violation(manifest))
+    }
+
+    fn violation(manifest: &str) -> SchemaViolation {
+        let value: Value = toml::from_str(manifest).unwrap();
+        validate(&value).unwrap_err()
+    }
+
+    #[test]
+    fn valid_manifest_passes() {
+        let value: Value = toml::from_str(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.outputs = "all"
+            ripgrep.flake = "github:BurntSushi/ripgrep"
+            [services.postgres]
+            command = "postgres"
+            [include]
+            environments = [{ dir = "../base" }, { remote = "owner/name" }]
+        "#})
+        .unwrap();
+        validate(&value).unwrap();
+    }
+
+    #[test]
+    fn suggests_misspelled_fields() {
+        let violation = violation(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.verison = "2.12"
+        "#});
+        assert_eq!(
+            violation.to_string(),
+            "unknown field `verison` in `install.hello`, did you mean `version`?"
+        );
+    }
+
+    #[test]
+    fn suggests_misspelled_fields_next_to_systems() {
+        let violation = violation(indoc! {r#"
+            version = 1
+            [hook]
+            on-activat = "echo hello"
+            [hook.aarch64-darwin]
+            on-activate = "echo darwin"
+        "#});
+        assert_eq!(
+            violation.to_string(),
+            "unknown field `on-activat` in `hook`, did you mean `on-activate`?"
+        );
+    }
+
+    #[test]
+    fn reports_path_of_invalid_types() {
+        let violation = violation(indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.version = 2
+        "#});
+        assert_eq!(
+            violation.to_string(),
+            "invalid type for `install.hello.version`: expected a string, found an integer"
+        );
+    }
+
+    #[test]
+    fn reports_array_indices() {
+        let violation = violation(indoc! {r#"
+            version = 1
+            [include]
+            environments = [{ dir = "../base" }, { remote = 1 }]
+        "#});
+        assert_eq!(
+            violation.to_string(),
+            "invalid type for `include.environments[1].remote`: expected a string, found an integer"
+        );
+    }
+
+    #[test]
+    fn located_error_points_at_invalid_value() {
+        let manifest = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.version = 2
+        "#};
+        let err = located(manifest);
+        assert_eq!(
+            err.message(),
+            "invalid type for `install.hello.version`: expected a string, found an integer"
+        );
+        assert_eq!(&manifest[err.span().unwrap()], "2");
+        assert!(err.to_string().contains("line 4, column 17"), "{err}");
+    }
+
+    #[test]
+    fn located_error_points_at_unknown_field() {
+        let manifest = indoc! {r#"
+            version = 1
+            [install]
+            hello.pkg-path = "hello"
+            hello.verison = "2.12"
+        "#};
+        let err = located(manifest);
+        assert_eq!(
+            err.message(),
+            "unknown field `verison` in `install.hello`, did you mean `version`?"
+        );
+        assert_eq!(&manifest[err.span().unwrap()], r#""2.12""#);
+    }
+
+    #[test]
+    fn located_error_points_at_array_elements() {
+        let manifest = indoc! {r#"
+            version = 1
+            [include]
+            environments = [{ dir = "../base" }, { remote = 1 }]
+        "#};
+        let err = located(manifest);
+        assert_eq!(&manifest[err.span().unwrap()], "1");
+    }
+}
@@ -0,0 +1,366 @@
+//! Migrating manifests to the latest schema version
+//!
+//! Every change to the manifest schema that requires existing manifests
+//! to be rewritten registers a [ManifestMigration] in [MANIFEST_MIGRATIONS].
+//! A migration transforms a [RawManifest] of version `from` into a manifest
+//! of version `from + 1` using [toml_edit],
+//! so that comments and formatting of the user's manifest are preserved.
+//! [migrate_manifest] runs all pending migrations in sequence.
+//!
+//! Lockfiles are not migrated in place,
+//! as their contents depend on the resolution of the manifest.
+//! Instead, a lockfile older than [LATEST_LOCKFILE_VERSION] is upgraded
+//! by locking the (migrated) manifest again.
+
+use std::str::FromStr;
+
+use similar::TextDiff;
+use thiserror::Error;
+
+use super::lockfile::LockedManifest;
+use super::manifest::{RawManifest, MANIFEST_VERSION_KEY};
+
+/// The version of the manifest schema written by this version of flox
+pub const LATEST_MANIFEST_VERSION: u8 = 1;
+
+/// The version of the lockfile written by this version of flox
+pub const LATEST_LOCKFILE_VERSION: u8 = 1;
+
+/// A transformation of a manifest from version `from` to version `from + 1`
+#[derive(Debug)]
+pub struct ManifestMigration {
+    /// The version of the manifests this migration applies to
+    pub from: u8,
+    /// A short description of the changes made by the migration
+    pub description: &'static str,
+    migrate: fn(&mut RawManifest),
+}
+
+impl ManifestMigration {
+    /// The version of the manifest after running the migration
+    pub fn to(&self) -> u8 {
+        self.from + 1
+    }
+}
+
+/// All manifest migrations, ordered by the version they apply to
+pub const MANIFEST_MIGRATIONS: &[ManifestMigration] = &[ManifestMigration {
+    from: 0,
+    description: "rename `hook.script` to `hook.on-activate`",
+    migrate: migrate_v0_to_v1,
+}];
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("manifest version {0} is not supported by this version of flox")]
+    UnsupportedVersion(i64),
+    #[error("no migration from manifest version {0}")]
+    MissingMigration(u8),
+    #[error("migrated manifest is invalid")]
+    InvalidManifest(#[source] toml_edit::de::Error),
+}
+
+/// The result of migrating a manifest without writing it
+#[derive(Debug)]
+pub struct MigrationPreview {
+    /// The migrations that would be run
+    pub migrations: Vec<&'static ManifestMigration>,
+    /// The migrated manifest
+    pub migrated: RawManifest,
+    /// A unified diff of the original and migrated manifest
+    pub diff: String,
+}
+
+/// Get the schema version of a manifest.
+///
+/// Manifests without a `version` field predate versioning
+/// and are treated as version 0.
+pub fn manifest_version(raw_manifest: &RawManifest) -> Result<u8, MigrationError> {
+    match raw_manifest.get_version() {
+        None => Ok(0),
+        Some(version) => u8::try_from(version)
+            .ok()
+            .filter(|version| *version <= LATEST_MANIFEST_VERSION)
+            .ok_or(MigrationError::UnsupportedVersion(version)),
+    }
+}
+
+/// The migrations that need to run to bring `raw_manifest` to
+/// [LATEST_MANIFEST_VERSION], in the order they need to run.
+pub fn pending_manifest_migrations(
+    raw_manifest: &RawManifest,
+) -> Result<Vec<&'static ManifestMigration>, MigrationError> {
+    (manifest_version(raw_manifest)?..LATEST_MANIFEST_VERSION)
+        .map(|version| {
+            MANIFEST_MIGRATIONS
+                .iter()
+                .find(|migration| migration.from == version)
+                .ok_or(MigrationError::MissingMigration(version))
+        })
+        .collect()
+}
+
+/// Migrate `raw_manifest` to [LATEST_MANIFEST_VERSION]
+/// and return the migrations that were run.
+///
+/// Return an error if the resulting manifest is not a valid manifest.
+/// Note that the modifications are still made even if an error is returned
+/// to allow callers to present the invalid manifest to the user.
+pub fn migrate_manifest(
+    raw_manifest: &mut RawManifest,
+) -> Result<Vec<&'static ManifestMigration>, MigrationError> {
+    let migrations = pending_manifest_migrations(raw_manifest)?;
+    for migration in &migrations {
+        (migration.migrate)(raw_manifest);
+        raw_manifest.insert(
+            MANIFEST_VERSION_KEY,
+            toml_edit::value(migration.to() as i64),
+        );
+    }
+
+    // Make sure it parses
+    raw_manifest
+        .to_typed()
+        .map_err(MigrationError::InvalidManifest)?;
+    Ok(migrations)
+}
+
+/// Migrate a copy of `raw_manifest` to [LATEST_MANIFEST_VERSION]
+/// and show the changes that would be made.
+pub fn preview_manifest_migration(
+    raw_manifest: &RawManifest,
+) -> Result<MigrationPreview, MigrationError> {
+    let original = raw_manifest.to_string();
+    // Round trip through a string rather than cloning the document,
+    // so that the preview is not affected by unrendered modifications.
+    let mut migrated =
+        RawManifest::from_str(&original).expect("rendered manifest should parse as a manifest");
+    let migrations = migrate_manifest(&mut migrated)?;
+
+    let migrated_contents = migrated.to_string();
+    let diff = TextDiff::from_lines(&original, &migrated_contents)
+        .unified_diff()
+        .header("manifest.toml", "manifest.toml (migrated)")
+        .to_string();
+
+    Ok(MigrationPreview {
+        migrations,
+        migrated,
+        diff,
+    })
+}
+
+/// Whether a lockfile was written by an older version of flox
+/// and needs to be upgraded by locking the manifest again.
+pub fn lockfile_needs_upgrade(lockfile: &LockedManifest) -> bool {
+    lockfile.version() < LATEST_LOCKFILE_VERSION
+}
+
+/// Move `hook.script` to `hook.on-activate` if `hook.on-activate` doesn't
+/// already exist.
+fn migrate_v0_to_v1(raw_manifest: &mut RawManifest) {
+    let hook = raw_manifest.get_mut("hook").and_then(|s| s.as_table_mut());
+    if let Some(hook) = hook {
+        if hook.get("on-activate").is_none() {
+            // Rename `hook.script` to `hook.on-activate`, preserving
+            // comments and formatting
+            if let Some((script_key, script_item)) = hook.remove_entry("script") {
+                // Unit tests cover this is safe to unwrap
+                let mut on_activate = toml_edit::Key::from_str("on-activate").unwrap();
+                let mut on_activate_key = on_activate.as_mut();
+                let decor = on_activate_key.leaf_decor_mut();
+                *decor = script_key.leaf_decor().clone();
+                let dotted_decor = on_activate_key.dotted_decor_mut();
+                *dotted_decor = script_key.dotted_decor().clone();
+                // Does not preserve order of hooks,
+                // but we only have one field in the hook section.
+                hook.insert_formatted(&on_activate, script_item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::formatdoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn raw_manifest(contents: &str) -> RawManifest {
+        RawManifest::from_str(contents).unwrap()
+    }
+
+    /// Every manifest version below the latest has exactly one migration,
+    /// so that manifests of any version can be migrated to the latest.
+    #[test]
+    fn migrations_are_contiguous() {
+        let versions: Vec<u8> = MANIFEST_MIGRATIONS.iter().map(|m| m.from).collect();
+        let expected: Vec<u8> = (0..LATEST_MANIFEST_VERSION).collect();
+        assert_eq!(versions, expected);
+    }
+
+    #[test]
+    fn manifest_version_defaults_to_0() {
+        assert_eq!(manifest_version(&raw_manifest("")).unwrap(), 0);
+        assert_eq!(manifest_version(&raw_manifest("version = 1")).unwrap(), 1);
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        // Parsing already rejects unsupported versions,
+        // so set them on a parsed manifest.
+        let mut manifest = raw_manifest("version = 1");
+        manifest.insert(MANIFEST_VERSION_KEY, toml_edit::value(2));
+        let err = migrate_manifest(&mut manifest).unwrap_err();
+        assert!(matches!(err, MigrationError::UnsupportedVersion(2)));
+
+        manifest.insert(MANIFEST_VERSION_KEY, toml_edit::value(-1));
+        let err = manifest_version(&manifest).unwrap_err();
+        assert!(matches!(err, MigrationError::UnsupportedVersion(-1)));
+    }
+
+    #[test]
+    fn latest_manifest_is_not_modified() {
+        let contents = formatdoc! {r#"
+            version = 1
+            [hook]
+            on-activate = "echo hello"
+            "#};
+        let mut manifest = raw_manifest(&contents);
+        let migrations = migrate_manifest(&mut manifest).unwrap();
+        assert!(migrations.is_empty());
+        assert_eq!(manifest.to_string(), contents);
+    }
+
+    /// [migrate_v0_to_v1] migrates a manifest with `script` in a `[hook]`
+    /// table correctly, maintaining comments and formatting.
+    #[test]
+    fn migrate_script_hook_table() {
+        let contents = formatdoc! {r#"
+            [vars]
+            foo = "bar"
+
+            # comment 1
+            [hook] # comment 2
+            # comment 3
+             script = "echo hello" # comment 4
+            # comment 5
+
+            [options]
+            "#};
+        let mut manifest = raw_manifest(&contents);
+        migrate_manifest(&mut manifest).unwrap();
+        assert_eq!(manifest.to_string(), formatdoc! {r#"
+                version = 1
+                [vars]
+                foo = "bar"
+
+                # comment 1
+                [hook] # comment 2
+                # comment 3
+                 on-activate = "echo hello" # comment 4
+                # comment 5
+
+                [options]
+                "#
+        });
+    }
+
+    /// [migrate_v0_to_v1] migrates a manifest with hook.script as a dotted
+    /// key correctly, maintaining comments and formatting.
+    #[test]
+    fn migrate_script_hook_dotted_decor() {
+        let contents = formatdoc! {r#"
+            vars.foo = "bar"
+
+            # comment 1
+            hook . script = "echo hello" # comment 2
+            # comment 3
+
+            options.allow.unfree = false
+            "#};
+        let mut manifest = raw_manifest(&contents);
+        migrate_manifest(&mut manifest).unwrap();
+        assert_eq!(manifest.to_string(), formatdoc! {r#"
+                vars.foo = "bar"
+
+                # comment 1
+                hook . on-activate = "echo hello" # comment 2
+                # comment 3
+
+                options.allow.unfree = false
+                version = 1
+                "#
+        });
+    }
+
+    /// If a manifest contains both `hook.script` and `hook.on-activate`,
+    /// [migrate_manifest] returns an error.
+    #[test]
+    fn migrate_script_skip_for_on_activate() {
+        let contents = formatdoc! {r#"
+            [hook]
+            script = "echo foo"
+            on-activate = "echo bar"
+            "#};
+        let mut manifest = raw_manifest(&contents);
+        let err = migrate_manifest(&mut manifest).unwrap_err();
+        assert_eq!(manifest.to_string(), formatdoc! {r#"
+                version = 1
+                [hook]
+                script = "echo foo"
+                on-activate = "echo bar"
+                "#
+        });
+        if let MigrationError::InvalidManifest(e) = err {
+            assert!(e.message().contains("unknown field `script`"));
+        } else {
+            panic!("expected InvalidManifest error");
+        }
+    }
+
+    /// Even if a manifest fails validation, it is still modified by
+    /// [migrate_manifest].
+    #[test]
+    fn migrate_script_modifies_on_error() {
+        let contents = formatdoc! {r#"
+            [hook]
+            script = "echo hello"
+            on-activate = "echo hello"
+            "#};
+        let mut manifest = raw_manifest(&contents);
+        assert!(manifest.get("version").is_none());
+        migrate_manifest(&mut manifest).unwrap_err();
+        assert_eq!(manifest.get("version").unwrap().as_integer().unwrap(), 1);
+    }
+
+    #[test]
+    fn preview_shows_diff_without_modifying_manifest() {
+        let contents = formatdoc! {r#"
+            [hook]
+            script = "echo hello"
+            "#};
+        let manifest = raw_manifest(&contents);
+        let preview = preview_manifest_migration(&manifest).unwrap();
+
+        assert_eq!(manifest.to_string(), contents);
+        assert_eq!(
+            preview
+                .migrations
+                .iter()
+                .map(|m| m.from)
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(preview.diff, formatdoc! {r#"
+            --- manifest.toml
+            +++ manifest.toml (migrated)
+            @@ -1,2 +1,3 @@
+            +version = 1
+             [hook]
+            -script = "echo hello"
+            +on-activate = "echo hello"
+            "#});
+    }
+}
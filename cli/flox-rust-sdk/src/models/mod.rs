@@ -1,10 +1,18 @@
 //# An attempt at defining a domain model for flox
+pub mod audit;
 pub mod container_builder;
 pub mod env_registry;
 pub mod environment;
+pub mod environment_diff;
 pub mod environment_ref;
+pub mod flake_export;
 pub mod floxmeta;
+pub mod lint;
 pub mod lockfile;
 pub mod manifest;
+pub mod manifest_schema;
+pub mod migration;
 pub mod pkgdb;
+pub mod sbom;
 pub mod search;
+pub mod secrets;
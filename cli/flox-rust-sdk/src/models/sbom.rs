@@ -0,0 +1,543 @@
+//! Software bills of materials (SBOMs) for locked environments
+//!
+//! Lists the packages locked for an environment in the
+//! [CycloneDX 1.5](https://cyclonedx.org/docs/1.5/json/) or
+//! [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/) JSON formats.
+//! Every package is listed once per system it is locked for,
+//! identified by its derivation and the locked nixpkgs or flake URL
+//! it is built from.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::lockfile::{LockedManifestCatalog, LockedPackage};
+use crate::data::System;
+
+/// The format of an SBOM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    CycloneDx,
+    Spdx,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown SBOM format '{0}', expected 'cyclonedx' or 'spdx'")]
+pub struct UnknownSbomFormat(String);
+
+impl FromStr for SbomFormat {
+    type Err = UnknownSbomFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cyclonedx" => Ok(SbomFormat::CycloneDx),
+            "spdx" => Ok(SbomFormat::Spdx),
+            _ => Err(UnknownSbomFormat(s.to_string())),
+        }
+    }
+}
+
+impl Display for SbomFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SbomFormat::CycloneDx => write!(f, "cyclonedx"),
+            SbomFormat::Spdx => write!(f, "spdx"),
+        }
+    }
+}
+
+/// Information about the SBOM document itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct SbomDocument {
+    /// The name of the environment the SBOM describes
+    pub name: String,
+    /// The version of flox generating the SBOM
+    pub tool_version: String,
+    pub timestamp: DateTime<Utc>,
+    /// Unique identifier of the document
+    pub serial_number: Uuid,
+}
+
+impl SbomDocument {
+    /// Describe a new SBOM document for the environment `name`
+    pub fn new(name: impl ToString, tool_version: impl ToString) -> Self {
+        SbomDocument {
+            name: name.to_string(),
+            tool_version: tool_version.to_string(),
+            timestamp: Utc::now(),
+            serial_number: Uuid::new_v4(),
+        }
+    }
+}
+
+/// A locked package as listed in an SBOM
+#[derive(Debug, Clone, PartialEq)]
+struct Component<'a> {
+    install_id: &'a str,
+    system: &'a System,
+    name: &'a str,
+    version: Option<&'a str>,
+    description: Option<&'a str>,
+    licenses: Vec<&'a str>,
+    /// The derivation or, for store paths, the store path of the package
+    derivation: &'a str,
+    /// The locked nixpkgs or flake URL the package is built from
+    locked_url: Option<&'a str>,
+    attr_path: Option<&'a str>,
+}
+
+impl<'a> Component<'a> {
+    fn new(package: &'a LockedPackage) -> Self {
+        match package {
+            LockedPackage::Catalog(pkg) => Component {
+                install_id: &pkg.install_id,
+                system: &pkg.system,
+                name: &pkg.pname,
+                version: Some(&pkg.version),
+                description: pkg.description.as_deref(),
+                licenses: pkg.license.as_deref().into_iter().collect(),
+                derivation: &pkg.derivation,
+                locked_url: Some(&pkg.locked_url),
+                attr_path: Some(&pkg.attr_path),
+            },
+            LockedPackage::Flake(pkg) => {
+                let installable = &pkg.locked_installable;
+                Component {
+                    install_id: &pkg.install_id,
+                    system: &installable.system,
+                    name: installable.pname.as_deref().unwrap_or(&installable.name),
+                    version: installable.version.as_deref(),
+                    description: installable.description.as_deref(),
+                    licenses: installable
+                        .licenses
+                        .iter()
+                        .flatten()
+                        .map(String::as_str)
+                        .collect(),
+                    derivation: &installable.derivation,
+                    locked_url: Some(&installable.locked_url),
+                    attr_path: Some(&installable.locked_flake_attr_path),
+                }
+            },
+            LockedPackage::StorePath(pkg) => Component {
+                install_id: &pkg.install_id,
+                system: &pkg.system,
+                name: &pkg.install_id,
+                version: None,
+                description: None,
+                licenses: vec![],
+                derivation: &pkg.store_path,
+                locked_url: None,
+                attr_path: None,
+            },
+        }
+    }
+
+    /// A reference to the component that is unique within the document
+    fn reference(&self) -> String {
+        format!("{}/{}", self.system, self.install_id)
+    }
+}
+
+/// Collect the components for `systems` in a stable order,
+/// or for all systems if `systems` is empty.
+fn components<'a>(lockfile: &'a LockedManifestCatalog, systems: &[System]) -> Vec<Component<'a>> {
+    let mut components = lockfile
+        .packages
+        .iter()
+        .map(Component::new)
+        .filter(|component| systems.is_empty() || systems.contains(component.system))
+        .collect::<Vec<_>>();
+    components.sort_by(|a, b| (a.system, a.install_id).cmp(&(b.system, b.install_id)));
+    components
+}
+
+/// Generate an SBOM for the packages in `lockfile`
+/// that are locked for any of `systems`, or for all systems if empty.
+pub fn generate_sbom(
+    lockfile: &LockedManifestCatalog,
+    systems: &[System],
+    format: SbomFormat,
+    document: &SbomDocument,
+) -> Value {
+    let components = components(lockfile, systems);
+    match format {
+        SbomFormat::CycloneDx => cyclonedx(&components, document),
+        SbomFormat::Spdx => spdx(&components, document),
+    }
+}
+
+fn timestamp(document: &SbomDocument) -> String {
+    document
+        .timestamp
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn cyclonedx(components: &[Component], document: &SbomDocument) -> Value {
+    let bom_components = components
+        .iter()
+        .map(|component| {
+            let mut properties = vec![
+                json!({ "name": "flox:install_id", "value": component.install_id }),
+                json!({ "name": "nix:system", "value": component.system }),
+                json!({ "name": "nix:derivation", "value": component.derivation }),
+            ];
+            if let Some(attr_path) = component.attr_path {
+                properties.push(json!({ "name": "nix:attr_path", "value": attr_path }));
+            }
+
+            let mut bom_component = json!({
+                "type": "application",
+                "bom-ref": component.reference(),
+                "name": component.name,
+                "properties": properties,
+            });
+            if let Some(version) = component.version {
+                bom_component["version"] = json!(version);
+            }
+            if let Some(description) = component.description {
+                bom_component["description"] = json!(description);
+            }
+            if !component.licenses.is_empty() {
+                bom_component["licenses"] = component
+                    .licenses
+                    .iter()
+                    .map(|license| json!({ "license": { "name": license } }))
+                    .collect();
+            }
+            if let Some(locked_url) = component.locked_url {
+                bom_component["externalReferences"] = json!([{ "type": "vcs", "url": locked_url }]);
+            }
+            bom_component
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", document.serial_number),
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp(document),
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "flox",
+                    "version": document.tool_version,
+                }],
+            },
+            "component": {
+                "type": "application",
+                "bom-ref": document.name,
+                "name": document.name,
+            },
+        },
+        "components": bom_components,
+        "dependencies": [{
+            "ref": document.name,
+            "dependsOn": components.iter().map(Component::reference).collect::<Vec<_>>(),
+        }],
+    })
+}
+
+/// SPDX identifiers may only contain letters, numbers, `.` and `-`.
+///
+/// Replacing other characters can map different references to the same id
+/// (e.g. `foo_bar` and `foo-bar`),
+/// so the position of the component in the document is part of the id.
+fn spdx_id(index: usize, component: &Component) -> String {
+    let reference: String = component
+        .reference()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-Package-{index}-{reference}")
+}
+
+/// Licenses reported by the catalog are usually SPDX license identifiers,
+/// anything else can't be declared in an SPDX document.
+fn spdx_license(licenses: &[&str]) -> String {
+    let is_spdx_id = |license: &&str| {
+        !license.is_empty()
+            && license
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+    };
+    if licenses.is_empty() || !licenses.iter().all(is_spdx_id) {
+        return "NOASSERTION".to_string();
+    }
+    licenses.join(" AND ")
+}
+
+fn spdx(components: &[Component], document: &SbomDocument) -> Value {
+    let ids = components
+        .iter()
+        .enumerate()
+        .map(|(index, component)| spdx_id(index, component))
+        .collect::<Vec<_>>();
+
+    let packages = components
+        .iter()
+        .zip(&ids)
+        .map(|(component, id)| {
+            let mut package = json!({
+                "SPDXID": id,
+                "name": component.name,
+                "downloadLocation": component.locked_url.unwrap_or("NOASSERTION"),
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": spdx_license(&component.licenses),
+                "copyrightText": "NOASSERTION",
+                "sourceInfo": format!(
+                    "installed as '{}' for {} from {}",
+                    component.install_id, component.system, component.derivation
+                ),
+            });
+            if let Some(version) = component.version {
+                package["versionInfo"] = json!(version);
+            }
+            if let Some(description) = component.description {
+                package["description"] = json!(description);
+            }
+            package
+        })
+        .collect::<Vec<_>>();
+
+    let relationships = ids
+        .iter()
+        .map(|id| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": id,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": document.name,
+        "documentNamespace": format!(
+            "https://flox.dev/spdxdocs/{}-{}",
+            document.name, document.serial_number
+        ),
+        "creationInfo": {
+            "created": timestamp(document),
+            "creators": [format!("Tool: flox-{}", document.tool_version)],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::models::lockfile::test_helpers::{
+        fake_catalog_package_lock,
+        fake_flake_installable_lock,
+    };
+
+    fn document() -> SbomDocument {
+        SbomDocument {
+            name: "myenv".to_string(),
+            tool_version: "1.3.0".to_string(),
+            timestamp: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            serial_number: Uuid::nil(),
+        }
+    }
+
+    fn lockfile() -> LockedManifestCatalog {
+        let (_, _, mut hello) = fake_catalog_package_lock("hello", None);
+        hello.install_id = "hello".to_string();
+        hello.version = "2.12.1".to_string();
+        hello.license = Some("GPL-3.0-or-later".to_string());
+        hello.derivation = "/nix/store/hello.drv".to_string();
+        hello.locked_url = "https://github.com/flox/nixpkgs?rev=abc".to_string();
+        let mut hello_linux = hello.clone();
+        hello_linux.system = "x86_64-linux".to_string();
+
+        let (_, _, mut flake) = fake_flake_installable_lock("tool");
+        flake.install_id = "tool".to_string();
+        flake.locked_installable.system = "x86_64-linux".to_string();
+        flake.locked_installable.licenses = Some(vec!["Custom license".to_string()]);
+
+        LockedManifestCatalog {
+            packages: vec![hello_linux.into(), flake.into(), hello.into()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sbom_format_round_trips() {
+        for format in [SbomFormat::CycloneDx, SbomFormat::Spdx] {
+            assert_eq!(format.to_string().parse::<SbomFormat>().unwrap(), format);
+        }
+        assert!("swid".parse::<SbomFormat>().is_err());
+    }
+
+    #[test]
+    fn components_are_filtered_by_system_and_sorted() {
+        let lockfile = lockfile();
+        let references = |systems: &[System]| {
+            components(&lockfile, systems)
+                .iter()
+                .map(Component::reference)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(references(&[]), vec![
+            "aarch64-darwin/hello",
+            "x86_64-linux/hello",
+            "x86_64-linux/tool",
+        ]);
+        assert_eq!(references(&["aarch64-darwin".to_string()]), vec![
+            "aarch64-darwin/hello"
+        ]);
+    }
+
+    #[test]
+    fn cyclonedx_lists_components() {
+        let sbom = generate_sbom(
+            &lockfile(),
+            &["aarch64-darwin".to_string()],
+            SbomFormat::CycloneDx,
+            &document(),
+        );
+        assert_eq!(
+            sbom,
+            json!({
+                "bomFormat": "CycloneDX",
+                "specVersion": "1.5",
+                "serialNumber": "urn:uuid:00000000-0000-0000-0000-000000000000",
+                "version": 1,
+                "metadata": {
+                    "timestamp": "2024-01-01T00:00:00Z",
+                    "tools": {
+                        "components": [{
+                            "type": "application",
+                            "name": "flox",
+                            "version": "1.3.0",
+                        }],
+                    },
+                    "component": {
+                        "type": "application",
+                        "bom-ref": "myenv",
+                        "name": "myenv",
+                    },
+                },
+                "components": [{
+                    "type": "application",
+                    "bom-ref": "aarch64-darwin/hello",
+                    "name": "hello",
+                    "version": "2.12.1",
+                    "licenses": [{ "license": { "name": "GPL-3.0-or-later" } }],
+                    "externalReferences": [{
+                        "type": "vcs",
+                        "url": "https://github.com/flox/nixpkgs?rev=abc",
+                    }],
+                    "properties": [
+                        { "name": "flox:install_id", "value": "hello" },
+                        { "name": "nix:system", "value": "aarch64-darwin" },
+                        { "name": "nix:derivation", "value": "/nix/store/hello.drv" },
+                        { "name": "nix:attr_path", "value": "hello" },
+                    ],
+                }],
+                "dependencies": [{
+                    "ref": "myenv",
+                    "dependsOn": ["aarch64-darwin/hello"],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn spdx_lists_packages() {
+        let sbom = generate_sbom(
+            &lockfile(),
+            &["x86_64-linux".to_string()],
+            SbomFormat::Spdx,
+            &document(),
+        );
+
+        assert_eq!(sbom["spdxVersion"], "SPDX-2.3");
+        assert_eq!(
+            sbom["documentNamespace"],
+            "https://flox.dev/spdxdocs/myenv-00000000-0000-0000-0000-000000000000"
+        );
+        assert_eq!(
+            sbom["creationInfo"]["creators"],
+            json!(["Tool: flox-1.3.0"])
+        );
+
+        let packages = sbom["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            packages[0]["SPDXID"],
+            "SPDXRef-Package-0-x86-64-linux-hello"
+        );
+        assert_eq!(packages[0]["versionInfo"], "2.12.1");
+        assert_eq!(packages[0]["licenseDeclared"], "GPL-3.0-or-later");
+        assert_eq!(
+            packages[0]["downloadLocation"],
+            "https://github.com/flox/nixpkgs?rev=abc"
+        );
+        // Licenses that aren't SPDX identifiers can't be declared
+        assert_eq!(packages[1]["SPDXID"], "SPDXRef-Package-1-x86-64-linux-tool");
+        assert_eq!(packages[1]["licenseDeclared"], "NOASSERTION");
+
+        assert_eq!(
+            sbom["relationships"][1],
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": "SPDXRef-Package-1-x86-64-linux-tool",
+            })
+        );
+    }
+
+    #[test]
+    fn spdx_ids_are_unique() {
+        let (_, _, mut underscore) = fake_catalog_package_lock("foo_bar", None);
+        underscore.install_id = "foo_bar".to_string();
+        let mut dash = underscore.clone();
+        dash.install_id = "foo-bar".to_string();
+        let lockfile = LockedManifestCatalog {
+            packages: vec![underscore.into(), dash.into()],
+            ..Default::default()
+        };
+
+        let sbom = generate_sbom(&lockfile, &[], SbomFormat::Spdx, &document());
+
+        let ids = sbom["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|package| package["SPDXID"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+        let related = sbom["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|relationship| relationship["relatedSpdxElement"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(related, ids);
+    }
+}
@@ -0,0 +1,306 @@
+//! Resolution of secret variables
+//!
+//! Variables in `[vars]` can reference a file, a variable in a dotenv file,
+//! or a command whose output is used as the value (see [SecretSource]).
+//! Their values are resolved when an environment is activated
+//! and only ever passed to the activated shell or command,
+//! so they are never written to the lockfile, a generation or the built environment.
+//!
+//! Errors intentionally don't include values, command lines or command output,
+//! as any of them may contain secrets.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+use thiserror::Error;
+use tracing::debug;
+
+use super::manifest::{ManifestVariables, SecretSource};
+
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("failed to read secret variable '{name}' from '{}'", .path.display())]
+    ReadFile {
+        name: String,
+        path: PathBuf,
+        #[source]
+        err: io::Error,
+    },
+
+    #[error(
+        "failed to read secret variable '{name}' from '{}': line {line} is not a valid assignment",
+        .path.display()
+    )]
+    ParseDotenv {
+        name: String,
+        path: PathBuf,
+        line: usize,
+    },
+
+    #[error("failed to read secret variable '{name}': '{key}' is not set in '{}'", .path.display())]
+    MissingDotenvKey {
+        name: String,
+        key: String,
+        path: PathBuf,
+    },
+
+    #[error("failed to run the command of secret variable '{name}'")]
+    SpawnCommand {
+        name: String,
+        #[source]
+        err: io::Error,
+    },
+
+    #[error("the command of secret variable '{name}' failed ({status})")]
+    CommandFailed { name: String, status: ExitStatus },
+
+    #[error("the command of secret variable '{name}' did not print valid UTF-8")]
+    InvalidOutput { name: String },
+}
+
+/// Resolve the values of all secret variables in `vars`
+///
+/// Relative paths are resolved against `base_directory`,
+/// which is also the working directory of commands.
+pub fn resolve_secrets(
+    vars: &ManifestVariables,
+    base_directory: &Path,
+) -> Result<Vec<(String, String)>, SecretError> {
+    vars.secrets()
+        .map(|(name, source)| {
+            debug!(name, "resolving secret variable");
+            let value = resolve_secret(name, source, base_directory)?;
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+fn resolve_secret(
+    name: &str,
+    source: &SecretSource,
+    base_directory: &Path,
+) -> Result<String, SecretError> {
+    match source {
+        SecretSource::File(file) => {
+            let path = resolve_path(&file.file, base_directory);
+            let contents = read_file(name, &path)?;
+            Ok(trim_trailing_newlines(contents))
+        },
+        SecretSource::Dotenv(dotenv) => {
+            let path = resolve_path(&dotenv.dotenv, base_directory);
+            let key = dotenv.key.as_deref().unwrap_or(name);
+            let contents = read_file(name, &path)?;
+            match parse_dotenv(&contents) {
+                Ok(entries) => entries
+                    .into_iter()
+                    .rev()
+                    .find(|(entry_key, _)| entry_key == key)
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| SecretError::MissingDotenvKey {
+                        name: name.to_string(),
+                        key: key.to_string(),
+                        path,
+                    }),
+                Err(line) => Err(SecretError::ParseDotenv {
+                    name: name.to_string(),
+                    path,
+                    line,
+                }),
+            }
+        },
+        SecretSource::Command(command) => {
+            // Inherit stdin and stderr so that password managers can prompt for credentials
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(&command.command)
+                .current_dir(base_directory)
+                .stdin(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .output()
+                .map_err(|err| SecretError::SpawnCommand {
+                    name: name.to_string(),
+                    err,
+                })?;
+            if !output.status.success() {
+                return Err(SecretError::CommandFailed {
+                    name: name.to_string(),
+                    status: output.status,
+                });
+            }
+            let stdout =
+                String::from_utf8(output.stdout).map_err(|_| SecretError::InvalidOutput {
+                    name: name.to_string(),
+                })?;
+            Ok(trim_trailing_newlines(stdout))
+        },
+    }
+}
+
+fn read_file(name: &str, path: &Path) -> Result<String, SecretError> {
+    std::fs::read_to_string(path).map_err(|err| SecretError::ReadFile {
+        name: name.to_string(),
+        path: path.to_path_buf(),
+        err,
+    })
+}
+
+/// Expand `~` and resolve relative paths against `base_directory`
+fn resolve_path(path: &Path, base_directory: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Some(home) = std::env::var_os("HOME") {
+            return Path::new(&home).join(rest);
+        }
+    }
+    base_directory.join(path)
+}
+
+/// Like command substitution in shells, ignore trailing newlines
+fn trim_trailing_newlines(mut value: String) -> String {
+    let trimmed_len = value.trim_end_matches(['\n', '\r']).len();
+    value.truncate(trimmed_len);
+    value
+}
+
+/// Parse the `KEY=VALUE` assignments of a dotenv file
+///
+/// Supports comments, empty lines, `export` prefixes,
+/// and single or double quoted values.
+/// Returns the (1-based) number of the first invalid line on error.
+fn parse_dotenv(contents: &str) -> Result<Vec<(String, String)>, usize> {
+    let mut entries = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(index + 1);
+        };
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(index + 1);
+        }
+
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            quoted.strip_suffix('\'').ok_or(index + 1)?.to_string()
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            unescape_double_quoted(quoted.strip_suffix('"').ok_or(index + 1)?)
+        } else {
+            // unquoted values end at an inline comment
+            match value.split_once(" #") {
+                Some((value, _comment)) => value.trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+        entries.push((key.to_string(), value));
+    }
+    Ok(entries)
+}
+
+fn unescape_double_quoted(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::models::manifest::TypedManifestCatalog;
+
+    fn parse_vars(manifest: &str) -> ManifestVariables {
+        toml::from_str::<TypedManifestCatalog>(manifest)
+            .unwrap()
+            .vars
+    }
+
+    #[test]
+    fn parses_dotenv_files() {
+        let entries = parse_dotenv(indoc! {r#"
+            # database
+            DB_USER=admin
+            export DB_PASSWORD = "s3cr\"et\n"
+            DB_HOST='localhost'
+            DB_PORT=5432 # default port
+        "#})
+        .unwrap();
+        assert_eq!(entries, vec![
+            ("DB_USER".to_string(), "admin".to_string()),
+            ("DB_PASSWORD".to_string(), "s3cr\"et\n".to_string()),
+            ("DB_HOST".to_string(), "localhost".to_string()),
+            ("DB_PORT".to_string(), "5432".to_string()),
+        ]);
+
+        assert_eq!(parse_dotenv("FOO=bar\nnot an assignment\n"), Err(2));
+    }
+
+    #[test]
+    fn resolves_secrets_from_all_sources() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("token"), "file-secret\n").unwrap();
+        std::fs::write(tempdir.path().join(".env"), "API_KEY=dotenv-secret\n").unwrap();
+
+        let vars = parse_vars(indoc! {r#"
+            version = 1
+            [vars]
+            STATIC = "not a secret"
+            FROM_FILE = { file = "token" }
+            API_KEY = { dotenv = ".env" }
+            RENAMED = { dotenv = ".env", key = "API_KEY" }
+            FROM_COMMAND = { command = "printf 'command-secret\n\n'" }
+        "#});
+
+        let secrets = resolve_secrets(&vars, tempdir.path()).unwrap();
+        assert_eq!(secrets, vec![
+            ("API_KEY".to_string(), "dotenv-secret".to_string()),
+            ("FROM_COMMAND".to_string(), "command-secret".to_string()),
+            ("FROM_FILE".to_string(), "file-secret".to_string()),
+            ("RENAMED".to_string(), "dotenv-secret".to_string()),
+        ]);
+        assert_eq!(vars.static_values().into_iter().collect::<Vec<_>>(), vec![
+            ("STATIC".to_string(), "not a secret".to_string())
+        ]);
+    }
+
+    #[test]
+    fn errors_do_not_include_secrets() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join(".env"), "OTHER=hunter2\n").unwrap();
+
+        let vars = parse_vars(indoc! {r#"
+            version = 1
+            [vars]
+            TOKEN = { command = "echo hunter2; exit 3" }
+        "#});
+        let err = resolve_secrets(&vars, tempdir.path()).unwrap_err();
+        assert!(matches!(err, SecretError::CommandFailed { .. }));
+        assert!(!err.to_string().contains("hunter2"), "{err}");
+
+        let vars = parse_vars(indoc! {r#"
+            version = 1
+            [vars]
+            TOKEN = { dotenv = ".env" }
+        "#});
+        let err = resolve_secrets(&vars, tempdir.path()).unwrap_err();
+        assert!(matches!(err, SecretError::MissingDotenvKey { .. }));
+        assert!(!err.to_string().contains("hunter2"), "{err}");
+    }
+}
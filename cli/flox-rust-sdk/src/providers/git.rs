@@ -367,6 +367,24 @@ impl GitCommandProvider {
         }
     }
 
+    /// Return the best common ancestor of two revisions
+    ///
+    /// Returns `None` if the revisions do not share any history.
+    pub fn merge_base(&self, rev1: &str, rev2: &str) -> Result<Option<String>, GitCommandError> {
+        let result = GitCommandProvider::run_command(
+            self.new_command().arg("merge-base").arg(rev1).arg(rev2),
+        );
+        match result {
+            Ok(hash) => Ok(Some(hash.to_string_lossy().trim().to_string())),
+            Err(GitCommandError::BadExit(1, stdout, stderr))
+                if stdout.is_empty() && stderr.is_empty() =>
+            {
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
     /// Create branch at a specified revision
     pub fn create_branch(&self, name: &str, rev: &str) -> Result<(), GitCommandError> {
         GitCommandProvider::run_command(self.new_command().arg("branch").arg(name).arg(rev))?;
@@ -1224,6 +1242,19 @@ pub mod tests {
         assert!(!repo.has_branch("branch_1").unwrap());
     }
 
+    #[test]
+    fn test_merge_base() {
+        let (repo, _tempdir_handle) = init_temp_repo(false);
+        repo.checkout("branch_1", true).unwrap();
+        commit_file(&repo, "dummy");
+        let hash = repo.branch_hash("branch_1").unwrap();
+
+        repo.create_branch("branch_2", &hash).unwrap();
+        commit_file(&repo, "dummy2");
+
+        assert_eq!(repo.merge_base("branch_1", "branch_2").unwrap(), Some(hash));
+    }
+
     #[test]
     fn test_create_branch() {
         let (repo, _tempdir_handle) = init_temp_repo(false);
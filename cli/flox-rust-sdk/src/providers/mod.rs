@@ -2,4 +2,5 @@ pub mod build;
 pub mod catalog;
 pub mod flox_cpp_utils;
 pub mod git;
+pub mod nix;
 pub mod services;
@@ -0,0 +1,43 @@
+use std::env;
+
+use log::debug;
+use once_cell::sync::Lazy;
+use tokio::process::Command;
+
+use crate::utils::CommandExt;
+
+// This is the `nix` path that we actually use.
+// This is set once and prefers the `NIX_BIN` env variable, but will use
+// the fallback to the binary available at build time if it is unset.
+pub static NIX_BIN: Lazy<String> =
+    Lazy::new(|| env::var("NIX_BIN").unwrap_or(env!("NIX_BIN").to_string()));
+
+/// Whether `store_path` exists locally or can be substituted.
+///
+/// Store paths that don't exist locally are queried with `nix build --dry-run`,
+/// which fails if the path is neither valid nor provided by a substituter.
+pub async fn store_path_is_available(store_path: &str) -> Result<bool, std::io::Error> {
+    if tokio::fs::symlink_metadata(store_path).await.is_ok() {
+        return Ok(true);
+    }
+
+    let mut command = Command::new(&*NIX_BIN);
+    command.args([
+        "--extra-experimental-features",
+        "nix-command",
+        "build",
+        "--dry-run",
+        "--no-link",
+        store_path,
+    ]);
+    debug!("querying store path: {}", command.as_std().display());
+
+    let output = command.output().await?;
+    if !output.status.success() {
+        debug!(
+            "store path '{store_path}' is not available: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(output.status.success())
+}
@@ -191,7 +191,7 @@ impl From<ManifestServices> for ProcessComposeConfig {
             .into_iter()
             .map(|(name, service)| {
                 let command = service.command;
-                let environment = service.vars.map(|vars| vars.0);
+                let environment = service.vars.map(|vars| vars.static_values());
                 (name, ProcessConfig {
                     command,
                     vars: environment,
@@ -0,0 +1,223 @@
+//! Running the `hook.on-deactivate` script of an environment on behalf of
+//! activations that terminated without running it themselves.
+//!
+//! Activations of an environment with a `hook.on-deactivate` script attach to
+//! an activation tracked by `flox-activations` and detach from it once their
+//! shell or command exits.
+//! The process that detaches last removes the activation and runs the script.
+//! Processes that are killed never detach, so during cleanup the watchdog
+//! removes the activations of the environment that no process is attached to
+//! anymore and runs the script for each of them.
+//! Activations are only removed while holding the lock on `activations.json`,
+//! so the script runs at most once per activation,
+//! even if several activations and watchdogs exit at the same time.
+
+use std::path::Path;
+use std::process::Command;
+
+use flox_activations::activations::{self, default_runtime_dir, Activation};
+use flox_rust_sdk::utils::traceable_path;
+use tracing::{debug, error, info};
+
+use crate::Error;
+
+/// Path of the hook script relative to the activated environment
+const HOOK_ON_DEACTIVATE: &str = "activate.d/hook-on-deactivate";
+
+/// Path of the envrc script relative to the activated environment
+const ENVRC: &str = "activate.d/envrc";
+
+/// Run the `hook.on-deactivate` script of the activations of `flox_env`
+/// that no process is attached to anymore.
+pub(crate) fn deactivate(flox_env: &Path) {
+    let runtime_dir = match default_runtime_dir() {
+        Ok(runtime_dir) => runtime_dir,
+        Err(err) => {
+            error!(%err, "failed to determine runtime directory");
+            return;
+        },
+    };
+    deactivate_in(&runtime_dir, flox_env);
+}
+
+fn deactivate_in(runtime_dir: &Path, flox_env: &Path) {
+    let detached = match remove_detached_activations(runtime_dir, flox_env) {
+        Ok(detached) => detached,
+        Err(err) => {
+            error!(%err, "failed to remove detached activations");
+            return;
+        },
+    };
+    if detached.is_empty() {
+        debug!(reason = "no detached activations", "skipped deactivation");
+    }
+    for activation in detached {
+        run_hook(flox_env, Path::new(activation.store_path()));
+    }
+}
+
+/// Remove the activations of `flox_env` that no process is attached to anymore
+///
+/// Activations of environments without a `hook.on-deactivate` script
+/// are not tracked, so there is nothing to remove if activations.json doesn't exist.
+fn remove_detached_activations(
+    runtime_dir: &Path,
+    flox_env: &Path,
+) -> Result<Vec<Activation>, Error> {
+    let activations_json_path = activations::activations_json_path(runtime_dir, flox_env)?;
+    if !activations_json_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let (activations, lock) = activations::read_activations_json(&activations_json_path)?;
+    let Some(mut activations) = activations else {
+        return Ok(Vec::new());
+    };
+
+    let detached = activations.remove_detached_activations();
+    activations::write_activations_json(&activations, &activations_json_path, lock)?;
+    Ok(detached)
+}
+
+/// Run the `hook.on-deactivate` script of the environment built at `store_path`
+///
+/// The script runs in a non-interactive bash shell with the environment's
+/// `envrc` sourced, so it has access to the variables set in `[vars]`.
+/// Secrets and variables set by `hook.on-activate` are not available.
+fn run_hook(flox_env: &Path, store_path: &Path) {
+    let hook = store_path.join(HOOK_ON_DEACTIVATE);
+    if !hook.exists() {
+        debug!(reason = "no hook-on-deactivate", "skipped deactivation");
+        return;
+    }
+
+    info!(hook = traceable_path(&hook), "running hook-on-deactivate");
+    let output = Command::new("bash")
+        .args(["--noprofile", "--norc", "-c"])
+        .arg(r#"[ ! -e "$1" ] || source "$1"; source "$2""#)
+        .arg("hook-on-deactivate")
+        .arg(store_path.join(ENVRC))
+        .arg(&hook)
+        .env("FLOX_ENV", flox_env)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            debug!(
+                stdout = %String::from_utf8_lossy(&output.stdout),
+                stderr = %String::from_utf8_lossy(&output.stderr),
+                "ran hook-on-deactivate"
+            );
+        },
+        Ok(output) => {
+            error!(
+                status = %output.status,
+                stderr = %String::from_utf8_lossy(&output.stderr),
+                "hook-on-deactivate failed"
+            );
+        },
+        Err(err) => error!(%err, "failed to run hook-on-deactivate"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Creates a fake environment with a hook-on-deactivate script
+    /// that appends to `out` in the environment
+    fn env_with_hook() -> TempDir {
+        let flox_env = TempDir::new().unwrap();
+        fs::create_dir_all(flox_env.path().join("activate.d")).unwrap();
+        fs::write(
+            flox_env.path().join(HOOK_ON_DEACTIVATE),
+            r#"echo deactivated >> "$FLOX_ENV/out""#,
+        )
+        .unwrap();
+        flox_env
+    }
+
+    /// PID of a process that has exited
+    fn terminated_pid() -> u32 {
+        let mut child = Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        child.id()
+    }
+
+    /// Start an activation of `flox_env` with `pid` attached to it
+    fn start_activation(runtime_dir: &Path, flox_env: &Path, pid: u32) {
+        let path = activations::activations_json_path(runtime_dir, flox_env).unwrap();
+        let (activations, lock) = activations::read_activations_json(&path).unwrap();
+        let mut activations = activations.unwrap_or_default();
+        activations
+            .create_activation(&flox_env.to_string_lossy(), pid)
+            .unwrap();
+        activations::write_activations_json(&activations, &path, lock).unwrap();
+    }
+
+    fn hook_runs(flox_env: &Path) -> usize {
+        fs::read_to_string(flox_env.join("out"))
+            .map(|out| out.lines().count())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn runs_hook_if_no_process_is_attached() {
+        let flox_env = env_with_hook();
+        let runtime_dir = TempDir::new().unwrap();
+        start_activation(runtime_dir.path(), flox_env.path(), terminated_pid());
+
+        deactivate_in(runtime_dir.path(), flox_env.path());
+        assert_eq!(hook_runs(flox_env.path()), 1);
+
+        // The activation has been removed, so it is only deactivated once
+        deactivate_in(runtime_dir.path(), flox_env.path());
+        assert_eq!(hook_runs(flox_env.path()), 1);
+    }
+
+    #[test]
+    fn skips_hook_if_process_is_attached() {
+        let flox_env = env_with_hook();
+        let runtime_dir = TempDir::new().unwrap();
+        start_activation(runtime_dir.path(), flox_env.path(), std::process::id());
+
+        deactivate_in(runtime_dir.path(), flox_env.path());
+
+        assert_eq!(hook_runs(flox_env.path()), 0);
+    }
+
+    #[test]
+    fn skips_hook_if_activation_detached() {
+        let flox_env = env_with_hook();
+        let runtime_dir = TempDir::new().unwrap();
+
+        // No activation is tracked, e.g. because the last process detached
+        // from it and ran the hook itself
+        deactivate_in(runtime_dir.path(), flox_env.path());
+
+        assert_eq!(hook_runs(flox_env.path()), 0);
+    }
+
+    /// Watchdogs of several activations that terminated at the same time
+    /// only run the hook once
+    #[test]
+    fn two_watchdogs_run_hook_once() {
+        let flox_env = env_with_hook();
+        let runtime_dir = TempDir::new().unwrap();
+        start_activation(runtime_dir.path(), flox_env.path(), terminated_pid());
+
+        let watchdogs = (0..2).map(|_| {
+            let runtime_dir = runtime_dir.path().to_path_buf();
+            let flox_env = flox_env.path().to_path_buf();
+            std::thread::spawn(move || deactivate_in(&runtime_dir, &flox_env))
+        });
+        for watchdog in watchdogs.collect::<Vec<_>>() {
+            watchdog.join().unwrap();
+        }
+
+        assert_eq!(hook_runs(flox_env.path()), 1);
+    }
+}
@@ -25,6 +25,7 @@ use tracing::{debug, error, info, instrument};
 
 use crate::process::Watcher;
 
+mod deactivate;
 mod logger;
 mod process;
 mod sentry;
@@ -62,6 +63,11 @@ pub struct Cli {
     #[arg(short, long = "log-dir", value_name = "PATH")]
     pub log_dir: Option<PathBuf>,
 
+    /// The path to the activated environment, used to run its
+    /// `hook.on-deactivate` script during cleanup
+    #[arg(long = "flox-env", value_name = "PATH")]
+    pub flox_env: Option<PathBuf>,
+
     /// Disable metric reporting
     #[arg(long)]
     pub disable_metrics: bool,
@@ -167,6 +173,9 @@ fn run(args: Cli) -> Result<(), Error> {
         Ok(WaitResult::CleanUp) => {
             // Exit
             info!("exiting");
+            if let Some(flox_env) = &args.flox_env {
+                deactivate::deactivate(flox_env);
+            }
             cleanup(&args.socket_path);
         },
         Ok(WaitResult::Terminate) => {
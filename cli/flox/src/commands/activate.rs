@@ -27,8 +27,10 @@ use flox_rust_sdk::models::environment::{
     FLOX_PROMPT_ENVIRONMENTS_VAR,
     FLOX_SERVICES_SOCKET_VAR,
 };
+use flox_rust_sdk::models::lockfile::LockedManifest;
 use flox_rust_sdk::models::manifest::TypedManifest;
 use flox_rust_sdk::models::pkgdb::{error_codes, CallPkgDbError, PkgDbError};
+use flox_rust_sdk::models::secrets::resolve_secrets;
 use flox_rust_sdk::providers::services::shutdown_process_compose_if_all_processes_stopped;
 use indexmap::IndexSet;
 use indoc::{formatdoc, indoc};
@@ -179,9 +181,21 @@ impl Activate {
         // Must come after getting an activation path to prevent premature
         // locking or migration. It must also not be evaluated inline with the
         // macro or we'll leak TRACE logs for reasons unknown.
-        let lockfile_version = environment.lockfile(&flox)?.version();
+        let lockfile = environment.lockfile(&flox)?;
+        let lockfile_version = lockfile.version();
         subcommand_metric!("activate#version", lockfile_version = lockfile_version);
 
+        // Secret variables are resolved on every activation and only passed
+        // to the activated shell or command, never stored in the environment.
+        // Use the locked manifest, so that secrets of included environments are resolved too.
+        let secrets = match &lockfile {
+            LockedManifest::Catalog(lockfile) => resolve_secrets(
+                &lockfile.manifest.vars.copy_for_system(&flox.system),
+                &environment.project_path()?,
+            )?,
+            LockedManifest::Pkgdb(_) => Vec::new(),
+        };
+
         // read the currently active environments from the environment
         let mut flox_active_environments = activated_environments();
 
@@ -382,6 +396,11 @@ impl Activate {
         }
 
         exports.extend(default_nix_env_vars());
+        exports.extend(
+            secrets
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone())),
+        );
 
         // Launch the watchdog process
         if !in_place && !is_ephemeral {
@@ -390,6 +409,7 @@ impl Activate {
                 &environment.log_path()?,
                 &path_hash(environment.dot_flox_path()),
                 socket_path,
+                &activation_path,
                 config.flox.disable_metrics,
             )?;
         }
@@ -423,6 +443,7 @@ impl Activate {
         log_dir: impl AsRef<Path>,
         path_hash: &str,
         socket_path: impl AsRef<Path>,
+        activation_path: impl AsRef<Path>,
         disable_metrics: bool,
     ) -> Result<()> {
         let log_dir = log_dir.as_ref();
@@ -458,6 +479,11 @@ impl Activate {
         cmd.arg("--registry");
         cmd.arg(reg_path);
 
+        // Set the environment to run hook.on-deactivate of during cleanup,
+        // if the activations of the environment terminated without running it
+        cmd.arg("--flox-env");
+        cmd.arg(activation_path.as_ref());
+
         // Redirect the output streams so watchdog output doesn't appear in the shell
         cmd.stdout(Stdio::null());
         cmd.stderr(Stdio::null());
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::data::CanonicalPath;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::audit::{audit_lockfile, Severity, VulnerabilityDatabase};
+use flox_rust_sdk::models::lockfile::LockedManifest;
+use tracing::instrument;
+
+use super::{environment_description, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::message;
+
+/// Check the packages of an environment for known vulnerabilities
+#[derive(Bpaf, Clone)]
+pub struct Audit {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Vulnerability database in the OSV or NVD JSON format
+    #[bpaf(long, argument("path"))]
+    database: PathBuf,
+
+    /// Also match OSV records of this package ecosystem, e.g. 'crates.io'
+    #[bpaf(long("ecosystem"), argument("ecosystem"), many)]
+    ecosystems: Vec<String>,
+
+    /// Display vulnerabilities as JSON
+    #[bpaf(long)]
+    json: bool,
+
+    /// Fail if vulnerabilities of this severity or higher are found
+    /// (one of 'unknown', 'low', 'medium', 'high' or 'critical')
+    #[bpaf(long("fail-on"), argument("severity"), fallback(Severity::Unknown))]
+    fail_on: Severity,
+}
+
+impl Audit {
+    #[instrument(name = "audit", skip_all)]
+    pub async fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("audit");
+
+        let concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Audit")?;
+        let description = environment_description(&concrete_environment)?;
+        let env = concrete_environment.into_dyn_environment();
+
+        // Audit the lockfile as is, so that auditing works offline
+        let Ok(lockfile_path) = CanonicalPath::new(env.lockfile_path(&flox)?) else {
+            bail!("Environment {description} has not been locked yet.");
+        };
+        let LockedManifest::Catalog(lockfile) = LockedManifest::read_from_file(&lockfile_path)?
+        else {
+            bail!("Auditing environments with version 0 manifests is not supported.");
+        };
+
+        let mut database = VulnerabilityDatabase::read(&self.database)?;
+        database.retain_ecosystems(&self.ecosystems)?;
+        let report = audit_lockfile(&lockfile, &database);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            for install_id in &report.insecure_packages {
+                message::warning(format!(
+                    "Package '{install_id}' is marked as insecure and is only installed because of 'options.allow.insecure'"
+                ));
+            }
+            if report.findings.is_empty() {
+                message::updated(format!(
+                    "No known vulnerabilities found in environment {description}"
+                ));
+            } else {
+                for finding in &report.findings {
+                    println!("{finding}");
+                }
+            }
+        }
+
+        let failures = report
+            .findings
+            .iter()
+            .filter(|finding| finding.vulnerability.severity >= self.fail_on)
+            .count();
+        if failures > 0 {
+            bail!(
+                "Found {failures} known {} with severity '{}' or higher in environment {description}",
+                if failures == 1 {
+                    "vulnerability"
+                } else {
+                    "vulnerabilities"
+                },
+                self.fail_on
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{bail, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::data::CanonicalPath;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::lint::{lint, Severity};
+use flox_rust_sdk::models::lockfile::LockedManifest;
+use flox_rust_sdk::models::manifest::TypedManifest;
+use tracing::instrument;
+
+use super::{environment_description, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::message;
+
+/// Check an environment's manifest and lockfile for common mistakes
+#[derive(Bpaf, Clone)]
+pub struct Check {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Display problems as JSON
+    #[bpaf(long)]
+    json: bool,
+
+    /// Fail if problems of this severity or higher are found
+    /// (one of 'info', 'warning' or 'error')
+    #[bpaf(long("fail-on"), argument("severity"), fallback(Severity::Error))]
+    fail_on: Severity,
+}
+
+impl Check {
+    #[instrument(name = "check", skip_all)]
+    pub async fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("check");
+
+        let concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Check")?;
+        let description = environment_description(&concrete_environment)?;
+        let env = concrete_environment.into_dyn_environment();
+
+        let TypedManifest::Catalog(manifest) = env.manifest(&flox)? else {
+            bail!("Checking environments with version 0 manifests is not supported.");
+        };
+        let lockfile = match CanonicalPath::new(env.lockfile_path(&flox)?) {
+            Ok(lockfile_path) => match LockedManifest::read_from_file(&lockfile_path)? {
+                LockedManifest::Catalog(lockfile) => Some(lockfile),
+                LockedManifest::Pkgdb(_) => None,
+            },
+            Err(_) => None,
+        };
+
+        let diagnostics = lint(&manifest, lockfile.as_ref(), &flox.system);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+        } else if diagnostics.is_empty() {
+            message::updated(format!("No problems found in environment {description}"));
+        } else {
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+        }
+
+        let failures = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity >= self.fail_on)
+            .count();
+        if failures > 0 {
+            bail!(
+                "Found {failures} {} with severity '{}' or higher in environment {description}",
+                if failures == 1 { "problem" } else { "problems" },
+                self.fail_on
+            );
+        }
+
+        Ok(())
+    }
+}
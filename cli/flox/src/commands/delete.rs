@@ -5,7 +5,7 @@ use flox_rust_sdk::models::environment::Environment;
 use indoc::formatdoc;
 use tracing::instrument;
 
-use super::{environment_select, EnvironmentSelect};
+use super::{environment_select, EnvironmentLocation, EnvironmentSelect};
 use crate::commands::{environment_description, ConcreteEnvironment};
 use crate::subcommand_metric;
 use crate::utils::dialog::{Confirm, Dialog};
@@ -41,7 +41,7 @@ impl Delete {
             bail!("{message}")
         }
 
-        let message = if let EnvironmentSelect::Unspecified = self.environment {
+        let message = if let EnvironmentLocation::Unspecified = self.environment.location {
             format!("You are about to delete your environment {description}. Are you sure?")
         } else {
             "Are you sure?".to_string()
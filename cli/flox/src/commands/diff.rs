@@ -0,0 +1,266 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::data::CanonicalPath;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment_diff::{LockedPackageChange, LockfileDiff};
+use flox_rust_sdk::models::lockfile::{LockedManifest, LockedManifestCatalog};
+use flox_rust_sdk::providers::git::{GitCommandProvider, GitProvider};
+use tracing::instrument;
+
+use super::{EnvironmentLocation, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::message;
+
+/// Compare the locked packages of two lockfiles or environments
+#[derive(Bpaf, Debug, Clone)]
+pub struct Diff {
+    /// Display output as JSON
+    #[bpaf(long)]
+    json: bool,
+
+    /// Lockfile or directory containing an environment to compare from.
+    /// If <to> is omitted, compare the environment's lockfile at git HEAD
+    /// to the working tree.
+    #[bpaf(positional("from"))]
+    from: Option<PathBuf>,
+
+    /// Lockfile or directory containing an environment to compare to
+    #[bpaf(positional("to"))]
+    to: Option<PathBuf>,
+}
+
+impl Diff {
+    #[instrument(name = "diff", skip_all)]
+    pub async fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("diff");
+
+        let (old, new, description) = match (self.from, self.to) {
+            (from, None) => {
+                let dir = from.unwrap_or_else(|| PathBuf::from("."));
+                let lockfile_path = lockfile_path(&flox, &dir)?;
+                let old = read_lockfile_at_head(&lockfile_path)?;
+                let new = read_lockfile(&lockfile_path)?;
+                (old, new, "git HEAD and the working tree".to_string())
+            },
+            (Some(from), Some(to)) => {
+                let old = read_lockfile(&lockfile_path(&flox, &from)?)?;
+                let new = read_lockfile(&lockfile_path(&flox, &to)?)?;
+                let description = format!("'{}' and '{}'", from.display(), to.display());
+                (old, new, description)
+            },
+            (None, Some(_)) => unreachable!("positional arguments are filled in order"),
+        };
+
+        let diff = LockfileDiff::new(&old, &new);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else if diff.is_empty() {
+            message::plain(format!(
+                "No changes to locked packages between {description}"
+            ));
+        } else {
+            print!("{}", LockfileDiffDisplay(&diff));
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolve a lockfile path, or the lockfile of the environment in a directory
+fn lockfile_path(flox: &Flox, path: &Path) -> Result<CanonicalPath> {
+    let lockfile_path = if path.is_dir() {
+        EnvironmentSelect {
+            location: EnvironmentLocation::Dir(path.to_path_buf()),
+            ..Default::default()
+        }
+        .to_concrete_environment(flox)?
+        .into_dyn_environment()
+        .lockfile_path(flox)?
+    } else {
+        path.to_path_buf()
+    };
+    CanonicalPath::new(&lockfile_path)
+        .with_context(|| format!("Lockfile '{}' not found", lockfile_path.display()))
+}
+
+/// Read a lockfile, rejecting lockfiles of version 0 environments
+fn read_lockfile(path: &CanonicalPath) -> Result<LockedManifestCatalog> {
+    let lockfile = LockedManifest::read_from_file(path)
+        .with_context(|| format!("Failed to read lockfile '{}'", path.display()))?;
+    catalog_lockfile(lockfile)
+}
+
+/// Read the committed version of a lockfile from the git repository
+/// containing it.
+fn read_lockfile_at_head(path: &CanonicalPath) -> Result<LockedManifestCatalog> {
+    let Some(dir) = path.parent() else {
+        bail!("Lockfile '{}' has no parent directory", path.display());
+    };
+    let repo = GitCommandProvider::discover(dir)
+        .with_context(|| format!("'{}' is not in a git repository", path.display()))?;
+    let workdir = repo
+        .workdir()
+        .context("Can't compare to git HEAD in a bare repository")?
+        .canonicalize()?;
+    let relative_path = path.strip_prefix(&workdir)?;
+
+    let contents = repo
+        .show(&format!("HEAD:{}", relative_path.display()))
+        .with_context(|| format!("'{}' is not committed at git HEAD", relative_path.display()))?;
+    let lockfile: LockedManifest = serde_json::from_slice(contents.as_encoded_bytes())
+        .with_context(|| format!("Failed to parse '{}' at git HEAD", relative_path.display()))?;
+    catalog_lockfile(lockfile)
+}
+
+fn catalog_lockfile(lockfile: LockedManifest) -> Result<LockedManifestCatalog> {
+    match lockfile {
+        LockedManifest::Catalog(lockfile) => Ok(lockfile),
+        LockedManifest::Pkgdb(_) => {
+            bail!("Comparing lockfiles of version 0 environments is not supported.")
+        },
+    }
+}
+
+/// Formats a [LockfileDiff] for display in the CLI.
+///
+/// Changes are grouped by system.
+/// Additions are marked with `+`, removals with `-` and changes with `~`.
+struct LockfileDiffDisplay<'a>(&'a LockfileDiff);
+
+impl Display for LockfileDiffDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn format_version(version: &Option<String>) -> &str {
+            version.as_deref().unwrap_or("unknown")
+        }
+
+        fn format_change(change: &LockedPackageChange) -> String {
+            let mut line = if change.old_version == change.new_version {
+                format!(
+                    "{} {}",
+                    change.install_id,
+                    format_version(&change.new_version)
+                )
+            } else {
+                format!(
+                    "{} {} -> {}",
+                    change.install_id,
+                    format_version(&change.old_version),
+                    format_version(&change.new_version)
+                )
+            };
+
+            let mut notes = Vec::new();
+            if change.derivation_changed && change.old_version == change.new_version {
+                notes.push("rebuilt".to_string());
+            }
+            if !change.outputs.is_empty() {
+                let outputs = [
+                    ("+", &change.outputs.added),
+                    ("-", &change.outputs.removed),
+                    ("~", &change.outputs.changed),
+                ]
+                .into_iter()
+                .flat_map(|(marker, outputs)| {
+                    outputs
+                        .iter()
+                        .map(move |output| format!("{marker}{output}"))
+                })
+                .collect::<Vec<_>>();
+                notes.push(format!("outputs: {}", outputs.join(" ")));
+            }
+            if !notes.is_empty() {
+                line.push_str(&format!(" ({})", notes.join(", ")));
+            }
+            line
+        }
+
+        for (system, packages) in &self.0.systems {
+            writeln!(f, "{system}:")?;
+            for package in &packages.added {
+                writeln!(
+                    f,
+                    "  + {} {}",
+                    package.install_id,
+                    format_version(&package.version)
+                )?;
+            }
+            for package in &packages.removed {
+                writeln!(
+                    f,
+                    "  - {} {}",
+                    package.install_id,
+                    format_version(&package.version)
+                )?;
+            }
+            for change in &packages.changed {
+                writeln!(f, "  ~ {}", format_change(change))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use flox_rust_sdk::models::environment_diff::{KeysDiff, LockedPackagesDiff, PackageVersion};
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn lockfile_diff_display() {
+        let change = |install_id: &str, old: &str, new: &str| LockedPackageChange {
+            install_id: install_id.to_string(),
+            old_version: Some(old.to_string()),
+            new_version: Some(new.to_string()),
+            derivation_changed: true,
+            outputs: KeysDiff::default(),
+        };
+        let diff = LockfileDiff {
+            systems: BTreeMap::from([
+                ("aarch64-darwin".to_string(), LockedPackagesDiff {
+                    added: vec![],
+                    removed: vec![],
+                    changed: vec![change("curl", "8.4.0", "8.5.0")],
+                }),
+                ("x86_64-linux".to_string(), LockedPackagesDiff {
+                    added: vec![PackageVersion {
+                        install_id: "cowsay".to_string(),
+                        version: Some("3.7.0".to_string()),
+                    }],
+                    removed: vec![PackageVersion {
+                        install_id: "vim".to_string(),
+                        version: Some("9.0".to_string()),
+                    }],
+                    changed: vec![change("hello", "2.12", "2.12"), LockedPackageChange {
+                        derivation_changed: false,
+                        outputs: KeysDiff {
+                            added: vec!["dev".to_string()],
+                            removed: vec!["man".to_string()],
+                            changed: vec![],
+                        },
+                        ..change("openssl", "3.0", "3.0")
+                    }],
+                }),
+            ]),
+        };
+
+        assert_eq!(LockfileDiffDisplay(&diff).to_string(), indoc! {"
+            aarch64-darwin:
+              ~ curl 8.4.0 -> 8.5.0
+            x86_64-linux:
+              + cowsay 3.7.0
+              - vim 9.0
+              ~ hello 2.12 (rebuilt)
+              ~ openssl 3.0 (outputs: +dev -man)
+        "});
+    }
+}
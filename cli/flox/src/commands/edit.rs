@@ -42,6 +42,43 @@ use crate::utils::errors::{
 };
 use crate::utils::message;
 
+/// Report the result of an edit to the user
+/// and warn about changes that require restarting services.
+///
+/// Shared by `flox edit` and the `flox manifest` subcommands.
+pub(crate) fn report_edit_result(
+    flox: &Flox,
+    environment: &dyn Environment,
+    active_environment: &UninitializedEnvironment,
+    result: &EditResult,
+) {
+    // outside the match to avoid rustfmt falling on its face
+    let reactivate_required_note = indoc::indoc! {"
+        Your manifest has changes that cannot be automatically applied.
+
+        Please 'exit' the environment and run 'flox activate' to see these changes.
+   "};
+
+    match result {
+        EditResult::Unchanged => {
+            message::warning("No changes made to environment.");
+        },
+        EditResult::ReActivateRequired { .. }
+            if activated_environments().is_active(active_environment) =>
+        {
+            message::warning(reactivate_required_note)
+        },
+        EditResult::ReActivateRequired { .. } => {
+            message::updated("Environment successfully updated.")
+        },
+        EditResult::Success { .. } => message::updated("Environment successfully updated."),
+    }
+
+    if *result != EditResult::Unchanged {
+        warn_manifest_changes_for_services(flox, environment);
+    }
+}
+
 // Edit declarative environment configuration
 #[derive(Bpaf, Clone)]
 pub struct Edit {
@@ -84,7 +121,7 @@ impl Edit {
         subcommand_metric!("edit");
 
         // Ensure the user is logged in for the following remote operations
-        if let EnvironmentSelect::Remote(_) = self.environment {
+        if self.environment.is_floxhub_remote() {
             ensure_floxhub_token(&mut flox).await?;
         };
 
@@ -219,31 +256,7 @@ impl Edit {
             None => Self::interactive_edit(flox, environment).await?,
         };
 
-        // outside the match to avoid rustfmt falling on its face
-        let reactivate_required_note = indoc::indoc! {"
-            Your manifest has changes that cannot be automatically applied.
-
-            Please 'exit' the environment and run 'flox activate' to see these changes.
-       "};
-
-        match result {
-            EditResult::Unchanged => {
-                message::warning("No changes made to environment.");
-            },
-            EditResult::ReActivateRequired { .. }
-                if activated_environments().is_active(&active_environment) =>
-            {
-                message::warning(reactivate_required_note)
-            },
-            EditResult::ReActivateRequired { .. } => {
-                message::updated("Environment successfully updated.")
-            },
-            EditResult::Success { .. } => message::updated("Environment successfully updated."),
-        }
-
-        if result != EditResult::Unchanged {
-            warn_manifest_changes_for_services(flox, environment);
-        }
+        report_edit_result(flox, environment, &active_environment, &result);
 
         Ok(())
     }
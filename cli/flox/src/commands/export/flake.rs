@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::flake_export::export_flake;
+use flox_rust_sdk::models::lockfile::LockedManifest;
+use tracing::instrument;
+
+use crate::commands::{environment_description, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::message;
+
+// Export an environment as a Nix flake
+#[derive(Bpaf, Clone)]
+pub struct Flake {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Directory to write 'flake.nix' and 'flake.lock' to
+    /// (default: current directory)
+    #[bpaf(long, short, argument("path"))]
+    output: Option<PathBuf>,
+
+    /// Don't write any files,
+    /// but fail if the flake in the output directory is out of date
+    #[bpaf(long)]
+    check: bool,
+}
+
+impl Flake {
+    #[instrument(name = "flake", skip_all)]
+    pub fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("export::flake");
+
+        let concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Export")?;
+        let description = environment_description(&concrete_environment)?;
+        let mut env = concrete_environment.into_dyn_environment();
+
+        let LockedManifest::Catalog(lockfile) = env.lockfile(&flox)? else {
+            bail!("Exporting environments with version 0 manifests is not supported.");
+        };
+        let export = export_flake(env.name().as_ref(), &lockfile)?;
+
+        for install_id in &export.skipped_packages {
+            message::warning(format!(
+                "Package '{install_id}' is installed from a store path and is not included in the flake"
+            ));
+        }
+        for name in &export.skipped_secrets {
+            message::warning(format!(
+                "Secret variable '{name}' is not included in the flake"
+            ));
+        }
+
+        let output = match self.output {
+            Some(output) => output,
+            None => std::env::current_dir().context("Could not get current directory")?,
+        };
+        let files = [
+            (output.join("flake.nix"), &export.flake_nix),
+            (output.join("flake.lock"), &export.flake_lock),
+        ];
+
+        if self.check {
+            for (path, expected) in &files {
+                if fs::read_to_string(path).ok().as_ref() != Some(*expected) {
+                    bail!(
+                        "'{}' is out of date with environment {description}, run 'flox export flake' to update it",
+                        path.display()
+                    );
+                }
+            }
+            message::updated(format!(
+                "The flake in '{}' is up to date with environment {description}",
+                output.display()
+            ));
+            return Ok(());
+        }
+
+        fs::create_dir_all(&output)
+            .with_context(|| format!("Could not create directory '{}'", output.display()))?;
+        for (path, contents) in &files {
+            fs::write(path, contents)
+                .with_context(|| format!("Could not write '{}'", path.display()))?;
+        }
+
+        message::created(format!(
+            "Exported environment {description} as a flake to '{}'",
+            output.display()
+        ));
+        Ok(())
+    }
+}
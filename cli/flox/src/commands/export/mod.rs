@@ -0,0 +1,25 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use tracing::instrument;
+
+mod flake;
+
+/// Export Commands.
+#[derive(Clone, Bpaf)]
+pub enum ExportCommands {
+    /// Export an environment as a Nix flake
+    #[bpaf(command, footer("Run 'man flox-export-flake' for more details."))]
+    Flake(#[bpaf(external(flake::flake))] flake::Flake),
+}
+
+impl ExportCommands {
+    #[instrument(name = "export", skip_all)]
+    pub fn handle(self, flox: Flox) -> Result<()> {
+        match self {
+            ExportCommands::Flake(args) => args.handle(flox)?,
+        }
+
+        Ok(())
+    }
+}
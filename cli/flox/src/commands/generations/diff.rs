@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::generations::GenerationId;
+use flox_rust_sdk::models::environment_diff::{EnvironmentDiff, KeysDiff};
+use tracing::instrument;
+
+use super::GenerationsEnvironment;
+use crate::commands::{environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::message;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Diff {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Display output as JSON
+    #[bpaf(long)]
+    json: bool,
+
+    /// The generation to compare from
+    #[bpaf(positional("from"))]
+    from: GenerationId,
+
+    /// The generation to compare to
+    #[bpaf(positional("to"))]
+    to: GenerationId,
+}
+
+impl Diff {
+    #[instrument(name = "diff", skip_all)]
+    pub async fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("generations::diff");
+
+        let (env, _) = GenerationsEnvironment::from_environment_selection(
+            &flox,
+            &self.environment,
+            "Compare generations of",
+        )?;
+
+        let diff = env.generations().diff(*self.from, *self.to, &flox.system)?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else if diff.is_empty() {
+            message::plain(format!(
+                "No changes between generation {} and generation {}",
+                self.from, self.to
+            ));
+        } else {
+            print!("{}", EnvironmentDiffDisplay(&diff));
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats an [EnvironmentDiff] for display in the CLI.
+///
+/// Additions are marked with `+`, removals with `-` and changes with `~`.
+/// Sections without changes are omitted.
+pub(crate) struct EnvironmentDiffDisplay<'a>(pub &'a EnvironmentDiff);
+
+impl Display for EnvironmentDiffDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn format_version(version: &Option<String>) -> &str {
+            version.as_deref().unwrap_or("unknown")
+        }
+
+        fn write_keys(
+            f: &mut std::fmt::Formatter<'_>,
+            title: &str,
+            keys: &KeysDiff,
+        ) -> std::fmt::Result {
+            if keys.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "{title}:")?;
+            for key in &keys.added {
+                writeln!(f, "  + {key}")?;
+            }
+            for key in &keys.removed {
+                writeln!(f, "  - {key}")?;
+            }
+            for key in &keys.changed {
+                writeln!(f, "  ~ {key}")?;
+            }
+            Ok(())
+        }
+
+        let packages = &self.0.packages;
+        if !packages.is_empty() {
+            writeln!(f, "Packages:")?;
+            for package in &packages.added {
+                writeln!(
+                    f,
+                    "  + {} {}",
+                    package.install_id,
+                    format_version(&package.version)
+                )?;
+            }
+            for package in &packages.removed {
+                writeln!(
+                    f,
+                    "  - {} {}",
+                    package.install_id,
+                    format_version(&package.version)
+                )?;
+            }
+            for package in &packages.changed {
+                if package.old_version == package.new_version {
+                    writeln!(
+                        f,
+                        "  ~ {} {} (descriptor changed)",
+                        package.install_id,
+                        format_version(&package.new_version)
+                    )?;
+                } else {
+                    writeln!(
+                        f,
+                        "  ~ {} {} -> {}",
+                        package.install_id,
+                        format_version(&package.old_version),
+                        format_version(&package.new_version)
+                    )?;
+                }
+            }
+        }
+
+        write_keys(f, "Variables", &self.0.vars)?;
+        write_keys(f, "Hooks", &self.0.hooks)?;
+        write_keys(f, "Services", &self.0.services)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flox_rust_sdk::models::environment_diff::{PackageChange, PackageVersion, PackagesDiff};
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn environment_diff_display() {
+        let diff = EnvironmentDiff {
+            packages: PackagesDiff {
+                added: vec![PackageVersion {
+                    install_id: "cowsay".to_string(),
+                    version: Some("3.7.0".to_string()),
+                }],
+                removed: vec![PackageVersion {
+                    install_id: "hello".to_string(),
+                    version: None,
+                }],
+                changed: vec![
+                    PackageChange {
+                        install_id: "curl".to_string(),
+                        old_version: Some("8.4.0".to_string()),
+                        new_version: Some("8.5.0".to_string()),
+                    },
+                    PackageChange {
+                        install_id: "vim".to_string(),
+                        old_version: Some("9.0".to_string()),
+                        new_version: Some("9.0".to_string()),
+                    },
+                ],
+            },
+            vars: KeysDiff {
+                added: vec!["FOO".to_string()],
+                removed: vec!["BAR".to_string()],
+                changed: vec![],
+            },
+            hooks: KeysDiff {
+                added: vec![],
+                removed: vec![],
+                changed: vec!["hook.on-activate".to_string()],
+            },
+            services: KeysDiff::default(),
+        };
+
+        assert_eq!(EnvironmentDiffDisplay(&diff).to_string(), indoc! {"
+            Packages:
+              + cowsay 3.7.0
+              - hello unknown
+              ~ curl 8.4.0 -> 8.5.0
+              ~ vim 9.0 (descriptor changed)
+            Variables:
+              + FOO
+              - BAR
+            Hooks:
+              ~ hook.on-activate
+        "});
+    }
+}
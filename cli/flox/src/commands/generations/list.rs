@@ -0,0 +1,179 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use bpaf::Bpaf;
+use chrono::{DateTime, Utc};
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::generations::AllGenerationsMetadata;
+use itertools::Itertools;
+use tracing::instrument;
+
+use super::GenerationsEnvironment;
+use crate::commands::{environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct List {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Display output as JSON
+    #[bpaf(long)]
+    json: bool,
+}
+
+impl List {
+    #[instrument(name = "list", skip_all)]
+    pub async fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("generations::list");
+
+        let (env, _) = GenerationsEnvironment::from_environment_selection(
+            &flox,
+            &self.environment,
+            "List generations of",
+        )?;
+
+        let metadata = env.generations().metadata()?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&metadata)?);
+        } else {
+            print!("{}", GenerationsDisplay(&metadata));
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats [AllGenerationsMetadata] as a table for display in the CLI.
+///
+/// The current generation is marked with a `*`,
+/// tags are listed next to the generation they refer to.
+struct GenerationsDisplay<'a>(&'a AllGenerationsMetadata);
+
+impl Display for GenerationsDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn format_time(time: &DateTime<Utc>) -> String {
+            time.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+        }
+
+        let id_width = self
+            .0
+            .generations
+            .keys()
+            .map(|id| id.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max("ID".len());
+        // Width of a formatted timestamp
+        let time_width = 23;
+        let author_width = self
+            .0
+            .generations
+            .values()
+            .filter_map(|generation| generation.author.as_ref())
+            .map(|author| author.len())
+            .max()
+            .unwrap_or(0)
+            .max("AUTHOR".len());
+        let tags = |id| self.0.tags_of(id).iter().join(",");
+        let tags_width = self
+            .0
+            .generations
+            .keys()
+            .map(|id| tags(id).len())
+            .max()
+            .unwrap_or(0)
+            .max("TAGS".len());
+
+        writeln!(
+            f,
+            "  {:>id_width$}  {:<time_width$}  {:<time_width$}  {:<author_width$}  {:<tags_width$}  DESCRIPTION",
+            "ID", "CREATED", "LAST ACTIVE", "AUTHOR", "TAGS"
+        )?;
+        for (id, generation) in self.0.generations.iter().rev() {
+            let marker = if Some(id) == self.0.current_gen.as_ref() {
+                "*"
+            } else {
+                " "
+            };
+            let last_active = generation
+                .last_active
+                .as_ref()
+                .map(format_time)
+                .unwrap_or_else(|| "-".to_string());
+            let author = generation.author.as_deref().unwrap_or("-");
+            let tags = match tags(id) {
+                tags if tags.is_empty() => "-".to_string(),
+                tags => tags,
+            };
+
+            writeln!(
+                f,
+                "{marker} {:>id_width$}  {:<time_width$}  {:<time_width$}  {:<author_width$}  {:<tags_width$}  {}",
+                id,
+                format_time(&generation.created),
+                last_active,
+                author,
+                tags,
+                generation.description
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use flox_rust_sdk::models::environment::generations::SingleGenerationMetadata;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn generation(
+        created: i64,
+        last_active: Option<i64>,
+        author: Option<&str>,
+        description: &str,
+    ) -> SingleGenerationMetadata {
+        SingleGenerationMetadata {
+            created: Utc.timestamp_opt(created, 0).unwrap(),
+            last_active: last_active.map(|t| Utc.timestamp_opt(t, 0).unwrap()),
+            author: author.map(String::from),
+            ..SingleGenerationMetadata::new(description.to_string())
+        }
+    }
+
+    #[test]
+    fn generations_display_marks_current_generation() {
+        let mut metadata = AllGenerationsMetadata::default();
+        metadata
+            .generations
+            .insert(1.into(), generation(0, Some(0), None, "initial"));
+        metadata.generations.insert(
+            2.into(),
+            generation(
+                60,
+                Some(120),
+                Some("alice"),
+                "installed packages: [\"hello\"]",
+            ),
+        );
+        metadata.generations.insert(
+            3.into(),
+            generation(90, None, Some("bob"), "manually edited"),
+        );
+        metadata.current_gen = Some(2.into());
+        metadata.tags.insert("stable".parse().unwrap(), 1.into());
+        metadata.tags.insert("release".parse().unwrap(), 1.into());
+
+        assert_eq!(GenerationsDisplay(&metadata).to_string(), indoc! {r#"
+              ID  CREATED                  LAST ACTIVE              AUTHOR  TAGS            DESCRIPTION
+               3  1970-01-01 00:01:30 UTC  -                        bob     -               manually edited
+            *  2  1970-01-01 00:01:00 UTC  1970-01-01 00:02:00 UTC  alice   -               installed packages: ["hello"]
+               1  1970-01-01 00:00:00 UTC  1970-01-01 00:00:00 UTC  -       release,stable  initial
+        "#});
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::generations::{GenerationId, Generations, RetentionPolicy};
+use flox_rust_sdk::models::environment::managed_environment::ManagedEnvironment;
+use flox_rust_sdk::models::environment::remote_environment::RemoteEnvironment;
+use flox_rust_sdk::models::environment_ref::GenerationTag;
+use tracing::instrument;
+
+use super::{environment_description, ConcreteEnvironment, EnvironmentSelect};
+use crate::config::Config;
+
+mod diff;
+mod list;
+pub(crate) mod prune;
+mod rollback;
+mod switch;
+mod tag;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GenerationsCommandsError {
+    #[error(
+        "Environment {description} does not have generations.\n\
+        \n\
+        Generations are only available for environments pushed to FloxHub.\n\
+        To push the environment, run 'flox push'"
+    )]
+    NoGenerations { description: String },
+}
+
+/// Generations Commands.
+#[derive(Debug, Clone, Bpaf)]
+pub enum GenerationsCommands {
+    /// List generations of an environment
+    #[bpaf(command, footer("Run 'man flox-generations-list' for more details."))]
+    List(#[bpaf(external(list::list))] list::List),
+
+    /// Compare two generations of an environment
+    #[bpaf(command, footer("Run 'man flox-generations-diff' for more details."))]
+    Diff(#[bpaf(external(diff::diff))] diff::Diff),
+
+    /// Switch to a generation of an environment
+    #[bpaf(command, footer("Run 'man flox-generations-switch' for more details."))]
+    Switch(#[bpaf(external(switch::switch))] switch::Switch),
+
+    /// Switch to the generation preceding the current generation
+    #[bpaf(
+        command,
+        footer("Run 'man flox-generations-rollback' for more details.")
+    )]
+    Rollback(#[bpaf(external(rollback::rollback))] rollback::Rollback),
+
+    /// Delete old generations of an environment
+    #[bpaf(command, footer("Run 'man flox-generations-prune' for more details."))]
+    Prune(#[bpaf(external(prune::prune))] prune::Prune),
+
+    /// Name a generation of an environment
+    #[bpaf(command, footer("Run 'man flox-generations-tag' for more details."))]
+    Tag(#[bpaf(external(tag::tag))] tag::Tag),
+}
+
+impl GenerationsCommands {
+    #[instrument(name = "generations", skip_all)]
+    pub async fn handle(self, config: Config, flox: Flox) -> Result<()> {
+        match self {
+            GenerationsCommands::List(args) => args.handle(flox).await?,
+            GenerationsCommands::Diff(args) => args.handle(flox).await?,
+            GenerationsCommands::Switch(args) => args.handle(flox).await?,
+            GenerationsCommands::Rollback(args) => args.handle(flox).await?,
+            GenerationsCommands::Prune(args) => args.handle(config, flox).await?,
+            GenerationsCommands::Tag(args) => args.handle(flox).await?,
+        }
+
+        Ok(())
+    }
+}
+
+/// A [ConcreteEnvironment] that has been checked to have generations,
+/// i.e. a managed or a remote environment.
+///
+/// Path environments do not have generations.
+pub enum GenerationsEnvironment {
+    Managed(ManagedEnvironment),
+    Remote(RemoteEnvironment),
+}
+
+impl GenerationsEnvironment {
+    /// Create a [GenerationsEnvironment] from an [EnvironmentSelect]
+    ///
+    /// Returns the environment and its description,
+    /// or an error if the selected environment does not have generations.
+    pub fn from_environment_selection(
+        flox: &Flox,
+        environment: &EnvironmentSelect,
+        message: &str,
+    ) -> Result<(Self, String)> {
+        let concrete_environment = environment.detect_concrete_environment(flox, message)?;
+        let description = environment_description(&concrete_environment)?;
+
+        let environment = match concrete_environment {
+            ConcreteEnvironment::Managed(environment) => Self::Managed(environment),
+            ConcreteEnvironment::Remote(environment) => Self::Remote(environment),
+            ConcreteEnvironment::Path(_) => Err(GenerationsCommandsError::NoGenerations {
+                description: description.clone(),
+            })?,
+        };
+
+        Ok((environment, description))
+    }
+
+    /// Return a read-only view of the generations of the environment
+    pub fn generations(&self) -> Generations {
+        match self {
+            GenerationsEnvironment::Managed(environment) => environment.generations(),
+            GenerationsEnvironment::Remote(environment) => environment.generations(),
+        }
+    }
+
+    /// Switch the environment to the given generation
+    pub fn switch_generation(&mut self, flox: &Flox, generation: GenerationId) -> Result<()> {
+        match self {
+            GenerationsEnvironment::Managed(environment) => {
+                environment.switch_generation(flox, generation)?
+            },
+            GenerationsEnvironment::Remote(environment) => {
+                environment.switch_generation(flox, generation)?
+            },
+        }
+        Ok(())
+    }
+
+    /// Switch the environment to the previous generation
+    ///
+    /// Returns the generation that was switched to.
+    pub fn rollback(&mut self, flox: &Flox) -> Result<GenerationId> {
+        let generation = match self {
+            GenerationsEnvironment::Managed(environment) => environment.rollback(flox)?,
+            GenerationsEnvironment::Remote(environment) => environment.rollback(flox)?,
+        };
+        Ok(generation)
+    }
+
+    /// Delete generations of the environment according to a [RetentionPolicy]
+    ///
+    /// Returns the generations that were deleted.
+    pub fn prune_generations(
+        &mut self,
+        flox: &Flox,
+        policy: &RetentionPolicy,
+    ) -> Result<Vec<GenerationId>> {
+        let pruned = match self {
+            GenerationsEnvironment::Managed(environment) => {
+                environment.prune_generations(flox, policy)?
+            },
+            GenerationsEnvironment::Remote(environment) => {
+                environment.prune_generations(flox, policy)?
+            },
+        };
+        Ok(pruned)
+    }
+
+    /// Tag a generation of the environment
+    pub fn tag_generation(
+        &mut self,
+        flox: &Flox,
+        tag: GenerationTag,
+        generation: GenerationId,
+    ) -> Result<()> {
+        match self {
+            GenerationsEnvironment::Managed(environment) => {
+                environment.tag_generation(flox, tag, generation)?
+            },
+            GenerationsEnvironment::Remote(environment) => {
+                environment.tag_generation(flox, tag, generation)?
+            },
+        }
+        Ok(())
+    }
+
+    /// Remove a tag from the environment
+    ///
+    /// Returns the generation the tag referred to.
+    pub fn remove_tag(&mut self, flox: &Flox, tag: &GenerationTag) -> Result<GenerationId> {
+        let generation = match self {
+            GenerationsEnvironment::Managed(environment) => environment.remove_tag(flox, tag)?,
+            GenerationsEnvironment::Remote(environment) => environment.remove_tag(flox, tag)?,
+        };
+        Ok(generation)
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use chrono::Duration;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::generations::RetentionPolicy;
+use flox_rust_sdk::models::environment::{DEFAULT_KEEP_GENERATIONS, DEFAULT_MAX_AGE_DAYS};
+use itertools::Itertools;
+use tracing::instrument;
+
+use super::GenerationsEnvironment;
+use crate::commands::{ensure_floxhub_token, environment_select, EnvironmentSelect};
+use crate::config::{Config, FloxConfig};
+use crate::subcommand_metric;
+use crate::utils::dialog::{Dialog, Spinner};
+use crate::utils::message;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Prune {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Keep this many of the newest generations
+    #[bpaf(long, argument("n"))]
+    keep: Option<usize>,
+
+    /// Keep generations created within this many days
+    #[bpaf(long, argument("days"))]
+    max_age: Option<u32>,
+}
+
+impl Prune {
+    #[instrument(name = "prune", skip_all)]
+    pub async fn handle(self, config: Config, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("generations::prune");
+
+        if self.environment.is_floxhub_remote() {
+            ensure_floxhub_token(&mut flox).await?;
+        }
+
+        let (mut env, description) = GenerationsEnvironment::from_environment_selection(
+            &flox,
+            &self.environment,
+            "Prune generations of",
+        )?;
+
+        let policy = retention_policy(&config.flox, self.keep, self.max_age);
+
+        let pruned = Dialog {
+            message: "Pruning generations...",
+            help_message: None,
+            typed: Spinner::new(|| env.prune_generations(&flox, &policy)),
+        }
+        .spin()?;
+
+        if pruned.is_empty() {
+            message::plain(format!(
+                "No generations of environment {description} to prune"
+            ));
+        } else {
+            message::deleted(format!(
+                "Pruned generations {} of environment {description}",
+                pruned.iter().join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Determine the [RetentionPolicy] from command line arguments or the user's config
+///
+/// If neither `keep` nor `max_age_days` is passed,
+/// both values are read from the config, falling back to their defaults.
+pub(crate) fn retention_policy(
+    config: &FloxConfig,
+    keep: Option<usize>,
+    max_age_days: Option<u32>,
+) -> RetentionPolicy {
+    if keep.is_none() && max_age_days.is_none() {
+        return RetentionPolicy {
+            keep_last: Some(config.keep_generations.unwrap_or(DEFAULT_KEEP_GENERATIONS)),
+            max_age: Some(Duration::days(
+                config
+                    .generations_max_age_days
+                    .unwrap_or(DEFAULT_MAX_AGE_DAYS)
+                    .into(),
+            )),
+        };
+    }
+
+    RetentionPolicy {
+        keep_last: keep,
+        max_age: max_age_days.map(|days| Duration::days(days.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn retention_policy_defaults() {
+        assert_eq!(
+            retention_policy(&FloxConfig::default(), None, None),
+            RetentionPolicy {
+                keep_last: Some(DEFAULT_KEEP_GENERATIONS),
+                max_age: Some(Duration::days(DEFAULT_MAX_AGE_DAYS.into())),
+            }
+        );
+    }
+
+    #[test]
+    fn retention_policy_from_config() {
+        let config = FloxConfig {
+            keep_generations: Some(3),
+            generations_max_age_days: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(retention_policy(&config, None, None), RetentionPolicy {
+            keep_last: Some(3),
+            max_age: Some(Duration::days(7)),
+        });
+    }
+
+    #[test]
+    fn retention_policy_arguments_override_config() {
+        let config = FloxConfig {
+            keep_generations: Some(3),
+            generations_max_age_days: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(retention_policy(&config, Some(5), None), RetentionPolicy {
+            keep_last: Some(5),
+            max_age: None,
+        });
+    }
+}
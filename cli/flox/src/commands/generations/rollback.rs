@@ -0,0 +1,46 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use tracing::instrument;
+
+use super::GenerationsEnvironment;
+use crate::commands::{ensure_floxhub_token, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::dialog::{Dialog, Spinner};
+use crate::utils::message;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Rollback {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+}
+
+impl Rollback {
+    #[instrument(name = "rollback", skip_all)]
+    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("generations::rollback");
+
+        if self.environment.is_floxhub_remote() {
+            ensure_floxhub_token(&mut flox).await?;
+        }
+
+        let (mut env, description) = GenerationsEnvironment::from_environment_selection(
+            &flox,
+            &self.environment,
+            "Roll back",
+        )?;
+
+        let generation = Dialog {
+            message: "Rolling back to previous generation...",
+            help_message: None,
+            typed: Spinner::new(|| env.rollback(&flox)),
+        }
+        .spin()?;
+
+        message::updated(format!(
+            "Rolled back environment {description} to generation {generation}"
+        ));
+
+        Ok(())
+    }
+}
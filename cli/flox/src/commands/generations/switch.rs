@@ -0,0 +1,61 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::generations::GenerationId;
+use tracing::instrument;
+
+use super::GenerationsEnvironment;
+use crate::commands::{ensure_floxhub_token, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::dialog::{Dialog, Spinner};
+use crate::utils::message;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Switch {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// The generation to switch to
+    #[bpaf(positional("generation"))]
+    generation: GenerationId,
+}
+
+impl Switch {
+    #[instrument(name = "switch", skip_all)]
+    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("generations::switch");
+
+        if self.environment.is_floxhub_remote() {
+            ensure_floxhub_token(&mut flox).await?;
+        }
+
+        let (mut env, description) = GenerationsEnvironment::from_environment_selection(
+            &flox,
+            &self.environment,
+            "Switch generation of",
+        )?;
+
+        let current_gen = env.generations().metadata()?.current_gen;
+        if current_gen.as_ref() == Some(&self.generation) {
+            message::plain(format!(
+                "Environment {description} is already at generation {}",
+                self.generation
+            ));
+            return Ok(());
+        }
+
+        Dialog {
+            message: &format!("Switching to generation {}...", self.generation),
+            help_message: None,
+            typed: Spinner::new(|| env.switch_generation(&flox, self.generation.clone())),
+        }
+        .spin()?;
+
+        message::updated(format!(
+            "Switched environment {description} to generation {}",
+            self.generation
+        ));
+
+        Ok(())
+    }
+}
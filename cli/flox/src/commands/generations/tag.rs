@@ -0,0 +1,89 @@
+use anyhow::{bail, Context, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::generations::GenerationId;
+use flox_rust_sdk::models::environment_ref::GenerationTag;
+use tracing::instrument;
+
+use super::GenerationsEnvironment;
+use crate::commands::{ensure_floxhub_token, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::dialog::{Dialog, Spinner};
+use crate::utils::message;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Tag {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Remove the tag instead of creating or moving it
+    #[bpaf(long)]
+    delete: bool,
+
+    /// The name of the tag
+    #[bpaf(positional("tag"))]
+    tag: GenerationTag,
+
+    /// The generation to tag (default: current generation)
+    #[bpaf(positional("generation"))]
+    generation: Option<GenerationId>,
+}
+
+impl Tag {
+    #[instrument(name = "tag", skip_all)]
+    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("generations::tag");
+
+        if self.delete && self.generation.is_some() {
+            bail!("A generation can not be specified when deleting a tag");
+        }
+
+        if self.environment.is_floxhub_remote() {
+            ensure_floxhub_token(&mut flox).await?;
+        }
+
+        let (mut env, description) = GenerationsEnvironment::from_environment_selection(
+            &flox,
+            &self.environment,
+            "Tag generation of",
+        )?;
+
+        if self.delete {
+            let generation = Dialog {
+                message: &format!("Removing tag '{}'...", self.tag),
+                help_message: None,
+                typed: Spinner::new(|| env.remove_tag(&flox, &self.tag)),
+            }
+            .spin()?;
+
+            message::deleted(format!(
+                "Removed tag '{}' from generation {generation} of environment {description}",
+                self.tag
+            ));
+            return Ok(());
+        }
+
+        let generation = match self.generation {
+            Some(generation) => generation,
+            None => env
+                .generations()
+                .metadata()?
+                .current_gen
+                .context("Environment does not have a current generation")?,
+        };
+
+        Dialog {
+            message: &format!("Tagging generation {generation}..."),
+            help_message: None,
+            typed: Spinner::new(|| env.tag_generation(&flox, self.tag.clone(), generation.clone())),
+        }
+        .spin()?;
+
+        message::updated(format!(
+            "Tagged generation {generation} of environment {description} as '{}'",
+            self.tag
+        ));
+
+        Ok(())
+    }
+}
@@ -152,6 +152,7 @@ impl InitHook for Go {
                 version: go_version,
                 systems: None,
             }]),
+            vars: None,
         }
     }
 }
@@ -0,0 +1,545 @@
+//! Translate environments defined for other tools into an [InitCustomization]
+//!
+//! Supported are devbox (`devbox.json`), asdf (`.tool-versions`)
+//! and mise (`mise.toml`).
+//! Packages are mapped to catalog pkg-paths,
+//! and requested versions are added as version constraints
+//! if the catalog provides a matching version.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::path_environment::InitCustomization;
+use flox_rust_sdk::models::manifest::CatalogPackage;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::{get_default_package, try_find_compatible_version};
+use crate::utils::message;
+
+/// Tool names used by devbox, asdf and mise that differ from their catalog pkg-path
+const TOOL_PKG_PATHS: &[(&str, &str)] = &[
+    ("golang", "go"),
+    ("java", "jdk"),
+    ("node", "nodejs"),
+    ("postgres", "postgresql"),
+    ("python", "python3"),
+    ("rust", "rustc"),
+];
+
+/// The formats that can be imported with `flox init --import`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ImportFormat {
+    Devbox,
+    ToolVersions,
+    Mise,
+}
+
+impl ImportFormat {
+    /// Detect the format of a file by its name
+    pub(super) fn detect(path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        match file_name.as_ref() {
+            "devbox.json" => Ok(ImportFormat::Devbox),
+            ".tool-versions" => Ok(ImportFormat::ToolVersions),
+            "mise.toml" | ".mise.toml" | "mise.local.toml" | ".mise.local.toml" => {
+                Ok(ImportFormat::Mise)
+            },
+            _ => bail!(
+                "Can't import '{}': expected a 'devbox.json', '.tool-versions' or 'mise.toml' file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// A package requested by an imported file
+#[derive(Debug, Clone, PartialEq)]
+struct ImportedPackage {
+    /// The name of the package or tool, before mapping it to a pkg-path
+    name: String,
+    version: Option<String>,
+}
+
+impl ImportedPackage {
+    /// Versions that don't constrain the version of a package
+    const UNCONSTRAINED_VERSIONS: &'static [&'static str] = &["latest", "lts", "stable", "system"];
+
+    fn new(name: impl ToString, version: Option<&str>) -> Self {
+        let version = version
+            .map(str::trim)
+            .filter(|version| !version.is_empty())
+            .filter(|version| !Self::UNCONSTRAINED_VERSIONS.contains(version))
+            .map(ToString::to_string);
+        Self {
+            name: name.to_string(),
+            version,
+        }
+    }
+
+    fn pkg_path(&self) -> &str {
+        TOOL_PKG_PATHS
+            .iter()
+            .find(|(tool, _)| *tool == self.name)
+            .map(|(_, pkg_path)| *pkg_path)
+            .unwrap_or(&self.name)
+    }
+}
+
+/// The parts of an imported file that can be translated to a manifest
+#[derive(Debug, Default, PartialEq)]
+struct ImportedEnvironment {
+    packages: Vec<ImportedPackage>,
+    vars: BTreeMap<String, String>,
+    hook: Option<String>,
+}
+
+/// Read `path` and translate it into an [InitCustomization]
+///
+/// Packages that can't be found in the catalog are skipped with a warning.
+pub(super) async fn import(flox: &Flox, path: &Path) -> Result<InitCustomization> {
+    let format = ImportFormat::detect(path)?;
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let imported = match format {
+        ImportFormat::Devbox => parse_devbox(&contents),
+        ImportFormat::ToolVersions => parse_tool_versions(&contents),
+        ImportFormat::Mise => parse_mise(&contents),
+    }
+    .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+    debug!(?format, ?imported, "parsed imported environment");
+
+    let mut packages = Vec::new();
+    for package in imported.packages {
+        if let Some(package) = resolve_package(flox, &package).await? {
+            packages.push(package);
+        }
+    }
+
+    Ok(InitCustomization {
+        hook_on_activate: imported.hook,
+        packages: (!packages.is_empty()).then_some(packages),
+        vars: (!imported.vars.is_empty()).then_some(imported.vars),
+        ..Default::default()
+    })
+}
+
+/// Find `package` in the catalog
+///
+/// Requested versions are kept as a version constraint if the catalog
+/// provides a matching version, otherwise the default version is used.
+async fn resolve_package(flox: &Flox, package: &ImportedPackage) -> Result<Option<CatalogPackage>> {
+    let pkg_path = package.pkg_path();
+
+    if let Some(version) = &package.version {
+        if let Some(found) = try_find_compatible_version(flox, pkg_path, version).await? {
+            return Ok(Some(CatalogPackage {
+                id: found.name,
+                pkg_path: pkg_path.to_string(),
+                version: Some(version.clone()),
+                systems: None,
+            }));
+        }
+    }
+
+    let Ok(found) = get_default_package(flox, &pkg_path.into()).await else {
+        message::warning(format!(
+            "Skipping '{}': '{pkg_path}' was not found in the catalog",
+            package.name
+        ));
+        return Ok(None);
+    };
+
+    if let Some(version) = &package.version {
+        message::warning(format!(
+            "No version of '{pkg_path}' matching '{version}' was found in the catalog, using {} instead",
+            found.display_version
+        ));
+    }
+
+    Ok(Some(CatalogPackage {
+        id: found.name,
+        pkg_path: pkg_path.to_string(),
+        version: None,
+        systems: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn first(&self) -> Option<&str> {
+        match self {
+            OneOrMany::One(value) => Some(value),
+            OneOrMany::Many(values) => values.first().map(String::as_str),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DevboxJson {
+    #[serde(default)]
+    packages: Option<DevboxPackages>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    shell: Option<DevboxShell>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DevboxPackages {
+    /// `["python@3.11", "go"]`
+    List(Vec<String>),
+    /// `{ "python": "3.11", "go": { "version": "latest" } }`
+    Map(BTreeMap<String, DevboxPackage>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DevboxPackage {
+    Version(String),
+    Table { version: Option<String> },
+}
+
+#[derive(Debug, Deserialize)]
+struct DevboxShell {
+    init_hook: Option<OneOrMany>,
+}
+
+fn parse_devbox(contents: &str) -> Result<ImportedEnvironment> {
+    let devbox: DevboxJson = serde_json::from_str(contents)?;
+
+    let requested = match devbox.packages {
+        None => vec![],
+        Some(DevboxPackages::List(packages)) => packages
+            .into_iter()
+            .map(|package| match package.rsplit_once('@') {
+                Some((name, version)) if !name.is_empty() => {
+                    (name.to_string(), Some(version.to_string()))
+                },
+                _ => (package, None),
+            })
+            .collect(),
+        Some(DevboxPackages::Map(packages)) => packages
+            .into_iter()
+            .map(|(name, package)| match package {
+                DevboxPackage::Version(version) => (name, Some(version)),
+                DevboxPackage::Table { version } => (name, version),
+            })
+            .collect::<Vec<_>>(),
+    };
+
+    let mut packages = Vec::new();
+    for (name, version) in requested {
+        // flake references and other plugins can't be translated to catalog packages
+        if name.contains(':') || name.contains('#') {
+            message::warning(format!(
+                "Skipping '{name}': only packages from nixpkgs can be imported"
+            ));
+            continue;
+        }
+        packages.push(ImportedPackage::new(name, version.as_deref()));
+    }
+
+    let hook = devbox
+        .shell
+        .and_then(|shell| shell.init_hook)
+        .map(|init_hook| match init_hook {
+            OneOrMany::One(script) => script,
+            OneOrMany::Many(lines) => lines.join("\n"),
+        })
+        .filter(|script| !script.trim().is_empty());
+
+    Ok(ImportedEnvironment {
+        packages,
+        vars: devbox.env,
+        hook,
+    })
+}
+
+/// Parse an asdf `.tool-versions` file
+///
+/// Each line names a tool followed by one or more versions,
+/// of which the first one is preferred.
+fn parse_tool_versions(contents: &str) -> Result<ImportedEnvironment> {
+    let mut packages = Vec::new();
+    for line in contents.lines() {
+        let line = line.split_once('#').map_or(line, |(line, _comment)| line);
+        let mut words = line.split_whitespace();
+        let Some(tool) = words.next() else {
+            continue;
+        };
+        let Some(version) = words.next() else {
+            bail!("no version given for '{tool}'");
+        };
+        packages.push(ImportedPackage::new(tool, tool_version(version)));
+    }
+
+    Ok(ImportedEnvironment {
+        packages,
+        ..Default::default()
+    })
+}
+
+/// Versions referring to a git ref or a local path can't be constrained
+fn tool_version(version: &str) -> Option<&str> {
+    if version.starts_with("ref:") || version.starts_with("path:") {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MiseToml {
+    #[serde(default)]
+    tools: BTreeMap<String, MiseTool>,
+    #[serde(default)]
+    env: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MiseTool {
+    /// `node = "20"` or `python = ["3.11", "3.10"]`
+    Version(OneOrMany),
+    /// `node = { version = "20", postinstall = "..." }`
+    Table { version: OneOrMany },
+}
+
+fn parse_mise(contents: &str) -> Result<ImportedEnvironment> {
+    let mise: MiseToml = toml::from_str(contents)?;
+
+    let mut packages = Vec::new();
+    for (tool, version) in mise.tools {
+        // tools from the core and asdf backends use the plain tool name,
+        // other backends (npm, cargo, ubi, ...) don't correspond to catalog packages
+        let name = match tool.split_once(':') {
+            None => tool.as_str(),
+            Some(("core" | "asdf", name)) => name,
+            Some(_) => {
+                message::warning(format!(
+                    "Skipping '{tool}': only tools from the core and asdf backends can be imported"
+                ));
+                continue;
+            },
+        };
+        let version = match &version {
+            MiseTool::Version(version) | MiseTool::Table { version } => version.first(),
+        };
+        packages.push(ImportedPackage::new(name, version.and_then(tool_version)));
+    }
+
+    let mut vars = BTreeMap::new();
+    for (name, value) in mise.env {
+        let value = match value {
+            toml::Value::String(value) => value,
+            toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                value.to_string()
+            },
+            _ => {
+                // includes directives like `_.file` and `_.path`
+                message::warning(format!(
+                    "Skipping environment variable '{name}': unsupported value"
+                ));
+                continue;
+            },
+        };
+        vars.insert(name, value);
+    }
+
+    Ok(ImportedEnvironment {
+        packages,
+        vars,
+        hook: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use flox_rust_sdk::data::System;
+    use flox_rust_sdk::flox::test_helpers::flox_instance;
+    use flox_rust_sdk::providers::catalog::test_helpers::resolved_pkg_group_with_dummy_package;
+    use flox_rust_sdk::providers::catalog::Client;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn detects_formats_by_file_name() {
+        assert_eq!(
+            ImportFormat::detect(Path::new("project/devbox.json")).unwrap(),
+            ImportFormat::Devbox
+        );
+        assert_eq!(
+            ImportFormat::detect(Path::new(".tool-versions")).unwrap(),
+            ImportFormat::ToolVersions
+        );
+        assert_eq!(
+            ImportFormat::detect(Path::new(".mise.toml")).unwrap(),
+            ImportFormat::Mise
+        );
+        assert!(ImportFormat::detect(Path::new("package.json")).is_err());
+    }
+
+    #[test]
+    fn parses_devbox_json() {
+        let imported = parse_devbox(indoc! {r#"
+            {
+              "packages": ["python@3.11", "go@latest", "ripgrep", "github:numtide/flake-utils#hello"],
+              "env": { "GREETING": "hello" },
+              "shell": {
+                "init_hook": ["echo one", "echo two"],
+                "scripts": { "test": "pytest" }
+              }
+            }
+        "#})
+        .unwrap();
+
+        assert_eq!(imported, ImportedEnvironment {
+            packages: vec![
+                ImportedPackage::new("python", Some("3.11")),
+                ImportedPackage::new("go", None),
+                ImportedPackage::new("ripgrep", None),
+            ],
+            vars: BTreeMap::from([("GREETING".to_string(), "hello".to_string())]),
+            hook: Some("echo one\necho two".to_string()),
+        });
+        assert_eq!(imported.packages[0].pkg_path(), "python3");
+
+        let imported = parse_devbox(indoc! {r#"
+            { "packages": { "nodejs": "20", "go": { "version": "1.22" } } }
+        "#})
+        .unwrap();
+        assert_eq!(imported.packages, vec![
+            ImportedPackage::new("go", Some("1.22")),
+            ImportedPackage::new("nodejs", Some("20")),
+        ]);
+    }
+
+    #[test]
+    fn parses_tool_versions() {
+        let imported = parse_tool_versions(indoc! {"
+            # runtimes
+            nodejs 20.11.0
+            python 3.11.4 3.10.13 # fallback
+            ruby ref:v3_3_0
+
+            terraform latest
+        "})
+        .unwrap();
+
+        assert_eq!(imported.packages, vec![
+            ImportedPackage::new("nodejs", Some("20.11.0")),
+            ImportedPackage::new("python", Some("3.11.4")),
+            ImportedPackage::new("ruby", None),
+            ImportedPackage::new("terraform", None),
+        ]);
+
+        assert!(parse_tool_versions("nodejs\n").is_err());
+    }
+
+    #[test]
+    fn parses_mise_toml() {
+        let imported = parse_mise(indoc! {r#"
+            [tools]
+            node = "20"
+            python = ["3.11", "3.10"]
+            "core:go" = { version = "1.22" }
+            "npm:prettier" = "latest"
+
+            [env]
+            DEBUG = true
+            PORT = 8080
+            NAME = "app"
+            _.file = ".env"
+        "#})
+        .unwrap();
+
+        assert_eq!(imported, ImportedEnvironment {
+            packages: vec![
+                ImportedPackage::new("go", Some("1.22")),
+                ImportedPackage::new("node", Some("20")),
+                ImportedPackage::new("python", Some("3.11")),
+            ],
+            vars: BTreeMap::from([
+                ("DEBUG".to_string(), "true".to_string()),
+                ("NAME".to_string(), "app".to_string()),
+                ("PORT".to_string(), "8080".to_string()),
+            ]),
+            hook: None,
+        });
+        assert_eq!(imported.packages[1].pkg_path(), "nodejs");
+    }
+
+    /// Versions found in the catalog are kept as constraints
+    #[tokio::test]
+    async fn resolve_package_keeps_compatible_version() {
+        let (mut flox, _temp_dir_handle) = flox_instance();
+
+        if let Client::Mock(ref mut client) = flox.catalog_client {
+            client.push_resolve_response(vec![resolved_pkg_group_with_dummy_package(
+                "python3_group",
+                &System::from("aarch64-darwin"),
+                "python3",
+                "python3",
+                "3.11.9",
+            )]);
+        }
+
+        let package = resolve_package(&flox, &ImportedPackage::new("python", Some("3.11")))
+            .await
+            .unwrap();
+        assert_eq!(
+            package,
+            Some(CatalogPackage {
+                id: "python3".to_string(),
+                pkg_path: "python3".to_string(),
+                version: Some("3.11".to_string()),
+                systems: None,
+            })
+        );
+    }
+
+    /// Unavailable versions fall back to the default version
+    #[tokio::test]
+    async fn resolve_package_falls_back_to_default_version() {
+        let (mut flox, _temp_dir_handle) = flox_instance();
+
+        if let Client::Mock(ref mut client) = flox.catalog_client {
+            client.push_resolve_response(vec![]);
+            client.push_resolve_response(vec![resolved_pkg_group_with_dummy_package(
+                "nodejs_group",
+                &System::from("aarch64-darwin"),
+                "nodejs",
+                "nodejs",
+                "22.2.0",
+            )]);
+        }
+
+        let package = resolve_package(&flox, &ImportedPackage::new("node", Some("8")))
+            .await
+            .unwrap();
+        assert_eq!(
+            package,
+            Some(CatalogPackage {
+                id: "nodejs".to_string(),
+                pkg_path: "nodejs".to_string(),
+                version: None,
+                systems: None,
+            })
+        );
+    }
+}
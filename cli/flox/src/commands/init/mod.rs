@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
@@ -28,6 +28,7 @@ use crate::utils::dialog::{Dialog, Spinner};
 use crate::utils::message;
 
 mod go;
+mod import;
 mod node;
 mod python;
 
@@ -81,6 +82,11 @@ pub struct Init {
     /// are being used in the containing directory
     #[bpaf(long)]
     auto_setup: bool,
+
+    /// Create the environment from a 'devbox.json', '.tool-versions'
+    /// or 'mise.toml' file instead of applying Flox recommendations
+    #[bpaf(long("import"), argument("file"))]
+    import: Option<PathBuf>,
 }
 
 impl Init {
@@ -108,8 +114,10 @@ impl Init {
             EnvironmentName::from_str(&name)?
         };
 
-        // Don't run language hooks in home dir
-        let customization = if dir != home_dir || self.auto_setup {
+        let customization = if let Some(ref import_path) = self.import {
+            import::import(&flox, import_path).await?
+        } else if dir != home_dir || self.auto_setup {
+            // Don't run language hooks in home dir
             self.run_language_hooks(&flox, &dir)
                 .await
                 .unwrap_or_else(|e| {
@@ -123,7 +131,11 @@ impl Init {
 
         let env = if customization.packages.is_some() {
             Dialog {
-                message: "Installing Flox suggested packages...",
+                message: if self.import.is_some() {
+                    "Installing imported packages..."
+                } else {
+                    "Installing Flox suggested packages..."
+                },
                 help_message: None,
                 typed: Spinner::new(|| {
                     PathEnvironment::init(PathPointer::new(env_name), &dir, &customization, &flox)
@@ -195,10 +207,14 @@ impl Init {
         let mut custom_profile_zsh_scripts: Vec<String> = vec![];
         // Deduplicate packages with a set
         let mut packages_set = HashSet::<CatalogPackage>::new();
+        let mut vars = BTreeMap::new();
         for customization in customizations {
             if let Some(packages) = customization.packages {
                 packages_set.extend(packages)
             }
+            if let Some(customization_vars) = customization.vars {
+                vars.extend(customization_vars)
+            }
             if let Some(hook_on_activate_script) = customization.hook_on_activate {
                 custom_hook_on_activate_scripts.push(hook_on_activate_script)
             }
@@ -284,6 +300,7 @@ impl Init {
             profile_tcsh: custom_profile_tcsh,
             profile_zsh: custom_profile_zsh,
             packages,
+            vars: (!vars.is_empty()).then_some(vars),
         }
     }
 }
@@ -309,6 +326,23 @@ fn format_customization(customization: &InitCustomization) -> Result<String> {
         DocumentMut::new()
     };
 
+    // Add the "vars" section to the toml document.
+    if let Some(vars) = &customization.vars {
+        let vars_table = {
+            let vars_field = toml
+                .entry("vars")
+                .or_insert_with(|| Item::Table(Table::new()));
+            let vars_field_type = vars_field.type_name();
+            vars_field.as_table_mut().context(format!(
+                "'vars' must be a table, but found {vars_field_type} instead"
+            ))?
+        };
+        vars_table.extend(
+            vars.iter()
+                .map(|(name, value)| (name, toml_edit::value(value))),
+        );
+    }
+
     // Add the "hook" section to the toml document.
     let hook_table = {
         let hook_field = toml
@@ -704,6 +738,7 @@ mod tests {
                         systems: None,
                     },
                 ]),
+                vars: None,
             },
             InitCustomization {
                 hook_on_activate: Some("hook_on_activate2".to_string()),
@@ -726,6 +761,7 @@ mod tests {
                         systems: None,
                     },
                 ]),
+                vars: None,
             },
         ];
 
@@ -823,6 +859,7 @@ mod tests {
                     systems: None,
                 },
             ]),
+            vars: None,
         });
     }
 }
@@ -689,6 +689,7 @@ impl InitHook for Node {
             profile_tcsh: None,
             profile_zsh: None,
             packages: Some(packages),
+            vars: None,
         }
     }
 }
@@ -789,6 +790,7 @@ mod tests {
                 profile_fish: None,
                 profile_tcsh: None,
                 profile_zsh: None,
+                vars: None,
             }
         );
     }
@@ -841,6 +843,7 @@ mod tests {
                 profile_fish: None,
                 profile_tcsh: None,
                 profile_zsh: None,
+                vars: None,
             }
         );
     }
@@ -875,6 +878,7 @@ mod tests {
                 profile_fish: None,
                 profile_tcsh: None,
                 profile_zsh: None,
+                vars: None,
             }
         );
     }
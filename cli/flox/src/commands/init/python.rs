@@ -461,6 +461,7 @@ impl Provider for PoetryPyProject {
                     systems: None,
                 },
             ]),
+            vars: None,
         }
     }
 }
@@ -652,6 +653,7 @@ impl Provider for PyProject {
                 version: python_version,
                 systems: None,
             }]),
+            vars: None,
         }
     }
 }
@@ -804,6 +806,7 @@ impl Provider for Requirements {
                 version: None,
                 systems: None,
             }]),
+            vars: None,
         }
     }
 }
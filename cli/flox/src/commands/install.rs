@@ -92,7 +92,7 @@ impl Install {
         );
 
         // Ensure the user is logged in for the following remote operations
-        if let EnvironmentSelect::Remote(_) = self.environment {
+        if self.environment.is_floxhub_remote() {
             ensure_floxhub_token(&mut flox).await?;
         }
 
@@ -1,7 +1,8 @@
 use std::io::{stdout, Write};
+use std::str::FromStr;
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bpaf::Bpaf;
 use flox_rust_sdk::flox::Flox;
 use flox_rust_sdk::models::environment::Environment;
@@ -9,11 +10,12 @@ use flox_rust_sdk::models::lockfile::{
     InstalledPackage,
     LockedManifest,
     LockedPackageFlake,
+    LockedPackageStorePath,
     PackageInfo,
     PackageToList,
     TypedLockedManifestPkgdb,
 };
-use flox_rust_sdk::models::manifest::DEFAULT_PRIORITY;
+use flox_rust_sdk::models::manifest::{RawManifest, DEFAULT_PRIORITY};
 use flox_rust_sdk::providers::flox_cpp_utils::LockedInstallable;
 use indoc::formatdoc;
 use itertools::Itertools;
@@ -67,7 +69,24 @@ impl List {
 
         let manifest_contents = env.manifest_contents(&flox)?;
         if self.list_mode == ListMode::Config {
-            println!("{}", manifest_contents);
+            // Secrets can only be redacted from a valid manifest,
+            // so never print the raw contents of an invalid one,
+            // not even the line containing the error.
+            let mut manifest = RawManifest::from_str(&manifest_contents).map_err(|err| {
+                let line = err
+                    .span()
+                    .map(|span| manifest_contents[..span.start].matches('\n').count() + 1);
+                let location = line
+                    .map(|line| format!(" at line {line}"))
+                    .unwrap_or_default();
+                anyhow!(formatdoc! {"
+                    Couldn't parse the manifest{location}: {message}
+                    Use 'flox edit' to fix it.",
+                    message = err.message().trim_end(),
+                })
+            })?;
+            manifest.redact_secrets();
+            print!("{}", *manifest);
             return Ok(());
         }
 
@@ -112,6 +131,7 @@ impl List {
             let install_id = match p {
                 PackageToList::CatalogOrPkgdb(p) => &p.install_id,
                 PackageToList::Flake(_, p) => &p.install_id,
+                PackageToList::StorePath(p) => &p.install_id,
             };
             writeln!(&mut out, "{install_id}")?;
         }
@@ -143,6 +163,14 @@ impl List {
                         flake = descriptor.flake
                     )?;
                 },
+                PackageToList::StorePath(locked_package) => {
+                    writeln!(
+                        &mut out,
+                        "{id}: {store_path}",
+                        id = locked_package.install_id,
+                        store_path = locked_package.store_path
+                    )?;
+                },
             }
         }
         Ok(())
@@ -155,6 +183,7 @@ impl List {
             .sorted_by_key(|p| match p {
                 PackageToList::CatalogOrPkgdb(p) => p.priority.unwrap_or(DEFAULT_PRIORITY),
                 PackageToList::Flake(..) => DEFAULT_PRIORITY,
+                PackageToList::StorePath(p) => p.priority,
             })
             .enumerate()
         {
@@ -241,6 +270,20 @@ impl List {
                         broken = broken.map(|b|b.to_string()).as_deref().unwrap_or("N/A"),
                     }
                 },
+                PackageToList::StorePath(package) => {
+                    let LockedPackageStorePath {
+                        install_id,
+                        store_path,
+                        priority,
+                        ..
+                    } = package;
+
+                    formatdoc! {"
+                    {install_id}:
+                      Store path: {store_path}
+                      Priority:   {priority}
+                    "}
+                },
             };
             // add an empty line between packages
             if idx < packages.len() - 1 {
@@ -294,6 +337,7 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::commands::EnvironmentLocation;
 
     fn test_packages() -> [PackageToList; 2] {
         [
@@ -589,7 +633,12 @@ mod tests {
         let environment =
             new_path_environment_from_env_files(&flox, MANUALLY_GENERATED.join("hello_v0"));
         List {
-            environment: EnvironmentSelect::Dir(environment.path.parent().unwrap().to_path_buf()),
+            environment: EnvironmentSelect {
+                location: EnvironmentLocation::Dir(
+                    environment.path.parent().unwrap().to_path_buf(),
+                ),
+                ..Default::default()
+            },
             list_mode: ListMode::Extended,
         }
         .handle(flox)
@@ -607,7 +656,12 @@ mod tests {
                 .unwrap();
         let environment = new_path_environment(&flox, &manifest_contents);
         let err = List {
-            environment: EnvironmentSelect::Dir(environment.path.parent().unwrap().to_path_buf()),
+            environment: EnvironmentSelect {
+                location: EnvironmentLocation::Dir(
+                    environment.path.parent().unwrap().to_path_buf(),
+                ),
+                ..Default::default()
+            },
             list_mode: ListMode::Extended,
         }
         .handle(flox)
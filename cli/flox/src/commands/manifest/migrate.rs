@@ -0,0 +1,92 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::manifest::RawManifest;
+use flox_rust_sdk::models::migration::{preview_manifest_migration, LATEST_MANIFEST_VERSION};
+use tracing::instrument;
+
+use crate::commands::{
+    ensure_floxhub_token,
+    environment_description,
+    environment_select,
+    EnvironmentSelect,
+};
+use crate::subcommand_metric;
+use crate::utils::dialog::{Dialog, Spinner};
+use crate::utils::message;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Migrate {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Show the changes that would be made to the manifest
+    /// without modifying the environment
+    #[bpaf(long)]
+    dry_run: bool,
+}
+
+impl Migrate {
+    #[instrument(name = "migrate", skip_all)]
+    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("manifest::migrate");
+
+        // Ensure the user is logged in for the following remote operations
+        if self.environment.is_floxhub_remote() && !self.dry_run {
+            ensure_floxhub_token(&mut flox).await?;
+        };
+
+        let concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Migrate")?;
+        let description = environment_description(&concrete_environment)?;
+        let mut environment = concrete_environment.into_dyn_environment();
+
+        let Some(migration_info) = environment.needs_migration(&flox)? else {
+            message::plain(format!(
+                "ℹ️  Environment {description} is already at manifest version {LATEST_MANIFEST_VERSION}."
+            ));
+            return Ok(());
+        };
+
+        if self.dry_run {
+            let raw_manifest = RawManifest::from_str(&environment.manifest_contents(&flox)?)?;
+            let preview = preview_manifest_migration(&raw_manifest)?;
+            for migration in &preview.migrations {
+                message::plain(format!(
+                    "Version {} to {}: {}",
+                    migration.from,
+                    migration.to(),
+                    migration.description
+                ));
+            }
+            if !preview.diff.is_empty() {
+                print!("{}", preview.diff);
+            }
+            if migration_info.needs_upgrade {
+                message::plain("The environment would be locked again, upgrading its packages.");
+            }
+            return Ok(());
+        }
+
+        let needs_upgrade = migration_info.needs_upgrade;
+        Dialog {
+            message: "Migrating environment...",
+            help_message: None,
+            typed: Spinner::new(|| environment.migrate(&flox, migration_info)),
+        }
+        .spin()?;
+
+        message::updated(format!(
+            "Migrated environment {description} to manifest version {LATEST_MANIFEST_VERSION}."
+        ));
+        if needs_upgrade {
+            message::plain(format!(
+                "⬆️  Upgraded all packages in environment {description}."
+            ));
+        }
+        Ok(())
+    }
+}
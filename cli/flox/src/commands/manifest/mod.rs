@@ -0,0 +1,75 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use tracing::instrument;
+
+use super::edit::report_edit_result;
+use super::{ConcreteEnvironment, UninitializedEnvironment};
+use crate::utils::dialog::{Dialog, Spinner};
+use crate::utils::errors::apply_doc_link_for_unsupported_packages;
+
+mod migrate;
+mod schema;
+mod set;
+mod unset;
+
+/// Manifest Commands.
+#[derive(Debug, Clone, Bpaf)]
+pub enum ManifestCommands {
+    /// Migrate the manifest to the latest schema version
+    #[bpaf(command, footer("Run 'man flox-manifest-migrate' for more details."))]
+    Migrate(#[bpaf(external(migrate::migrate))] migrate::Migrate),
+
+    /// Set keys in the manifest
+    #[bpaf(command, footer("Run 'man flox-manifest-set' for more details."))]
+    Set(#[bpaf(external(set::set))] set::Set),
+
+    /// Remove keys from the manifest
+    #[bpaf(command, footer("Run 'man flox-manifest-unset' for more details."))]
+    Unset(#[bpaf(external(unset::unset))] unset::Unset),
+
+    /// Print the JSON Schema of the manifest
+    #[bpaf(command, footer("Run 'man flox-manifest-schema' for more details."))]
+    Schema(#[bpaf(external(schema::schema))] schema::Schema),
+}
+
+impl ManifestCommands {
+    #[instrument(name = "manifest", skip_all)]
+    pub async fn handle(self, flox: Flox) -> Result<()> {
+        match self {
+            ManifestCommands::Migrate(args) => args.handle(flox).await?,
+            ManifestCommands::Schema(args) => args.handle()?,
+            ManifestCommands::Set(args) => args.handle(flox).await?,
+            ManifestCommands::Unset(args) => args.handle(flox).await?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Apply `edit` to the manifest of `environment`,
+/// then validate, lock and build the result like `flox edit`.
+///
+/// The environment is only modified if all of these steps succeed.
+fn apply_manifest_edit(
+    flox: &Flox,
+    environment: ConcreteEnvironment,
+    edit: impl FnOnce(&str) -> Result<String>,
+) -> Result<()> {
+    let active_environment = UninitializedEnvironment::from_concrete_environment(&environment)?;
+    let mut environment = environment.into_dyn_environment();
+
+    let contents = edit(&environment.manifest_contents(flox)?)?;
+
+    let result = Dialog {
+        message: "Building environment to validate edit...",
+        help_message: None,
+        typed: Spinner::new(|| environment.edit(flox, contents)),
+    }
+    .spin()
+    .map_err(apply_doc_link_for_unsupported_packages)?;
+
+    report_edit_result(flox, environment.as_ref(), &active_environment, &result);
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::models::manifest_schema::manifest_schema;
+use tracing::instrument;
+
+use crate::subcommand_metric;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Schema {}
+
+impl Schema {
+    #[instrument(name = "schema", skip_all)]
+    pub fn handle(self) -> Result<()> {
+        subcommand_metric!("manifest::schema");
+
+        println!("{}", serde_json::to_string_pretty(&manifest_schema())?);
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::manifest::set_value;
+use toml_edit::Key;
+use tracing::instrument;
+
+use super::apply_manifest_edit;
+use crate::commands::{
+    ensure_floxhub_token,
+    environment_select,
+    maybe_migrate_environment_to_v1,
+    EnvironmentSelect,
+};
+use crate::subcommand_metric;
+
+/// A `<key>=<value>` pair to set in the manifest
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    key: String,
+    value: String,
+}
+
+impl std::str::FromStr for Assignment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Quoted keys may contain '=' (e.g. `vars."A=B"=1`),
+        // so split at the first '=' that ends a valid dotted key.
+        // If there is none, keep the first split and let `set_value`
+        // report the invalid key.
+        let split_points = s.match_indices('=').map(|(index, _)| index);
+        let index = split_points
+            .clone()
+            .find(|index| Key::parse(&s[..*index]).is_ok())
+            .or_else(|| split_points.clone().next())
+            .ok_or_else(|| anyhow!("expected <key>=<value>, found '{s}'"))?;
+        Ok(Assignment {
+            key: s[..index].to_string(),
+            value: s[index + 1..].to_string(),
+        })
+    }
+}
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Set {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Dotted key and value to set, e.g. 'vars.FOO=bar'
+    #[bpaf(
+        positional("key=value"),
+        some("must provide at least one key=value pair")
+    )]
+    assignments: Vec<Assignment>,
+}
+
+impl Set {
+    #[instrument(name = "set", skip_all)]
+    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("manifest::set");
+
+        // Ensure the user is logged in for the following remote operations
+        if self.environment.is_floxhub_remote() {
+            ensure_floxhub_token(&mut flox).await?;
+        };
+
+        let mut concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Modify")?;
+        maybe_migrate_environment_to_v1(&flox, &mut concrete_environment).await?;
+
+        apply_manifest_edit(&flox, concrete_environment, |contents| {
+            self.assignments
+                .iter()
+                .try_fold(contents.to_string(), |contents, assignment| {
+                    Ok(set_value(&contents, &assignment.key, &assignment.value)?.to_string())
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(s: &str) -> (String, String) {
+        let assignment = s.parse::<Assignment>().unwrap();
+        (assignment.key, assignment.value)
+    }
+
+    #[test]
+    fn assignment_splits_at_first_equals_sign() {
+        assert_eq!(
+            assignment("vars.FOO=bar"),
+            ("vars.FOO".into(), "bar".into())
+        );
+        assert_eq!(
+            assignment("vars.FOO=a=b"),
+            ("vars.FOO".into(), "a=b".into())
+        );
+        assert_eq!(assignment("vars.FOO="), ("vars.FOO".into(), "".into()));
+    }
+
+    #[test]
+    fn assignment_keeps_equals_sign_in_quoted_keys() {
+        assert_eq!(
+            assignment(r#"vars."A=B"=1"#),
+            (r#"vars."A=B""#.into(), "1".into())
+        );
+        assert_eq!(
+            assignment("vars.'A=B'=x=y"),
+            ("vars.'A=B'".into(), "x=y".into())
+        );
+    }
+
+    #[test]
+    fn assignment_requires_equals_sign() {
+        assert!("vars.FOO".parse::<Assignment>().is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use bpaf::Bpaf;
+use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::manifest::unset_value;
+use tracing::instrument;
+
+use super::apply_manifest_edit;
+use crate::commands::{
+    ensure_floxhub_token,
+    environment_select,
+    maybe_migrate_environment_to_v1,
+    EnvironmentSelect,
+};
+use crate::subcommand_metric;
+
+#[derive(Bpaf, Debug, Clone)]
+pub struct Unset {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Dotted key to remove, e.g. 'vars.FOO'
+    #[bpaf(positional("key"), some("must provide at least one key"))]
+    keys: Vec<String>,
+}
+
+impl Unset {
+    #[instrument(name = "unset", skip_all)]
+    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+        subcommand_metric!("manifest::unset");
+
+        // Ensure the user is logged in for the following remote operations
+        if self.environment.is_floxhub_remote() {
+            ensure_floxhub_token(&mut flox).await?;
+        };
+
+        let mut concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Modify")?;
+        maybe_migrate_environment_to_v1(&flox, &mut concrete_environment).await?;
+
+        apply_manifest_edit(&flox, concrete_environment, |contents| {
+            self.keys
+                .iter()
+                .try_fold(contents.to_string(), |contents, key| {
+                    Ok(unset_value(&contents, key)?.to_string())
+                })
+        })
+    }
+}
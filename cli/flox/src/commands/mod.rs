@@ -1,16 +1,23 @@
 mod activate;
+mod audit;
 mod auth;
 mod build;
+mod check;
 mod containerize;
 mod delete;
+mod diff;
 mod edit;
 mod envs;
+mod export;
 mod general;
+mod generations;
 mod init;
 mod install;
 mod list;
+mod manifest;
 mod pull;
 mod push;
+mod sbom;
 mod search;
 mod services;
 mod show;
@@ -41,6 +48,7 @@ use flox_rust_sdk::flox::{
     FLOX_VERSION,
 };
 use flox_rust_sdk::models::env_registry::{EnvRegistry, ENV_REGISTRY_FILENAME};
+use flox_rust_sdk::models::environment::generations::GenerationId;
 use flox_rust_sdk::models::environment::managed_environment::{
     ManagedEnvironment,
     ManagedEnvironmentError,
@@ -70,6 +78,7 @@ use toml_edit::Key;
 use url::Url;
 
 use self::envs::DisplayEnvironments;
+use self::generations::GenerationsCommandsError;
 use crate::commands::general::update_config;
 use crate::config::{Config, EnvironmentTrust, FLOX_CONFIG_FILE};
 use crate::utils::dialog::{Confirm, Dialog, Select, Spinner};
@@ -100,7 +109,7 @@ static FLOX_DESCRIPTION: &'_ str = indoc! {"
 
 /// Manually documented commands that are to keep the help text short
 const ADDITIONAL_COMMANDS: &str = indoc! {"
-    auth, config, envs, upgrade
+    audit, auth, check, config, diff, envs, export, generations, manifest, sbom, upgrade
 "};
 
 fn vec_len<T>(x: Vec<T>) -> usize {
@@ -168,6 +177,11 @@ pub struct FloxArgs {
     #[bpaf(long, req_flag(()), many, map(vec_not_empty), hide)]
     pub debug: bool,
 
+    /// Fail instead of locking environments again
+    /// if their lockfile is missing or out of date
+    #[bpaf(long)]
+    pub locked: bool,
+
     /// Print the version of the program
     #[allow(dead_code)] // fake arg, `--version` is checked for separately (see [Version])
     #[bpaf(long, short('V'))]
@@ -323,6 +337,7 @@ impl FloxArgs {
             catalog_client,
             installable_locker: Default::default(),
             features: config.features.clone().unwrap_or_default(),
+            locked: self.locked || config.flox.locked,
         };
 
         // in debug mode keep the tempdir to reproduce nix commands
@@ -806,9 +821,9 @@ enum SharingCommands {
 }
 
 impl SharingCommands {
-    async fn handle(self, _config: Config, flox: Flox) -> Result<()> {
+    async fn handle(self, config: Config, flox: Flox) -> Result<()> {
         match self {
-            SharingCommands::Push(args) => args.handle(flox).await?,
+            SharingCommands::Push(args) => args.handle(config, flox).await?,
             SharingCommands::Pull(args) => args.handle(flox).await?,
             SharingCommands::Containerize(args) => args.handle(flox).await?,
         }
@@ -847,6 +862,36 @@ enum AdditionalCommands {
     /// Show active and available environments
     #[bpaf(command, hide, footer("Run 'man flox-envs' for more details."))]
     Envs(#[bpaf(external(envs::envs))] envs::Envs),
+
+    /// List, compare and switch generations of an environment
+    #[bpaf(command, hide)]
+    Generations(
+        #[bpaf(external(generations::generations_commands))] generations::GenerationsCommands,
+    ),
+
+    /// Check the packages of an environment for known vulnerabilities
+    #[bpaf(command, hide, footer("Run 'man flox-audit' for more details."))]
+    Audit(#[bpaf(external(audit::audit))] audit::Audit),
+
+    /// Check an environment for common mistakes
+    #[bpaf(command, hide, footer("Run 'man flox-check' for more details."))]
+    Check(#[bpaf(external(check::check))] check::Check),
+
+    /// Compare the locked packages of two lockfiles or environments
+    #[bpaf(command, hide, footer("Run 'man flox-diff' for more details."))]
+    Diff(#[bpaf(external(diff::diff))] diff::Diff),
+
+    /// Export an environment to other formats
+    #[bpaf(command, hide)]
+    Export(#[bpaf(external(export::export_commands))] export::ExportCommands),
+
+    /// Inspect, migrate and modify the manifest
+    #[bpaf(command, hide)]
+    Manifest(#[bpaf(external(manifest::manifest_commands))] manifest::ManifestCommands),
+
+    /// Generate a software bill of materials for an environment
+    #[bpaf(command, hide, footer("Run 'man flox-sbom' for more details."))]
+    Sbom(#[bpaf(external(sbom::sbom))] sbom::Sbom),
 }
 
 impl AdditionalCommands {
@@ -858,9 +903,16 @@ impl AdditionalCommands {
 
     async fn handle(self, config: Config, flox: Flox) -> Result<()> {
         match self {
+            AdditionalCommands::Audit(args) => args.handle(flox).await?,
+            AdditionalCommands::Check(args) => args.handle(flox).await?,
             AdditionalCommands::Config(args) => args.handle(config, flox).await?,
+            AdditionalCommands::Diff(args) => args.handle(flox).await?,
             AdditionalCommands::Documentation(args) => args.handle(),
             AdditionalCommands::Envs(args) => args.handle(flox)?,
+            AdditionalCommands::Export(args) => args.handle(flox)?,
+            AdditionalCommands::Generations(args) => args.handle(config, flox).await?,
+            AdditionalCommands::Manifest(args) => args.handle(flox).await?,
+            AdditionalCommands::Sbom(args) => args.handle(flox)?,
             AdditionalCommands::Update(args) => args.handle(flox).await?,
             AdditionalCommands::Upgrade(args) => args.handle(flox).await?,
         }
@@ -877,6 +929,7 @@ impl AdditionalCommandsDocumentation {
 }
 
 /// Additional Commands. Use "flox COMMAND --help" for more info
+#[allow(clippy::large_enum_variant)] // there's only a single instance of this enum
 #[derive(Bpaf, Clone)]
 #[bpaf(hide)]
 enum InternalCommands {
@@ -946,23 +999,45 @@ impl Version {
     }
 }
 
+/// The environment a command operates on
+#[derive(Debug, Default, Bpaf, Clone)]
+pub struct EnvironmentSelect {
+    #[bpaf(external(environment_location), fallback(Default::default()))]
+    pub location: EnvironmentLocation,
+
+    /// Use a generation of a managed or remote environment
+    /// instead of its current generation
+    #[bpaf(long, argument("generation"))]
+    pub generation: Option<GenerationId>,
+}
+
+/// Where to find the environment selected with [EnvironmentSelect]
 #[derive(Debug, Default, Bpaf, Clone)]
-pub enum EnvironmentSelect {
+pub enum EnvironmentLocation {
     Dir(
         /// Path containing a .flox/ directory
         #[bpaf(long("dir"), short('d'), argument("path"))]
         PathBuf,
     ),
-    Remote(
-        /// A remote environment on FloxHub
-        #[bpaf(long("remote"), short('r'), argument("owner>/<name"))]
-        environment_ref::EnvironmentRef,
-    ),
+    Remote(#[bpaf(external(remote_select))] RemoteSelect),
     #[default]
     #[bpaf(hide)]
     Unspecified,
 }
 
+/// A remote environment selected with `--remote`
+#[derive(Debug, Bpaf, Clone)]
+pub struct RemoteSelect {
+    /// A remote environment on FloxHub,
+    /// optionally at a tagged generation (<owner>/<name>@<tag>)
+    #[bpaf(long("remote"), short('r'), argument("owner>/<name"))]
+    pub env_ref: environment_ref::EnvironmentRef,
+
+    /// Git repository hosting the remote environment instead of FloxHub
+    #[bpaf(long("git-remote"), argument("url"))]
+    pub git_remote: Option<Url>,
+}
+
 #[derive(Debug, Error)]
 pub enum EnvironmentSelectError {
     #[error(transparent)]
@@ -974,6 +1049,20 @@ pub enum EnvironmentSelectError {
 }
 
 impl EnvironmentSelect {
+    /// Whether the selected environment is hosted on FloxHub,
+    /// which requires the user to be logged in to modify it.
+    ///
+    /// Authentication with plain git remotes is left to git.
+    pub fn is_floxhub_remote(&self) -> bool {
+        matches!(
+            self.location,
+            EnvironmentLocation::Remote(RemoteSelect {
+                git_remote: None,
+                ..
+            })
+        )
+    }
+
     /// Open a concrete environment, not detecting the currently active
     /// environment.
     ///
@@ -985,30 +1074,22 @@ impl EnvironmentSelect {
         &self,
         flox: &Flox,
     ) -> Result<ConcreteEnvironment, EnvironmentSelectError> {
-        match self {
-            EnvironmentSelect::Dir(path) => Ok(open_path(flox, path)?),
-            EnvironmentSelect::Unspecified => {
+        self.ensure_single_generation()?;
+        let environment = match &self.location {
+            EnvironmentLocation::Dir(path) => open_path(flox, path)?,
+            EnvironmentLocation::Unspecified => {
                 let current_dir = env::current_dir().context("could not get current directory")?;
                 let maybe_found_environment = find_dot_flox(&current_dir)?;
                 match maybe_found_environment {
                     Some(found) => {
-                        Ok(UninitializedEnvironment::DotFlox(found)
-                            .into_concrete_environment(flox)?)
+                        UninitializedEnvironment::DotFlox(found).into_concrete_environment(flox)?
                     },
                     None => Err(EnvironmentSelectError::EnvNotFoundInCurrentDirectory)?,
                 }
             },
-            EnvironmentSelect::Remote(env_ref) => {
-                let pointer = ManagedPointer::new(
-                    env_ref.owner().clone(),
-                    env_ref.name().clone(),
-                    &flox.floxhub,
-                );
-
-                let env = RemoteEnvironment::new(flox, pointer).map_err(anyhow::Error::new)?;
-                Ok(ConcreteEnvironment::Remote(env))
-            },
-        }
+            EnvironmentLocation::Remote(remote) => open_remote(flox, remote)?,
+        };
+        self.at_generation(environment)
     }
 
     /// Open a concrete environment, detecting the currently active environment.
@@ -1022,26 +1103,92 @@ impl EnvironmentSelect {
         flox: &Flox,
         message: &str,
     ) -> Result<ConcreteEnvironment, EnvironmentSelectError> {
-        match self {
-            EnvironmentSelect::Dir(path) => Ok(open_path(flox, path)?),
+        self.ensure_single_generation()?;
+        let environment = match &self.location {
+            EnvironmentLocation::Dir(path) => open_path(flox, path)?,
             // If the user doesn't specify an environment, check if there's an
             // already activated environment or an environment in the current
             // directory.
-            EnvironmentSelect::Unspecified => match detect_environment(message)? {
-                Some(env) => Ok(env.into_concrete_environment(flox)?),
+            EnvironmentLocation::Unspecified => match detect_environment(message)? {
+                Some(env) => env.into_concrete_environment(flox)?,
                 None => Err(EnvironmentSelectError::EnvNotFoundInCurrentDirectory)?,
             },
-            EnvironmentSelect::Remote(env_ref) => {
-                let pointer = ManagedPointer::new(
-                    env_ref.owner().clone(),
-                    env_ref.name().clone(),
-                    &flox.floxhub,
-                );
+            EnvironmentLocation::Remote(remote) => open_remote(flox, remote)?,
+        };
+        self.at_generation(environment)
+    }
 
-                let env = RemoteEnvironment::new(flox, pointer).map_err(anyhow::Error::new)?;
-                Ok(ConcreteEnvironment::Remote(env))
-            },
+    /// Pin the opened environment to the generation selected with `--generation`
+    fn at_generation(
+        &self,
+        environment: ConcreteEnvironment,
+    ) -> Result<ConcreteEnvironment, EnvironmentSelectError> {
+        match &self.generation {
+            Some(generation) => Ok(environment_at_generation(environment, generation.clone())?),
+            None => Ok(environment),
+        }
+    }
+
+    /// Fail if a generation is selected both with a tag and with `--generation`
+    fn ensure_single_generation(&self) -> Result<(), EnvironmentSelectError> {
+        if let EnvironmentLocation::Remote(RemoteSelect { env_ref, .. }) = &self.location {
+            if env_ref.tag().is_some() && self.generation.is_some() {
+                Err(anyhow!(
+                    "Select a generation either with a tag or with '--generation', not both."
+                ))?;
+            }
         }
+        Ok(())
+    }
+}
+
+/// Open a [RemoteEnvironment] for a [RemoteSelect]
+///
+/// If the reference includes a tag, e.g. `owner/name@stable`,
+/// the environment is pinned to the tagged generation.
+fn open_remote(
+    flox: &Flox,
+    remote: &RemoteSelect,
+) -> Result<ConcreteEnvironment, EnvironmentError> {
+    let env_ref = &remote.env_ref;
+    let mut pointer = ManagedPointer::new(
+        env_ref.owner().clone(),
+        env_ref.name().clone(),
+        &flox.floxhub,
+    );
+    if let Some(git_remote) = &remote.git_remote {
+        pointer = pointer.with_git_remote(git_remote.clone());
+    }
+
+    let mut env = RemoteEnvironment::new(flox, pointer)?;
+    if let Some(tag) = env_ref.tag() {
+        let generation = env.resolve_tag(tag)?;
+        env.pin_generation(generation)?;
+    }
+    Ok(ConcreteEnvironment::Remote(env))
+}
+
+/// Use `generation` of an environment instead of its current generation
+///
+/// Managed and remote environments are pinned to `generation`,
+/// which is read and built without changing the environment.
+/// Path environments do not have generations.
+fn environment_at_generation(
+    environment: ConcreteEnvironment,
+    generation: GenerationId,
+) -> Result<ConcreteEnvironment> {
+    match environment {
+        ConcreteEnvironment::Managed(mut environment) => {
+            environment.pin_generation(generation)?;
+            Ok(ConcreteEnvironment::Managed(environment))
+        },
+        ConcreteEnvironment::Remote(mut environment) => {
+            environment.pin_generation(generation)?;
+            Ok(ConcreteEnvironment::Remote(environment))
+        },
+        ConcreteEnvironment::Path(_) => Err(GenerationsCommandsError::NoGenerations {
+            description: environment_description(&environment)?,
+        })?,
     }
 }
 
@@ -1191,7 +1338,7 @@ pub enum UninitializedEnvironment {
     /// Container for "local" environments pointed to by [DotFlox]
     DotFlox(DotFlox),
     /// Container for [RemoteEnvironment]
-    Remote(ManagedPointer),
+    Remote(Box<ManagedPointer>),
 }
 
 impl UninitializedEnvironment {
@@ -1213,7 +1360,7 @@ impl UninitializedEnvironment {
             },
             ConcreteEnvironment::Remote(remote_env) => {
                 let env_ref = remote_env.pointer().clone();
-                Ok(Self::Remote(env_ref))
+                Ok(Self::Remote(Box::new(env_ref)))
             },
         }
     }
@@ -1241,14 +1388,14 @@ impl UninitializedEnvironment {
                     },
                     EnvironmentPointer::Managed(managed_pointer) => {
                         debug!("detected concrete environment type: managed");
-                        let env = ManagedEnvironment::open(flox, managed_pointer, dot_flox_path)?;
+                        let env = ManagedEnvironment::open(flox, *managed_pointer, dot_flox_path)?;
                         ConcreteEnvironment::Managed(env)
                     },
                 };
                 Ok(env)
             },
             UninitializedEnvironment::Remote(pointer) => {
-                let env = RemoteEnvironment::new(flox, pointer)?;
+                let env = RemoteEnvironment::new(flox, *pointer)?;
                 Ok(ConcreteEnvironment::Remote(env))
             },
         }
@@ -1690,7 +1837,7 @@ async fn maybe_migrate_environment_to_v1_inner(
     let mut confirmed_upgrade = false;
     if let Some(migration_info) = concrete_environment
         .dyn_environment_ref_mut()
-        .needs_migration_to_v1(flox)?
+        .needs_migration(flox)?
     {
         // If the migration requires changes to a manifest, check if the environment is in sync
         // before asking whether to migrate
@@ -1734,7 +1881,7 @@ async fn maybe_migrate_environment_to_v1_inner(
             typed: Spinner::new(|| {
                 concrete_environment
                     .dyn_environment_ref_mut()
-                    .migrate_to_v1(flox, migration_info)
+                    .migrate(flox, migration_info)
             }),
         }
         .spin();
@@ -2332,4 +2479,22 @@ mod tests {
             "maybe_migrate_environment_to_v1_inner _should not_ fail, since no migration is needed",
         );
     }
+
+    /// `--generation` is accepted by all environment commands,
+    /// but can't be combined with a tagged remote environment
+    #[test]
+    fn environment_select_generation_excludes_tag() {
+        let parse = |args: &[&str]| environment_select().to_options().run_inner(args).unwrap();
+
+        let select = parse(&["-d", "path", "--generation", "2"]);
+        assert_eq!(select.generation, Some(GenerationId::from(2)));
+        select.ensure_single_generation().unwrap();
+
+        parse(&["-r", "owner/name@stable"])
+            .ensure_single_generation()
+            .unwrap();
+        parse(&["-r", "owner/name@stable", "--generation", "2"])
+            .ensure_single_generation()
+            .unwrap_err();
+    }
 }
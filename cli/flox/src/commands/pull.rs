@@ -24,6 +24,7 @@ use indoc::{formatdoc, indoc};
 use log::debug;
 use toml_edit::DocumentMut;
 use tracing::instrument;
+use url::Url;
 
 use super::services::warn_manifest_changes_for_services;
 use super::{open_path, ConcreteEnvironment};
@@ -73,6 +74,10 @@ pub struct Pull {
     #[bpaf(short, long)]
     copy: bool,
 
+    /// Pull a new environment from a git repository instead of FloxHub
+    #[bpaf(long, argument("url"))]
+    git_remote: Option<Url>,
+
     #[bpaf(external(pull_select), fallback(Default::default()))]
     pull_select: PullSelect,
 }
@@ -96,7 +101,10 @@ impl Pull {
                 let start_message = format!(
                     "⬇️  Remote: pulling and building {env_ref} from {host} into {into_dir}",
                     env_ref = &remote,
-                    host = flox.floxhub.base_url(),
+                    host = self
+                        .git_remote
+                        .as_ref()
+                        .unwrap_or_else(|| flox.floxhub.base_url()),
                     into_dir = if let Some(dir) = self.dir.as_deref() {
                         format!("{}", dir.display())
                     } else {
@@ -114,12 +122,17 @@ impl Pull {
                     &flox,
                     dir,
                     remote,
+                    self.git_remote,
                     self.copy,
                     self.force,
                     &start_message,
                 )?;
             },
             PullSelect::Existing {} => {
+                if self.git_remote.is_some() {
+                    bail!("'--git-remote' can only be used when pulling a new environment");
+                }
+
                 let dir = self.dir.unwrap_or_else(|| std::env::current_dir().unwrap());
 
                 debug!("Resolved user intent: pull changes for environment found in {dir:?}");
@@ -127,7 +140,7 @@ impl Pull {
                 let pointer = {
                     let p = DotFlox::open_in(&dir)?.pointer;
                     match p {
-                        EnvironmentPointer::Managed(managed_pointer) => managed_pointer,
+                        EnvironmentPointer::Managed(managed_pointer) => *managed_pointer,
                         EnvironmentPointer::Path(_) => bail!("Cannot pull into a path environment"),
                     }
                 };
@@ -171,7 +184,10 @@ impl Pull {
             "⬇️  Remote: pulling {owner}/{name} from {floxhub_host}",
             owner = pointer.owner,
             name = pointer.name,
-            floxhub_host = flox.floxhub.base_url()
+            floxhub_host = pointer
+                .git_remote
+                .as_ref()
+                .unwrap_or_else(|| flox.floxhub.base_url())
         );
 
         let state = Dialog {
@@ -216,6 +232,18 @@ impl Pull {
                             {owner}/{name} is already up to date.
                         ", owner = pointer.owner, name = pointer.name});
             },
+            PullResult::Merged => {
+                message::updated(formatdoc! {"
+                    Merged local changes with {owner}/{name} from {floxhub_host}
+
+                    Use 'flox push' to share the merged environment.
+                    ",
+                    owner = pointer.owner, name = pointer.name,
+                    floxhub_host = flox.floxhub.base_url(),
+                });
+
+                warn_manifest_changes_for_services(flox, &env);
+            },
         }
 
         Ok(())
@@ -223,6 +251,7 @@ impl Pull {
 
     /// Pull a new environment from FloxHub into the given directory
     ///
+    /// If `git_remote` is set, the environment is pulled from that git repository instead.
     /// This will create a new environment in the given directory.
     /// Uses [ManagedEnvironment::open] which will try to clone the environment.
     ///
@@ -232,6 +261,7 @@ impl Pull {
         flox: &Flox,
         env_path: PathBuf,
         env_ref: EnvironmentRef,
+        git_remote: Option<Url>,
         copy: bool,
         force: bool,
         message: &str,
@@ -267,11 +297,17 @@ impl Pull {
         }
 
         // region: write pointer
-        let pointer = ManagedPointer::new(
+        let mut pointer = ManagedPointer::new(
             env_ref.owner().clone(),
             env_ref.name().clone(),
             &flox.floxhub,
         );
+        if let Some(git_remote) = git_remote {
+            pointer = pointer.with_git_remote(git_remote);
+        }
+        if let Some(tag) = env_ref.tag() {
+            pointer = pointer.with_tag(tag.clone());
+        }
         let pointer_content =
             serde_json::to_string_pretty(&pointer).context("Could not serialize pointer")?;
 
@@ -402,7 +438,7 @@ impl Pull {
                     });
                 }
 
-                let migration_info = env.needs_migration_to_v1(flox)?;
+                let migration_info = env.needs_migration(flox)?;
 
                 // Query functions will return `Ok(false)` if the user chose to abort the pull.
                 // The unwrap() is only reached if !force,
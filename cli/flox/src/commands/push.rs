@@ -1,10 +1,11 @@
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use bpaf::Bpaf;
 use flox_rust_sdk::data::CanonicalPath;
 use flox_rust_sdk::flox::{EnvironmentOwner, Flox};
+use flox_rust_sdk::models::environment::generations::{GenerationId, RetentionPolicy};
 use flox_rust_sdk::models::environment::managed_environment::{
     ManagedEnvironment,
     ManagedEnvironmentError,
@@ -18,10 +19,14 @@ use flox_rust_sdk::models::environment::{
     PathPointer,
 };
 use indoc::formatdoc;
+use itertools::Itertools;
 use log::debug;
 use tracing::instrument;
+use url::Url;
 
+use super::generations::prune::retention_policy;
 use crate::commands::ensure_floxhub_token;
+use crate::config::Config;
 use crate::subcommand_metric;
 use crate::utils::dialog::{Dialog, Spinner};
 use crate::utils::errors::format_core_error;
@@ -41,45 +46,84 @@ pub struct Push {
     /// Forceably overwrite the remote copy of the environment
     #[bpaf(long, short)]
     force: bool,
+
+    /// Push the environment to a git repository instead of FloxHub
+    /// (only when pushing an environment for the first time)
+    #[bpaf(long, argument("url"))]
+    git_remote: Option<Url>,
 }
 
 impl Push {
     #[instrument(name = "push", skip_all)]
-    pub async fn handle(self, mut flox: Flox) -> Result<()> {
+    pub async fn handle(self, config: Config, mut flox: Flox) -> Result<()> {
         subcommand_metric!("push");
 
-        // Ensure the user is logged in for the following remote operations
-        ensure_floxhub_token(&mut flox).await?;
-
-        // Start a span that doesn't include authentication
-        let span = tracing::info_span!("post-auth");
-        let _guard = span.enter();
-
         let dir = self.dir.unwrap_or_else(|| std::env::current_dir().unwrap());
 
         let dot_flox = DotFlox::open_in(dir)?;
         let canonical_dot_flox_path =
             CanonicalPath::new(&dot_flox.path).expect("DotFlox path was just opened");
 
+        let git_remote = match &dot_flox.pointer {
+            EnvironmentPointer::Managed(_) if self.git_remote.is_some() => {
+                bail!("'--git-remote' can only be used when pushing an environment for the first time")
+            },
+            EnvironmentPointer::Managed(managed_pointer) => managed_pointer.git_remote.clone(),
+            EnvironmentPointer::Path(_) => self.git_remote,
+        };
+
+        // Ensure the user is logged in for the following remote operations.
+        // Authentication with plain git remotes is left to git.
+        if git_remote.is_none() {
+            ensure_floxhub_token(&mut flox).await?;
+        }
+
+        // Start a span that doesn't include authentication
+        let span = tracing::info_span!("post-auth");
+        let _guard = span.enter();
+
+        let remote_description = match &git_remote {
+            Some(git_remote) => git_remote.to_string(),
+            None => "FloxHub".to_string(),
+        };
+
         match dot_flox.pointer {
             EnvironmentPointer::Managed(managed_pointer) => {
-                let message = Self::push_existing_message(&managed_pointer, self.force);
+                let message =
+                    Self::push_existing_message(&managed_pointer, &remote_description, self.force);
 
-                Dialog {
-                    message: "Pushing updates to FloxHub...",
+                let policy = config
+                    .flox
+                    .prune_generations_on_push
+                    .unwrap_or(false)
+                    .then(|| retention_policy(&config.flox, None, None));
+
+                let pruned = Dialog {
+                    message: &format!("Pushing updates to {remote_description}..."),
                     help_message: None,
                     typed: Spinner::new(|| {
-                        Self::push_managed_env(&flox, managed_pointer, &dot_flox.path, self.force)
+                        Self::push_managed_env(
+                            &flox,
+                            *managed_pointer,
+                            &dot_flox.path,
+                            self.force,
+                            policy.as_ref(),
+                        )
                     }),
                 }
                 .spin()?;
 
                 message::updated(message);
+                if !pruned.is_empty() {
+                    message::deleted(format!("Pruned generations {}", pruned.iter().join(", ")));
+                }
             },
 
             EnvironmentPointer::Path(path_pointer) => {
                 let owner = if let Some(owner) = self.owner {
                     owner
+                } else if git_remote.is_some() {
+                    bail!("Specify an owner for the environment with '--owner'")
                 } else {
                     EnvironmentOwner::from_str(
                         flox.floxhub_token
@@ -90,7 +134,7 @@ impl Push {
                 };
 
                 let env = Dialog {
-                    message: "Pushing environment to FloxHub...",
+                    message: &format!("Pushing environment to {remote_description}..."),
                     help_message: None,
                     typed: Spinner::new(|| {
                         Self::push_make_managed(
@@ -98,45 +142,72 @@ impl Push {
                             path_pointer,
                             canonical_dot_flox_path,
                             owner,
+                            git_remote,
                             self.force,
                         )
                     }),
                 }
                 .spin()?;
 
-                message::updated(Self::push_new_message(env.pointer(), self.force));
+                message::updated(Self::push_new_message(
+                    env.pointer(),
+                    &remote_description,
+                    self.force,
+                ));
             },
         }
         Ok(())
     }
 
+    /// Push a managed environment to FloxHub
+    ///
+    /// If a [RetentionPolicy] is given, generations are pruned after the push
+    /// and the result is pushed again.
+    /// Returns the generations that were pruned.
     fn push_managed_env(
         flox: &Flox,
         managed_pointer: ManagedPointer,
         dot_flox_dir: &Path,
         force: bool,
-    ) -> Result<()> {
+        retention_policy: Option<&RetentionPolicy>,
+    ) -> Result<Vec<GenerationId>> {
         let mut env = ManagedEnvironment::open(flox, managed_pointer.clone(), dot_flox_dir)?;
         env.push(flox, force)
-            .map_err(|err| Self::convert_error(err, managed_pointer, false))?;
+            .map_err(|err| Self::convert_error(err, managed_pointer.clone(), false))?;
 
-        Ok(())
+        let Some(retention_policy) = retention_policy else {
+            return Ok(Vec::new());
+        };
+
+        let pruned = env.prune_generations(flox, retention_policy)?;
+        if !pruned.is_empty() {
+            env.push(flox, false)
+                .map_err(|err| Self::convert_error(err, managed_pointer, false))?;
+        }
+
+        Ok(pruned)
     }
 
-    /// pushes a path environment in a directory to FloxHub and makes it a managed environment
+    /// pushes a path environment in a directory to FloxHub
+    /// or a git remote and makes it a managed environment
     fn push_make_managed(
         flox: &Flox,
         path_pointer: PathPointer,
         dot_flox_path: CanonicalPath,
         owner: EnvironmentOwner,
+        git_remote: Option<Url>,
         force: bool,
     ) -> Result<ManagedEnvironment> {
         let path_environment =
             path_environment::PathEnvironment::open(flox, path_pointer, dot_flox_path)?;
 
-        let pointer = ManagedPointer::new(owner.clone(), path_environment.name(), &flox.floxhub);
+        let mut pointer =
+            ManagedPointer::new(owner.clone(), path_environment.name(), &flox.floxhub);
+        if let Some(ref git_remote) = git_remote {
+            pointer = pointer.with_git_remote(git_remote.clone());
+        }
 
-        let env = ManagedEnvironment::push_new(flox, path_environment, owner, force)
+        let env = ManagedEnvironment::push_new(flox, path_environment, owner, git_remote, force)
             .map_err(|err| Self::convert_error(err, pointer, true))?;
 
         Ok(env)
@@ -184,32 +255,43 @@ impl Push {
     /// construct a message for an updated environment
     ///
     /// todo: add FloxHub base url when it's available
-    fn push_existing_message(env: &ManagedPointer, force: bool) -> String {
-        let owner = &env.owner;
+    fn push_existing_message(env: &ManagedPointer, remote: &str, force: bool) -> String {
         let name = &env.name;
+        let pull_command = Self::pull_command(env);
 
         let suffix = if force { " (forced)" } else { "" };
 
         formatdoc! {"
-            Updates to {name} successfully pushed to FloxHub{suffix}
+            Updates to {name} successfully pushed to {remote}{suffix}
 
-            Use 'flox pull {owner}/{name}' to get this environment in any other location.
+            Use '{pull_command}' to get this environment in any other location.
         "}
     }
 
     /// construct a message for a newly created environment
     ///
     /// todo: add FloxHub base url when it's available
-    fn push_new_message(env: &ManagedPointer, force: bool) -> String {
-        let owner = &env.owner;
+    fn push_new_message(env: &ManagedPointer, remote: &str, force: bool) -> String {
         let name = &env.name;
+        let pull_command = Self::pull_command(env);
 
         let suffix = if force { " (forced)" } else { "" };
 
         formatdoc! {"
-            {name} successfully pushed to FloxHub{suffix}
+            {name} successfully pushed to {remote}{suffix}
 
-            Use 'flox pull {owner}/{name}' to get this environment in any other location.
+            Use '{pull_command}' to get this environment in any other location.
         "}
     }
+
+    /// The command to pull the environment in another location
+    fn pull_command(env: &ManagedPointer) -> String {
+        let owner = &env.owner;
+        let name = &env.name;
+
+        match &env.git_remote {
+            Some(git_remote) => format!("flox pull --git-remote {git_remote} {owner}/{name}"),
+            None => format!("flox pull {owner}/{name}"),
+        }
+    }
 }
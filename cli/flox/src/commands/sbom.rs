@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use bpaf::Bpaf;
+use flox_rust_sdk::data::System;
+use flox_rust_sdk::flox::{Flox, FLOX_VERSION};
+use flox_rust_sdk::models::lockfile::LockedManifest;
+use flox_rust_sdk::models::sbom::{generate_sbom, SbomDocument, SbomFormat};
+use tracing::instrument;
+
+use crate::commands::{environment_description, environment_select, EnvironmentSelect};
+use crate::subcommand_metric;
+use crate::utils::message;
+
+/// Generate a software bill of materials for an environment
+#[derive(Bpaf, Debug, Clone)]
+pub struct Sbom {
+    #[bpaf(external(environment_select), fallback(Default::default()))]
+    environment: EnvironmentSelect,
+
+    /// Format of the SBOM, 'cyclonedx' or 'spdx'
+    /// (default: cyclonedx)
+    #[bpaf(long, argument("format"), fallback(SbomFormat::CycloneDx))]
+    format: SbomFormat,
+
+    /// Only include packages for this system,
+    /// may be repeated (default: all systems in the lockfile)
+    #[bpaf(long("system"), argument("system"))]
+    systems: Vec<System>,
+
+    /// File to write the SBOM to (default: stdout)
+    #[bpaf(long, short, argument("path"))]
+    output: Option<PathBuf>,
+}
+
+impl Sbom {
+    #[instrument(name = "sbom", skip_all)]
+    pub fn handle(self, flox: Flox) -> Result<()> {
+        subcommand_metric!("sbom");
+
+        let concrete_environment = self
+            .environment
+            .detect_concrete_environment(&flox, "Generate an SBOM for")?;
+        let description = environment_description(&concrete_environment)?;
+        let mut env = concrete_environment.into_dyn_environment();
+
+        let LockedManifest::Catalog(lockfile) = env.lockfile(&flox)? else {
+            bail!("Generating SBOMs for environments with version 0 manifests is not supported.");
+        };
+
+        if let Some(manifest_systems) = &lockfile.manifest.options.systems {
+            for system in &self.systems {
+                if !manifest_systems.contains(system) {
+                    bail!("Environment {description} does not support system '{system}'");
+                }
+            }
+        }
+
+        let document = SbomDocument::new(env.name(), &*FLOX_VERSION);
+        let sbom = generate_sbom(&lockfile, &self.systems, self.format, &document);
+        let sbom = serde_json::to_string_pretty(&sbom)?;
+
+        match self.output {
+            Some(output) => {
+                fs::write(&output, format!("{sbom}\n"))
+                    .with_context(|| format!("Could not write '{}'", output.display()))?;
+                message::created(format!(
+                    "Wrote {} SBOM for environment {description} to '{}'",
+                    self.format,
+                    output.display()
+                ));
+            },
+            None => println!("{sbom}"),
+        }
+
+        Ok(())
+    }
+}
@@ -108,7 +108,7 @@ impl ServicesEnvironment {
     /// Returns an error if the environment doesn't support services.
     pub fn from_concrete_environment(
         flox: &Flox,
-        environment: ConcreteEnvironment,
+        mut environment: ConcreteEnvironment,
     ) -> Result<Self> {
         let socket = environment
             .dyn_environment_ref()
@@ -119,7 +119,16 @@ impl ServicesEnvironment {
             return Err(CoreEnvironmentError::ServicesWithV0.into());
         };
 
-        let manifest = *manifest;
+        // Services of included environments are only part of the merged manifest,
+        // which is recorded in the lockfile
+        let manifest = if manifest.include.environments.is_empty() {
+            *manifest
+        } else {
+            match environment.dyn_environment_ref_mut().lockfile(flox)? {
+                LockedManifest::Catalog(lockfile) => lockfile.manifest,
+                LockedManifest::Pkgdb(_) => *manifest,
+            }
+        };
 
         Ok(Self {
             environment,
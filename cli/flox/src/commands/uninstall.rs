@@ -44,7 +44,7 @@ impl Uninstall {
         );
 
         // Ensure the user is logged in for the following remote operations
-        if let EnvironmentSelect::Remote(_) = self.environment {
+        if self.environment.is_floxhub_remote() {
             ensure_floxhub_token(&mut flox).await?;
         };
 
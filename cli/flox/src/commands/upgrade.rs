@@ -1,6 +1,12 @@
-use anyhow::Result;
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
 use bpaf::Bpaf;
+use flox_rust_sdk::data::System;
 use flox_rust_sdk::flox::Flox;
+use flox_rust_sdk::models::environment::UpgradeResult;
+use indoc::formatdoc;
+use serde::Serialize;
 use tracing::instrument;
 
 use super::services::warn_manifest_changes_for_services;
@@ -16,6 +22,15 @@ pub struct Upgrade {
     #[bpaf(external(environment_select), fallback(Default::default()))]
     environment: EnvironmentSelect,
 
+    /// Show the packages that would be upgraded
+    /// without modifying the environment
+    #[bpaf(long)]
+    dry_run: bool,
+
+    /// Display the upgrades as JSON, requires '--dry-run'
+    #[bpaf(long)]
+    json: bool,
+
     /// ID of a package or pkg-group name to upgrade
     #[bpaf(positional("package or pkg-group"))]
     groups_or_iids: Vec<String>,
@@ -29,8 +44,12 @@ impl Upgrade {
             "upgrading groups and install ids"
         );
 
+        if self.json && !self.dry_run {
+            bail!("'--json' can only be used with '--dry-run'");
+        }
+
         // Ensure the user is logged in for the following remote operations
-        if let EnvironmentSelect::Remote(_) = self.environment {
+        if self.environment.is_floxhub_remote() && !self.dry_run {
             ensure_floxhub_token(&mut flox).await?;
         };
 
@@ -42,7 +61,12 @@ impl Upgrade {
 
         let mut environment = concrete_environment.into_dyn_environment();
 
-        if let Some(migration_info) = environment.needs_migration_to_v1(&flox)? {
+        if let Some(migration_info) = environment.needs_migration(&flox)? {
+            if self.dry_run {
+                bail!(formatdoc! {"
+                    Environment {description} has to be migrated before upgrades can be previewed.
+                    Use 'flox manifest migrate --dry-run' to preview the migration."});
+            }
             if migration_info.needs_upgrade {
                 message::warning(
                         "Detected an old environment version. Attempting to migrate to version 1 and upgrade packages.",
@@ -50,7 +74,7 @@ impl Upgrade {
                 Dialog {
                     message: "Upgrading packages...",
                     help_message: None,
-                    typed: Spinner::new(|| environment.migrate_to_v1(&flox, migration_info)),
+                    typed: Spinner::new(|| environment.migrate(&flox, migration_info)),
                 }
                 .spin()?;
                 message::plain(format!(
@@ -63,7 +87,7 @@ impl Upgrade {
                 Dialog {
                     message: "Migrating environment...",
                     help_message: None,
-                    typed: Spinner::new(|| environment.migrate_to_v1(&flox, migration_info)),
+                    typed: Spinner::new(|| environment.migrate(&flox, migration_info)),
                 }
                 .spin()?;
                 message::plain(format!(
@@ -76,6 +100,46 @@ impl Upgrade {
             return Ok(());
         }
 
+        if self.dry_run {
+            let result = Dialog {
+                message: "Resolving upgrades...",
+                help_message: None,
+                typed: Spinner::new(|| {
+                    environment.dry_upgrade(
+                        &flox,
+                        &self
+                            .groups_or_iids
+                            .iter()
+                            .map(String::as_str)
+                            .collect::<Vec<_>>(),
+                    )
+                }),
+            }
+            .spin()?;
+
+            let preview = UpgradePreview::new(&result);
+            if self.json {
+                println!("{}", serde_json::to_string_pretty(&preview)?);
+            } else {
+                for include in &preview.includes {
+                    message::plain(format!(
+                        "Included environment '{include}' would be updated in environment {description}."
+                    ));
+                }
+                if preview.packages.is_empty() {
+                    message::plain(format!(
+                        "ℹ️  No packages need to be upgraded in environment {description}."
+                    ));
+                } else {
+                    message::plain(format!(
+                        "The following packages would be upgraded in environment {description}:"
+                    ));
+                    print!("{preview}");
+                }
+            }
+            return Ok(());
+        }
+
         let result = Dialog {
             message: "Upgrading packages...",
             help_message: None,
@@ -94,6 +158,12 @@ impl Upgrade {
 
         let upgraded = result.packages;
 
+        for include in &result.includes {
+            message::updated(format!(
+                "Updated included environment '{include}' in environment {description}."
+            ));
+        }
+
         if upgraded.is_empty() {
             if self.groups_or_iids.is_empty() {
                 message::plain(format!(
@@ -117,3 +187,162 @@ impl Upgrade {
         Ok(())
     }
 }
+
+/// The packages that would be upgraded, with their versions before and after
+/// the upgrade on each system.
+#[derive(Debug, Serialize)]
+struct UpgradePreview {
+    packages: Vec<PackageUpgrade>,
+    /// Names of included environments that would be updated
+    includes: Vec<String>,
+}
+
+/// A package that would be upgraded on a single system.
+///
+/// Packages that would be added or removed by updating an included environment
+/// have no old or new version respectively.
+#[derive(Debug, Serialize)]
+struct PackageUpgrade {
+    install_id: String,
+    system: System,
+    old_version: Option<String>,
+    new_version: Option<String>,
+}
+
+impl UpgradePreview {
+    fn new(result: &UpgradeResult) -> Self {
+        let mut packages = Vec::new();
+        for (system, diff) in &result.diff.systems {
+            for change in &diff.changed {
+                packages.push(PackageUpgrade {
+                    install_id: change.install_id.clone(),
+                    system: system.clone(),
+                    old_version: change.old_version.clone(),
+                    new_version: change.new_version.clone(),
+                });
+            }
+            for added in &diff.added {
+                packages.push(PackageUpgrade {
+                    install_id: added.install_id.clone(),
+                    system: system.clone(),
+                    old_version: None,
+                    new_version: added.version.clone(),
+                });
+            }
+            for removed in &diff.removed {
+                packages.push(PackageUpgrade {
+                    install_id: removed.install_id.clone(),
+                    system: system.clone(),
+                    old_version: removed.version.clone(),
+                    new_version: None,
+                });
+            }
+        }
+        packages.sort_by(|a, b| (&a.install_id, &a.system).cmp(&(&b.install_id, &b.system)));
+
+        UpgradePreview {
+            packages,
+            includes: result.includes.clone(),
+        }
+    }
+}
+
+/// Formats the packages of an [UpgradePreview] as a table
+impl Display for UpgradePreview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn format_version(version: &Option<String>) -> &str {
+            version.as_deref().unwrap_or("-")
+        }
+
+        let header = ["PACKAGE", "SYSTEM", "OLD", "NEW"];
+        let rows = self
+            .packages
+            .iter()
+            .map(|package| {
+                [
+                    package.install_id.as_str(),
+                    package.system.as_str(),
+                    format_version(&package.old_version),
+                    format_version(&package.new_version),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let [id_width, system_width, old_width, _] = widths;
+
+        for [install_id, system, old, new] in std::iter::once(header).chain(rows) {
+            writeln!(
+                f,
+                "{install_id:<id_width$}  {system:<system_width$}  {old:<old_width$}  {new}"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flox_rust_sdk::models::environment_diff::{
+        KeysDiff,
+        LockedPackageChange,
+        LockedPackagesDiff,
+        LockfileDiff,
+        PackageVersion,
+    };
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn upgrade_preview_table() {
+        let change = |install_id: &str, old: &str, new: &str| LockedPackageChange {
+            install_id: install_id.to_string(),
+            old_version: Some(old.to_string()),
+            new_version: Some(new.to_string()),
+            derivation_changed: true,
+            outputs: KeysDiff::default(),
+        };
+        let result = UpgradeResult {
+            packages: vec!["hello".to_string(), "curl".to_string()],
+            includes: vec![],
+            diff: LockfileDiff {
+                systems: [
+                    ("aarch64-darwin".to_string(), LockedPackagesDiff {
+                        added: vec![],
+                        removed: vec![],
+                        changed: vec![change("hello", "2.10", "2.12.1")],
+                    }),
+                    ("x86_64-linux".to_string(), LockedPackagesDiff {
+                        added: vec![PackageVersion {
+                            install_id: "cowsay".to_string(),
+                            version: Some("3.7.0".to_string()),
+                        }],
+                        removed: vec![],
+                        changed: vec![
+                            change("hello", "2.10", "2.12.1"),
+                            change("curl", "8.4.0", "8.5.0"),
+                        ],
+                    }),
+                ]
+                .into(),
+            },
+            store_path: None,
+        };
+
+        assert_eq!(UpgradePreview::new(&result).to_string(), indoc! {"
+            PACKAGE  SYSTEM          OLD    NEW
+            cowsay   x86_64-linux    -      3.7.0
+            curl     x86_64-linux    8.4.0  8.5.0
+            hello    aarch64-darwin  2.10   2.12.1
+            hello    x86_64-linux    2.10   2.12.1
+        "});
+    }
+}
@@ -87,6 +87,39 @@ pub struct FloxConfig {
 
     /// Hide environments named 'default' from the shell prompt
     pub hide_default_prompt: Option<bool>,
+
+    /// Number of newest generations to keep when pruning generations
+    pub keep_generations: Option<usize>,
+
+    /// Keep generations created within this many days when pruning generations
+    pub generations_max_age_days: Option<u32>,
+
+    /// Prune generations of managed environments after pushing them
+    pub prune_generations_on_push: Option<bool>,
+
+    /// Fail instead of locking environments again
+    /// if their lockfile is missing or out of date (see `--locked`)
+    #[serde(default, deserialize_with = "deserialize_bool_or_int")]
+    pub locked: bool,
+}
+
+/// Deserialize a boolean that may also be set as `0` or `1`,
+/// as is common for environment variables, e.g. `FLOX_LOCKED=1`
+fn deserialize_bool_or_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrInt {
+        Bool(bool),
+        Int(i64),
+    }
+
+    Ok(match BoolOrInt::deserialize(deserializer)? {
+        BoolOrInt::Bool(value) => value,
+        BoolOrInt::Int(value) => value != 0,
+    })
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -434,6 +467,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_locked_by_env() {
+        let tempdir = tempfile::tempdir().unwrap();
+        for (value, expected) in [("1", true), ("true", true), ("0", false)] {
+            temp_env::with_vars(
+                [
+                    (
+                        "HOME",
+                        Some(tempdir.path().as_os_str().to_string_lossy().as_ref()),
+                    ),
+                    ("FLOX_LOCKED", Some(value)),
+                ],
+                || {
+                    let config = Config::parse().unwrap();
+                    assert_eq!(config.flox.locked, expected, "FLOX_LOCKED={value}");
+                    env::remove_var(FLOX_CONFIG_DIR_VAR);
+                },
+            );
+        }
+    }
+
     #[test]
     fn test_writing_value() {
         let config_content = Config::write_to(
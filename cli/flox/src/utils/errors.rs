@@ -233,7 +233,7 @@ pub fn format_core_error(err: &CoreEnvironmentError) -> String {
             err = err.message().trim()
         },
         CoreEnvironmentError::MigrateManifest(err) => formatdoc! {
-            "Could not automatically migrate manifest to version 1:
+            "Could not automatically migrate manifest to the latest version:
 
             {err}
 
@@ -252,6 +252,7 @@ pub fn format_core_error(err: &CoreEnvironmentError) -> String {
         ",
             err = format_core_error(err)
         },
+        CoreEnvironmentError::MigrationVersion(_) => display_chain(err),
         CoreEnvironmentError::MakeSandbox(_) => display_chain(err),
         // witin transaction, user should not see this and likely can't do anything about it
         CoreEnvironmentError::WriteLockfile(_) => display_chain(err),
@@ -399,6 +400,21 @@ pub fn format_core_error(err: &CoreEnvironmentError) -> String {
             format_locked_manifest_error(locked_manifest_error)
         },
 
+        CoreEnvironmentError::Include(include_error) => display_chain(include_error),
+
+        CoreEnvironmentError::LockfileOutdated(outdated) => formatdoc! {"
+            {outdated}
+
+            Locking environments is disabled by '--locked' or 'FLOX_LOCKED'.
+            Lock the environment without '--locked' and commit the updated lockfile.
+        "},
+
+        CoreEnvironmentError::UpgradeLocked => formatdoc! {"
+            Upgrading packages is disabled by '--locked' or 'FLOX_LOCKED',
+            because it writes a new lockfile.
+            Use 'flox upgrade --dry-run' to preview upgrades.
+        "},
+
         CoreEnvironmentError::ContainerizeUnsupportedSystem(system) => formatdoc! {"
             'containerize' is currently only supported on linux (found {system}).
         "},
@@ -606,6 +622,51 @@ pub fn format_managed_error(err: &ManagedEnvironmentError) -> String {
 
             {err}
         ",err = display_chain(e) },
+        ManagedEnvironmentError::ReadGenerationsMetadata(_) => display_chain(err),
+        ManagedEnvironmentError::SwitchGeneration(_, _) => display_chain(err),
+        ManagedEnvironmentError::PruneGenerations(_) => display_chain(err),
+        ManagedEnvironmentError::TagGeneration(_, _) => display_chain(err),
+        ManagedEnvironmentError::TagNotFound(tag) => formatdoc! {"
+            The environment does not have a generation tagged '{tag}'.
+
+            Run 'flox generations list' to see all generations and their tags.
+        "},
+        ManagedEnvironmentError::Pinned(generation) => formatdoc! {"
+            The environment is pinned to generation {generation} by a tag
+            and can not be modified.
+
+            Pull the environment without a tag to modify its current generation.
+        "},
+        ManagedEnvironmentError::ReadGeneration(_, _) => display_chain(err),
+        ManagedEnvironmentError::ReadMergeGenerations(_) => display_chain(err),
+        ManagedEnvironmentError::ParseMergeManifest(_) => display_chain(err),
+        ManagedEnvironmentError::MergeConflicts(conflicts) => formatdoc! {"
+            The environment has diverged from the remote
+            and local and remote changes could not be merged automatically:
+            {conflicts}
+
+            To resolve this issue, either
+             * run 'flox pull --force'
+               to discard local changes,
+               then reapply them with 'flox edit' and run 'flox push'.
+             * run 'flox push --force'
+               to overwrite the remote environment with the local changes.
+               Attention: this will discard any changes made on the remote machine!
+        "},
+        ManagedEnvironmentError::BuildMerged(core_environment_error) => formatdoc! {"
+            {err}
+
+            Local and remote changes were merged, but the merged environment failed to build.
+            The environment was left unchanged.
+            ",
+            err = format_core_error(core_environment_error)
+        },
+        ManagedEnvironmentError::NoPreviousGeneration(generation) => formatdoc! {"
+            Generation {generation} is the oldest generation of the environment.
+            There is no previous generation to roll back to.
+
+            Run 'flox generations list' to see all generations of the environment.
+        "},
         ManagedEnvironmentError::CanonicalizePath(canonicalize_err) => formatdoc! {"
             Invalid path to environment: {canonicalize_err}
 
@@ -690,6 +751,12 @@ pub fn format_remote_error(err: &RemoteEnvironmentError) -> String {
         RemoteEnvironmentError::ReadInternalOutLink(_) => display_chain(err),
         RemoteEnvironmentError::DeleteOldOutLink(_) => display_chain(err),
         RemoteEnvironmentError::WriteNewOutlink(_) => display_chain(err),
+        RemoteEnvironmentError::Pinned(generation) => formatdoc! {"
+            The environment is pinned to generation {generation} by a tag
+            and can not be modified.
+
+            Refer to the environment without a tag to modify its current generation.
+        "},
     }
 }
 
@@ -803,8 +870,14 @@ pub fn format_locked_manifest_error(err: &LockedManifestError) -> String {
         LockedManifestError::BrokenNotAllowed(_) => display_chain(err),
         // User facing
         LockedManifestError::UnfreeNotAllowed(_) => display_chain(err),
+        // User facing
+        LockedManifestError::InsecureNotAllowed(_) => display_chain(err),
         LockedManifestError::MissingPackageDescriptor(_) => display_chain(err),
         LockedManifestError::LockFlakeNixError(_) => display_chain(err),
+        LockedManifestError::InvalidStorePath { .. } => display_chain(err),
+        LockedManifestError::StorePathNotFound { .. } => display_chain(err),
+        LockedManifestError::QueryStorePath { .. } => display_chain(err),
+        LockedManifestError::MissingPackageOutputs { .. } => display_chain(err),
         LockedManifestError::InstallIdNotInManifest(_) => display_chain(err),
     }
 }
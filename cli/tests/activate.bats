@@ -1124,6 +1124,50 @@ EOF
 
 # ---------------------------------------------------------------------------- #
 
+# bats test_tags=activate,activate:envVar:secret
+@test "activate resolves secret vars without storing them" {
+  project_setup
+  echo "file-secret" > "$PROJECT_DIR/token"
+  echo 'API_KEY="dotenv-secret"' > "$PROJECT_DIR/.env"
+  cat << "EOF" | "$FLOX_BIN" edit -d "$PROJECT_DIR" -f -
+version = 1
+[vars]
+FROM_FILE = { file = "token" }
+API_KEY = { dotenv = ".env" }
+FROM_COMMAND = { command = "echo \"command\"-secret" }
+EOF
+
+  FLOX_SHELL="bash" run "$FLOX_BIN" activate --dir "$PROJECT_DIR" -- \
+    bash -c 'echo "$FROM_FILE $API_KEY $FROM_COMMAND"'
+  assert_success
+  assert_output --partial "file-secret dotenv-secret command-secret"
+
+  # neither the lockfile nor the built environment contain the values
+  run grep -R -e "file-secret" -e "dotenv-secret" -e "command-secret" "$PROJECT_DIR/.flox"
+  assert_failure
+
+  run "$FLOX_BIN" list --config --dir "$PROJECT_DIR"
+  assert_success
+  assert_line 'FROM_COMMAND = { command = "<redacted>" }'
+}
+
+# bats test_tags=activate,activate:envVar:secret
+@test "activate fails without leaking output of failed secret commands" {
+  project_setup
+  cat << "EOF" | "$FLOX_BIN" edit -d "$PROJECT_DIR" -f -
+version = 1
+[vars]
+TOKEN = { command = "echo hunter2; exit 1" }
+EOF
+
+  FLOX_SHELL="bash" run "$FLOX_BIN" activate --dir "$PROJECT_DIR" -- true
+  assert_failure
+  assert_output --partial "the command of secret variable 'TOKEN' failed"
+  refute_output --partial "hunter2"
+}
+
+# ---------------------------------------------------------------------------- #
+
 # bats test_tags=activate,activate:envVar-before-hook
 @test "{bash,fish,tcsh,zsh}: activate sets env var before hook" {
   project_setup
@@ -2526,3 +2570,111 @@ EOF
   refute_output --partial "_flox"
   refute_output --partial "_FLOX"
 }
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=activate,activate:hook,activate:hook:on-deactivate
+@test "activate runs hook.on-deactivate after the command exits" {
+  project_setup
+
+  MANIFEST_CONTENTS="$(cat << "EOF"
+    version = 1
+    [vars]
+    GREETING = "goodbye"
+    [hook]
+    on-activate = """
+      export ACTIVATED_BY="hook.on-activate"
+    """
+    on-deactivate = """
+      echo "sourcing hook.on-deactivate: $GREETING from $ACTIVATED_BY"
+    """
+EOF
+  )"
+
+  echo "$MANIFEST_CONTENTS" | "$FLOX_BIN" edit -f -
+
+  run --separate-stderr "$FLOX_BIN" activate -- bash -c 'echo "running command"; exit 3'
+  assert_equal "$status" 3
+  assert_output "running command"
+  [[ "$stderr" == *"sourcing hook.on-deactivate: goodbye from hook.on-activate"* ]]
+}
+
+# bats test_tags=activate,activate:hook,activate:hook:on-deactivate
+@test "nested activation does not run hook.on-deactivate" {
+  project_setup
+
+  MANIFEST_CONTENTS="$(cat << "EOF"
+    version = 1
+    [hook]
+    on-deactivate = """
+      echo "sourcing hook.on-deactivate"
+    """
+EOF
+  )"
+
+  echo "$MANIFEST_CONTENTS" | "$FLOX_BIN" edit -f -
+
+  run "$FLOX_BIN" activate -- "$FLOX_BIN" activate -- true
+  assert_success
+  assert_equal "$(echo "$output" | grep -c "sourcing hook.on-deactivate")" 1
+}
+
+# bats test_tags=activate,activate:hook,activate:hook:on-deactivate
+@test "overlapping activations run hook.on-deactivate once after the last one exits" {
+  project_setup
+
+  MANIFEST_CONTENTS="$(cat << EOF
+    version = 1
+    [hook]
+    on-deactivate = """
+      echo "deactivated" >> "$PROJECT_DIR/deactivated"
+    """
+EOF
+  )"
+
+  echo "$MANIFEST_CONTENTS" | "$FLOX_BIN" edit -f -
+
+  "$FLOX_BIN" activate -- bash -c "touch '$PROJECT_DIR/started'; sleep 5" &
+  first="$!"
+  timeout 30s bash -c "while ! [ -e '$PROJECT_DIR/started' ]; do sleep 0.1; done"
+
+  # The first activation is still running
+  run "$FLOX_BIN" activate -- true
+  assert_success
+  assert [ ! -e "$PROJECT_DIR/deactivated" ]
+
+  wait "$first"
+  assert_equal "$(cat "$PROJECT_DIR/deactivated")" "deactivated"
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=activate,activate:system-specific
+@test "activate merges vars, hook and profile for the current system" {
+  project_setup
+
+  MANIFEST_CONTENTS="$(cat << EOF
+    version = 1
+    [vars]
+    GREETING = "common"
+    OTHER = "common"
+    [vars.$NIX_SYSTEM]
+    GREETING = "system"
+    [hook]
+    on-activate = "echo 'common hook'"
+    [hook.$NIX_SYSTEM]
+    on-activate = "echo 'system hook'"
+    [profile.$NIX_SYSTEM]
+    common = "echo 'system profile'"
+EOF
+  )"
+
+  echo "$MANIFEST_CONTENTS" | "$FLOX_BIN" edit -f -
+
+  run "$FLOX_BIN" activate -- bash -c 'echo "$GREETING $OTHER"'
+  assert_success
+  assert_line "common hook"
+  assert_line "system hook"
+  assert_line "system profile"
+  assert_line "system common"
+}
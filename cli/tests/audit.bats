@@ -0,0 +1,120 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test auditing environments with `flox audit`
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=audit
+
+# ---------------------------------------------------------------------------- #
+
+setup_file() {
+  common_file_setup
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown_file() {
+  unset _FLOX_USE_CATALOG_MOCK
+  common_file_teardown
+}
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=audit:osv
+@test "audit: reports vulnerable packages and fails" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  cat > osv.json << EOF
+[{
+  "id": "TEST-2024-1",
+  "summary": "greeting is too friendly",
+  "database_specific": { "severity": "HIGH" },
+  "affected": [{
+    "package": { "name": "hello" },
+    "ranges": [{ "type": "ECOSYSTEM", "events": [{ "introduced": "0" }] }]
+  }]
+}]
+EOF
+
+  run "$FLOX_BIN" audit --database osv.json
+  assert_failure
+  assert_output --partial "high[TEST-2024-1] hello"
+  assert_output --partial "Found 1 known vulnerability"
+
+  run "$FLOX_BIN" audit --database osv.json --fail-on critical
+  assert_success
+}
+
+# bats test_tags=audit:clean
+@test "audit: succeeds without known vulnerabilities" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  echo '{ "vulns": [] }' > osv.json
+
+  run "$FLOX_BIN" audit --database osv.json
+  assert_success
+  assert_output --partial "No known vulnerabilities found"
+}
+
+# bats test_tags=audit:ecosystem
+@test "audit: ignores other ecosystems unless selected" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  cat > osv.json << EOF
+[{
+  "id": "TEST-2024-2",
+  "affected": [{
+    "package": { "ecosystem": "crates.io", "name": "hello" },
+    "ranges": [{ "type": "SEMVER", "events": [{ "introduced": "0" }] }]
+  }]
+}]
+EOF
+
+  run "$FLOX_BIN" audit --database osv.json
+  assert_success
+  assert_output --partial "No known vulnerabilities found"
+
+  run "$FLOX_BIN" audit --database osv.json --ecosystem crates.io
+  assert_failure
+  assert_output --partial "[TEST-2024-2] hello"
+
+  run "$FLOX_BIN" audit --database osv.json --ecosystem Debian
+  assert_failure
+  assert_output --partial "ecosystem 'Debian' uses distribution specific versions"
+}
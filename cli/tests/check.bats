@@ -0,0 +1,94 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test the `flox check` linter
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=check
+
+# ---------------------------------------------------------------------------- #
+
+setup_file() {
+  common_file_setup
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown_file() {
+  unset _FLOX_USE_CATALOG_MOCK
+  common_file_teardown
+}
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=check:clean
+@test "check: succeeds for a freshly initialized environment" {
+  "$FLOX_BIN" init
+
+  run "$FLOX_BIN" check
+  assert_success
+  assert_output --partial "No problems found"
+}
+
+# bats test_tags=check:warning
+@test "check: reports warnings without failing unless requested" {
+  "$FLOX_BIN" init
+  "$FLOX_BIN" edit -f - << "EOF"
+version = 1
+[vars]
+FOO = "bar"
+[hook]
+on-activate = 'export FOO="baz"'
+EOF
+
+  run "$FLOX_BIN" check
+  assert_success
+  assert_output --partial "warning[var-shadowed-by-hook] vars.FOO"
+
+  run "$FLOX_BIN" check --fail-on warning
+  assert_failure
+  assert_output --partial "Found 1 problem with severity 'warning' or higher"
+}
+
+# bats test_tags=check:stale,check:json
+@test "check: reports a stale lockfile as JSON" {
+  "$FLOX_BIN" init
+  cat << "EOF" >> .flox/env/manifest.toml
+[vars]
+FOO = "bar"
+EOF
+
+  run --separate-stderr "$FLOX_BIN" check --json
+  assert_failure
+  run jq -r '.[] | "\(.severity) \(.lint)"' <<< "$output"
+  assert_output "error stale-lockfile"
+}
@@ -0,0 +1,90 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test comparing lockfiles with `flox diff`
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=diff
+
+# ---------------------------------------------------------------------------- #
+
+setup_file() {
+  common_file_setup
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown_file() {
+  unset _FLOX_USE_CATALOG_MOCK
+  common_file_teardown
+}
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=diff:files
+@test "diff: compares two lockfiles" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/old_hello.json" \
+    "$FLOX_BIN" install hello
+  cp .flox/env/manifest.lock old.lock
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" upgrade
+
+  run "$FLOX_BIN" diff old.lock .
+  assert_success
+  assert_output --partial "$NIX_SYSTEM:"
+  assert_output --partial "~ hello"
+
+  run "$FLOX_BIN" diff --json old.lock .flox/env/manifest.lock
+  assert_success
+  run jq -r ".systems.\"$NIX_SYSTEM\".changed[0].install_id" <<< "$output"
+  assert_output "hello"
+}
+
+# bats test_tags=diff:git
+@test "diff: compares the working tree to git HEAD" {
+  git init --quiet
+  "$FLOX_BIN" init
+  git add .flox
+  git -c user.name=test -c user.email=test@example.com commit --quiet -m "init"
+
+  run "$FLOX_BIN" diff
+  assert_success
+  assert_output --partial "No changes to locked packages"
+
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  run "$FLOX_BIN" diff
+  assert_success
+  assert_output --partial "+ hello"
+}
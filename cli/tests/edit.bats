@@ -157,6 +157,75 @@ EOF
 
 # ---------------------------------------------------------------------------- #
 
+# bats test_tags=edit:manifest:file:invalid
+@test "'flox edit' points at misspelled fields in the manifest" {
+  "$FLOX_BIN" init
+  ORIGINAL_MANIFEST_CONTENTS="$(cat "$MANIFEST_PATH")" # for check_manifest_unchanged
+
+  cat << "EOF" > ./manifest.toml
+version = 1
+[install]
+hello.pkg-path = "hello"
+hello.verison = "2.12"
+EOF
+
+  run "$FLOX_BIN" edit -f ./manifest.toml
+  assert_failure
+  assert_output --partial 'unknown field `verison` in `install.hello`, did you mean `version`?'
+  run check_manifest_unchanged
+  assert_success
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=manifest:schema
+@test "'flox manifest schema' prints the manifest JSON Schema" {
+  run "$FLOX_BIN" manifest schema
+  assert_success
+  run jq -r '.properties.version.const' <<< "$output"
+  assert_output "1"
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=manifest:set
+@test "'flox manifest set' and 'unset' modify the manifest" {
+  "$FLOX_BIN" init
+  echo '# keep me' >> "$MANIFEST_PATH"
+
+  run "$FLOX_BIN" manifest set vars.FOO=bar 'options.systems=["x86_64-linux"]' \
+    "hook.on-activate=echo hello"
+  assert_success
+  assert_output --partial "successfully updated."
+  run "$FLOX_BIN" list --config
+  assert_output --partial 'FOO = "bar"'
+  assert_output --partial 'systems = ["x86_64-linux"]'
+  assert_output --partial 'on-activate = "echo hello"'
+  assert_output --partial '# keep me'
+
+  run "$FLOX_BIN" manifest unset vars.FOO
+  assert_success
+  run "$FLOX_BIN" list --config
+  refute_output --partial 'FOO = "bar"'
+}
+
+# bats test_tags=manifest:set
+@test "'flox manifest set' leaves the manifest unchanged if it's invalid" {
+  "$FLOX_BIN" init
+  ORIGINAL_MANIFEST_CONTENTS="$(cat "$MANIFEST_PATH")"
+
+  run "$FLOX_BIN" manifest set options.systems=true
+  assert_failure
+  check_manifest_unchanged
+
+  run "$FLOX_BIN" manifest unset vars.DOES_NOT_EXIST
+  assert_failure
+  assert_output --partial "couldn't find 'vars.DOES_NOT_EXIST' in the manifest"
+  check_manifest_unchanged
+}
+
+# ---------------------------------------------------------------------------- #
+
 @test "'flox edit' fails when provided filename doesn't exist" {
   run "$FLOX_BIN" edit -f "does_not_exist.toml"
   assert_failure
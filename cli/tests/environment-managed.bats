@@ -52,6 +52,12 @@ teardown() {
 
 # ---------------------------------------------------------------------------- #
 
+# Replace the manifest of the environment in the current directory
+# with a manifest that only sets the given variables
+function set_vars() {
+  printf 'version = 1\n[vars]\n%s\n' "$@" | "$FLOX_BIN" edit -f -
+}
+
 # init path environment and push to remote
 function make_empty_remote_env() {
   "$FLOX_BIN" init
@@ -158,6 +164,53 @@ EOF
   popd > /dev/null || return
 }
 
+# bats test_tags=managed,git-remote
+@test "m4: environment pushed to a git remote can be pulled without FloxHub" {
+  mkdir a a_data
+  mkdir b b_data
+  git init --quiet --bare remote.git
+  GIT_REMOTE="file://$(pwd)/remote.git"
+
+  # git remotes do not require a FloxHub token
+  unset FLOX_FLOXHUB_TOKEN
+
+  # on machine a, create and push the environment
+  export FLOX_DATA_DIR="$(pwd)/a_data"
+  pushd a > /dev/null || return
+  "$FLOX_BIN" init
+  set_vars 'FOO = "foo"'
+  run "$FLOX_BIN" push --owner "$OWNER" --git-remote "$GIT_REMOTE"
+  assert_success
+  assert_output --partial "a successfully pushed to $GIT_REMOTE"
+  assert_output --partial "flox pull --git-remote $GIT_REMOTE $OWNER/a"
+  popd > /dev/null || return
+
+  # on another b machine, pull the environment
+  export FLOX_DATA_DIR="$(pwd)/b_data"
+  pushd b > /dev/null || return
+  run "$FLOX_BIN" pull --git-remote "$GIT_REMOTE" "$OWNER/a"
+  assert_success
+  set_vars 'FOO = "bar"'
+  run "$FLOX_BIN" push
+  assert_success
+  assert_output --partial "Updates to a successfully pushed to $GIT_REMOTE"
+  popd > /dev/null || return
+
+  # machine a receives the update from the git remote
+  export FLOX_DATA_DIR="$(pwd)/a_data"
+  pushd a > /dev/null || return
+  run "$FLOX_BIN" pull
+  assert_success
+  run "$FLOX_BIN" list --config
+  assert_output --partial 'FOO = "bar"'
+  popd > /dev/null || return
+
+  # the environment can be used as a remote environment
+  run "$FLOX_BIN" list --config --remote "$OWNER/a" --git-remote "$GIT_REMOTE"
+  assert_success
+  assert_output --partial 'FOO = "bar"'
+}
+
 # bats test_tags=managed,update,managed:update
 @test "m5: updated environment can be pulled" {
   mkdir a a_data
@@ -197,7 +250,7 @@ EOF
 }
 
 # bats test_tags=managed,diverged,managed:diverged
-@test "m7: remote can not be pulled into diverged environment" {
+@test "m7: remote can not be pulled into environment with conflicting changes" {
   mkdir a a_data
   mkdir b b_data
 
@@ -210,27 +263,25 @@ EOF
 
   # on another b machine,
   #  - pull the environment
-  #  - install a package
+  #  - set a variable
   #  - push the environment
   export FLOX_DATA_DIR="$(pwd)/b_data"
   pushd b > /dev/null || return
   "$FLOX_BIN" pull --remote "$OWNER/a"
-  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/vim.json" \
-    "$FLOX_BIN" install vim
+  set_vars 'FOO = "b"'
   "$FLOX_BIN" push --owner "$OWNER"
   popd > /dev/null || return
 
-  # on machine a, pull the environment
-  # and check that the package is installed
+  # on machine a, set the same variable to a different value
+  # and pull the environment
   export FLOX_DATA_DIR="$(pwd)/a_data"
   pushd a > /dev/null || return
-  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/emacs.json" \
-    run "$FLOX_BIN" install emacs
+  set_vars 'FOO = "a"'
   # assert that pulling fails
   run "$FLOX_BIN" pull
   assert_failure
-  # assert that the environment contains the installed package
   assert_output --partial "diverged"
+  assert_output --partial "'vars.FOO'"
 
   # assert that pulling with `--force` succeeds
   run "$FLOX_BIN" pull --force
@@ -239,6 +290,75 @@ EOF
   popd > /dev/null || return
 }
 
+# bats test_tags=managed,diverged,managed:diverged
+@test "m7: non-conflicting changes are merged when pulling a diverged environment" {
+  mkdir a a_data
+  mkdir b b_data
+
+  export FLOX_DATA_DIR="$(pwd)/a_data"
+  pushd a > /dev/null || return
+  "$FLOX_BIN" init
+  "$FLOX_BIN" push --owner "$OWNER"
+  popd > /dev/null || return
+
+  export FLOX_DATA_DIR="$(pwd)/b_data"
+  pushd b > /dev/null || return
+  "$FLOX_BIN" pull --remote "$OWNER/a"
+  set_vars 'B = "b"'
+  "$FLOX_BIN" push --owner "$OWNER"
+  popd > /dev/null || return
+
+  export FLOX_DATA_DIR="$(pwd)/a_data"
+  pushd a > /dev/null || return
+  set_vars 'A = "a"'
+  run "$FLOX_BIN" pull
+  assert_success
+  assert_output --partial "Merged local changes"
+
+  run cat .flox/env/manifest.toml
+  assert_output --partial 'A = "a"'
+  assert_output --partial 'B = "b"'
+
+  run "$FLOX_BIN" push
+  assert_success
+  popd > /dev/null || return
+}
+
+# bats test_tags=managed,diverged,managed:diverged
+@test "m7: non-conflicting changes are merged when pushing a diverged environment" {
+  mkdir a a_data
+  mkdir b b_data
+
+  export FLOX_DATA_DIR="$(pwd)/a_data"
+  pushd a > /dev/null || return
+  "$FLOX_BIN" init
+  "$FLOX_BIN" push --owner "$OWNER"
+  popd > /dev/null || return
+
+  export FLOX_DATA_DIR="$(pwd)/b_data"
+  pushd b > /dev/null || return
+  "$FLOX_BIN" pull --remote "$OWNER/a"
+  set_vars 'B = "b"'
+  "$FLOX_BIN" push --owner "$OWNER"
+  popd > /dev/null || return
+
+  export FLOX_DATA_DIR="$(pwd)/a_data"
+  pushd a > /dev/null || return
+  set_vars 'A = "a"'
+  run "$FLOX_BIN" push
+  assert_success
+  popd > /dev/null || return
+
+  export FLOX_DATA_DIR="$(pwd)/b_data"
+  pushd b > /dev/null || return
+  run "$FLOX_BIN" pull
+  assert_success
+  run cat .flox/env/manifest.toml
+  assert_output --partial 'A = "a"'
+  assert_output --partial 'B = "b"'
+  popd > /dev/null || return
+}
+
 # bats test_tags=managed,diverged,managed:diverged-upstream
 @test "m8: remote can be force pulled into diverged environment" {
   mkdir a
@@ -252,13 +372,11 @@ EOF
 
   pushd b > /dev/null || return
   FLOX_DATA_DIR="$(pwd)/b_data" "$FLOX_BIN" pull --remote "$OWNER/a"
-  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/vim.json" \
-    FLOX_DATA_DIR="$(pwd)/b_data" "$FLOX_BIN" install vim
+  FLOX_DATA_DIR="$(pwd)/b_data" set_vars 'FOO = "b"'
   popd > /dev/null || return
 
   pushd a > /dev/null || return
-  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/emacs.json" \
-    FLOX_DATA_DIR="$(pwd)/a_data" "$FLOX_BIN" install emacs
+  FLOX_DATA_DIR="$(pwd)/a_data" set_vars 'FOO = "a"'
   FLOX_DATA_DIR="$(pwd)/a_data" "$FLOX_BIN" push
   popd > /dev/null || return
 
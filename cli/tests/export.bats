@@ -0,0 +1,92 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test exporting environments with `flox export`
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=export
+
+# ---------------------------------------------------------------------------- #
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+  "$FLOX_BIN" init
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json"
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=export:flake
+@test "export flake: pins nixpkgs and exports vars and hooks" {
+  "$FLOX_BIN" install hello
+  "$FLOX_BIN" edit -f - << "EOF"
+version = 1
+[install]
+hello.pkg-path = "hello"
+[vars]
+GREETING = "hello from flox"
+[hook]
+on-activate = "echo activated"
+EOF
+
+  run "$FLOX_BIN" export flake -o exported
+  assert_success
+
+  rev="$(jq -r '.packages[0].rev' .flox/env/manifest.lock)"
+  run cat exported/flake.nix
+  assert_output --partial "url = \"github:flox/nixpkgs/$rev\";"
+  assert_output --partial ".hello"
+  assert_output --partial 'GREETING = "hello from flox";'
+  assert_output --partial "echo activated"
+
+  run jq -r ".nodes.root.inputs | keys[]" exported/flake.lock
+  assert_output "nixpkgs-${rev:0:12}"
+}
+
+# bats test_tags=export:flake,export:flake:check
+@test "export flake: --check fails when the flake is out of date" {
+  "$FLOX_BIN" install hello
+  "$FLOX_BIN" export flake -o exported
+
+  run "$FLOX_BIN" export flake -o exported --check
+  assert_success
+  assert_output --partial "is up to date"
+
+  "$FLOX_BIN" edit -f - << "EOF"
+version = 1
+[install]
+hello.pkg-path = "hello"
+[vars]
+FOO = "bar"
+EOF
+
+  run "$FLOX_BIN" export flake -o exported --check
+  assert_failure
+  assert_output --partial "flake.nix' is out of date"
+}
@@ -0,0 +1,296 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test the `flox generations` subcommands.
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+
+# ---------------------------------------------------------------------------- #
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/project-generations-${BATS_TEST_NUMBER?}"
+  export PROJECT_NAME="${PROJECT_DIR##*/}"
+  export OWNER="owner"
+
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+  floxhub_setup "$OWNER"
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown() {
+  wait_for_watchdogs
+  project_teardown
+  common_test_teardown
+}
+
+# ---------------------------------------------------------------------------- #
+
+# Create a managed environment with two generations,
+# the second one containing 'hello'.
+function make_remote_env_with_two_generations() {
+  "$FLOX_BIN" init
+  "$FLOX_BIN" push --owner "$OWNER"
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=generations,generations:list
+@test "generations list: fails for path environments" {
+  "$FLOX_BIN" init
+
+  run "$FLOX_BIN" generations list
+  assert_failure
+  assert_output --partial "does not have generations"
+}
+
+# bats test_tags=generations,generations:list
+@test "generations list: lists generations and marks the current one" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations list
+  assert_success
+  assert_line --regexp '^\*  2 .*installed packages'
+  assert_line --regexp '^   1 '
+}
+
+# bats test_tags=generations,generations:list
+@test "generations list: --json prints metadata" {
+  make_remote_env_with_two_generations
+
+  run --separate-stderr "$FLOX_BIN" generations list --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.currentGen')" "2"
+  assert_equal "$(echo "$output" | jq -r '.generations | keys | length')" "2"
+}
+
+# bats test_tags=generations,generations:list
+@test "generations list: records provenance of generations" {
+  make_remote_env_with_two_generations
+
+  run --separate-stderr "$FLOX_BIN" generations list --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.generations."1".command')" "push"
+  assert_equal "$(echo "$output" | jq -r '.generations."2".command')" "install"
+  assert_equal "$(echo "$output" | jq -r '.generations."2".author')" "test"
+  assert_equal "$(echo "$output" | jq -r '.generations."2".system')" "$NIX_SYSTEM"
+}
+
+# bats test_tags=generations,generations:switch
+@test "generations switch: switches to an older generation" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations switch 1
+  assert_success
+  assert_output --partial "to generation 1"
+
+  run --separate-stderr "$FLOX_BIN" list --name
+  assert_success
+  assert_output ""
+
+  run "$FLOX_BIN" generations list
+  assert_success
+  assert_line --regexp '^\*  1 '
+}
+
+# bats test_tags=generations,generations:switch
+@test "generations switch: fails for missing generation" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations switch 3
+  assert_failure
+  assert_output --partial "generation 3 not found"
+}
+
+# bats test_tags=generations,generations:rollback
+@test "generations rollback: switches to the previous generation" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations rollback
+  assert_success
+  assert_output --partial "to generation 1"
+
+  run --separate-stderr "$FLOX_BIN" list --name
+  assert_success
+  assert_output ""
+
+  run "$FLOX_BIN" generations rollback
+  assert_failure
+  assert_output --partial "oldest generation"
+}
+
+# bats test_tags=generations,generations:diff
+@test "generations diff: shows added packages" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations diff 1 2
+  assert_success
+  assert_line "Packages:"
+  assert_line --regexp '^  \+ hello '
+}
+
+# bats test_tags=generations,generations:diff
+@test "generations diff: --json prints changes" {
+  make_remote_env_with_two_generations
+
+  run --separate-stderr "$FLOX_BIN" generations diff 2 1 --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.packages.removed[0].install_id')" "hello"
+}
+
+# bats test_tags=generations,generations:prune
+@test "generations prune: deletes old generations and keeps the current one" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations prune --keep 1
+  assert_success
+  assert_output --partial "Pruned generations 1"
+
+  run --separate-stderr "$FLOX_BIN" generations list --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.generations | keys | join(",")')" "2"
+
+  run "$FLOX_BIN" generations prune --keep 1
+  assert_success
+  assert_output --partial "No generations"
+}
+
+# bats test_tags=generations,generations:prune
+@test "generations prune: never deletes the current generation" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations rollback
+  assert_success
+
+  run "$FLOX_BIN" generations prune --keep 1
+  assert_success
+
+  run --separate-stderr "$FLOX_BIN" generations list --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.generations | keys | join(",")')" "1,2"
+}
+
+# bats test_tags=generations,generations:tag
+@test "generations tag: tags are listed and keep generations from being pruned" {
+  make_remote_env_with_two_generations
+
+  run "$FLOX_BIN" generations tag stable 1
+  assert_success
+  assert_output --partial "Tagged generation 1"
+
+  run "$FLOX_BIN" generations list
+  assert_success
+  assert_line --regexp '^   1 .* stable '
+
+  run --separate-stderr "$FLOX_BIN" generations list --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.tags.stable')" "1"
+
+  run "$FLOX_BIN" generations prune --keep 1
+  assert_success
+  assert_output --partial "No generations"
+
+  run "$FLOX_BIN" generations tag --delete stable
+  assert_success
+  assert_output --partial "Removed tag 'stable' from generation 1"
+
+  run "$FLOX_BIN" generations tag --delete stable
+  assert_failure
+  assert_output --partial "does not have a generation tagged 'stable'"
+}
+
+# bats test_tags=generations,generations:tag
+@test "generations tag: pushed tags select the generation of remote and pulled environments" {
+  make_remote_env_with_two_generations
+  "$FLOX_BIN" generations tag stable 1
+  "$FLOX_BIN" push
+
+  run --separate-stderr "$FLOX_BIN" list -r "$OWNER/$PROJECT_NAME@stable"
+  assert_success
+  refute_output --partial "hello"
+
+  run --separate-stderr "$FLOX_BIN" list -r "$OWNER/$PROJECT_NAME"
+  assert_success
+  assert_output --partial "hello"
+
+  # pinned remote environments can not be modified
+  run "$FLOX_BIN" uninstall -r "$OWNER/$PROJECT_NAME@stable" hello
+  assert_failure
+  assert_output --partial "pinned to generation 1"
+
+  # pulled environments keep the tag and leave the current generation unchanged
+  mkdir pulled
+  run "$FLOX_BIN" pull -d pulled "$OWNER/$PROJECT_NAME@stable"
+  assert_success
+  assert_equal "$(jq -r '.tag' pulled/.flox/env.json)" "stable"
+  run --separate-stderr "$FLOX_BIN" list -d pulled
+  assert_success
+  refute_output --partial "hello"
+  run --separate-stderr "$FLOX_BIN" generations list -d pulled --json
+  assert_equal "$(echo "$output" | jq -r '.currentGen')" "2"
+
+  run "$FLOX_BIN" uninstall -d pulled hello
+  assert_failure
+  assert_output --partial "pinned to generation 1"
+}
+
+# bats test_tags=generations,generations:activate
+@test "activate --generation: activates an older generation without switching to it" {
+  make_remote_env_with_two_generations
+
+  FLOX_SHELL="bash" run "$FLOX_BIN" activate --generation 1 -- bash -c 'command -v hello || echo "no hello"'
+  assert_success
+  assert_output --partial "no hello"
+
+  FLOX_SHELL="bash" run "$FLOX_BIN" activate --generation 2 -- bash -c 'command -v hello'
+  assert_success
+  assert_output --partial "hello"
+
+  run --separate-stderr "$FLOX_BIN" generations list --json
+  assert_success
+  assert_equal "$(echo "$output" | jq -r '.currentGen')" "2"
+}
+
+# bats test_tags=generations,generations:activate
+@test "activate --generation: fails for path environments" {
+  "$FLOX_BIN" init
+
+  FLOX_SHELL="bash" run "$FLOX_BIN" activate --generation 1 -- true
+  assert_failure
+  assert_output --partial "does not have generations"
+}
+
+# bats test_tags=generations,generations:select
+@test "--generation: selects a generation for any environment command" {
+  make_remote_env_with_two_generations
+
+  run --separate-stderr "$FLOX_BIN" list --generation 1
+  assert_success
+  refute_output --partial "hello"
+
+  run "$FLOX_BIN" uninstall --generation 1 hello
+  assert_failure
+  assert_output --partial "pinned to generation 1"
+
+  run "$FLOX_BIN" list -r "$OWNER/$PROJECT_NAME@stable" --generation 1
+  assert_failure
+  assert_output --partial "not both"
+}
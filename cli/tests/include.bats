@@ -0,0 +1,154 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test including environments with the `[include]` section of a manifest
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=include
+
+# ---------------------------------------------------------------------------- #
+
+setup_file() {
+  common_file_setup
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown_file() {
+  unset _FLOX_USE_CATALOG_MOCK
+  common_file_teardown
+}
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR/base" "$PROJECT_DIR/app"
+  pushd "$PROJECT_DIR/app" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# Create the environment in `$PROJECT_DIR/base` with the given manifest
+init_base() {
+  "$FLOX_BIN" init -d "$PROJECT_DIR/base"
+  echo "$1" | "$FLOX_BIN" edit -d "$PROJECT_DIR/base" -f -
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=include:merge
+@test "include: merges vars and hooks of a local environment" {
+  init_base "$(cat << "EOF"
+version = 1
+
+[vars]
+FOO = "base"
+BAR = "base"
+
+[hook]
+on-activate = "echo base hook"
+EOF
+)"
+
+  "$FLOX_BIN" init
+  run "$FLOX_BIN" edit -f - << "EOF"
+version = 1
+
+[vars]
+BAR = "app"
+
+[hook]
+on-activate = "echo app hook"
+
+[include]
+environments = [{ dir = "../base" }]
+EOF
+  assert_success
+
+  run "$FLOX_BIN" activate -- bash -c 'echo "$FOO $BAR"'
+  assert_success
+  assert_line "base hook"
+  assert_line "app hook"
+  assert_line "base app"
+
+  run jq -r '.compose.include[0].name' .flox/env/manifest.lock
+  assert_output "base"
+}
+
+# bats test_tags=include:upgrade
+@test "include: changes to included environments are applied on upgrade" {
+  init_base "$(cat << "EOF"
+version = 1
+
+[vars]
+FOO = "before"
+EOF
+)"
+
+  "$FLOX_BIN" init
+  "$FLOX_BIN" edit -f - << "EOF"
+version = 1
+
+[include]
+environments = [{ dir = "../base" }]
+EOF
+
+  echo 'version = 1
+[vars]
+FOO = "after"' | "$FLOX_BIN" edit -d "$PROJECT_DIR/base" -f -
+
+  run "$FLOX_BIN" activate -- bash -c 'echo "$FOO"'
+  assert_success
+  assert_output "before"
+
+  run "$FLOX_BIN" upgrade
+  assert_success
+  assert_output --partial "Updated included environment 'base'"
+
+  run "$FLOX_BIN" activate -- bash -c 'echo "$FOO"'
+  assert_success
+  assert_output "after"
+}
+
+# bats test_tags=include:nested
+@test "include: fails to include an environment that includes environments" {
+  mkdir -p "$PROJECT_DIR/other"
+  "$FLOX_BIN" init -d "$PROJECT_DIR/other"
+  init_base "$(cat << "EOF"
+version = 1
+
+[include]
+environments = [{ dir = "../other" }]
+EOF
+)"
+
+  "$FLOX_BIN" init
+  run "$FLOX_BIN" edit -f - << "EOF"
+version = 1
+
+[include]
+environments = [{ dir = "../base" }]
+EOF
+  assert_failure
+  assert_output --partial "it includes other environments itself"
+}
@@ -212,6 +212,34 @@ EOF
   assert_equal "$systems" '["aarch64-darwin","aarch64-linux","x86_64-darwin","x86_64-linux"]'
 }
 
+# bats test_tags=init:import
+@test "init --import translates variables and hooks of devbox.json" {
+  cat > devbox.json << "EOF"
+{
+  "env": { "GREETING": "hello from devbox" },
+  "shell": { "init_hook": ["echo imported hook"] }
+}
+EOF
+
+  run "$FLOX_BIN" init --import devbox.json
+  assert_success
+
+  run tomlq -r '.vars.GREETING' .flox/env/manifest.toml
+  assert_output "hello from devbox"
+  run tomlq -r '.hook."on-activate"' .flox/env/manifest.toml
+  assert_output --partial "echo imported hook"
+}
+
+# bats test_tags=init:import
+@test "init --import rejects unsupported files" {
+  echo '{}' > package.json
+
+  run "$FLOX_BIN" init --import package.json
+  assert_failure
+  assert_output --partial "expected a 'devbox.json', '.tool-versions' or 'mise.toml' file"
+  assert [ ! -d .flox ]
+}
+
 # ---------------------------------------------------------------------------- #
 #
 #
@@ -478,3 +478,114 @@ EOF
   installed_flake=$(tomlq -r -c -t ".install.hello" "$MANIFEST_PATH")
   assert_equal "$installed_flake" "flake = \"$input_flake\""
 }
+
+# ---------------------------------------------------------------------------- #
+
+# Add a store path containing an executable 'store-path-hello' to the store
+function add_store_path() {
+  mkdir -p "$BATS_TEST_TMPDIR/store-path-hello/bin"
+  cat > "$BATS_TEST_TMPDIR/store-path-hello/bin/store-path-hello" << EOF
+#!/bin/sh
+echo "hello from a store path"
+EOF
+  chmod +x "$BATS_TEST_TMPDIR/store-path-hello/bin/store-path-hello"
+  nix-store --add "$BATS_TEST_TMPDIR/store-path-hello"
+}
+
+@test "store path: installed, listed and activated" {
+  "$FLOX_BIN" init
+  store_path="$(add_store_path)"
+
+  MANIFEST_CONTENTS="$(
+    cat << EOF
+    version = 1
+
+    [install]
+    store-path-hello.store-path = "$store_path"
+
+    [options]
+    systems = ["$NIX_SYSTEM"]
+EOF
+  )"
+  echo "$MANIFEST_CONTENTS" | "$FLOX_BIN" edit -f -
+
+  run "$FLOX_BIN" list
+  assert_success
+  assert_output "store-path-hello: $store_path"
+
+  run "$FLOX_BIN" activate -- store-path-hello
+  assert_success
+  assert_output --partial "hello from a store path"
+}
+
+@test "store path: paths outside of the store are rejected" {
+  "$FLOX_BIN" init
+
+  MANIFEST_CONTENTS="$(
+    cat << EOF
+    version = 1
+
+    [install]
+    tmp.store-path = "$BATS_TEST_TMPDIR"
+EOF
+  )"
+  run "$FLOX_BIN" edit -f - <<< "$MANIFEST_CONTENTS"
+  assert_failure
+  assert_output --partial "store path '$BATS_TEST_TMPDIR' of package 'tmp' is not a path in the Nix store"
+}
+
+@test "store path: missing store paths are rejected" {
+  "$FLOX_BIN" init
+
+  MANIFEST_CONTENTS="$(
+    cat << EOF
+    version = 1
+
+    [install]
+    missing.store-path = "/nix/store/00000000000000000000000000000000-missing"
+EOF
+  )"
+  run "$FLOX_BIN" edit -f - <<< "$MANIFEST_CONTENTS"
+  assert_failure
+  assert_output --partial "store path '/nix/store/00000000000000000000000000000000-missing' of package 'missing' does not exist and can't be substituted"
+}
+
+# ---------------------------------------------------------------------------- #
+
+@test "outputs: selected outputs are recorded in the lockfile" {
+  "$FLOX_BIN" init
+
+  MANIFEST_CONTENTS="$(
+    cat << EOF
+    version = 1
+
+    [install]
+    hello.pkg-path = "hello"
+    hello.outputs = ["out"]
+EOF
+  )"
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    run "$FLOX_BIN" edit -f - <<< "$MANIFEST_CONTENTS"
+  assert_success
+
+  run jq -c '.packages[0].requested_outputs_to_install' .flox/env/manifest.lock
+  assert_output '["out"]'
+}
+
+@test "outputs: outputs not provided by the package are rejected" {
+  "$FLOX_BIN" init
+
+  MANIFEST_CONTENTS="$(
+    cat << EOF
+    version = 1
+
+    [install]
+    hello.pkg-path = "hello"
+    hello.outputs = ["out", "nonexistent"]
+EOF
+  )"
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    run "$FLOX_BIN" edit -f - <<< "$MANIFEST_CONTENTS"
+  assert_failure
+  assert_output --partial "package 'hello' does not provide the output(s) 'nonexistent'"
+}
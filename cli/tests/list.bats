@@ -183,4 +183,20 @@ EOF
   assert_output "$MANIFEST_CONTENTS"
 }
 
+# bats test_tags=list,list:config
+@test "'flox list --config' doesn't print invalid manifests" {
+  "$FLOX_BIN" init
+  cat > "$PROJECT_DIR/.flox/env/manifest.toml" << "TOMLEOF"
+version = 1
+[vars]
+TOKEN = { command = "echo hunter2" }
+[install
+TOMLEOF
+
+  run "$FLOX_BIN" list --config
+  assert_failure
+  assert_output --partial "Couldn't parse the manifest"
+  refute_output --partial "hunter2"
+}
+
 # ---------------------------------------------------------------------------- #
@@ -0,0 +1,109 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test refusing to lock environments with `--locked` and `FLOX_LOCKED`
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=locked
+
+# ---------------------------------------------------------------------------- #
+
+setup_file() {
+  common_file_setup
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown_file() {
+  unset _FLOX_USE_CATALOG_MOCK
+  common_file_teardown
+}
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# bats test_tags=locked:up-to-date
+@test "locked: commands succeed with an up to date lockfile" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  run "$FLOX_BIN" --locked list
+  assert_success
+  assert_output --partial "hello"
+}
+
+# bats test_tags=locked:outdated
+@test "locked: fails instead of locking a modified manifest" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+  cp .flox/env/manifest.lock old.lock
+
+  tomlq --in-place -t '.install.hello.version = "2.10"' .flox/env/manifest.toml
+
+  run "$FLOX_BIN" --locked list
+  assert_failure
+  assert_output --partial "The manifest was modified since the environment was locked."
+  assert_output --partial "'hello' (changed)"
+  run diff old.lock .flox/env/manifest.lock
+  assert_success
+}
+
+# bats test_tags=locked:env
+@test "locked: FLOX_LOCKED fails for a missing lockfile" {
+  "$FLOX_BIN" init
+  rm -f .flox/env/manifest.lock
+
+  FLOX_LOCKED=1 run "$FLOX_BIN" list
+  assert_failure
+  assert_output --partial "The environment has not been locked."
+  assert [ ! -e .flox/env/manifest.lock ]
+}
+
+# bats test_tags=locked:upgrade
+@test "locked: upgrade fails but --dry-run previews upgrades" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/old_hello.json" \
+    "$FLOX_BIN" install hello
+  cp .flox/env/manifest.lock old.lock
+
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    run "$FLOX_BIN" --locked upgrade
+  assert_failure
+  assert_output --partial "Upgrading packages is disabled by '--locked' or 'FLOX_LOCKED'"
+
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    run "$FLOX_BIN" --locked upgrade --dry-run
+  assert_success
+  assert_output --partial "The following packages would be upgraded"
+
+  run diff old.lock .flox/env/manifest.lock
+  assert_success
+}
@@ -0,0 +1,85 @@
+#! /usr/bin/env bats
+# -*- mode: bats; -*-
+# ============================================================================ #
+#
+# Test generating SBOMs with `flox sbom`
+#
+# ---------------------------------------------------------------------------- #
+
+load test_support.bash
+# bats file_tags=sbom
+
+# ---------------------------------------------------------------------------- #
+
+setup_file() {
+  common_file_setup
+  export _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/empty.json"
+}
+
+teardown_file() {
+  unset _FLOX_USE_CATALOG_MOCK
+  common_file_teardown
+}
+
+# Helpers for project based tests.
+
+project_setup() {
+  export PROJECT_DIR="${BATS_TEST_TMPDIR?}/test"
+  rm -rf "$PROJECT_DIR"
+  mkdir -p "$PROJECT_DIR"
+  pushd "$PROJECT_DIR" > /dev/null || return
+}
+
+project_teardown() {
+  popd > /dev/null || return
+  rm -rf "${PROJECT_DIR?}"
+  unset PROJECT_DIR
+}
+
+# ---------------------------------------------------------------------------- #
+
+setup() {
+  common_test_setup
+  setup_isolated_flox
+  project_setup
+}
+teardown() {
+  project_teardown
+  common_test_teardown
+}
+
+# ---------------------------------------------------------------------------- #
+
+# bats test_tags=sbom:cyclonedx
+@test "sbom: lists locked packages as CycloneDX" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  run "$FLOX_BIN" sbom --system "$NIX_SYSTEM"
+  assert_success
+  sbom="$output"
+
+  run jq -r '.bomFormat' <<< "$sbom"
+  assert_output "CycloneDX"
+  run jq -r '.components[0].name' <<< "$sbom"
+  assert_output "hello"
+  run jq -r '.components[0].properties[] | select(.name == "nix:system") | .value' <<< "$sbom"
+  assert_output "$NIX_SYSTEM"
+}
+
+# bats test_tags=sbom:spdx
+@test "sbom: writes SPDX to a file" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    "$FLOX_BIN" install hello
+
+  run "$FLOX_BIN" sbom --format spdx --system "$NIX_SYSTEM" -o sbom.json
+  assert_success
+  assert_output --partial "Wrote spdx SBOM"
+
+  run jq -r '.spdxVersion' sbom.json
+  assert_output "SPDX-2.3"
+  run jq -r '.packages[0].name' sbom.json
+  assert_output "hello"
+}
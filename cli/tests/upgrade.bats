@@ -95,6 +95,37 @@ setup_pkgdb_env() {
   assert_not_equal "$old_hello_locked_drv" "$hello_locked_drv"
 }
 
+@test "upgrade --dry-run shows versions without upgrading" {
+  "$FLOX_BIN" init
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/old_hello.json" "$FLOX_BIN" install hello
+  cp "$LOCK_PATH" old.lock
+
+  old_hello_version=$(jq -r '.[0].[0].page.packages[0].version' "$GENERATED_DATA/resolve/old_hello.json")
+  hello_version=$(jq -r '.[0].[0].page.packages[0].version' "$GENERATED_DATA/resolve/hello.json")
+
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    run "$FLOX_BIN" upgrade --dry-run
+  assert_success
+  assert_line "The following packages would be upgraded in environment 'test':"
+  assert_line --regexp "^hello +$NIX_SYSTEM +$old_hello_version +$hello_version\$"
+
+  _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/hello.json" \
+    run --separate-stderr "$FLOX_BIN" upgrade --dry-run --json
+  assert_success
+  run jq -c '.packages[] | select(.system == "'"$NIX_SYSTEM"'") | [.install_id, .old_version, .new_version]' <<< "$output"
+  assert_output "[\"hello\",\"$old_hello_version\",\"$hello_version\"]"
+
+  run diff old.lock "$LOCK_PATH"
+  assert_success
+}
+
+@test "upgrade --json requires --dry-run" {
+  "$FLOX_BIN" init
+  run "$FLOX_BIN" upgrade --json
+  assert_failure
+  assert_output --partial "'--json' can only be used with '--dry-run'"
+}
+
 @test "upgrade by group" {
   "$FLOX_BIN" init
   cp "$MANIFEST_PATH" "$TMP_MANIFEST_PATH"
@@ -258,6 +289,34 @@ EOF
   assert_output --partial "⬆️  Migrated environment to version 1 and upgraded all packages for environment '$NAME'."
 }
 
+# bats test_tags=upgrade:migrate:dry-run
+@test "manifest migrate --dry-run shows changes without migrating" {
+  NAME="name"
+  setup_pkgdb_env "$NAME"
+  MANIFEST_BEFORE="$(cat "$PROJECT_DIR/.flox/env/manifest.toml")"
+
+  run "$FLOX_BIN" manifest migrate --dry-run
+  assert_success
+  assert_output --partial "Version 0 to 1:"
+  assert_output --partial "+version = 1"
+  assert_output --partial "The environment would be locked again, upgrading its packages."
+  assert_equal "$(cat "$PROJECT_DIR/.flox/env/manifest.toml")" "$MANIFEST_BEFORE"
+}
+
+# bats test_tags=upgrade:migrate:manifest
+@test "manifest migrate migrates manifest" {
+  NAME="name"
+  setup_pkgdb_env "$NAME"
+  rm -f "$PROJECT_DIR/.flox/env/manifest.lock"
+
+  run "$FLOX_BIN" manifest migrate
+  assert_success
+  assert_output --partial "Migrated environment '$NAME' to manifest version 1."
+  run "$FLOX_BIN" manifest migrate
+  assert_success
+  assert_output --partial "Environment '$NAME' is already at manifest version 1."
+}
+
 @test "catalog: package names and systems are deduped" {
   "$FLOX_BIN" init
   _FLOX_USE_CATALOG_MOCK="$GENERATED_DATA/resolve/old_hello.json" \
@@ -28,6 +28,12 @@ struct BuildenvLockedPackage
   resolver::LockedInputRaw input;
   AttrPath                 attrPath;
   unsigned                 priority;
+  /** Store path of packages installed by store path rather than from an
+   *  input. If set, `input` and `attrPath` are not used. */
+  std::optional<std::string> storePath;
+  /** Outputs of the package to install as requested by the user.
+   *  If unset, all outputs are installed. */
+  std::optional<std::vector<std::string>> outputs;
 };
 
 
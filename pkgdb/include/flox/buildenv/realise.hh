@@ -329,6 +329,18 @@ getRealisedOutputs( nix::ref<nix::EvalState> &         state,
                     const resolver::LockedPackageRaw & lockedPackage,
                     const System &                     system );
 
+/**
+ * @brief Realises a package that is installed by store path.
+ *
+ * The store path is substituted if it is not valid locally.
+ * @param state A nix evaluator.
+ * @param lockedPackage The locked package from the lockfile.
+ * @return The package for the store path of the locked package.
+ */
+std::vector<std::pair<buildenv::RealisedPackage, nix::StorePath>>
+getStorePathOutputs( nix::ref<nix::EvalState> &    state,
+                     const BuildenvLockedPackage & lockedPackage );
+
 
 /* -------------------------------------------------------------------------- */
 
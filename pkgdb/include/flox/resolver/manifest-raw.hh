@@ -187,6 +187,10 @@ struct HookRaw
    * after the user's profile scripts have been sourced.*/
   std::optional<std::string> onActivate;
 
+  /** Defines an inline script to be run non-interactively from a bash subshell
+   * when an activation of the environment exits. */
+  std::optional<std::string> onDeactivate;
+
 
   /**
    * @brief Validate `Hook` fields, throwing an exception if its contents
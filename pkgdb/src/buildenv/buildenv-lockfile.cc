@@ -134,10 +134,16 @@ buildenvPackageFromV1Descriptor( const nlohmann::json &  jfrom,
   pkg.installId = installId;
   pkg.system    = system;
 
+  // Store path packages are not evaluated and only have a store_path.
   // Catalog packages don't come from a flake context so only have attr-path.
   // Flake packages will always have locked-flake-attr-path.
-  // For now, use this to differentiate between the two.
-  if ( jfrom.contains( "locked-flake-attr-path" ) )
+  // For now, use this to differentiate between the three.
+  if ( jfrom.contains( "store_path" ) )
+    {
+      pkg.storePath = jfrom["store_path"];
+      pkg.priority  = jfrom["priority"];
+    }
+  else if ( jfrom.contains( "locked-flake-attr-path" ) )
     {
       LockedInstallable lockedInstallable = LockedInstallable();
       jfrom.get_to( lockedInstallable );
@@ -160,6 +166,13 @@ buildenvPackageFromV1Descriptor( const nlohmann::json &  jfrom,
 
       pkg.priority = jfrom["priority"];
 
+      if ( jfrom.contains( "requested_outputs_to_install" )
+           && ! jfrom["requested_outputs_to_install"].is_null() )
+        {
+          pkg.outputs = jfrom["requested_outputs_to_install"]
+                          .get<std::vector<std::string>>();
+        }
+
       // Set `input` to a flox-nixpkgs input
       pkg.input              = resolver::LockedInputRaw();
       std::string locked_url = jfrom["locked_url"];
@@ -191,7 +204,19 @@ BuildenvLockfile::from_v1_content( const nlohmann::json & jfrom )
   try
     {
       auto value = jfrom.at( "manifest" ).value( "vars", nlohmann::json() );
-      value.get_to( this->manifest.vars );
+      if ( value.is_object() )
+        {
+          /* Secret variables reference a source rather than a value.
+           * They are resolved by the CLI at activation time and must never
+           * be written to the environment, so skip them here. */
+          std::unordered_map<std::string, std::string> vars;
+          for ( const auto & [name, var] : value.items() )
+            {
+              if ( var.is_string() ) { vars[name] = var.get<std::string>(); }
+            }
+          this->manifest.vars = vars;
+        }
+      else { value.get_to( this->manifest.vars ); }
     }
   catch ( nlohmann::json::exception & err )
     {
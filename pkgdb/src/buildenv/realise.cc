@@ -7,6 +7,7 @@
  *
  * -------------------------------------------------------------------------- */
 
+#include <algorithm>
 #include <filesystem>
 #include <fstream>
 
@@ -504,6 +505,19 @@ getRealisedOutputs( nix::ref<nix::EvalState> &    state,
   auto outputsToOutpaths
     = outpathsForPackageOutputs( state, lockedPackage.installId, cursor );
 
+  /* Only install the outputs requested by the user, if any. */
+  if ( lockedPackage.outputs.has_value() )
+    {
+      std::erase_if( outputsToOutpaths,
+                     [&]( const auto & output )
+                     {
+                       return std::find( lockedPackage.outputs->begin(),
+                                         lockedPackage.outputs->end(),
+                                         output.first )
+                              == lockedPackage.outputs->end();
+                     } );
+    }
+
 
   auto pkgs        = collectRealisedOutputs( state,
                                       lockedPackage,
@@ -564,6 +578,33 @@ getRealisedOutputs( nix::ref<nix::EvalState> &    state,
 }
 
 
+/* -------------------------------------------------------------------------- */
+
+std::vector<std::pair<buildenv::RealisedPackage, nix::StorePath>>
+getStorePathOutputs( nix::ref<nix::EvalState> &    state,
+                     const BuildenvLockedPackage & lockedPackage )
+{
+  auto storePath = state->store->parseStorePath( *lockedPackage.storePath );
+  try
+    {
+      state->store->ensurePath( storePath );
+    }
+  catch ( const nix::Error & e )
+    {
+      throw PackageBuildFailure( "Failed to realise store path of package '"
+                                   + lockedPackage.installId + "'",
+                                 nix::filterANSIEscapes( e.what(), true ) );
+    }
+
+  auto                      path = state->store->printStorePath( storePath );
+  buildenv::RealisedPackage pkg( path,
+                                 true,
+                                 buildenv::Priority( lockedPackage.priority,
+                                                     path ) );
+  return { { pkg, storePath } };
+}
+
+
 /* -------------------------------------------------------------------------- */
 
 void
@@ -750,7 +791,7 @@ makeActivationScripts( nix::EvalState &         state,
         }
     }
 
-  /* Add 'hook-on-activate' script. */
+  /* Add 'hook-on-activate' and 'hook-on-deactivate' scripts. */
   auto hook = manifest.hook;
   if ( hook.has_value() )
     {
@@ -772,6 +813,14 @@ makeActivationScripts( nix::EvalState &         state,
                                  activateScriptsDir,
                                  "hook-on-activate" );
         }
+
+      if ( hook->onDeactivate.has_value() )
+        {
+          debugLog( "adding 'hook.on-deactivate' to activation scripts" );
+          addScriptToScriptsDir( *hook->onDeactivate,
+                                 activateScriptsDir,
+                                 "hook-on-deactivate" );
+        }
     }
 
   debugLog( "adding activation scripts to store" );
@@ -905,7 +954,9 @@ createFloxEnv( nix::ref<nix::EvalState> &         state,
       // Skip any packages not for this system
       if ( package.system == system )
         {
-          auto realised = getRealisedOutputs( state, package, system );
+          auto realised = package.storePath.has_value()
+                            ? getStorePathOutputs( state, package )
+                            : getRealisedOutputs( state, package, system );
           for ( auto [realisedPackage, storePath] : realised )
             {
               pkgs.push_back( realisedPackage );
@@ -91,6 +91,8 @@ from_json( const nlohmann::json & jfrom, Options::Allows & allow )
                 + value.dump() );
             }
         }
+      /* Not used within pkgdb */
+      else if ( key == "insecure" ) { ; }
       else
         {
           throw InvalidManifestFileException(
@@ -318,8 +320,9 @@ from_json( const nlohmann::json & jfrom, HookRaw & hook )
                                                     "manifest field 'hook'" );
 
   /* Clear fields. */
-  hook.script     = std::nullopt;
-  hook.onActivate = std::nullopt;
+  hook.script       = std::nullopt;
+  hook.onActivate   = std::nullopt;
+  hook.onDeactivate = std::nullopt;
 
   for ( const auto & [key, value] : jfrom.items() )
     {
@@ -349,6 +352,20 @@ from_json( const nlohmann::json & jfrom, HookRaw & hook )
                 + value.dump() );
             }
         }
+      else if ( key == "on-deactivate" )
+        {
+          try
+            {
+              value.get_to( hook.onDeactivate );
+            }
+          catch ( const nlohmann::json::exception & )
+            {
+              throw InvalidManifestFileException(
+                "failed to parse manifest field 'hook.on-deactivate' with "
+                "value: "
+                + value.dump() );
+            }
+        }
       else
         {
           throw InvalidManifestFileException(
@@ -364,12 +381,16 @@ static void
 to_json( nlohmann::json & jto, const HookRaw & hook )
 {
   hook.check();
-  if ( hook.script.has_value() ) { jto = { { "script", *hook.script } }; }
+  jto = nlohmann::json::object();
+  if ( hook.script.has_value() ) { jto["script"] = *hook.script; }
   else if ( hook.onActivate.has_value() )
     {
-      jto = { { "on-activate", *hook.onActivate } };
+      jto["on-activate"] = *hook.onActivate;
+    }
+  if ( hook.onDeactivate.has_value() )
+    {
+      jto["on-deactivate"] = *hook.onDeactivate;
     }
-  else { jto = nlohmann::json::object(); }
 }
 
 
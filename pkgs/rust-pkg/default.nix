@@ -58,7 +58,7 @@ let
       # rather than relying on or modifying the user's `PATH` variable
       GIT_PKG = gitMinimal;
       NIX_PKG = nix;
-      NIX_BIN = "${nix}/bin/nix"; # only used for nix invocations in tests
+      NIX_BIN = "${nix}/bin/nix";
       PKGDB_BIN = if flox-pkgdb == null then "pkgdb" else "${flox-pkgdb}/bin/pkgdb";
       WATCHDOG_BIN = if WATCHDOG_BIN == null then "flox-watchdog" else WATCHDOG_BIN;
       FLOX_ZDOTDIR = flox-activation-scripts + activate.d/zdotdir;